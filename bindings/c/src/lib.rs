@@ -0,0 +1,4 @@
+//! shared and static library of the uciengine c api, the functions are those of
+//! uciengine::capi, declared in include/uciengine.h
+
+pub use uciengine::capi::*;
@@ -0,0 +1,147 @@
+#[macro_use]
+extern crate napi_derive;
+
+use std::collections::HashMap;
+
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::JsFunction;
+
+use tokio::sync::broadcast::error::RecvError;
+
+use uciengine::uciengine::*;
+
+/// go job description
+#[napi(object)]
+pub struct GoJobSpec {
+    /// position fen, starting position if missing
+    pub fen: Option<String>,
+    /// space separated uci moves
+    pub moves: Option<String>,
+    /// uci options
+    pub uci_options: Option<HashMap<String, String>>,
+    /// go options ( depth, movetime, ... )
+    pub go_options: Option<HashMap<String, String>>,
+}
+
+/// go result
+#[napi(object)]
+pub struct GoResultSpec {
+    /// best move if any
+    pub bestmove: Option<String>,
+    /// ponder if any
+    pub ponder: Option<String>,
+    /// last analysis info as json
+    pub info: String,
+}
+
+/// convert go job description to go job
+fn to_go_job(spec: GoJobSpec) -> GoJob {
+    let mut go_job = match spec.fen {
+        Some(fen) => GoJob::new().pos_fen(fen),
+        _ => GoJob::new().pos_startpos(),
+    };
+
+    if let Some(moves) = spec.moves {
+        go_job = go_job.pos_moves(moves);
+    }
+
+    for (key, value) in spec.uci_options.unwrap_or_default() {
+        go_job = go_job.uci_opt(key, value);
+    }
+
+    for (key, value) in spec.go_options.unwrap_or_default() {
+        go_job = go_job.go_opt(key, value);
+    }
+
+    go_job
+}
+
+/// uci engine
+#[napi]
+pub struct Engine {
+    engine: std::sync::Arc<UciEngine>,
+    /// task calling the info callback
+    info_task: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+/// engine implementation
+#[napi]
+impl Engine {
+    /// spawn engine at path
+    #[napi(constructor)]
+    pub fn new(path: String) -> Self {
+        let engine = within_runtime_if_available(|| UciEngine::new(path));
+
+        Self {
+            engine,
+            info_task: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// search, resolves with the result
+    #[napi]
+    pub async fn go(&self, job: GoJobSpec) -> Result<GoResultSpec> {
+        let go_result = self
+            .engine
+            .try_go(to_go_job(job))
+            .await
+            .map_err(|err| Error::from_reason(err.to_string()))?;
+
+        Ok(GoResultSpec {
+            bestmove: go_result.bestmove,
+            ponder: go_result.ponder,
+            info: go_result.ai.to_json().unwrap_or_default(),
+        })
+    }
+
+    /// call callback with every analysis info as json, until the engine quits,
+    /// replaces the previous callback, the callback does not keep node running
+    #[napi(ts_args_type = "callback: (info: string) => void")]
+    pub fn on_info(&self, env: Env, callback: JsFunction) -> Result<()> {
+        let mut tsfn: ThreadsafeFunction<String, ErrorStrategy::Fatal> =
+            callback.create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))?;
+
+        // the info broadcast stays open as long as the engine, a referenced callback
+        // would keep the event loop alive forever
+        tsfn.unref(&env)?;
+
+        let mut arx = self.engine.atx.subscribe();
+
+        let info_task = spawn(async move {
+            loop {
+                match arx.recv().await {
+                    Ok(ai) => {
+                        if let Ok(json) = ai.to_json() {
+                            tsfn.call(json, ThreadsafeFunctionCallMode::NonBlocking);
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        if let Some(previous) = self.info_task.lock().unwrap().replace(info_task) {
+            previous.abort();
+        }
+
+        Ok(())
+    }
+
+    /// stop running search, the pending go resolves with its result
+    #[napi]
+    pub fn stop(&self) {
+        self.engine.issue_command("stop");
+    }
+
+    /// quit engine, the info callback is released
+    #[napi]
+    pub fn quit(&self) {
+        if let Some(info_task) = self.info_task.lock().unwrap().take() {
+            info_task.abort();
+        }
+
+        self.engine.quit();
+    }
+}
@@ -12,12 +12,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .uci_opt("Threads", 4)
         .pos_fen("k7/8/8/8/8/8/R7/7K w - - 0 1")
         .pos_moves("h1h2")
-        .tc(Timecontrol {
-            wtime: 15000,
-            winc: 0,
-            btime: 15000,
-            binc: 0,
-        });
+        .tc(Timecontrol::new(15000, 0, 15000, 0));
 
     let go_job2 = GoJob::new()
         .uci_opt("UCI_Variant", "chess")
@@ -31,13 +26,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // issue two parallel async go commands, to demonstrate that they will be queued and processed one a time
     tokio::spawn(async move {
-        let go_result1 = engine_clone1.go(go_job1).await;
+        let go_result1 = engine_clone1.try_go(go_job1).await;
 
         println!("go result 1 {:?}", go_result1);
     });
 
     tokio::spawn(async move {
-        let go_result2 = engine_clone2.go(go_job2).await;
+        let go_result2 = engine_clone2.try_go(go_job2).await;
 
         println!("go result 2 {:?}", go_result2);
     });
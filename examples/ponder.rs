@@ -11,26 +11,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .pos_startpos()
         .pos_moves("e2e4 e7e5")
         .ponder()
-        .tc(Timecontrol {
-            wtime: 15000,
-            winc: 0,
-            btime: 15000,
-            binc: 0,
-        });
+        .tc(Timecontrol::new(15000, 0, 15000, 0));
 
     let engine = UciEngine::new("stockfish12.exe");
 
     // start engine detached
-    let _ = engine.go(go_job);
+    drop(engine.try_go(go_job));
 
     // do something in the meanwhile
     println!("doing something");
 
     // issue ponderhit
-    let result = engine.go(GoJob::new().ponderhit()).await;
+    let result = engine.ponderhit().await;
 
     // issue pondermiss
-    //let result = engine.go(GoJob::new().pondermiss()).recv().await;
+    //let result = engine.pondermiss().await;
 
     println!("{:?}", result);
 
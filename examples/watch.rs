@@ -15,7 +15,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let engine = UciEngine::new("stockfish12.exe");
 
     // start engine detached
-    let _ = engine.go(go_job);
+    drop(engine.try_go(go_job));
 
     let mut arx = engine.atx.subscribe();
 
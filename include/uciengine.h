@@ -0,0 +1,81 @@
+/* uciengine c api, build the library in bindings/c */
+
+#ifndef UCIENGINE_H
+#define UCIENGINE_H
+
+/* generated with cbindgen, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * opaque engine handle
+ */
+typedef struct CEngine CEngine;
+
+/**
+ * info callback, called with the analysis info as json and the registered user data,
+ * the json string is only valid during the call
+ */
+typedef void (*InfoCallback)(const char *json, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * spawn engine at path, returns null on failure,
+ * the handle has to be freed with uciengine_free
+ */
+CEngine *uciengine_new(const char *path);
+
+/**
+ * quit engine and free handle
+ */
+void uciengine_free(CEngine *handle);
+
+/**
+ * start search, fen null for the starting position, moves space separated uci moves
+ * or null, go_params the parameters of the go command ( "depth 20",
+ * "movetime 1000 searchmoves e2e4 d2d4" ) or null for a plain go, the result of the
+ * previous search is cleared, returns 0 on success, -2 if go_params are malformed
+ */
+int uciengine_start_search(CEngine *handle,
+                           const char *fen,
+                           const char *moves,
+                           const char *go_params);
+
+/**
+ * stop running search, its result becomes available through uciengine_poll_result
+ */
+void uciengine_stop(CEngine *handle);
+
+/**
+ * latest analysis info as json, the string has to be freed with uciengine_string_free
+ */
+char *uciengine_poll_info(CEngine *handle);
+
+/**
+ * result of the last search as json ( bestmove, ponder, info ), or ( error ) if the
+ * search failed, null while searching, the string has to be freed with uciengine_string_free
+ */
+char *uciengine_poll_result(CEngine *handle);
+
+/**
+ * register info callback, called from a runtime thread with every analysis info,
+ * replaces the previous callback, null callback unregisters
+ */
+void uciengine_set_info_callback(CEngine *handle, InfoCallback callback, void *user_data);
+
+/**
+ * free string returned by the api
+ */
+void uciengine_string_free(char *string);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* UCIENGINE_H */
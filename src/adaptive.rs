@@ -0,0 +1,254 @@
+use log::{debug, log_enabled, Level};
+
+use tokio::sync::Notify;
+
+use crate::analysis::*;
+use crate::uciengine::*;
+
+/// adaptive multipv policy
+#[derive(Debug, Clone)]
+pub struct AdaptiveMultiPv {
+    /// multipv to widen to
+    pub wide_multipv: usize,
+    /// score swing between consecutive depths ( in centipawns ) that counts as instability
+    pub instability_cp: i32,
+    /// depths below this are not considered, early iterations always swing
+    pub min_depth: usize,
+    /// number of unstable iterations that trigger widening
+    pub max_unstable: usize,
+    /// widen on request of the user ( clone it before starting the analysis and call notify_one )
+    pub widen_request: std::sync::Arc<Notify>,
+    /// last observed depth and score of the top line
+    last: Option<(usize, Score)>,
+    /// number of unstable iterations seen so far
+    unstable: usize,
+}
+
+/// adaptive multipv policy implementation
+impl AdaptiveMultiPv {
+    /// create new adaptive multipv policy with defaults
+    /// ( widen to multipv 5 once the top score swung by 50 cp or more twice from depth 8 on )
+    pub fn new() -> Self {
+        Self {
+            wide_multipv: 5,
+            instability_cp: 50,
+            min_depth: 8,
+            max_unstable: 2,
+            widen_request: std::sync::Arc::new(Notify::new()),
+            last: None,
+            unstable: 0,
+        }
+    }
+
+    /// set multipv to widen to and return self
+    pub fn wide_multipv(mut self, wide_multipv: usize) -> Self {
+        self.wide_multipv = wide_multipv;
+
+        self
+    }
+
+    /// set instability threshold in centipawns and return self
+    pub fn instability_cp(mut self, instability_cp: i32) -> Self {
+        self.instability_cp = instability_cp;
+
+        self
+    }
+
+    /// set minimum depth and return self
+    pub fn min_depth(mut self, min_depth: usize) -> Self {
+        self.min_depth = min_depth;
+
+        self
+    }
+
+    /// set number of unstable iterations that trigger widening and return self
+    pub fn max_unstable(mut self, max_unstable: usize) -> Self {
+        self.max_unstable = max_unstable;
+
+        self
+    }
+
+    /// observe analysis info of the top line, returns true if multipv should be widened
+    pub fn observe(&mut self, ai: &AnalysisInfo) -> bool {
+        if ai.multipv > 1 || ai.depth < self.min_depth || ai.bestmove().is_none() {
+            return false;
+        }
+
+        if !matches!(ai.scoretype, ScoreType::Exact) {
+            // fail high / fail low
+            self.unstable += 1;
+        }
+
+        if let Some((depth, score)) = self.last {
+            if ai.depth > depth
+                && (ai.score.sort_key() - score.sort_key()).abs() >= self.instability_cp
+            {
+                self.unstable += 1;
+            }
+        }
+
+        self.last = Some((ai.depth, ai.score));
+
+        self.unstable >= self.max_unstable
+    }
+}
+
+/// implement Default for AdaptiveMultiPv
+impl Default for AdaptiveMultiPv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// analyse with adaptive multipv,
+/// the search starts with multipv 1 and is widened on the fly
+/// ( stopped, multipv set, resumed on the same engine so the hash is kept )
+/// when the top score is unstable or widening was requested,
+/// make_job should return a go job with position and search limits set,
+/// multipv is set back to 1 once the widened search is done
+pub async fn analyse_adaptive<F>(
+    engine: &UciEngine,
+    make_job: F,
+    mut policy: AdaptiveMultiPv,
+) -> Result<GoResult, EngineError>
+where
+    F: Fn() -> GoJob,
+{
+    let cancelled = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+    // the infos of this job only, unsampled, the engine may run other jobs before it
+    let GoStream {
+        mut infos,
+        result: mut pending,
+    } = engine.go_stream(
+        make_job()
+            .uci_opt("MultiPV", 1)
+            .cancel_flag(cancelled.clone()),
+    );
+
+    let widen_request = policy.widen_request.clone();
+
+    loop {
+        let widen = tokio::select! {
+            go_result = &mut pending => return go_result,
+            _ = widen_request.notified() => true,
+            received = infos.recv() => match received {
+                Some(ai) => policy.observe(&ai),
+                _ => return pending.await,
+            },
+        };
+
+        if widen {
+            if log_enabled!(Level::Debug) {
+                debug!("widening multipv to {}", policy.wide_multipv);
+            }
+
+            // a job still queued is dropped, only its own running search is stopped
+            cancelled.store(true, std::sync::atomic::Ordering::SeqCst);
+
+            if engine.running_job() == Some(pending.id()) {
+                engine.issue_command("stop");
+            }
+
+            // the stopped search result is discarded
+            let _ = (&mut pending).await;
+
+            let pending = engine.try_go(make_job().uci_opt("MultiPV", policy.wide_multipv));
+
+            // queued right behind the widened search, so later jobs are not widened
+            engine.try_go(GoJob::new().uci_opt("MultiPV", 1));
+
+            return pending.await;
+        }
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn adaptive_widening() {
+    // the narrow search swings until stopped, the wide one ends right away
+    let path = fake_engine(
+        "adaptive",
+        "    *MultiPV\\ value\\ 1) wide=;;
+    *MultiPV\\ value\\ 3) wide=1;;
+    go*) if [ -n \"$wide\" ]; then echo 'info depth 12 multipv 1 score cp 20 pv d2d4'; echo 'bestmove d2d4'; else d=8; for cp in 0 80 0; do d=$((d + 1)); echo \"info depth $d score cp $cp pv e2e4\"; done; fi;;
+    stop) echo 'bestmove e2e4';;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let go_result = analyse_adaptive(
+        &engine,
+        || GoJob::new().pos_startpos().go_opt("depth", 20),
+        AdaptiveMultiPv::new().wide_multipv(3),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(go_result.bestmove.as_deref(), Some("d2d4"));
+
+    engine.is_ready().await.unwrap();
+
+    let log = fake_engine_log(&path);
+
+    assert!(log.contains(&"stop".to_string()));
+    assert_eq!(
+        log[log.len() - 4..],
+        [
+            "go depth 20",
+            "setoption name MultiPV value 1",
+            "isready",
+            "isready"
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn adaptive_widening_queued() {
+    let path = fake_engine(
+        "adaptive-queued",
+        "    go\\ depth\\ 9) sleep 0.3; echo 'bestmove e2e4';;
+    go*) echo 'info depth 12 multipv 1 score cp 20 pv d2d4'; echo 'bestmove d2d4';;
+    stop) echo 'bestmove a2a3';;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let first = engine.try_go(GoJob::new().pos_startpos().go_opt("depth", 9));
+
+    let policy = AdaptiveMultiPv::new().wide_multipv(3);
+
+    // widened while the narrow search is queued behind the first job
+    policy.widen_request.notify_one();
+
+    let go_result = analyse_adaptive(
+        &engine,
+        || GoJob::new().pos_startpos().go_opt("depth", 20),
+        policy,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(go_result.bestmove.as_deref(), Some("d2d4"));
+
+    // the search of the other job is not stopped
+    assert_eq!(first.await.unwrap().bestmove.as_deref(), Some("e2e4"));
+
+    engine.is_ready().await.unwrap();
+
+    assert_eq!(
+        fake_engine_log(&path),
+        [
+            "position startpos",
+            "go depth 9",
+            "setoption name MultiPV value 3",
+            "isready",
+            "position startpos",
+            "go depth 20",
+            "setoption name MultiPV value 1",
+            "isready",
+            "isready"
+        ]
+    );
+}
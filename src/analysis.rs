@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 
 use thiserror::Error;
 
+use crate::notation::*;
+
 /// InfoParseError captures possible info parsing errors
 #[derive(Error, Debug)]
 pub enum InfoParseError {
@@ -204,6 +206,224 @@ pub enum Score {
     Mate(i32),
 }
 
+/// centipawn value used to rank mate scores above any centipawn score
+pub const MATE_SCORE: i32 = 100_000;
+
+/// score implementation
+impl Score {
+    /// sort key, higher is better for the side to move,
+    /// shorter mates rank higher, longer mates against rank higher
+    pub fn sort_key(&self) -> i32 {
+        match *self {
+            Score::Cp(cp) => cp,
+            Score::Mate(mate) if mate > 0 => MATE_SCORE - mate,
+            Score::Mate(mate) => -MATE_SCORE - mate,
+        }
+    }
+
+    /// score from the point of view of the other side
+    /// ( "mate 0", the side to move is mated, becomes a mate for the other side )
+    pub fn flip(&self) -> Self {
+        match *self {
+            Score::Cp(cp) => Score::Cp(-cp),
+            Score::Mate(mate) => Score::Mate(-mate),
+        }
+    }
+
+    /// centipawn equivalent, mates are clamped to plus / minus MATE_CP_EQUIVALENT
+    pub fn cp_equivalent(&self) -> i32 {
+        match *self {
+            Score::Cp(cp) => cp.clamp(-MATE_CP_EQUIVALENT, MATE_CP_EQUIVALENT),
+            Score::Mate(mate) if mate > 0 => MATE_CP_EQUIVALENT,
+            Score::Mate(mate) if mate < 0 => -MATE_CP_EQUIVALENT,
+            // mated
+            Score::Mate(_) => -MATE_CP_EQUIVALENT,
+        }
+    }
+
+    /// win probability of the side to move between 0 and 1
+    /// ( logistic curve fitted to game results, as used by lichess )
+    pub fn win_probability(&self) -> f64 {
+        match *self {
+            Score::Mate(mate) if mate > 0 => 1.0,
+            Score::Mate(_) => 0.0,
+            Score::Cp(cp) => 1.0 / (1.0 + (-0.00368208 * cp as f64).exp()),
+        }
+    }
+}
+
+/// centipawn equivalent of mate scores in move quality deltas
+pub const MATE_CP_EQUIVALENT: i32 = 1000;
+
+/// quality of a played move compared to the engine best move
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveDelta {
+    /// engine best move in the position before the move
+    pub best_move: Option<String>,
+    /// played move
+    pub played_move: String,
+    /// score of the best move from the point of view of the mover
+    pub best: Score,
+    /// score of the played move from the point of view of the mover
+    pub played: Score,
+    /// centipawn loss ( mates count as MATE_CP_EQUIVALENT ), never negative
+    pub cp_loss: i32,
+    /// loss of win probability between 0 and 1, never negative
+    pub win_probability_loss: f64,
+}
+
+/// move delta implementation
+impl MoveDelta {
+    /// compute move delta from the final analysis of the position before the move
+    /// ( mover to move ) and of the position after it ( opponent to move ),
+    /// a played move equal to the best move has no loss
+    pub fn compute(
+        played: &str,
+        analysis_before: &AnalysisInfo,
+        analysis_after: &AnalysisInfo,
+    ) -> Self {
+        let best_move = analysis_before.bestmove();
+        let best = analysis_before.score;
+
+        let played_score = match best_move.as_deref() {
+            Some(best_move) if best_move == played => best,
+            _ => analysis_after.score.flip(),
+        };
+
+        let cp_loss = (best.cp_equivalent() - played_score.cp_equivalent()).max(0);
+        let win_probability_loss =
+            (best.win_probability() - played_score.win_probability()).max(0.0);
+
+        Self {
+            best_move,
+            played_move: played.to_string(),
+            best,
+            played: played_score,
+            cp_loss,
+            win_probability_loss,
+        }
+    }
+}
+
+/// score convention of an engine, how tablebase results show in its scores
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScoreConvention {
+    /// centipawn scores at least this large are tablebase wins,
+    /// None if the engine reports tablebase wins as mate or not at all
+    pub tb_win_cp: Option<i32>,
+}
+
+/// score convention implementation
+impl ScoreConvention {
+    /// stockfish convention ( tablebase wins are reported as cp 20000 minus ply )
+    pub fn stockfish() -> Self {
+        Self {
+            tb_win_cp: Some(19_000),
+        }
+    }
+
+    /// convention of engines without special tablebase scores
+    pub fn plain() -> Self {
+        Self { tb_win_cp: None }
+    }
+}
+
+/// where the result of a search comes from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ResultSource {
+    /// regular search
+    Search,
+    /// tablebase, the score is exact
+    Tablebase,
+    /// opening book, the engine moved without searching
+    Book,
+}
+
+/// result source implementation
+impl ResultSource {
+    /// classify final analysis info and bestmove of a search,
+    /// a bestmove without any search info is a book move,
+    /// a mate or tablebase win score with tablebase hits is a tablebase result
+    pub fn classify(
+        ai: &AnalysisInfo,
+        bestmove: Option<&str>,
+        convention: &ScoreConvention,
+    ) -> Self {
+        let moved = matches!(bestmove, Some(bestmove) if bestmove != "(none)");
+
+        if ai.depth == 0 && moved {
+            return ResultSource::Book;
+        }
+
+        if ai.tbhits > 0 {
+            let tb_score = match ai.score {
+                Score::Mate(_) => true,
+                Score::Cp(cp) => convention
+                    .tb_win_cp
+                    .map(|tb_win_cp| cp.abs() >= tb_win_cp)
+                    .unwrap_or(false),
+            };
+
+            if tb_score {
+                return ResultSource::Tablebase;
+            }
+        }
+
+        ResultSource::Search
+    }
+}
+
+/// anomaly of a search result, marks results worth searching again
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Anomaly {
+    /// bestmove is not the first move of the final pv
+    BestmoveNotInPv,
+    /// score changed sign between the last two depths
+    ScoreSignFlip,
+    /// search reported zero nodes
+    ZeroNodes,
+}
+
+/// anomaly implementation
+impl Anomaly {
+    /// detect anomalies of final analysis info and bestmove of a search,
+    /// score_sign_flipped tells whether the score flipped sign vs the previous depth,
+    /// only searched results can be anomalous
+    pub fn detect(
+        ai: &AnalysisInfo,
+        bestmove: Option<&str>,
+        source: ResultSource,
+        score_sign_flipped: bool,
+    ) -> Vec<Anomaly> {
+        let mut anomalies = vec![];
+
+        let bestmove = match bestmove {
+            Some(bestmove) if bestmove != "(none)" => bestmove,
+            _ => return anomalies,
+        };
+
+        if !matches!(source, ResultSource::Search) {
+            return anomalies;
+        }
+
+        if let Some(pv_bestmove) = ai.bestmove() {
+            if pv_bestmove != bestmove {
+                anomalies.push(Anomaly::BestmoveNotInPv);
+            }
+        }
+
+        if score_sign_flipped {
+            anomalies.push(Anomaly::ScoreSignFlip);
+        }
+
+        if ai.nodes == 0 {
+            anomalies.push(Anomaly::ZeroNodes);
+        }
+
+        anomalies
+    }
+}
+
 /// score type
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum ScoreType {
@@ -508,11 +728,44 @@ impl AnalysisInfo {
         self.pv.to_opt()
     }
 
+    // get pv moves, null moves kept apart
+    pub fn pv_moves(self) -> Vec<PvMove> {
+        self.pv().map(|pv| pv_moves(&pv)).unwrap_or_default()
+    }
+
+    /// set pv, best move and ponder are taken from it
+    pub fn set_pv<T: AsRef<str>>(&mut self, pv: T) {
+        let pv = pv.as_ref();
+        let mut moves = pv.split_whitespace();
+
+        match moves.next() {
+            Some(bestmove) => self.bestmove = UciBuff::from(bestmove),
+            _ => {
+                self.bestmove.reset();
+            }
+        }
+
+        match moves.next() {
+            Some(ponder) => self.ponder = UciBuff::from(ponder),
+            _ => {
+                self.ponder.reset();
+            }
+        }
+
+        // trimmed at a move boundary like parsed pvs
+        self.pv.set_trim(format!("{} ", pv), ' ');
+    }
+
     // get current move
     pub fn currmove(self) -> Option<String> {
         self.currmove.to_opt()
     }
 
+    /// search time as duration ( the time field holds milliseconds )
+    pub fn elapsed(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.time as u64)
+    }
+
     /// parse info string
     pub fn parse<T: std::convert::AsRef<str>>(&mut self, info: T) -> Result<(), InfoParseError> {
         let info = info.as_ref();
@@ -729,10 +982,251 @@ impl AnalysisInfo {
             }
         }
 
-        self.pv.set_trim(pv_buff, ' ');
+        // the trailing space keeps the last move when the pv fits
+        self.pv.set_trim(format!("{} ", pv_buff), ' ');
 
         Ok(())
     }
+
+    /// emit as uci info line, the inverse of parse, fields holding their
+    /// default ( zero or empty ) are left out, score is emitted along with the pv,
+    /// used to rewrite and re-emit engine traffic
+    pub fn to_info_line(&self) -> String {
+        let mut tokens: Vec<String> = vec!["info".to_string()];
+
+        let mut push = |key: &str, value: String| {
+            tokens.push(key.to_string());
+            tokens.push(value);
+        };
+
+        if self.depth > 0 {
+            push("depth", self.depth.to_string());
+        }
+
+        if self.seldepth > 0 {
+            push("seldepth", self.seldepth.to_string());
+        }
+
+        if self.multipv > 0 {
+            push("multipv", self.multipv.to_string());
+        }
+
+        let pv = self.pv();
+
+        if pv.is_some() {
+            let score = match self.score {
+                Score::Cp(cp) => format!("cp {}", cp),
+                Score::Mate(moves) => format!("mate {}", moves),
+            };
+
+            let score = match self.scoretype {
+                ScoreType::Exact => score,
+                ScoreType::Lowerbound => format!("{} lowerbound", score),
+                ScoreType::Upperbound => format!("{} upperbound", score),
+            };
+
+            push("score", score);
+        }
+
+        if self.wdl.win + self.wdl.draw + self.wdl.loss > 0 {
+            push(
+                "wdl",
+                format!("{} {} {}", self.wdl.win, self.wdl.draw, self.wdl.loss),
+            );
+        }
+
+        if self.nodes > 0 {
+            push("nodes", self.nodes.to_string());
+        }
+
+        if self.nps > 0 {
+            push("nps", self.nps.to_string());
+        }
+
+        if self.hashfull > 0 {
+            push("hashfull", self.hashfull.to_string());
+        }
+
+        if self.tbhits > 0 {
+            push("tbhits", self.tbhits.to_string());
+        }
+
+        if self.cpuload > 0 {
+            push("cpuload", self.cpuload.to_string());
+        }
+
+        if self.time > 0 {
+            push("time", self.time.to_string());
+        }
+
+        if let Some(currmove) = self.currmove() {
+            push("currmove", currmove);
+        }
+
+        if self.currmovenumber > 0 {
+            push("currmovenumber", self.currmovenumber.to_string());
+        }
+
+        // pv takes the rest of the line
+        if let Some(pv) = pv {
+            push("pv", pv);
+        }
+
+        tokens.join(" ")
+    }
+}
+
+/// full pvs and currlines of a search, AnalysisInfo keeps only the first
+/// MAX_PV_MOVES moves of the pv in a fixed buffer so it stays Copy, the lines
+/// here are kept whole however long they are
+///
+/// memory policy : only the latest pv of every multipv and the latest currline
+/// of every cpu are kept, each replaces the one before, and the lines are cleared
+/// when a new search starts, so memory stays bounded by the longest current lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct FullLines {
+    /// pv by multipv ( 1 for engines not sending multipv )
+    pvs: std::collections::BTreeMap<usize, String>,
+    /// currline by cpu ( 1 for engines not sending the cpu )
+    currlines: std::collections::BTreeMap<usize, String>,
+}
+
+/// full lines implementation
+impl FullLines {
+    /// create new empty lines
+    pub fn new() -> Self {
+        Self {
+            pvs: std::collections::BTreeMap::new(),
+            currlines: std::collections::BTreeMap::new(),
+        }
+    }
+
+    /// observe engine output line, returns true if it carried a pv or currline
+    pub fn observe(&mut self, line: &str) -> bool {
+        if !line.starts_with("info ") || line.starts_with("info string") {
+            return false;
+        }
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        let multipv = tokens
+            .windows(2)
+            .find(|pair| pair[0] == "multipv")
+            .and_then(|pair| pair[1].parse::<usize>().ok())
+            .unwrap_or(1)
+            .max(1);
+
+        // pv and currline take the rest of the line
+        match tokens
+            .iter()
+            .position(|token| *token == "pv" || *token == "currline")
+        {
+            Some(index) if tokens[index] == "pv" => {
+                let pv = tokens[index + 1..].join(" ");
+
+                if pv.is_empty() {
+                    return false;
+                }
+
+                self.pvs.insert(multipv, pv);
+
+                true
+            }
+            Some(index) => {
+                let mut moves = &tokens[index + 1..];
+
+                let cpu = match moves.first().and_then(|cpu| cpu.parse::<usize>().ok()) {
+                    Some(cpu) => {
+                        moves = &moves[1..];
+
+                        cpu
+                    }
+                    _ => 1,
+                };
+
+                if moves.is_empty() {
+                    return false;
+                }
+
+                self.currlines.insert(cpu, moves.join(" "));
+
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// full pv of multipv ( 0 or 1 for the main line ), None if there was none
+    pub fn pv(&self, multipv: usize) -> Option<&str> {
+        self.pvs.get(&multipv.max(1)).map(|pv| pv.as_str())
+    }
+
+    /// full pvs by multipv
+    pub fn pvs(&self) -> &std::collections::BTreeMap<usize, String> {
+        &self.pvs
+    }
+
+    /// full currline of cpu, None if there was none
+    pub fn currline(&self, cpu: usize) -> Option<&str> {
+        self.currlines
+            .get(&cpu.max(1))
+            .map(|currline| currline.as_str())
+    }
+
+    /// full currlines by cpu
+    pub fn currlines(&self) -> &std::collections::BTreeMap<usize, String> {
+        &self.currlines
+    }
+
+    /// full pv of multipv split in chunks of at most plies moves, for transports
+    /// limiting the length of a message, empty if there was no pv
+    pub fn pv_chunks(&self, multipv: usize, plies: usize) -> Vec<String> {
+        let moves: Vec<&str> = match self.pv(multipv) {
+            Some(pv) => pv.split(' ').collect(),
+            _ => return vec![],
+        };
+
+        moves
+            .chunks(plies.max(1))
+            .map(|chunk| chunk.join(" "))
+            .collect()
+    }
+
+    /// clear lines, as a new search starts
+    pub fn clear(&mut self) {
+        self.pvs.clear();
+        self.currlines.clear();
+    }
+}
+
+/// implement Default for FullLines
+impl Default for FullLines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn info_line_round_trip() {
+    let lines = [
+        "info depth 20 seldepth 28 multipv 1 score cp 35 wdl 120 850 30 nodes 1234567 nps 987654 hashfull 312 tbhits 5 time 1250 pv e2e4 e7e5",
+        "info depth 7 score mate -3 upperbound nodes 4000 pv d8h4 g2g3",
+        "info depth 12 currmove g1f3 currmovenumber 4",
+    ];
+
+    for line in lines.iter() {
+        let mut ai = AnalysisInfo::new();
+
+        ai.parse(line).unwrap();
+
+        assert_eq!(ai.to_info_line(), *line);
+
+        let mut reparsed = AnalysisInfo::new();
+
+        reparsed.parse(ai.to_info_line()).unwrap();
+
+        assert_eq!(reparsed.to_info_line(), ai.to_info_line());
+    }
 }
 
 #[test]
@@ -762,3 +1256,134 @@ fn parse_error() {
     assert_eq!(format!("{:?}", ai.score), format!("{:?}", Score::Mate(5)));
     assert_eq!(format!("{:?}", ai.ponder()), format!("{:?}", Some("e7e5")));
 }
+
+#[test]
+fn score_sort_key() {
+    let mut scores = vec![
+        Score::Cp(30),
+        Score::Mate(-2),
+        Score::Mate(3),
+        Score::Cp(-500),
+        Score::Mate(1),
+        Score::Mate(-5),
+    ];
+
+    scores.sort_by_key(|score| -score.sort_key());
+
+    assert_eq!(
+        format!("{:?}", scores),
+        "[Mate(1), Mate(3), Cp(30), Cp(-500), Mate(-5), Mate(-2)]"
+    );
+}
+
+#[test]
+fn result_source() {
+    let mut ai = AnalysisInfo::new();
+
+    ai.parse("info depth 30 score cp 19990 tbhits 12 pv e2e4 e7e5")
+        .unwrap();
+
+    assert_eq!(
+        ResultSource::classify(&ai, Some("e2e4"), &ScoreConvention::stockfish()),
+        ResultSource::Tablebase
+    );
+    assert_eq!(
+        ResultSource::classify(&ai, Some("e2e4"), &ScoreConvention::plain()),
+        ResultSource::Search
+    );
+
+    assert_eq!(
+        ResultSource::classify(
+            &AnalysisInfo::new(),
+            Some("e2e4"),
+            &ScoreConvention::stockfish()
+        ),
+        ResultSource::Book
+    );
+}
+
+#[test]
+fn move_delta() {
+    let mut before = AnalysisInfo::new();
+
+    before
+        .parse("info depth 20 score cp 50 pv e2e4 e7e5")
+        .unwrap();
+
+    let mut after = AnalysisInfo::new();
+
+    after
+        .parse("info depth 20 score cp 20 pv d7d5 c2c4")
+        .unwrap();
+
+    let delta = MoveDelta::compute("d2d4", &before, &after);
+
+    assert_eq!(delta.cp_loss, 70);
+    assert!(delta.win_probability_loss > 0.0);
+    assert_eq!(MoveDelta::compute("e2e4", &before, &after).cp_loss, 0);
+
+    before
+        .parse("info depth 20 score mate 2 pv d1h5 e7e5")
+        .unwrap();
+    after
+        .parse("info depth 20 score cp -300 pv h7h6 g1f3")
+        .unwrap();
+
+    let delta = MoveDelta::compute("g1f3", &before, &after);
+
+    assert!(matches!(delta.played, Score::Cp(300)));
+    assert_eq!(delta.cp_loss, 700);
+    assert_eq!(Score::Mate(0).flip().win_probability(), 0.0);
+}
+
+#[test]
+fn anomalies() {
+    let mut ai = AnalysisInfo::new();
+
+    ai.parse("info depth 12 nodes 0 score cp 30 pv e2e4 e7e5")
+        .unwrap();
+
+    assert_eq!(
+        Anomaly::detect(&ai, Some("d2d4"), ResultSource::Search, true),
+        vec![
+            Anomaly::BestmoveNotInPv,
+            Anomaly::ScoreSignFlip,
+            Anomaly::ZeroNodes
+        ]
+    );
+    assert!(Anomaly::detect(&ai, Some("e2e4"), ResultSource::Book, false).is_empty());
+}
+
+#[test]
+fn full_lines() {
+    let moves = ["e2e4", "e7e5", "g1f3", "b8c6"];
+    let pv: Vec<&str> = moves.iter().cycle().take(240).copied().collect();
+    let pv = pv.join(" ");
+
+    let line = format!("info depth 60 multipv 2 score cp 20 pv {}", pv);
+
+    let mut ai = AnalysisInfo::new();
+
+    ai.parse(&line).unwrap();
+
+    // the analysis info keeps a bounded pv
+    assert!(ai.pv().unwrap().len() < pv.len());
+
+    let mut lines = FullLines::new();
+
+    assert!(lines.observe(&line));
+    assert_eq!(lines.pv(2), Some(pv.as_str()));
+    assert_eq!(lines.pv(1), None);
+    assert_eq!(lines.pv_chunks(2, 100).len(), 3);
+    assert_eq!(lines.pv_chunks(2, 100).join(" "), pv);
+
+    assert!(lines.observe(&format!("info currline 2 {}", pv)));
+    assert_eq!(lines.currline(2), Some(pv.as_str()));
+    assert!(lines.observe("info depth 1 pv d2d4"));
+    assert_eq!(lines.pv(0), Some("d2d4"));
+    assert!(!lines.observe("info string pv of nothing"));
+
+    lines.clear();
+
+    assert!(lines.pvs().is_empty());
+}
@@ -0,0 +1,321 @@
+use log::{debug, info, log_enabled, warn, Level};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::analysis::*;
+use crate::pool::*;
+use crate::uciengine::*;
+
+/// default interval the pool is polled at while it is busy
+pub const DEFAULT_IDLE_POLL: Duration = Duration::from_secs(1);
+
+/// evaluation of a position as kept in an eval database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredEval {
+    /// fen of the position
+    pub fen: String,
+    /// best move, None if unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bestmove: Option<String>,
+    /// score from the side to move
+    pub score: Score,
+    /// depth the position was searched to
+    pub depth: usize,
+    /// nodes searched
+    pub nodes: u64,
+}
+
+/// eval database a backfill upgrades
+pub trait EvalStore {
+    /// up to limit entries searched below depth or, if given, below nodes
+    fn shallow(&self, depth: usize, nodes: Option<u64>, limit: usize) -> Vec<StoredEval>;
+
+    /// replace entry of the position of eval with eval, atomically and only if the
+    /// stored entry is still the one searched at previous depth and nodes ( it was not
+    /// upgraded meanwhile ), returns true if it was replaced
+    fn upgrade(&self, eval: StoredEval, previous: (usize, u64)) -> bool;
+}
+
+/// in memory eval database
+pub struct MemoryEvalStore {
+    evals: std::sync::Mutex<HashMap<String, StoredEval>>,
+}
+
+/// memory eval store implementation
+impl MemoryEvalStore {
+    /// create new empty store
+    pub fn new() -> Self {
+        Self {
+            evals: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// insert or replace eval
+    pub fn insert(&self, eval: StoredEval) {
+        let mut evals = self.evals.lock().unwrap();
+
+        evals.insert(eval.fen.to_owned(), eval);
+    }
+
+    /// eval of fen if any
+    pub fn get(&self, fen: &str) -> Option<StoredEval> {
+        let evals = self.evals.lock().unwrap();
+
+        evals.get(fen).cloned()
+    }
+}
+
+/// implement Default for MemoryEvalStore
+impl Default for MemoryEvalStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// eval store implementation for memory eval store
+impl EvalStore for MemoryEvalStore {
+    fn shallow(&self, depth: usize, nodes: Option<u64>, limit: usize) -> Vec<StoredEval> {
+        let evals = self.evals.lock().unwrap();
+
+        evals
+            .values()
+            .filter(|eval| eval.depth < depth || nodes.is_some_and(|nodes| eval.nodes < nodes))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    fn upgrade(&self, eval: StoredEval, previous: (usize, u64)) -> bool {
+        let mut evals = self.evals.lock().unwrap();
+
+        match evals.get(&eval.fen) {
+            Some(stored) if (stored.depth, stored.nodes) != previous => false,
+            _ => {
+                evals.insert(eval.fen.to_owned(), eval);
+
+                true
+            }
+        }
+    }
+}
+
+/// outcome of a backfill
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackfillReport {
+    /// positions searched again
+    pub searched: usize,
+    /// entries upgraded
+    pub upgraded: usize,
+    /// entries left as they were, upgraded meanwhile or not searched deeper
+    pub skipped: usize,
+    /// searches that got no result
+    pub failed: usize,
+}
+
+/// backfill of an eval database, finds entries below a target budget and searches
+/// them again at the target budget while the pool is idle, so a long lived database
+/// keeps improving without competing with live jobs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Backfill {
+    /// depth entries are upgraded to
+    pub target_depth: usize,
+    /// nodes entries are upgraded to, None to go by depth only
+    pub target_nodes: Option<u64>,
+    /// entries fetched and searched per batch
+    pub batch: usize,
+    /// interval the pool is polled at while it is busy
+    pub idle_poll: Duration,
+}
+
+/// backfill implementation
+impl Backfill {
+    /// create new backfill to target depth, in batches of 16
+    pub fn new(target_depth: usize) -> Self {
+        Self {
+            target_depth,
+            target_nodes: None,
+            batch: 16,
+            idle_poll: DEFAULT_IDLE_POLL,
+        }
+    }
+
+    /// set target nodes and return self
+    pub fn target_nodes(mut self, target_nodes: u64) -> Self {
+        self.target_nodes = Some(target_nodes);
+
+        self
+    }
+
+    /// set batch size and return self
+    pub fn batch(mut self, batch: usize) -> Self {
+        self.batch = batch.max(1);
+
+        self
+    }
+
+    /// set idle poll interval and return self
+    pub fn idle_poll(mut self, idle_poll: Duration) -> Self {
+        self.idle_poll = idle_poll;
+
+        self
+    }
+
+    /// go job searching eval again at the target budget
+    pub fn to_go_job(&self, eval: &StoredEval) -> GoJob {
+        let go_job = GoJob::new()
+            .pos_fen(&eval.fen)
+            .go_opt("depth", self.target_depth);
+
+        match self.target_nodes {
+            Some(nodes) => go_job.go_opt("nodes", nodes),
+            _ => go_job,
+        }
+    }
+
+    /// eval of a search of eval, None if the search did not go deeper than eval
+    pub fn upgraded(&self, eval: &StoredEval, go_result: &GoResult) -> Option<StoredEval> {
+        let ai = go_result.ai;
+
+        if ai.depth <= eval.depth && ai.nodes <= eval.nodes {
+            return None;
+        }
+
+        Some(StoredEval {
+            fen: eval.fen.to_owned(),
+            bestmove: go_result.bestmove.to_owned().or_else(|| ai.bestmove()),
+            score: ai.score,
+            depth: ai.depth,
+            nodes: ai.nodes,
+        })
+    }
+
+    /// wait until the pool has no pending jobs, false if it started draining
+    async fn wait_idle(&self, pool: &EnginePool) -> bool {
+        while pool.in_flight() > 0 {
+            if pool.is_draining() {
+                return false;
+            }
+
+            tokio::time::sleep(self.idle_poll).await;
+        }
+
+        !pool.is_draining()
+    }
+
+    /// search one batch of shallow entries during idle pool time and upgrade them
+    pub async fn run_batch<S: EvalStore>(&self, pool: &EnginePool, store: &S) -> BackfillReport {
+        let mut report = BackfillReport::default();
+
+        for eval in store.shallow(self.target_depth, self.target_nodes, self.batch) {
+            if !self.wait_idle(pool).await {
+                break;
+            }
+
+            let go_result = match pool.go(self.to_go_job(&eval)) {
+                Ok(pending) => pending.await.ok(),
+                _ => None,
+            };
+
+            report.searched += 1;
+
+            let go_result = match go_result {
+                Some(go_result) => go_result,
+                _ => {
+                    warn!("backfill search of {} got no result", eval.fen);
+
+                    report.failed += 1;
+
+                    continue;
+                }
+            };
+
+            let upgraded = self
+                .upgraded(&eval, &go_result)
+                .is_some_and(|upgraded| store.upgrade(upgraded, (eval.depth, eval.nodes)));
+
+            match upgraded {
+                true => {
+                    if log_enabled!(Level::Debug) {
+                        debug!(
+                            "backfill upgraded {} from depth {} to {}",
+                            eval.fen, eval.depth, go_result.ai.depth
+                        );
+                    }
+
+                    report.upgraded += 1;
+                }
+                _ => report.skipped += 1,
+            }
+        }
+
+        report
+    }
+
+    /// run batches until no shallow entries are left or the pool drains, entries a
+    /// batch could not upgrade are not searched again by this run
+    pub async fn run<S: EvalStore>(&self, pool: &EnginePool, store: &S) -> BackfillReport {
+        let mut report = BackfillReport::default();
+
+        loop {
+            let batch = self.run_batch(pool, store).await;
+
+            report.searched += batch.searched;
+            report.upgraded += batch.upgraded;
+            report.skipped += batch.skipped;
+            report.failed += batch.failed;
+
+            // a batch that upgraded nothing would fetch the same entries again
+            if batch.upgraded == 0 || pool.is_draining() {
+                break;
+            }
+        }
+
+        if log_enabled!(Level::Info) {
+            info!(
+                "backfill searched {} positions, upgraded {}",
+                report.searched, report.upgraded
+            );
+        }
+
+        report
+    }
+}
+
+#[test]
+fn backfill_store() {
+    let store = MemoryEvalStore::new();
+
+    let eval = |fen: &str, depth: usize, nodes: u64| StoredEval {
+        fen: fen.to_string(),
+        bestmove: None,
+        score: Score::Cp(20),
+        depth,
+        nodes,
+    };
+
+    store.insert(eval("a", 10, 10_000));
+    store.insert(eval("b", 24, 5_000_000));
+    store.insert(eval("c", 24, 500_000));
+
+    let backfill = Backfill::new(20);
+
+    assert_eq!(store.shallow(20, None, 10).len(), 1);
+    assert_eq!(store.shallow(20, Some(1_000_000), 10).len(), 2);
+
+    let commands = backfill
+        .target_nodes(1_000_000)
+        .to_go_job(&eval("a", 10, 0))
+        .to_commands();
+
+    assert_eq!(commands[0], "position fen a");
+    assert!(commands[1].contains("depth 20") && commands[1].contains("nodes 1000000"));
+
+    // an entry upgraded meanwhile is not overwritten
+    assert!(!store.upgrade(eval("a", 20, 2_000_000), (8, 5_000)));
+    assert!(store.upgrade(eval("a", 20, 2_000_000), (10, 10_000)));
+    assert_eq!(store.get("a").map(|eval| eval.depth), Some(20));
+    assert!(store.shallow(20, None, 10).is_empty());
+}
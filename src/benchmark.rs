@@ -0,0 +1,382 @@
+use log::{debug, log_enabled, warn, Level};
+
+use serde::{Deserialize, Serialize};
+
+use shakmaty::fen::{Epd, Fen};
+use shakmaty::san::SanPlus;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, EnPassantMode};
+
+use thiserror::Error;
+
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::uciengine::*;
+use crate::warmup::*;
+
+/// SuiteParseError captures possible test suite parsing errors
+#[derive(Error, Debug)]
+pub enum SuiteParseError {
+    #[error("invalid epd '{0}'")]
+    InvalidEpd(String),
+    #[error("invalid move '{1}' in position '{0}'")]
+    InvalidMove(String, String),
+    #[error("no bm or am operation in position '{0}'")]
+    NoSolution(String),
+}
+
+/// test suite position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuitePosition {
+    /// id of the position ( id operation, or line number if missing )
+    pub id: String,
+    /// fen of the position
+    pub fen: String,
+    /// best moves in uci notation ( bm operation )
+    pub best_moves: Vec<String>,
+    /// moves to avoid in uci notation ( am operation )
+    pub avoid_moves: Vec<String>,
+}
+
+/// test suite position implementation
+impl SuitePosition {
+    /// parse epd line with bm / am and id operations
+    /// ( 'r1b1k2r/... w kq - bm Qxf7+; id "WAC.001";' ), moves may be san or uci
+    pub fn from_epd<T: AsRef<str>>(line: T, line_number: usize) -> Result<Self, SuiteParseError> {
+        let line = line.as_ref().trim();
+
+        let fields: Vec<&str> = line.splitn(5, ' ').collect();
+
+        if fields.len() < 4 {
+            return Err(SuiteParseError::InvalidEpd(line.to_string()));
+        }
+
+        let epd = fields[..4].join(" ");
+
+        let pos: Chess = Epd::from_ascii(epd.as_bytes())
+            .ok()
+            .and_then(|epd| epd.into_position(CastlingMode::Standard).ok())
+            .ok_or_else(|| SuiteParseError::InvalidEpd(epd.to_owned()))?;
+
+        let mut position = Self {
+            id: format!("{}", line_number),
+            fen: Fen::from_position(&pos, EnPassantMode::Legal).to_string(),
+            best_moves: vec![],
+            avoid_moves: vec![],
+        };
+
+        for operation in fields.get(4).unwrap_or(&"").split(';') {
+            let mut tokens = operation.split_whitespace();
+
+            let opcode = match tokens.next() {
+                Some(opcode) => opcode,
+                _ => continue,
+            };
+
+            let operands: Vec<&str> = tokens.collect();
+
+            match opcode {
+                "id" => position.id = operands.join(" ").trim_matches('"').to_string(),
+                "bm" | "am" => {
+                    for operand in operands {
+                        let uci = SanPlus::from_ascii(operand.as_bytes())
+                            .ok()
+                            .and_then(|san| san.san.to_move(&pos).ok())
+                            .map(|m| UciMove::from_move(m, CastlingMode::Standard))
+                            .or_else(|| {
+                                operand
+                                    .parse::<UciMove>()
+                                    .ok()
+                                    .filter(|uci| uci.to_move(&pos).is_ok())
+                            })
+                            .ok_or_else(|| {
+                                SuiteParseError::InvalidMove(epd.to_owned(), operand.to_string())
+                            })?;
+
+                        match opcode {
+                            "bm" => position.best_moves.push(uci.to_string()),
+                            _ => position.avoid_moves.push(uci.to_string()),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if position.best_moves.is_empty() && position.avoid_moves.is_empty() {
+            return Err(SuiteParseError::NoSolution(epd));
+        }
+
+        Ok(position)
+    }
+
+    /// true if move solves the position
+    pub fn is_solution(&self, uci: &str) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.iter().any(|m| m == uci))
+            && !self.avoid_moves.iter().any(|m| m == uci)
+    }
+}
+
+/// parse test suite in epd format, one position per line, empty lines and
+/// lines starting with '#' are skipped
+pub fn parse_suite<T: AsRef<str>>(suite: T) -> Result<Vec<SuitePosition>, SuiteParseError> {
+    suite
+        .as_ref()
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .map(|(i, line)| SuitePosition::from_epd(line, i + 1))
+        .collect()
+}
+
+/// result of a suite position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionResult {
+    /// id of the position
+    pub id: String,
+    /// move played by the engine
+    pub bestmove: Option<String>,
+    /// true if the move played solves the position
+    pub solved: bool,
+    /// time in milliseconds from which the engine kept a solving move on top, None if not solved
+    pub time_to_solution: Option<usize>,
+    /// depth reached
+    pub depth: usize,
+    /// nodes per second reported last
+    pub nps: u64,
+}
+
+/// benchmark report of one engine on a suite
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuiteReport {
+    /// label of the run ( engine version, hardware )
+    pub label: String,
+    /// time per position in milliseconds
+    pub movetime: usize,
+    /// results by position
+    pub results: Vec<PositionResult>,
+    /// warm-up run before the suite, not part of the results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warmup: Option<WarmupReport>,
+}
+
+/// analysis quality summary, comparable across engine versions and hardware
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualitySummary {
+    /// label of the run
+    pub label: String,
+    /// number of positions
+    pub positions: usize,
+    /// number of solved positions
+    pub solved: usize,
+    /// fraction of solved positions
+    pub hit_rate: f64,
+    /// median time to solution of solved positions in milliseconds
+    pub median_time_to_solution: Option<usize>,
+    /// 90th percentile time to solution of solved positions in milliseconds
+    pub p90_time_to_solution: Option<usize>,
+    /// mean nodes per second
+    pub mean_nps: f64,
+}
+
+/// suite report implementation
+impl SuiteReport {
+    /// percentile of the time to solution of solved positions
+    pub fn time_to_solution_percentile(&self, percentile: f64) -> Option<usize> {
+        let mut times: Vec<usize> = self
+            .results
+            .iter()
+            .filter_map(|result| result.time_to_solution)
+            .collect();
+
+        if times.is_empty() {
+            return None;
+        }
+
+        times.sort_unstable();
+
+        let index = ((times.len() - 1) as f64 * percentile).round() as usize;
+
+        Some(times[index.min(times.len() - 1)])
+    }
+
+    /// analysis quality summary
+    pub fn summary(&self) -> QualitySummary {
+        let positions = self.results.len();
+        let solved = self.results.iter().filter(|result| result.solved).count();
+
+        QualitySummary {
+            label: self.label.to_owned(),
+            positions,
+            solved,
+            hit_rate: match positions {
+                0 => 0.0,
+                _ => solved as f64 / positions as f64,
+            },
+            median_time_to_solution: self.time_to_solution_percentile(0.5),
+            p90_time_to_solution: self.time_to_solution_percentile(0.9),
+            mean_nps: match positions {
+                0 => 0.0,
+                _ => {
+                    self.results
+                        .iter()
+                        .map(|result| result.nps as f64)
+                        .sum::<f64>()
+                        / positions as f64
+                }
+            },
+        }
+    }
+}
+
+/// format summaries as a plain text table, one row per run
+pub fn quality_table(summaries: &[QualitySummary]) -> String {
+    let ms = |time: Option<usize>| match time {
+        Some(time) => format!("{}", time),
+        _ => "-".to_string(),
+    };
+
+    let mut table = format!(
+        "{:<24} {:>9} {:>8} {:>10} {:>10} {:>12}\n",
+        "label", "solved", "hit %", "median ms", "p90 ms", "nps"
+    );
+
+    for summary in summaries {
+        table += &format!(
+            "{:<24} {:>9} {:>8.1} {:>10} {:>10} {:>12.0}\n",
+            summary.label,
+            format!("{}/{}", summary.solved, summary.positions),
+            summary.hit_rate * 100.0,
+            ms(summary.median_time_to_solution),
+            ms(summary.p90_time_to_solution),
+            summary.mean_nps
+        );
+    }
+
+    table
+}
+
+/// run suite on engine with given time per position in milliseconds,
+/// the engine should be configured ( options, MultiPV 1 ) beforehand
+pub async fn run_suite<T>(
+    engine: &UciEngine,
+    suite: &[SuitePosition],
+    movetime: usize,
+    label: T,
+) -> SuiteReport
+where
+    T: core::fmt::Display,
+{
+    let mut report = SuiteReport {
+        label: label.to_string(),
+        movetime,
+        results: vec![],
+        warmup: None,
+    };
+
+    for position in suite {
+        let mut arx = engine.atx.subscribe();
+
+        let mut rrx = engine.try_go(
+            GoJob::new()
+                .pos_fen(&position.fen)
+                .go_opt("movetime", movetime),
+        );
+
+        // time from which a solving move has been on top of the pv
+        let mut solved_at: Option<usize> = None;
+        let mut last_time = 0;
+
+        let go_result = loop {
+            tokio::select! {
+                go_result = &mut rrx => break go_result.ok(),
+                received = arx.recv() => match received {
+                    Ok(ai) if ai.multipv <= 1 => {
+                        if let Some(bestmove) = ai.bestmove() {
+                            if !position.is_solution(&bestmove) {
+                                solved_at = None;
+                            } else if solved_at.is_none() {
+                                solved_at = Some(ai.time);
+                            }
+
+                            last_time = ai.time;
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break rrx.await.ok(),
+                },
+            }
+        };
+
+        let (bestmove, ai) = match go_result {
+            Some(go_result) => (go_result.bestmove, go_result.ai),
+            _ => {
+                warn!("no result for suite position {}", position.id);
+
+                (None, engine.get_ai())
+            }
+        };
+
+        let solved = bestmove
+            .as_ref()
+            .map(|bestmove| position.is_solution(bestmove))
+            .unwrap_or(false);
+
+        let result = PositionResult {
+            id: position.id.to_owned(),
+            bestmove,
+            solved,
+            time_to_solution: match solved {
+                true => Some(solved_at.unwrap_or(last_time)),
+                _ => None,
+            },
+            depth: ai.depth,
+            nps: ai.nps,
+        };
+
+        if log_enabled!(Level::Debug) {
+            debug!("suite position result {:?}", result);
+        }
+
+        report.results.push(result);
+    }
+
+    report
+}
+
+/// run warm-up and then the suite on engine, see run_suite,
+/// the warm-up is reported apart from the results
+pub async fn run_suite_warm<T>(
+    engine: &UciEngine,
+    suite: &[SuitePosition],
+    movetime: usize,
+    label: T,
+    warmup: &Warmup,
+) -> SuiteReport
+where
+    T: core::fmt::Display,
+{
+    let warmup_report = warmup.run(engine).await;
+
+    let mut report = run_suite(engine, suite, movetime, label).await;
+
+    report.warmup = Some(warmup_report);
+
+    report
+}
+
+#[test]
+fn parse_epd() {
+    assert!(parse_suite("8/8/8/8/8/8/8/8 w - - bm Qg6;").is_err());
+
+    let suite = parse_suite(
+        "# win at chess\n\
+        2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";",
+    )
+    .unwrap();
+
+    assert_eq!(suite[0].id, "WAC.001");
+    assert_eq!(suite[0].best_moves, vec!["g3g6"]);
+    assert!(suite[0].is_solution("g3g6"));
+    assert!(!suite[0].is_solution("f6g4"));
+}
@@ -0,0 +1,227 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// magic bytes of a saved filter
+const BLOOM_MAGIC: &[u8; 8] = b"UCIBLOOM";
+
+/// normalized fen key of a position, the first four fen fields
+/// ( board, side to move, castling, en passant ), move counters do not change
+/// the analysis of a position
+pub fn normalized_fen_key(fen: &str) -> String {
+    fen.split_whitespace()
+        .take(4)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// fnv-1a hash, stable across runs and platforms so that saved filters stay valid
+fn fnv1a(bytes: &[u8], seed: u64) -> u64 {
+    let mut hash = 0xcbf29ce484222325 ^ seed;
+
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
+
+/// invalid data error
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+/// bloom filter of analyzed positions, keyed by normalized fen,
+/// answers "certainly not analyzed" or "probably analyzed" without hitting
+/// the eval database, for skipping positions when crawling huge corpora
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzedFilter {
+    /// bit array
+    bits: Vec<u64>,
+    /// number of bits
+    num_bits: u64,
+    /// number of hashes per key
+    num_hashes: u32,
+    /// number of inserted keys
+    len: u64,
+}
+
+/// analyzed filter implementation
+impl AnalyzedFilter {
+    /// create new filter sized for expected number of positions at given false positive rate
+    pub fn new(expected: usize, false_positive_rate: f64) -> Self {
+        let expected = expected.max(1) as f64;
+        let rate = false_positive_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+
+        let num_bits = ((-expected * rate.ln() / (ln2 * ln2)).ceil() as u64).max(64);
+        let num_hashes = ((num_bits as f64 / expected * ln2).round() as u32).max(1);
+
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+            len: 0,
+        }
+    }
+
+    /// bit indices of fen
+    fn indices(&self, fen: &str) -> impl Iterator<Item = u64> {
+        let key = normalized_fen_key(fen);
+
+        let h1 = fnv1a(key.as_bytes(), 0);
+        let h2 = fnv1a(key.as_bytes(), 0x9e3779b97f4a7c15) | 1;
+        let num_bits = self.num_bits;
+
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+
+    /// insert position, returns false if it was probably present already
+    pub fn insert(&mut self, fen: &str) -> bool {
+        let mut inserted = false;
+
+        for index in self.indices(fen).collect::<Vec<u64>>() {
+            let (word, bit) = ((index / 64) as usize, index % 64);
+
+            if self.bits[word] & (1 << bit) == 0 {
+                self.bits[word] |= 1 << bit;
+
+                inserted = true;
+            }
+        }
+
+        if inserted {
+            self.len += 1;
+        }
+
+        inserted
+    }
+
+    /// true if position was probably inserted, false if it was certainly not
+    pub fn contains(&self, fen: &str) -> bool {
+        self.indices(fen)
+            .all(|index| self.bits[(index / 64) as usize] & (1 << (index % 64)) != 0)
+    }
+
+    /// number of inserted positions ( approximate, probable duplicates are not counted )
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// true if no position was inserted
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// expected false positive rate at the current fill
+    pub fn false_positive_rate(&self) -> f64 {
+        let k = self.num_hashes as f64;
+
+        (1.0 - (-k * self.len as f64 / self.num_bits as f64).exp()).powf(k)
+    }
+
+    /// size of the bit array in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.bits.len() * 8
+    }
+
+    /// write filter to writer
+    pub fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(BLOOM_MAGIC)?;
+        writer.write_all(&self.num_bits.to_le_bytes())?;
+        writer.write_all(&self.num_hashes.to_le_bytes())?;
+        writer.write_all(&self.len.to_le_bytes())?;
+
+        for word in self.bits.iter() {
+            writer.write_all(&word.to_le_bytes())?;
+        }
+
+        writer.flush()
+    }
+
+    /// read filter from reader
+    pub fn read_from<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let mut magic = [0u8; 8];
+        let mut u64_buf = [0u8; 8];
+        let mut u32_buf = [0u8; 4];
+
+        reader.read_exact(&mut magic)?;
+
+        if &magic != BLOOM_MAGIC {
+            return Err(invalid_data("not an analyzed filter"));
+        }
+
+        reader.read_exact(&mut u64_buf)?;
+        let num_bits = u64::from_le_bytes(u64_buf);
+        reader.read_exact(&mut u32_buf)?;
+        let num_hashes = u32::from_le_bytes(u32_buf);
+        reader.read_exact(&mut u64_buf)?;
+        let len = u64::from_le_bytes(u64_buf);
+
+        if num_bits == 0 || num_hashes == 0 {
+            return Err(invalid_data("corrupt analyzed filter header"));
+        }
+
+        let mut bits = vec![0; num_bits.div_ceil(64) as usize];
+
+        for word in bits.iter_mut() {
+            reader.read_exact(&mut u64_buf)?;
+
+            *word = u64::from_le_bytes(u64_buf);
+        }
+
+        Ok(Self {
+            bits,
+            num_bits,
+            num_hashes,
+            len,
+        })
+    }
+
+    /// save filter to file ( written to a temporary file first, so a crash
+    /// does not leave a truncated filter )
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+
+        self.write_to(std::io::BufWriter::new(std::fs::File::create(&tmp)?))?;
+
+        std::fs::rename(tmp, path)
+    }
+
+    /// load filter from file
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::read_from(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    /// load filter from file if it exists, otherwise create new one
+    pub fn load_or_new<P: AsRef<Path>>(
+        path: P,
+        expected: usize,
+        false_positive_rate: f64,
+    ) -> std::io::Result<Self> {
+        match path.as_ref().exists() {
+            true => Self::load(path),
+            _ => Ok(Self::new(expected, false_positive_rate)),
+        }
+    }
+}
+
+#[test]
+fn analyzed_filter() {
+    let mut filter = AnalyzedFilter::new(1000, 0.01);
+
+    let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+
+    assert!(!filter.contains(fen));
+    assert!(filter.insert(fen));
+    assert!(!filter.insert(fen));
+    // move counters are not part of the key
+    assert!(filter.contains("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 3 7"));
+
+    let mut saved = vec![];
+
+    filter.write_to(&mut saved).unwrap();
+
+    assert_eq!(AnalyzedFilter::read_from(&saved[..]).unwrap(), filter);
+}
@@ -0,0 +1,189 @@
+use log::{info, log_enabled, Level};
+
+use std::convert::TryInto;
+use std::path::Path;
+
+use shakmaty::uci::UciMove;
+use shakmaty::zobrist::Zobrist64;
+use shakmaty::{CastlingMode, Chess, EnPassantMode, Position};
+
+/// size of a polyglot book entry in bytes
+const ENTRY_SIZE: usize = 16;
+
+/// polyglot book entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookEntry {
+    /// polyglot hash of the position
+    pub key: u64,
+    /// move in polyglot encoding
+    pub raw_move: u16,
+    /// weight of the move
+    pub weight: u16,
+    /// learn value
+    pub learn: u32,
+}
+
+/// book entry implementation
+impl BookEntry {
+    /// move as uci text ( castling as king takes rook, as polyglot encodes it )
+    pub fn raw_uci(&self) -> String {
+        let square = |file: u16, rank: u16| {
+            format!(
+                "{}{}",
+                (b'a' + file as u8) as char,
+                (b'1' + rank as u8) as char
+            )
+        };
+
+        let m = self.raw_move;
+
+        let promotion = match (m >> 12) & 7 {
+            1 => "n",
+            2 => "b",
+            3 => "r",
+            4 => "q",
+            _ => "",
+        };
+
+        format!(
+            "{}{}{}",
+            square((m >> 6) & 7, (m >> 9) & 7),
+            square(m & 7, (m >> 3) & 7),
+            promotion
+        )
+    }
+}
+
+/// polyglot opening book, loaded into memory
+#[derive(Debug, Clone)]
+pub struct PolyglotBook {
+    /// entries sorted by key
+    entries: Vec<BookEntry>,
+}
+
+/// polyglot book implementation
+impl PolyglotBook {
+    /// create new book from the bytes of a polyglot file
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut entries: Vec<BookEntry> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| BookEntry {
+                key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+                raw_move: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+                learn: u32::from_be_bytes(chunk[12..16].try_into().unwrap()),
+            })
+            .collect();
+
+        // books are sorted by key, sorting again is cheap insurance
+        entries.sort_by_key(|entry| entry.key);
+
+        Self { entries }
+    }
+
+    /// open polyglot book file
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let book = Self::from_bytes(&std::fs::read(path.as_ref())?);
+
+        if log_enabled!(Level::Info) {
+            info!(
+                "opened book {:?} with {} entries",
+                path.as_ref(),
+                book.len()
+            );
+        }
+
+        Ok(book)
+    }
+
+    /// number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// true if the book has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// polyglot hash of position
+    pub fn key(pos: &Chess) -> u64 {
+        pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0
+    }
+
+    /// entries of position
+    pub fn entries(&self, pos: &Chess) -> &[BookEntry] {
+        let key = Self::key(pos);
+
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+
+        &self.entries[start..end]
+    }
+
+    /// legal book moves of position as uci with their weights, highest weight first
+    pub fn moves(&self, pos: &Chess) -> Vec<(String, u16)> {
+        let mut moves: Vec<(String, u16)> = self
+            .entries(pos)
+            .iter()
+            .filter_map(|entry| {
+                let m = entry.raw_uci().parse::<UciMove>().ok()?.to_move(pos).ok()?;
+
+                Some((
+                    UciMove::from_move(m, CastlingMode::Standard).to_string(),
+                    entry.weight,
+                ))
+            })
+            .collect();
+
+        // stable, so equal weights keep the book order
+        moves.sort_by_key(|(_, weight)| std::cmp::Reverse(*weight));
+
+        moves
+    }
+
+    /// book move of position with the highest weight, None if there is no move
+    /// with at least the minimum weight
+    pub fn best_move(&self, pos: &Chess, min_weight: u16) -> Option<String> {
+        self.moves(pos)
+            .into_iter()
+            .find(|(_, weight)| *weight >= min_weight)
+            .map(|(uci, _)| uci)
+    }
+}
+
+#[test]
+fn polyglot_book() {
+    let start = Chess::default();
+
+    let entry = |key: u64, raw_move: u16, weight: u16| -> Vec<u8> {
+        let mut bytes = vec![];
+
+        bytes.extend_from_slice(&key.to_be_bytes());
+        bytes.extend_from_slice(&raw_move.to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+
+        bytes
+    };
+
+    let encode = |from_file: u16, from_rank: u16, to_file: u16, to_rank: u16| {
+        to_file | (to_rank << 3) | (from_file << 6) | (from_rank << 9)
+    };
+
+    let mut bytes = entry(0x1234, encode(4, 0, 7, 0), 1);
+
+    bytes.extend(entry(PolyglotBook::key(&start), encode(4, 1, 4, 3), 10));
+    bytes.extend(entry(PolyglotBook::key(&start), encode(3, 1, 3, 3), 20));
+
+    let book = PolyglotBook::from_bytes(&bytes);
+
+    assert_eq!(PolyglotBook::key(&start), 0x463b96181691fc9c);
+    assert_eq!(book.len(), 3);
+    assert_eq!(
+        book.moves(&start),
+        vec![("d2d4".to_string(), 20), ("e2e4".to_string(), 10)]
+    );
+    assert_eq!(book.best_move(&start, 15), Some("d2d4".to_string()));
+    assert_eq!(book.best_move(&start, 25), None);
+}
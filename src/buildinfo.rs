@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// build information of the crate, tells which optional subsystems a binary contains
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    /// crate version
+    pub version: String,
+    /// enabled cargo features
+    pub features: Vec<String>,
+    /// compiled integrations ( modules behind features or platforms )
+    pub integrations: Vec<String>,
+}
+
+/// build information of the running binary
+pub fn build_info() -> BuildInfo {
+    let features = [
+        ("capi", cfg!(feature = "capi")),
+        ("chaos", cfg!(feature = "chaos")),
+        ("chess", cfg!(feature = "chess")),
+        ("python", cfg!(feature = "python")),
+        ("sqlite", cfg!(feature = "sqlite")),
+    ];
+
+    let integrations = [
+        ("benchmark", cfg!(feature = "chess")),
+        ("capi", cfg!(feature = "capi")),
+        ("chaos", cfg!(feature = "chaos")),
+        ("daemon", cfg!(unix)),
+        ("matchdb", cfg!(feature = "sqlite")),
+        ("python", cfg!(feature = "python")),
+        ("tournament", cfg!(feature = "chess")),
+        ("verify", cfg!(feature = "chess")),
+    ];
+
+    let enabled = |list: &[(&str, bool)]| -> Vec<String> {
+        list.iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name.to_string())
+            .collect()
+    };
+
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        features: enabled(&features),
+        integrations: enabled(&integrations),
+    }
+}
+
+#[test]
+fn build_info_features() {
+    let info = build_info();
+
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+        info.features.contains(&"chess".to_string()),
+        cfg!(feature = "chess")
+    );
+    assert_eq!(
+        info.integrations.contains(&"daemon".to_string()),
+        cfg!(unix)
+    );
+}
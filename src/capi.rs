@@ -0,0 +1,401 @@
+use log::{debug, log_enabled, warn, Level};
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::server_side::*;
+use crate::uciengine::*;
+
+/// info callback, called with the analysis info as json and the registered user data,
+/// the json string is only valid during the call
+pub type InfoCallback = extern "C" fn(json: *const c_char, user_data: *mut c_void);
+
+/// registered callback with its user data
+struct Callback {
+    callback: InfoCallback,
+    user_data: *mut c_void,
+}
+
+// the caller is responsible for the user data being usable from the callback thread
+unsafe impl Send for Callback {}
+
+/// opaque engine handle
+pub struct CEngine {
+    runtime: tokio::runtime::Runtime,
+    engine: std::sync::Arc<UciEngine>,
+    /// id of the last started search with the json of its result once it finished,
+    /// results of earlier searches are discarded
+    result: std::sync::Arc<std::sync::Mutex<(u64, Option<String>)>>,
+    /// task calling the info callback
+    callback_task: Option<tokio::task::JoinHandle<()>>,
+}
+
+/// c string argument as str, None for null or invalid utf8
+unsafe fn arg<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(ptr).to_str().ok()
+}
+
+/// string as c string owned by the caller, null on interior nul
+fn to_c_string(value: String) -> *mut c_char {
+    CString::new(value)
+        .map(|value| value.into_raw())
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// spawn engine at path, returns null on failure,
+/// the handle has to be freed with uciengine_free
+///
+/// # Safety
+///
+/// path has to be a valid nul terminated string
+#[no_mangle]
+pub unsafe extern "C" fn uciengine_new(path: *const c_char) -> *mut CEngine {
+    let path = match arg(path) {
+        Some(path) => path,
+        _ => return std::ptr::null_mut(),
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            warn!("could not create runtime {:?}", err);
+
+            return std::ptr::null_mut();
+        }
+    };
+
+    let engine = {
+        let _guard = runtime.enter();
+
+        UciEngine::new(path)
+    };
+
+    Box::into_raw(Box::new(CEngine {
+        runtime,
+        engine,
+        result: std::sync::Arc::new(std::sync::Mutex::new((0, None))),
+        callback_task: None,
+    }))
+}
+
+/// quit engine and free handle
+///
+/// # Safety
+///
+/// handle has to be returned by uciengine_new and not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn uciengine_free(handle: *mut CEngine) {
+    if handle.is_null() {
+        return;
+    }
+
+    let handle = Box::from_raw(handle);
+
+    handle.engine.quit();
+
+    if let Some(callback_task) = &handle.callback_task {
+        callback_task.abort();
+    }
+
+    // give the engine time to quit gracefully, the sleep is created on the runtime
+    handle.runtime.block_on(async {
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    });
+}
+
+/// go job searching the position of fen ( None for the starting position ) after
+/// moves, go params are parsed like a go command of a gui, without params the job
+/// sends a plain go, None if the params are malformed
+fn search_job(fen: Option<&str>, moves: Option<&str>, go_params: Option<&str>) -> Option<GoJob> {
+    let params = match GoParams::parse(go_params.unwrap_or("")) {
+        Ok(params) => params,
+        Err(err) => {
+            warn!("invalid go params {:?}", err);
+
+            return None;
+        }
+    };
+
+    let go_job = params.to_go_job();
+
+    let go_job = match fen {
+        Some(fen) => go_job.pos_fen(fen),
+        _ => go_job.pos_startpos(),
+    };
+
+    match moves.filter(|moves| !moves.trim().is_empty()) {
+        Some(moves) => Some(go_job.pos_moves(moves)),
+        _ => Some(go_job),
+    }
+}
+
+/// start search, fen null for the starting position, moves space separated uci moves
+/// or null, go_params the parameters of the go command ( "depth 20",
+/// "movetime 1000 searchmoves e2e4 d2d4" ) or null for a plain go, the result of the
+/// previous search is cleared, returns 0 on success, -2 if go_params are malformed
+///
+/// # Safety
+///
+/// handle has to be valid, the strings have to be null or valid nul terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn uciengine_start_search(
+    handle: *mut CEngine,
+    fen: *const c_char,
+    moves: *const c_char,
+    go_params: *const c_char,
+) -> c_int {
+    let handle = match handle.as_mut() {
+        Some(handle) => handle,
+        _ => return -1,
+    };
+
+    let go_job = match search_job(arg(fen), arg(moves), arg(go_params)) {
+        Some(go_job) => go_job,
+        _ => return -2,
+    };
+
+    let pending = handle.engine.try_go(go_job);
+    let search = pending.id();
+
+    *handle.result.lock().unwrap() = (search, None);
+
+    let result = handle.result.clone();
+
+    handle.runtime.spawn(async move {
+        let json = match pending.await {
+            Ok(go_result) => serde_json::json!({
+                "bestmove": go_result.bestmove,
+                "ponder": go_result.ponder,
+                "info": go_result.ai.to_serde(),
+            }),
+            Err(err) => serde_json::json!({ "error": err.to_string() }),
+        };
+
+        let mut result = result.lock().unwrap();
+
+        // a search started since then owns the result
+        if result.0 == search {
+            result.1 = Some(json.to_string());
+        }
+    });
+
+    0
+}
+
+/// stop running search, its result becomes available through uciengine_poll_result
+///
+/// # Safety
+///
+/// handle has to be valid
+#[no_mangle]
+pub unsafe extern "C" fn uciengine_stop(handle: *mut CEngine) {
+    if let Some(handle) = handle.as_ref() {
+        handle.engine.issue_command("stop");
+    }
+}
+
+/// latest analysis info as json, the string has to be freed with uciengine_string_free
+///
+/// # Safety
+///
+/// handle has to be valid
+#[no_mangle]
+pub unsafe extern "C" fn uciengine_poll_info(handle: *mut CEngine) -> *mut c_char {
+    match handle.as_ref() {
+        Some(handle) => to_c_string(handle.engine.get_ai().to_json().unwrap_or_default()),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// result of the last search as json ( bestmove, ponder, info ), or ( error ) if the
+/// search failed, null while searching, the string has to be freed with uciengine_string_free
+///
+/// # Safety
+///
+/// handle has to be valid
+#[no_mangle]
+pub unsafe extern "C" fn uciengine_poll_result(handle: *mut CEngine) -> *mut c_char {
+    match handle.as_ref() {
+        Some(handle) => match handle.result.lock().unwrap().1.to_owned() {
+            Some(json) => to_c_string(json),
+            _ => std::ptr::null_mut(),
+        },
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// register info callback, called from a runtime thread with every analysis info,
+/// replaces the previous callback, null callback unregisters
+///
+/// # Safety
+///
+/// handle has to be valid, user data has to be usable from another thread
+#[no_mangle]
+pub unsafe extern "C" fn uciengine_set_info_callback(
+    handle: *mut CEngine,
+    callback: Option<InfoCallback>,
+    user_data: *mut c_void,
+) {
+    let handle = match handle.as_mut() {
+        Some(handle) => handle,
+        _ => return,
+    };
+
+    if let Some(callback_task) = handle.callback_task.take() {
+        callback_task.abort();
+    }
+
+    let callback = match callback {
+        Some(callback) => Callback {
+            callback,
+            user_data,
+        },
+        _ => return,
+    };
+
+    let mut arx = handle.engine.atx.subscribe();
+
+    handle.callback_task = Some(handle.runtime.spawn(async move {
+        loop {
+            match arx.recv().await {
+                Ok(ai) => {
+                    if let Ok(Ok(json)) = ai.to_json().map(CString::new) {
+                        (callback.callback)(json.as_ptr(), callback.user_data);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    if log_enabled!(Level::Debug) {
+                        debug!("info callback lagged, skipped {}", skipped);
+                    }
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }));
+}
+
+/// free string returned by the api
+///
+/// # Safety
+///
+/// string has to be returned by the api and not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn uciengine_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// json of the result of the search of handle, polled until it is there
+#[cfg(all(test, unix))]
+unsafe fn wait_result(handle: *mut CEngine) -> String {
+    loop {
+        let result = uciengine_poll_result(handle);
+
+        if !result.is_null() {
+            let json = CStr::from_ptr(result).to_str().unwrap().to_string();
+
+            uciengine_string_free(result);
+
+            return json;
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn c_search() {
+    assert_eq!(
+        search_job(None, Some("e2e4"), Some("infinite searchmoves d7d5 e7e5"))
+            .unwrap()
+            .to_commands(),
+        vec![
+            "position startpos moves e2e4",
+            "go infinite searchmoves d7d5 e7e5"
+        ]
+    );
+    assert!(search_job(None, None, Some("depth x")).is_none());
+
+    let path = fake_engine("capi", "    go*) echo 'bestmove e2e4';;");
+    let path = CString::new(path).unwrap();
+
+    unsafe {
+        let handle = uciengine_new(path.as_ptr());
+
+        assert!(!handle.is_null());
+
+        // without params a plain go is sent
+        assert_eq!(
+            uciengine_start_search(handle, std::ptr::null(), std::ptr::null(), std::ptr::null()),
+            0
+        );
+
+        assert!(wait_result(handle).contains("\"bestmove\":\"e2e4\""));
+
+        uciengine_free(handle);
+    }
+
+    assert_eq!(
+        fake_engine_log(path.to_str().unwrap()),
+        vec![
+            "position startpos",
+            format!("go movetime {}", DEFAULT_MOVETIME).as_str(),
+            "quit"
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn c_search_results() {
+    let path = fake_engine(
+        "capi-results",
+        "    go\\ infinite) ;;
+    stop) echo 'bestmove a2a3';;
+    go*) echo 'bestmove e2e4';;",
+    );
+    let path = CString::new(path).unwrap();
+
+    let infinite = CString::new("infinite").unwrap();
+    let depth = CString::new("depth 1").unwrap();
+
+    unsafe {
+        let handle = uciengine_new(path.as_ptr());
+
+        uciengine_start_search(
+            handle,
+            std::ptr::null(),
+            std::ptr::null(),
+            infinite.as_ptr(),
+        );
+        uciengine_start_search(handle, std::ptr::null(), std::ptr::null(), depth.as_ptr());
+
+        // the stopped search finishes after the second one started
+        uciengine_stop(handle);
+
+        assert!(wait_result(handle).contains("\"bestmove\":\"e2e4\""));
+
+        uciengine_free(handle);
+    }
+
+    let path = CString::new(fake_engine("capi-error", "    go*) exit 1;;")).unwrap();
+
+    unsafe {
+        let handle = uciengine_new(path.as_ptr());
+
+        uciengine_start_search(handle, std::ptr::null(), std::ptr::null(), depth.as_ptr());
+
+        // a failed search has an error result
+        assert!(wait_result(handle).starts_with("{\"error\":\"engine exited"));
+
+        uciengine_free(handle);
+    }
+}
@@ -0,0 +1,176 @@
+use log::warn;
+
+use std::time::Duration;
+
+/// garbage lines injected into engine output
+const GARBAGE: [&str; 4] = [
+    "info depth x score cp",
+    "bestmov e2e4",
+    "\u{fffd}\u{fffd}\u{fffd}",
+    "info score mate",
+];
+
+/// fault injection of engine output, for testing how applications handle
+/// misbehaving engines, faults are drawn from a seeded generator so runs are reproducible
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChaosConfig {
+    /// seed of the fault generator
+    pub seed: u64,
+    /// probability of delaying a line and the maximum delay
+    pub delay: Option<(f64, Duration)>,
+    /// probability of truncating a line
+    pub truncate: f64,
+    /// probability of injecting a garbage line before a line
+    pub garbage: f64,
+    /// probability of disconnecting ( ending the engine output ) at a line
+    pub disconnect: f64,
+}
+
+/// chaos config implementation
+impl ChaosConfig {
+    /// create new chaos config without faults
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            delay: None,
+            truncate: 0.0,
+            garbage: 0.0,
+            disconnect: 0.0,
+        }
+    }
+
+    /// set probability and maximum of line delays and return self
+    pub fn delay(mut self, probability: f64, max: Duration) -> Self {
+        self.delay = Some((probability, max));
+
+        self
+    }
+
+    /// set probability of truncated lines and return self
+    pub fn truncate(mut self, probability: f64) -> Self {
+        self.truncate = probability;
+
+        self
+    }
+
+    /// set probability of garbage lines and return self
+    pub fn garbage(mut self, probability: f64) -> Self {
+        self.garbage = probability;
+
+        self
+    }
+
+    /// set probability of a disconnect per line and return self
+    pub fn disconnect(mut self, probability: f64) -> Self {
+        self.disconnect = probability;
+
+        self
+    }
+}
+
+/// fault injector of an engine output stream
+#[derive(Debug)]
+pub struct ChaosLines {
+    config: ChaosConfig,
+    /// xorshift state
+    state: u64,
+    /// line to deliver after an injected garbage line
+    held: Option<String>,
+    /// true once disconnected
+    disconnected: bool,
+}
+
+/// chaos lines implementation
+impl ChaosLines {
+    /// create new fault injector
+    pub fn new(config: ChaosConfig) -> Self {
+        Self {
+            config,
+            // xorshift must not start from zero
+            state: config.seed | 1,
+            held: None,
+            disconnected: false,
+        }
+    }
+
+    /// next random number between 0 and 1
+    fn next(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// true with given probability
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next() < probability
+    }
+
+    /// line held back by a garbage injection, to be delivered next
+    pub fn take_held(&mut self) -> Option<String> {
+        self.held.take()
+    }
+
+    /// apply faults to a line of engine output, None once disconnected,
+    /// a garbage line is returned in place of the line, which is then held back
+    pub async fn apply(&mut self, line: String) -> Option<String> {
+        if self.disconnected || self.chance(self.config.disconnect) {
+            if !self.disconnected {
+                warn!("chaos : disconnecting engine output");
+            }
+
+            self.disconnected = true;
+
+            return None;
+        }
+
+        if let Some((probability, max)) = self.config.delay {
+            if self.chance(probability) {
+                let delay = max.mul_f64(self.next());
+
+                tokio::time::sleep(delay).await;
+            }
+        }
+
+        if self.chance(self.config.garbage) {
+            let garbage = GARBAGE[(self.next() * GARBAGE.len() as f64) as usize % GARBAGE.len()];
+
+            self.held = Some(line);
+
+            return Some(garbage.to_string());
+        }
+
+        if self.chance(self.config.truncate) && !line.is_empty() {
+            let mut len = (self.next() * line.len() as f64) as usize;
+
+            while !line.is_char_boundary(len) {
+                len -= 1;
+            }
+
+            return Some(line[0..len].to_string());
+        }
+
+        Some(line)
+    }
+}
+
+#[tokio::test]
+async fn chaos_lines() {
+    let mut clean = ChaosLines::new(ChaosConfig::new(7));
+
+    assert_eq!(clean.apply("readyok".to_string()).await.unwrap(), "readyok");
+
+    let mut disconnecting = ChaosLines::new(ChaosConfig::new(7).disconnect(1.0));
+
+    assert!(disconnecting.apply("readyok".to_string()).await.is_none());
+    assert!(disconnecting.apply("readyok".to_string()).await.is_none());
+
+    let mut garbage = ChaosLines::new(ChaosConfig::new(7).garbage(1.0));
+
+    assert_ne!(
+        garbage.apply("readyok".to_string()).await.unwrap(),
+        "readyok"
+    );
+    assert_eq!(garbage.take_held().unwrap(), "readyok");
+}
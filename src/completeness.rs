@@ -0,0 +1,145 @@
+use crate::analysis::*;
+
+/// depth at which the depth part of the completeness is full
+pub const FULL_DEPTH: usize = 24;
+/// number of last iterations checked for stability
+const STABILITY_ITERATIONS: usize = 3;
+/// score change in centipawns still considered stable
+const STABILITY_CP: i32 = 30;
+/// score in centipawns on both sides of zero that counts as a sign flip
+const SIGN_FLIP_CP: i32 = 50;
+
+/// top line of a search at a given depth
+#[derive(Debug, Clone)]
+struct Iteration {
+    depth: usize,
+    bestmove: Option<String>,
+    sort_key: i32,
+}
+
+/// tracks the iterations of a search to estimate how complete its result is
+#[derive(Debug, Clone)]
+pub struct CompletenessTracker {
+    iterations: Vec<Iteration>,
+    seldepth: usize,
+    time: usize,
+    mate: bool,
+}
+
+/// completeness tracker implementation
+impl CompletenessTracker {
+    /// create new empty tracker
+    pub fn new() -> Self {
+        Self {
+            iterations: vec![],
+            seldepth: 0,
+            time: 0,
+            mate: false,
+        }
+    }
+
+    /// observe analysis info, only exact top lines count
+    pub fn observe(&mut self, ai: &AnalysisInfo) {
+        if ai.multipv > 1 || ai.depth == 0 || !matches!(ai.scoretype, ScoreType::Exact) {
+            return;
+        }
+
+        let iteration = Iteration {
+            depth: ai.depth,
+            bestmove: ai.bestmove(),
+            sort_key: ai.score.sort_key(),
+        };
+
+        match self.iterations.last_mut() {
+            Some(last) if last.depth >= ai.depth => *last = iteration,
+            _ => self.iterations.push(iteration),
+        }
+
+        self.seldepth = self.seldepth.max(ai.seldepth);
+        self.time = self.time.max(ai.time);
+        self.mate = matches!(ai.score, Score::Mate(moves) if moves > 0);
+    }
+
+    /// true if the score changed sign between the last two depths,
+    /// by at least SIGN_FLIP_CP on both sides of zero
+    pub fn score_sign_flipped(&self) -> bool {
+        match self.iterations.len() {
+            len if len >= 2 => {
+                let (prev, last) = (&self.iterations[len - 2], &self.iterations[len - 1]);
+
+                (prev.sort_key >= SIGN_FLIP_CP && last.sort_key <= -SIGN_FLIP_CP)
+                    || (prev.sort_key <= -SIGN_FLIP_CP && last.sort_key >= SIGN_FLIP_CP)
+            }
+            _ => false,
+        }
+    }
+
+    /// completeness between 0 and 1, from the depth reached ( selective depth counts
+    /// a quarter ), the stability of the last iterations and, if the search had a time
+    /// budget in milliseconds, the part of it used, a stable forced mate is complete
+    pub fn score(&self, budget: Option<usize>) -> f64 {
+        let last = match self.iterations.last() {
+            Some(last) => last,
+            _ => return 0.0,
+        };
+
+        let recent = &self.iterations[self.iterations.len().saturating_sub(STABILITY_ITERATIONS)..];
+
+        let stable = recent
+            .iter()
+            .filter(|iteration| {
+                iteration.bestmove == last.bestmove
+                    && (iteration.sort_key - last.sort_key).abs() <= STABILITY_CP
+            })
+            .count();
+
+        let stability = stable as f64 / STABILITY_ITERATIONS as f64;
+
+        if self.mate && stable == STABILITY_ITERATIONS {
+            return 1.0;
+        }
+
+        let depth = last.depth as f64 + self.seldepth.saturating_sub(last.depth) as f64 / 4.0;
+        let depth = (depth / FULL_DEPTH as f64).min(1.0);
+
+        match budget {
+            Some(budget) if budget > 0 => {
+                let used = (self.time as f64 / budget as f64).min(1.0);
+
+                0.4 * depth + 0.4 * stability + 0.2 * used
+            }
+            _ => 0.5 * depth + 0.5 * stability,
+        }
+    }
+}
+
+/// implement Default for CompletenessTracker
+impl Default for CompletenessTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn completeness() {
+    let mut tracker = CompletenessTracker::new();
+
+    assert_eq!(tracker.score(None), 0.0);
+
+    for depth in 1..=24 {
+        let mut ai = AnalysisInfo::new();
+
+        ai.parse(format!(
+            "info depth {} seldepth {} multipv 1 score cp 20 time {} pv e2e4 e7e5",
+            depth,
+            depth,
+            depth * 10
+        ))
+        .unwrap();
+
+        tracker.observe(&ai);
+    }
+
+    assert_eq!(tracker.score(None), 1.0);
+    assert!((tracker.score(Some(480)) - 0.9).abs() < 1e-9);
+}
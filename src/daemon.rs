@@ -0,0 +1,451 @@
+use log::{debug, info, log_enabled, warn, Level};
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::analysis::*;
+use crate::buildinfo::*;
+use crate::uciengine::*;
+
+/// environment variable holding the socket path of a daemon to serve
+pub const DAEMON_SOCKET_ENV: &str = "UCIENGINE_DAEMON_SOCKET";
+/// environment variable holding the engine path of a daemon to serve
+pub const DAEMON_ENGINE_ENV: &str = "UCIENGINE_DAEMON_ENGINE";
+/// default time a daemon stays up without connections
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+/// number of attempts to connect to a daemon that was just started
+const CONNECT_ATTEMPTS: usize = 50;
+/// delay between connection attempts
+const CONNECT_DELAY: Duration = Duration::from_millis(50);
+/// part of a request deadline reserved for the round trip and queueing
+pub const DEADLINE_MARGIN: Duration = Duration::from_millis(50);
+
+/// search request sent to a daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonRequest {
+    /// position fen, starting position if None
+    pub fen: Option<String>,
+    /// space separated uci moves
+    pub moves: Option<String>,
+    /// uci options
+    pub uci_options: Vec<(String, String)>,
+    /// go options ( depth, movetime, ... )
+    pub go_options: Vec<(String, String)>,
+    /// labels of the job
+    #[serde(default)]
+    pub labels: Labels,
+    /// time in milliseconds the client waits for the response,
+    /// the search budget is capped to fit into it
+    #[serde(default)]
+    pub deadline_ms: Option<u64>,
+}
+
+/// daemon request implementation
+impl DaemonRequest {
+    /// create new request for the starting position
+    pub fn new() -> Self {
+        Self {
+            fen: None,
+            moves: None,
+            uci_options: vec![],
+            go_options: vec![],
+            labels: Labels::new(),
+            deadline_ms: None,
+        }
+    }
+
+    /// set deadline and return self
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline_ms = Some(deadline.as_millis() as u64);
+
+        self
+    }
+
+    /// set fen and return self
+    pub fn fen<T>(mut self, fen: T) -> Self
+    where
+        T: core::fmt::Display,
+    {
+        self.fen = Some(fen.to_string());
+
+        self
+    }
+
+    /// set moves and return self
+    pub fn moves<T>(mut self, moves: T) -> Self
+    where
+        T: core::fmt::Display,
+    {
+        self.moves = Some(moves.to_string());
+
+        self
+    }
+
+    /// add uci option and return self
+    pub fn uci_opt<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.uci_options.push((key.to_string(), value.to_string()));
+
+        self
+    }
+
+    /// add go option and return self
+    pub fn go_opt<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.go_options.push((key.to_string(), value.to_string()));
+
+        self
+    }
+
+    /// set label and return self
+    pub fn label<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.labels.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    /// convert to go job
+    pub fn to_go_job(&self) -> GoJob {
+        let mut go_job = match &self.fen {
+            Some(fen) => GoJob::new().pos_fen(fen),
+            _ => GoJob::new().pos_startpos(),
+        };
+
+        if let Some(moves) = &self.moves {
+            go_job = go_job.pos_moves(moves);
+        }
+
+        for (key, value) in self.uci_options.iter() {
+            go_job = go_job.uci_opt(key, value);
+        }
+
+        for (key, value) in self.go_options.iter() {
+            go_job = go_job.go_opt(key, value);
+        }
+
+        for (key, value) in self.labels.iter() {
+            go_job = go_job.label(key, value);
+        }
+
+        if let Some(deadline_ms) = self.deadline_ms {
+            go_job = go_job.deadline(Duration::from_millis(deadline_ms), DEADLINE_MARGIN);
+        }
+
+        go_job
+    }
+}
+
+/// implement Default for DaemonRequest
+impl Default for DaemonRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// search response of a daemon
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DaemonResponse {
+    /// best move if any
+    pub bestmove: Option<String>,
+    /// ponder if any
+    pub ponder: Option<String>,
+    /// last analysis info
+    pub ai: AnalysisInfoSerde,
+    /// labels of the daemon engine and the job
+    #[serde(default)]
+    pub labels: Labels,
+}
+
+/// invalid data error
+fn invalid_data<E: std::fmt::Display>(err: E) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// serve requests of one connection, one json request per line,
+/// answered with one json response per line
+async fn serve_connection(engine: std::sync::Arc<UciEngine>, stream: UnixStream) {
+    let (reader, mut writer) = stream.into_split();
+
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let request: DaemonRequest = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                warn!("invalid daemon request {} : {:?}", line, err);
+
+                break;
+            }
+        };
+
+        let go_result = match engine.try_go(request.to_go_job()).await {
+            Ok(go_result) => go_result,
+            // engine is gone
+            _ => break,
+        };
+
+        let response = DaemonResponse {
+            bestmove: go_result.bestmove,
+            ponder: go_result.ponder,
+            ai: go_result.ai.to_serde(),
+            labels: go_result.labels,
+        };
+
+        let json = match serde_json::to_string(&response) {
+            Ok(json) => json,
+            _ => break,
+        };
+
+        if writer
+            .write_all(format!("{}\n", json).as_bytes())
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// serve engine on unix socket until no connection was open for the idle timeout,
+/// the engine is spawned once and kept warm for all connections
+pub async fn serve<P, T>(socket: P, engine_path: T, idle_timeout: Duration) -> std::io::Result<()>
+where
+    P: AsRef<Path>,
+    T: core::fmt::Display,
+{
+    let socket = socket.as_ref();
+
+    // a socket file left by a daemon that did not shut down cleanly blocks bind
+    if socket.exists() && UnixStream::connect(socket).await.is_err() {
+        std::fs::remove_file(socket)?;
+    }
+
+    let listener = UnixListener::bind(socket)?;
+
+    let engine = UciEngine::new(engine_path);
+
+    let open = std::sync::Arc::new(AtomicUsize::new(0));
+
+    if log_enabled!(Level::Info) {
+        info!(
+            "engine daemon listening on {:?}, build {:?}",
+            socket,
+            build_info()
+        );
+    }
+
+    loop {
+        match tokio::time::timeout(idle_timeout, listener.accept()).await {
+            Ok(Ok((stream, _))) => {
+                if log_enabled!(Level::Debug) {
+                    debug!("engine daemon accepted connection");
+                }
+
+                let engine = engine.clone();
+                let open = open.clone();
+
+                open.fetch_add(1, Ordering::SeqCst);
+
+                tokio::spawn(async move {
+                    serve_connection(engine, stream).await;
+
+                    open.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+            Ok(Err(err)) => warn!("engine daemon accept failed {:?}", err),
+            _ => {
+                if open.load(Ordering::SeqCst) == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    if log_enabled!(Level::Info) {
+        info!("engine daemon idle, shutting down");
+    }
+
+    engine.quit();
+
+    std::fs::remove_file(socket)
+}
+
+/// serve daemon if the process was started as one by DaemonClient::connect,
+/// returns true if it served ( the caller should exit then ),
+/// call this first thing in main of a program using DaemonClient
+pub async fn serve_if_requested() -> bool {
+    match (
+        std::env::var(DAEMON_SOCKET_ENV),
+        std::env::var(DAEMON_ENGINE_ENV),
+    ) {
+        (Ok(socket), Ok(engine_path)) => {
+            if let Err(err) = serve(&socket, engine_path, DEFAULT_IDLE_TIMEOUT).await {
+                warn!("engine daemon on {} failed {:?}", socket, err);
+            }
+
+            true
+        }
+        _ => false,
+    }
+}
+
+/// client of an engine daemon
+pub struct DaemonClient {
+    lines: tokio::io::Lines<BufReader<tokio::net::unix::OwnedReadHalf>>,
+    writer: tokio::net::unix::OwnedWriteHalf,
+}
+
+/// daemon client implementation
+impl DaemonClient {
+    /// connect to the daemon on socket, if there is none start one
+    /// ( the current executable is started again in the background as daemon,
+    /// see serve_if_requested ) and connect to it once it listens,
+    /// a process started as daemon does not start another one
+    pub async fn connect<P, T>(socket: P, engine_path: T) -> std::io::Result<Self>
+    where
+        P: AsRef<Path>,
+        T: core::fmt::Display,
+    {
+        let socket = socket.as_ref();
+
+        if let Ok(stream) = UnixStream::connect(socket).await {
+            return Ok(Self::from_stream(stream));
+        }
+
+        // the program did not call serve_if_requested, every daemon would start another
+        if std::env::var_os(DAEMON_SOCKET_ENV).is_some() {
+            return Err(std::io::Error::other(format!(
+                "{} is set, not starting an engine daemon from a daemon",
+                DAEMON_SOCKET_ENV
+            )));
+        }
+
+        if log_enabled!(Level::Info) {
+            info!("starting engine daemon on {:?}", socket);
+        }
+
+        std::process::Command::new(std::env::current_exe()?)
+            .env(DAEMON_SOCKET_ENV, socket)
+            .env(DAEMON_ENGINE_ENV, engine_path.to_string())
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()?;
+
+        let mut last_err = None;
+
+        for _ in 0..CONNECT_ATTEMPTS {
+            tokio::time::sleep(CONNECT_DELAY).await;
+
+            match UnixStream::connect(socket).await {
+                Ok(stream) => return Ok(Self::from_stream(stream)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| invalid_data("engine daemon did not start")))
+    }
+
+    /// create client from connected stream
+    fn from_stream(stream: UnixStream) -> Self {
+        let (reader, writer) = stream.into_split();
+
+        Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        }
+    }
+
+    /// send request and wait for its response
+    pub async fn go(&mut self, request: &DaemonRequest) -> std::io::Result<DaemonResponse> {
+        let json = serde_json::to_string(request).map_err(invalid_data)?;
+
+        self.writer
+            .write_all(format!("{}\n", json).as_bytes())
+            .await?;
+
+        match self.lines.next_line().await? {
+            Some(line) => serde_json::from_str(&line).map_err(invalid_data),
+            _ => Err(invalid_data("engine daemon closed the connection")),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn daemon_serve() {
+    let path = fake_engine("daemon", "    go*) echo 'bestmove e2e4';;");
+
+    let socket = std::env::temp_dir().join(format!("uciengine-daemon-{}.sock", std::process::id()));
+
+    let daemon = tokio::spawn(serve(
+        socket.clone(),
+        path.clone(),
+        Duration::from_millis(200),
+    ));
+
+    let stream = loop {
+        match UnixStream::connect(&socket).await {
+            Ok(stream) => break stream,
+            _ => tokio::time::sleep(CONNECT_DELAY).await,
+        }
+    };
+
+    let mut client = DaemonClient::from_stream(stream);
+
+    let response = client
+        .go(&DaemonRequest::new().go_opt("depth", 1).label("user", "1"))
+        .await
+        .unwrap();
+
+    assert_eq!(response.bestmove.as_deref(), Some("e2e4"));
+    assert_eq!(
+        response.labels.get("user").map(|user| user.as_str()),
+        Some("1")
+    );
+
+    drop(client);
+
+    // the daemon shuts down once idle, quitting its engine and removing its socket
+    daemon.await.unwrap().unwrap();
+
+    assert!(!socket.exists());
+    assert_eq!(
+        fake_engine_log(&path),
+        ["position startpos", "go depth 1", "quit"]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn daemon_no_spawn_from_daemon() {
+    let socket =
+        std::env::temp_dir().join(format!("uciengine-no-daemon-{}.sock", std::process::id()));
+
+    std::env::set_var(DAEMON_SOCKET_ENV, &socket);
+
+    let connected = DaemonClient::connect(&socket, "engine").await;
+
+    std::env::remove_var(DAEMON_SOCKET_ENV);
+
+    assert_eq!(
+        connected.err().map(|err| err.kind()),
+        Some(std::io::ErrorKind::Other)
+    );
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::*;
+
+/// resignation and draw offer policy of a bot, scores are seen from the bot
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DecisionPolicy {
+    /// resign when the score stayed at or below minus this many centipawns
+    pub resign_cp: i32,
+    /// for this many consecutive searches
+    pub resign_moves: usize,
+    /// and the engine wdl, if it reports one, gives at most this many
+    /// per mille of wins and draws together
+    pub resign_wdl: u64,
+    /// accept a draw when the score, shifted by the contempt, stayed at or below
+    /// this many centipawns
+    pub draw_cp: i32,
+    /// for this many consecutive searches
+    pub draw_moves: usize,
+    /// and the engine wdl, if it reports one, gives at least this many per mille of draws
+    pub draw_wdl: u64,
+    /// centipawns added to the score before deciding on a draw,
+    /// positive values make the bot play on in equal positions
+    pub contempt: i32,
+}
+
+/// decision policy implementation
+impl DecisionPolicy {
+    /// create new policy with conservative defaults
+    pub fn new() -> Self {
+        Self {
+            resign_cp: 700,
+            resign_moves: 5,
+            resign_wdl: 20,
+            draw_cp: 20,
+            draw_moves: 8,
+            draw_wdl: 700,
+            contempt: 0,
+        }
+    }
+
+    /// set resign threshold in centipawns over consecutive searches and return self
+    pub fn resign(mut self, cp: i32, moves: usize) -> Self {
+        self.resign_cp = cp;
+        self.resign_moves = moves.max(1);
+
+        self
+    }
+
+    /// set draw threshold in centipawns over consecutive searches and return self
+    pub fn draw(mut self, cp: i32, moves: usize) -> Self {
+        self.draw_cp = cp;
+        self.draw_moves = moves.max(1);
+
+        self
+    }
+
+    /// set wdl thresholds in per mille for resigning and accepting draws and return self
+    pub fn wdl(mut self, resign_wdl: u64, draw_wdl: u64) -> Self {
+        self.resign_wdl = resign_wdl;
+        self.draw_wdl = draw_wdl;
+
+        self
+    }
+
+    /// set contempt in centipawns and return self
+    pub fn contempt(mut self, contempt: i32) -> Self {
+        self.contempt = contempt;
+
+        self
+    }
+}
+
+/// implement Default for DecisionPolicy
+impl Default for DecisionPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// wdl in per mille, None if the engine did not report one
+fn wdl_permille(ai: &AnalysisInfo) -> Option<(u64, u64, u64)> {
+    let total = ai.wdl.win + ai.wdl.draw + ai.wdl.loss;
+
+    if total == 0 {
+        return None;
+    }
+
+    Some((
+        ai.wdl.win * 1000 / total,
+        ai.wdl.draw * 1000 / total,
+        ai.wdl.loss * 1000 / total,
+    ))
+}
+
+/// last n searched infos of history, None if there are fewer
+fn last_searched(history: &[AnalysisInfo], n: usize) -> Option<Vec<&AnalysisInfo>> {
+    let last: Vec<&AnalysisInfo> = history
+        .iter()
+        .rev()
+        .filter(|ai| ai.depth > 0)
+        .take(n)
+        .collect();
+
+    if last.len() < n {
+        return None;
+    }
+
+    Some(last)
+}
+
+/// true if the bot should resign, history holds the final infos of its searches
+/// in game order, the score has to be lost for the last resign moves searches
+/// and the wdl, where reported, has to agree
+pub fn should_resign(history: &[AnalysisInfo], policy: &DecisionPolicy) -> bool {
+    let last = match last_searched(history, policy.resign_moves) {
+        Some(last) => last,
+        _ => return false,
+    };
+
+    last.iter().all(|ai| {
+        ai.score.cp_equivalent() <= -policy.resign_cp
+            && wdl_permille(ai).is_none_or(|(win, draw, _)| win + draw <= policy.resign_wdl)
+    })
+}
+
+/// true if the bot should accept a draw offer, history holds the final infos of its
+/// searches in game order, the score shifted by the contempt has to be no better than
+/// the draw threshold for the last draw moves searches and the wdl, where reported,
+/// has to give enough draws ( a lost position accepts regardless of the wdl draws )
+pub fn should_accept_draw(history: &[AnalysisInfo], policy: &DecisionPolicy) -> bool {
+    let last = match last_searched(history, policy.draw_moves) {
+        Some(last) => last,
+        _ => return false,
+    };
+
+    last.iter().all(|ai| {
+        let cp = ai.score.cp_equivalent() + policy.contempt;
+
+        cp <= policy.draw_cp
+            && (cp < -policy.draw_cp
+                || wdl_permille(ai).is_none_or(|(_, draw, _)| draw >= policy.draw_wdl))
+    })
+}
+
+#[test]
+fn resign_and_draw() {
+    let history = |lines: &[&str]| -> Vec<AnalysisInfo> {
+        lines
+            .iter()
+            .map(|line| {
+                let mut ai = AnalysisInfo::new();
+
+                ai.parse(line).unwrap();
+
+                ai
+            })
+            .collect()
+    };
+
+    let policy = DecisionPolicy::new().resign(500, 2).draw(20, 2);
+
+    let losing = history(&[
+        "info depth 20 score cp -200",
+        "info depth 20 score cp -600",
+        "info depth 20 score mate -5",
+    ]);
+
+    assert!(should_resign(&losing, &policy));
+    assert!(!should_resign(&losing[..2], &policy));
+    assert!(should_accept_draw(&losing, &policy));
+
+    let drawn = history(&[
+        "info depth 20 score cp 5 wdl 20 900 80",
+        "info depth 20 score cp 0 wdl 10 950 40",
+    ]);
+
+    assert!(!should_resign(&drawn, &policy));
+    assert!(should_accept_draw(&drawn, &policy));
+    assert!(!should_accept_draw(&drawn, &policy.contempt(50)));
+
+    let unclear = history(&[
+        "info depth 20 score cp 5 wdl 300 400 300",
+        "info depth 20 score cp 0 wdl 300 400 300",
+    ]);
+
+    assert!(!should_accept_draw(&unclear, &policy));
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+/// canonical kind of an engine diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DiagnosticKind {
+    /// neural network loaded
+    NnueLoaded,
+    /// neural network could not be loaded
+    NnueMissing,
+    /// tablebases found
+    TablebasesFound,
+    /// warning
+    Warning,
+    /// error
+    Error,
+    /// diagnostic no rule matched
+    Unknown,
+}
+
+/// engine diagnostic, an info string normalized to its canonical kind
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineDiagnostic {
+    /// canonical kind
+    pub kind: DiagnosticKind,
+    /// text of the info string as the engine sent it
+    pub text: String,
+}
+
+/// diagnostics of known engine builds, localized ones included, in order of precedence
+const KNOWN_DIAGNOSTICS: &[(&str, DiagnosticKind)] = &[
+    // english
+    ("not loaded", DiagnosticKind::NnueMissing),
+    ("failed to load", DiagnosticKind::NnueMissing),
+    ("nnue evaluation using", DiagnosticKind::NnueLoaded),
+    ("loaded nnue", DiagnosticKind::NnueLoaded),
+    ("tablebases found", DiagnosticKind::TablebasesFound),
+    // german
+    ("nnue nicht geladen", DiagnosticKind::NnueMissing),
+    ("nnue geladen", DiagnosticKind::NnueLoaded),
+    ("tablebases gefunden", DiagnosticKind::TablebasesFound),
+    ("warnung", DiagnosticKind::Warning),
+    ("fehler", DiagnosticKind::Error),
+    // french
+    ("nnue non chargé", DiagnosticKind::NnueMissing),
+    ("nnue chargé", DiagnosticKind::NnueLoaded),
+    ("tables trouvées", DiagnosticKind::TablebasesFound),
+    ("avertissement", DiagnosticKind::Warning),
+    ("erreur", DiagnosticKind::Error),
+    // spanish
+    ("nnue no cargada", DiagnosticKind::NnueMissing),
+    ("nnue cargada", DiagnosticKind::NnueLoaded),
+    ("tablas encontradas", DiagnosticKind::TablebasesFound),
+    ("advertencia", DiagnosticKind::Warning),
+    // russian
+    ("nnue не загружен", DiagnosticKind::NnueMissing),
+    ("nnue загружен", DiagnosticKind::NnueLoaded),
+    ("предупреждение", DiagnosticKind::Warning),
+    ("ошибка", DiagnosticKind::Error),
+    // english catch all, after the specific rules
+    ("warning", DiagnosticKind::Warning),
+    ("error", DiagnosticKind::Error),
+];
+
+/// table mapping diagnostics to their canonical kinds, rules match case insensitive
+/// substrings of the info string and the first matching rule wins
+#[derive(Debug, Clone)]
+pub struct DiagnosticTable {
+    rules: Vec<(String, DiagnosticKind)>,
+}
+
+/// diagnostic table implementation
+impl DiagnosticTable {
+    /// create new table without rules
+    pub fn empty() -> Self {
+        Self { rules: vec![] }
+    }
+
+    /// create new table with the rules of known builds
+    pub fn new() -> Self {
+        Self {
+            rules: KNOWN_DIAGNOSTICS
+                .iter()
+                .map(|(pattern, kind)| (pattern.to_string(), *kind))
+                .collect(),
+        }
+    }
+
+    /// add rule taking precedence over the rules so far and return self
+    pub fn rule<T: AsRef<str>>(mut self, pattern: T, kind: DiagnosticKind) -> Self {
+        self.rules
+            .insert(0, (pattern.as_ref().to_lowercase(), kind));
+
+        self
+    }
+
+    /// normalize info string line, None if it is not an info string
+    pub fn normalize(&self, line: &str) -> Option<EngineDiagnostic> {
+        let text = line.strip_prefix("info string")?.trim();
+
+        let lower = text.to_lowercase();
+
+        let kind = self
+            .rules
+            .iter()
+            .find(|(pattern, _)| lower.contains(pattern.as_str()))
+            .map_or(DiagnosticKind::Unknown, |(_, kind)| *kind);
+
+        Some(EngineDiagnostic {
+            kind,
+            text: text.to_string(),
+        })
+    }
+}
+
+/// implement Default for DiagnosticTable
+impl Default for DiagnosticTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn normalize_diagnostics() {
+    let table = DiagnosticTable::new();
+
+    let kind = |line: &str| table.normalize(line).map(|diagnostic| diagnostic.kind);
+
+    assert_eq!(
+        kind("info string NNUE evaluation using nn-b1a57edbea57.nnue enabled"),
+        Some(DiagnosticKind::NnueLoaded)
+    );
+    assert_eq!(
+        kind("info string NNUE geladen : nn-b1a57edbea57.nnue"),
+        Some(DiagnosticKind::NnueLoaded)
+    );
+    assert_eq!(
+        kind("info string NNUE nicht geladen"),
+        Some(DiagnosticKind::NnueMissing)
+    );
+    assert_eq!(
+        kind("info string Found 145 tablebases"),
+        Some(DiagnosticKind::Unknown)
+    );
+    assert_eq!(kind("info depth 10"), None);
+
+    let table = table.rule("found 145 tablebases", DiagnosticKind::TablebasesFound);
+
+    assert_eq!(
+        table
+            .normalize("info string Found 145 tablebases")
+            .map(|diagnostic| diagnostic.kind),
+        Some(DiagnosticKind::TablebasesFound)
+    );
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::*;
+
+/// score gap in centipawns between the top lines at which closeness is 1 / e
+const CLOSENESS_CP: f64 = 100.0;
+/// mean score swing in centipawns between depths that counts as fully volatile
+const VOLATILITY_CP: f64 = 100.0;
+
+/// difficulty estimate of a position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Difficulty {
+    /// fen of the position
+    pub fen: String,
+    /// closeness of the top two lines at the final depth between 0 and 1
+    /// ( 1 if they are equal, 0 if there is a single line )
+    pub closeness: f64,
+    /// part of the search depth the bestmove needed to settle, between 0 and 1
+    pub stability_depth: f64,
+    /// mean score swing of the top line between depths, between 0 and 1
+    pub volatility: f64,
+    /// difficulty between 0 ( easy ) and 1 ( hard )
+    pub score: f64,
+}
+
+/// estimate difficulty of position from the infos of a quick multipv analysis
+/// ( every info line, in order, multipv 2 or more ), combining how close the top
+/// lines are, how deep the bestmove needed to settle and how much the score swung,
+/// used by puzzle generation and to allocate more budget to hard positions
+pub fn difficulty<T>(position: T, quick_analysis: &[AnalysisInfo]) -> Difficulty
+where
+    T: core::fmt::Display,
+{
+    // top line per depth, the last info of a depth wins
+    let mut top: Vec<(usize, Option<String>, i32)> = vec![];
+    let mut final_depth = 0;
+
+    for ai in quick_analysis.iter().filter(|ai| ai.depth > 0) {
+        final_depth = final_depth.max(ai.depth);
+
+        if ai.multipv > 1 {
+            continue;
+        }
+
+        let line = (ai.depth, ai.bestmove(), ai.score.cp_equivalent());
+
+        match top.last_mut() {
+            Some(last) if last.0 == ai.depth => *last = line,
+            _ => top.push(line),
+        }
+    }
+
+    let final_key = |multipv: usize| {
+        quick_analysis
+            .iter()
+            .rev()
+            .find(|ai| ai.depth == final_depth && ai.multipv.max(1) == multipv)
+            .map(|ai| ai.score.cp_equivalent())
+    };
+
+    let closeness = match (final_key(1), final_key(2)) {
+        (Some(first), Some(second)) => (-((first - second).abs() as f64) / CLOSENESS_CP).exp(),
+        _ => 0.0,
+    };
+
+    let stability_depth = match top.last() {
+        Some((last_depth, last_move, _)) if *last_depth > 0 => {
+            let settled = top
+                .iter()
+                .rev()
+                .take_while(|(_, bestmove, _)| bestmove == last_move)
+                .last()
+                .map(|(depth, _, _)| *depth)
+                .unwrap_or(*last_depth);
+
+            (settled.saturating_sub(1)) as f64 / *last_depth as f64
+        }
+        _ => 0.0,
+    };
+
+    let volatility = match top.len() {
+        len if len > 1 => {
+            let swing: i32 = top
+                .windows(2)
+                .map(|pair| (pair[1].2 - pair[0].2).abs())
+                .sum();
+
+            (swing as f64 / (len - 1) as f64 / VOLATILITY_CP).min(1.0)
+        }
+        _ => 0.0,
+    };
+
+    Difficulty {
+        fen: position.to_string(),
+        closeness,
+        stability_depth,
+        volatility,
+        score: 0.4 * closeness + 0.35 * stability_depth + 0.25 * volatility,
+    }
+}
+
+#[test]
+fn position_difficulty() {
+    let parse = |lines: &[&str]| -> Vec<AnalysisInfo> {
+        lines
+            .iter()
+            .map(|line| {
+                let mut ai = AnalysisInfo::new();
+
+                ai.parse(line).unwrap();
+
+                ai
+            })
+            .collect()
+    };
+
+    let easy = parse(&[
+        "info depth 1 multipv 1 score cp 300 pv d1h5 e7e5",
+        "info depth 1 multipv 2 score cp 0 pv e2e4 e7e5",
+        "info depth 2 multipv 1 score cp 300 pv d1h5 e7e5",
+        "info depth 2 multipv 2 score cp 0 pv e2e4 e7e5",
+    ]);
+
+    let hard = parse(&[
+        "info depth 1 multipv 1 score cp 50 pv e2e4 e7e5",
+        "info depth 1 multipv 2 score cp 40 pv d2d4 d7d5",
+        "info depth 2 multipv 1 score cp -60 pv d2d4 d7d5",
+        "info depth 2 multipv 2 score cp -70 pv e2e4 e7e5",
+    ]);
+
+    let easy = difficulty("easy", &easy);
+    let hard = difficulty("hard", &hard);
+
+    assert_eq!(easy.stability_depth, 0.0);
+    assert_eq!(hard.stability_depth, 0.5);
+    assert!(hard.score > easy.score);
+}
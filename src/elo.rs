@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game::*;
+
+#[cfg(feature = "chess")]
+use crate::profile::*;
+#[cfg(feature = "chess")]
+use crate::timecontrol::*;
+#[cfg(feature = "chess")]
+use crate::tournament::*;
+
+/// z value of the 95% confidence interval
+const Z_95: f64 = 1.96;
+
+/// elo difference corresponding to an expected score
+pub fn elo_diff(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// elo estimate with 95% confidence interval
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EloEstimate {
+    /// estimated elo
+    pub elo: f64,
+    /// lower bound of the 95% confidence interval
+    pub lower: f64,
+    /// upper bound of the 95% confidence interval
+    pub upper: f64,
+    /// record the estimate is based on
+    pub wdl: WdlRecord,
+}
+
+/// elo estimate implementation
+impl EloEstimate {
+    /// estimate elo from record against an opponent of given elo, None if no games,
+    /// a perfect score is treated as half a point short of it
+    pub fn from_record(wdl: WdlRecord, opponent_elo: f64) -> Option<Self> {
+        let score = wdl.score()?;
+        let games = wdl.games() as f64;
+
+        // standard error of the mean score per game
+        let variance = (wdl.wins as f64 * (1.0 - score).powi(2)
+            + wdl.draws as f64 * (0.5 - score).powi(2)
+            + wdl.losses as f64 * score.powi(2))
+            / games;
+        let stderr = (variance / games).sqrt();
+
+        let bound = 0.5 / games;
+        let clamp = |score: f64| score.max(bound).min(1.0 - bound);
+
+        Some(Self {
+            elo: opponent_elo + elo_diff(clamp(score)),
+            lower: opponent_elo + elo_diff(clamp(score - Z_95 * stderr)),
+            upper: opponent_elo + elo_diff(clamp(score + Z_95 * stderr)),
+            wdl,
+        })
+    }
+
+    /// half width of the confidence interval
+    pub fn margin(&self) -> f64 {
+        (self.upper - self.lower) / 2.0
+    }
+}
+
+/// estimate elo of engine by playing a gauntlet of games against a reference engine
+/// of known strength ( UCI_Elo option of the reference, see EngineProfile::fixed_elo ),
+/// colors alternate so an odd number of games is rounded up, the two profiles
+/// must have different names, None if no game finished
+#[cfg(feature = "chess")]
+pub async fn estimate_elo(
+    engine: &EngineProfile,
+    reference: &EngineProfile,
+    games: usize,
+    tc: MatchTimecontrol,
+) -> Option<EloEstimate> {
+    let reference_elo = reference
+        .option("UCI_Elo")
+        .and_then(|elo| elo.parse::<f64>().ok())
+        .unwrap_or(0.0);
+
+    let records = Tournament::new()
+        .engine(engine.to_owned())
+        .engine(reference.to_owned())
+        .rounds(games.div_ceil(2))
+        .match_tc(tc)
+        .run()
+        .await;
+
+    let mut wdl = WdlRecord::default();
+
+    for record in records.iter() {
+        wdl.add(record, &engine.name);
+    }
+
+    EloEstimate::from_record(wdl, reference_elo)
+}
+
+#[test]
+fn elo_estimate() {
+    assert_eq!(elo_diff(0.5), 0.0);
+    assert!((elo_diff(0.75) - 190.85).abs() < 0.01);
+
+    let estimate = EloEstimate::from_record(
+        WdlRecord {
+            wins: 30,
+            draws: 40,
+            losses: 30,
+        },
+        1500.0,
+    )
+    .unwrap();
+
+    assert_eq!(estimate.elo, 1500.0);
+    assert!(estimate.lower < 1500.0 && estimate.upper > 1500.0);
+    assert!((estimate.margin() - 54.0).abs() < 1.0);
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+
+use crate::analysis::*;
+
+/// default value mate scores are clamped to, in centipawns
+pub const DEFAULT_MATE_CP: i32 = 1000;
+
+/// smoothing method of the eval bar
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// exponential moving average with the given weight of the newest score ( 0 to 1 )
+    Exponential(f64),
+    /// median of the last given number of scores
+    Median(usize),
+}
+
+/// eval bar, smooths the scores of streamed analysis infos into a stable value,
+/// scores are taken as given ( from the point of view of the side to move ),
+/// mate scores are clamped to the mate value and reset the smoothing,
+/// so that a found mate shows at once
+#[derive(Debug, Clone)]
+pub struct EvalBar {
+    /// smoothing method
+    smoothing: Smoothing,
+    /// value mate scores are clamped to
+    mate_cp: i32,
+    /// last scores, newest last ( median smoothing )
+    window: VecDeque<i32>,
+    /// current value, None before the first score
+    value: Option<f64>,
+}
+
+/// eval bar implementation
+impl EvalBar {
+    /// create new eval bar with default smoothing
+    /// ( exponential with weight 0.3, mate clamped to 1000 cp )
+    pub fn new() -> Self {
+        Self {
+            smoothing: Smoothing::Exponential(0.3),
+            mate_cp: DEFAULT_MATE_CP,
+            window: VecDeque::new(),
+            value: None,
+        }
+    }
+
+    /// set smoothing method and return self
+    pub fn smoothing(mut self, smoothing: Smoothing) -> Self {
+        self.smoothing = smoothing;
+
+        self
+    }
+
+    /// set value mate scores are clamped to and return self
+    pub fn mate_cp(mut self, mate_cp: i32) -> Self {
+        self.mate_cp = mate_cp;
+
+        self
+    }
+
+    /// clear the bar ( new position )
+    pub fn reset(&mut self) {
+        self.window.clear();
+        self.value = None;
+    }
+
+    /// observe analysis info, only exact top line scores count
+    pub fn observe(&mut self, ai: &AnalysisInfo) {
+        if ai.multipv > 1 || ai.depth == 0 || !matches!(ai.scoretype, ScoreType::Exact) {
+            return;
+        }
+
+        self.push(ai.score);
+    }
+
+    /// push score and return the smoothed value in centipawns
+    pub fn push(&mut self, score: Score) -> f64 {
+        let cp = match score {
+            Score::Cp(cp) => cp.clamp(-self.mate_cp, self.mate_cp),
+            Score::Mate(moves) => {
+                let cp = if moves > 0 {
+                    self.mate_cp
+                } else {
+                    -self.mate_cp
+                };
+
+                self.reset();
+
+                self.window.push_back(cp);
+                self.value = Some(cp as f64);
+
+                return cp as f64;
+            }
+        };
+
+        let value = match self.smoothing {
+            Smoothing::Exponential(weight) => match self.value {
+                Some(value) => value + weight * (cp as f64 - value),
+                _ => cp as f64,
+            },
+            Smoothing::Median(size) => {
+                self.window.push_back(cp);
+
+                while self.window.len() > size.max(1) {
+                    self.window.pop_front();
+                }
+
+                let mut sorted: Vec<i32> = self.window.iter().copied().collect();
+
+                sorted.sort_unstable();
+
+                let mid = sorted.len() / 2;
+
+                match sorted.len() % 2 {
+                    0 => (sorted[mid - 1] + sorted[mid]) as f64 / 2.0,
+                    _ => sorted[mid] as f64,
+                }
+            }
+        };
+
+        self.value = Some(value);
+
+        value
+    }
+
+    /// smoothed value in centipawns, None before the first score
+    pub fn value(&self) -> Option<f64> {
+        self.value
+    }
+
+    /// fill of the bar between 0 and 1 ( 0.5 is equal ), from the smoothed value
+    /// by the usual logistic mapping of centipawns to expected score
+    pub fn fill(&self) -> f64 {
+        match self.value {
+            Some(value) => 1.0 / (1.0 + 10f64.powf(-value / 400.0)),
+            _ => 0.5,
+        }
+    }
+}
+
+/// implement Default for EvalBar
+impl Default for EvalBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn eval_bar() {
+    let mut bar = EvalBar::new().smoothing(Smoothing::Median(3));
+
+    assert_eq!(bar.fill(), 0.5);
+
+    bar.push(Score::Cp(20));
+    bar.push(Score::Cp(300));
+
+    assert_eq!(bar.push(Score::Cp(30)), 30.0);
+    assert_eq!(bar.push(Score::Mate(-3)), -1000.0);
+    assert!(bar.fill() < 0.01);
+
+    let mut bar = EvalBar::new().smoothing(Smoothing::Exponential(0.5));
+
+    bar.push(Score::Cp(100));
+
+    assert_eq!(bar.push(Score::Cp(200)), 150.0);
+}
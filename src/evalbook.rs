@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::analysis::*;
+use crate::session::*;
+
+/// ply of an analysed game as added to an eval book
+#[derive(Debug, Clone)]
+pub struct BookPly {
+    /// move played
+    pub played: String,
+    /// score of the position after the move from the point of view of white,
+    /// None if the position was not analysed
+    pub white_score: Option<Score>,
+    /// move the engine preferred in the position after the move, None if unknown
+    pub bestmove: Option<String>,
+}
+
+/// node of an eval book, the position reached by the moves leading to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalBookNode {
+    /// times the position was reached
+    pub visits: u64,
+    /// analysed visits the average is taken over
+    pub evals: u64,
+    /// average score in centipawns from the point of view of white ( mates clamped )
+    pub avg_cp: f64,
+    /// moves the engine preferred in the position, with the number of visits
+    /// that preferred them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub preferred: BTreeMap<String, u64>,
+    /// positions reached by the moves played in the position
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub children: BTreeMap<String, EvalBookNode>,
+}
+
+/// eval book node implementation
+impl EvalBookNode {
+    /// create new unvisited node
+    fn new() -> Self {
+        Self {
+            visits: 0,
+            evals: 0,
+            avg_cp: 0.0,
+            preferred: BTreeMap::new(),
+            children: BTreeMap::new(),
+        }
+    }
+
+    /// count a visit of the position with its analysis
+    fn visit(&mut self, ply: &BookPly) {
+        self.visits += 1;
+
+        if let Some(score) = ply.white_score {
+            self.evals += 1;
+
+            self.avg_cp += (score.cp_equivalent() as f64 - self.avg_cp) / self.evals as f64;
+        }
+
+        if let Some(bestmove) = &ply.bestmove {
+            *self.preferred.entry(bestmove.to_owned()).or_insert(0) += 1;
+        }
+    }
+
+    /// move the engine preferred most often in the position, None if unknown
+    pub fn preferred_move(&self) -> Option<&str> {
+        self.preferred
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(bestmove, _)| bestmove.as_str())
+    }
+
+    /// drop children visited less than min visits
+    fn prune(&mut self, min_visits: u64) {
+        self.children.retain(|_, child| child.visits >= min_visits);
+
+        for child in self.children.values_mut() {
+            child.prune(min_visits);
+        }
+    }
+}
+
+/// opening eval book, aggregates the analysis of many games into a tree of the
+/// opening positions with their average evals, visits and the continuations
+/// the engine preferred, moves are keyed as played ( san or uci, as long as all
+/// games use the same notation ), the preferred moves are in uci
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalBook {
+    /// plies of every game added to the book
+    pub max_plies: usize,
+    /// number of games added
+    pub games: u64,
+    /// starting position
+    pub root: EvalBookNode,
+}
+
+/// eval book implementation
+impl EvalBook {
+    /// create new empty book of the first max plies of the games
+    pub fn new(max_plies: usize) -> Self {
+        Self {
+            max_plies,
+            games: 0,
+            root: EvalBookNode::new(),
+        }
+    }
+
+    /// create new book from analysis sessions of games
+    pub fn from_sessions<'a, I>(sessions: I, max_plies: usize) -> Self
+    where
+        I: IntoIterator<Item = &'a AnalysisSession>,
+    {
+        let mut book = Self::new(max_plies);
+
+        for session in sessions {
+            book.add_session(session);
+        }
+
+        book
+    }
+
+    /// add the plies of a game
+    pub fn add_game(&mut self, plies: &[BookPly]) {
+        self.games += 1;
+        self.root.visits += 1;
+
+        let mut node = &mut self.root;
+
+        for ply in plies.iter().take(self.max_plies) {
+            node = node
+                .children
+                .entry(ply.played.to_owned())
+                .or_insert_with(EvalBookNode::new);
+
+            node.visit(ply);
+        }
+    }
+
+    /// add the analysis session of a game, positions searched to depth 0 count as not analysed
+    pub fn add_session(&mut self, session: &AnalysisSession) {
+        let plies: Vec<BookPly> = session
+            .moves()
+            .into_iter()
+            .map(|(played, ai, white_moved)| {
+                let analysed = ai.depth > 0;
+
+                // the score is from the point of view of the side to move after the move
+                let white_score = match white_moved {
+                    true => ai.score.flip(),
+                    _ => ai.score,
+                };
+
+                BookPly {
+                    played: played.to_string(),
+                    white_score: analysed.then_some(white_score),
+                    bestmove: ai.bestmove().filter(|_| analysed),
+                }
+            })
+            .collect();
+
+        self.add_game(&plies);
+    }
+
+    /// node of the position reached by moves, None if no game reached it
+    pub fn node(&self, moves: &[&str]) -> Option<&EvalBookNode> {
+        moves
+            .iter()
+            .try_fold(&self.root, |node, played| node.children.get(*played))
+    }
+
+    /// drop positions visited less than min visits, keeps the book to the lines
+    /// that were actually played
+    pub fn prune(&mut self, min_visits: u64) {
+        self.root.prune(min_visits);
+    }
+
+    /// book as json
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// write book as json to path
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = self
+            .to_json()
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+
+        std::fs::write(path, json)
+    }
+}
+
+#[test]
+fn eval_book() {
+    let session = |lines: &[(&str, &str)]| {
+        let mut session = AnalysisSession::new();
+
+        for (played, info) in lines {
+            let mut ai = AnalysisInfo::new();
+
+            ai.parse(*info).unwrap();
+
+            session.record(played, ai);
+        }
+
+        session
+    };
+
+    let games = [
+        session(&[
+            ("e4", "info depth 20 score cp -30 pv c7c5"),
+            ("c5", "info depth 20 score cp 40 pv g1f3"),
+        ]),
+        session(&[
+            ("e4", "info depth 20 score cp -20 pv e7e5"),
+            ("e5", "info depth 20 score cp 25 pv g1f3"),
+        ]),
+        session(&[("d4", "info depth 20 score cp -15 pv d7d5")]),
+    ];
+
+    let mut book = EvalBook::from_sessions(games.iter(), 8);
+
+    let e4 = book.node(&["e4"]).unwrap();
+
+    assert_eq!(book.games, 3);
+    assert_eq!(e4.visits, 2);
+    assert_eq!(e4.avg_cp, 25.0);
+    assert_eq!(e4.children.len(), 2);
+    // white to move after e4 c5, the score is taken as it is
+    assert_eq!(book.node(&["e4", "c5"]).unwrap().avg_cp, 40.0);
+    assert!(book.node(&["c4"]).is_none());
+
+    book.prune(2);
+
+    assert!(book.node(&["d4"]).is_none());
+    assert!(book.node(&["e4", "c5"]).is_none());
+    assert!(book.to_json().unwrap().contains("\"avg_cp\": 25.0"));
+}
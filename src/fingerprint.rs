@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use crate::options::*;
+
+/// engine fingerprint, the banner an engine answers uci with
+/// ( id name, id author and the option list ),
+/// tells apart engine binaries that changed between restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineFingerprint {
+    /// id name
+    pub name: Option<String>,
+    /// id author
+    pub author: Option<String>,
+    /// option lines without the leading "option "
+    pub options: Vec<String>,
+}
+
+/// engine identity, the id lines of the uci handshake
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineIdentity {
+    /// id name ( engine name and version )
+    pub name: String,
+    /// id author
+    pub author: Option<String>,
+}
+
+/// engine fingerprint implementation
+impl EngineFingerprint {
+    /// create new empty fingerprint
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            author: None,
+            options: vec![],
+        }
+    }
+
+    /// true if no banner line was observed
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.author.is_none() && self.options.is_empty()
+    }
+
+    /// identity of the engine, None if it did not send id name
+    pub fn identity(&self) -> Option<EngineIdentity> {
+        self.name.as_ref().map(|name| EngineIdentity {
+            name: name.to_owned(),
+            author: self.author.to_owned(),
+        })
+    }
+
+    /// typed options of the banner
+    pub fn engine_options(&self) -> EngineOptions {
+        parse_options(self.options.iter())
+    }
+
+    /// observe engine output line, returns true if it is a banner line
+    pub fn observe(&mut self, line: &str) -> bool {
+        if let Some(name) = line.strip_prefix("id name ") {
+            self.name = Some(name.trim().to_string());
+        } else if let Some(author) = line.strip_prefix("id author ") {
+            self.author = Some(author.trim().to_string());
+        } else if let Some(option) = line.strip_prefix("option ") {
+            self.options.push(option.trim().to_string());
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    /// human readable changes from self to other, empty if they are the same
+    pub fn changes(&self, other: &EngineFingerprint) -> Vec<String> {
+        let mut changes = vec![];
+
+        if self.name != other.name {
+            changes.push(format!("name {:?} -> {:?}", self.name, other.name));
+        }
+
+        if self.author != other.author {
+            changes.push(format!("author {:?} -> {:?}", self.author, other.author));
+        }
+
+        for option in self.options.iter() {
+            if !other.options.contains(option) {
+                changes.push(format!("option removed : {}", option));
+            }
+        }
+
+        for option in other.options.iter() {
+            if !self.options.contains(option) {
+                changes.push(format!("option added : {}", option));
+            }
+        }
+
+        changes
+    }
+}
+
+/// implement Default for EngineFingerprint
+impl Default for EngineFingerprint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn fingerprint_changes() {
+    let mut old = EngineFingerprint::new();
+
+    for line in [
+        "id name Stockfish 16",
+        "id author the Stockfish developers",
+        "option name Hash type spin default 16 min 1 max 33554432",
+        "uciok",
+    ] {
+        old.observe(line);
+    }
+
+    assert_eq!(
+        old.identity().map(|identity| identity.name),
+        Some("Stockfish 16".to_string())
+    );
+
+    let mut new = old.clone();
+
+    assert!(old.changes(&new).is_empty());
+
+    new.observe("id name Stockfish 17");
+
+    assert_eq!(
+        old.changes(&new),
+        vec!["name Some(\"Stockfish 16\") -> Some(\"Stockfish 17\")"]
+    );
+}
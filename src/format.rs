@@ -0,0 +1,238 @@
+use crate::analysis::*;
+
+/// unit of centipawn scores
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreUnit {
+    /// pawns with decimals ( "+1.3" )
+    Pawns,
+    /// centipawns ( "+130" )
+    Centipawns,
+}
+
+/// display format options
+#[derive(Debug, Clone, Copy)]
+pub struct FormatOptions {
+    /// use decimal comma instead of decimal point
+    pub decimal_comma: bool,
+    /// unit of centipawn scores
+    pub unit: ScoreUnit,
+    /// number of decimals of pawn scores
+    pub precision: usize,
+    /// truncate displayed pvs of mate scores at the mating move
+    pub mate_pv: bool,
+    /// truncate displayed pvs after this many plies
+    pub max_pv_plies: Option<usize>,
+}
+
+/// format options implementation
+impl FormatOptions {
+    /// create default format options
+    /// ( decimal point, pawn scores with one decimal )
+    pub fn new() -> Self {
+        Self {
+            decimal_comma: false,
+            unit: ScoreUnit::Pawns,
+            precision: 1,
+            mate_pv: false,
+            max_pv_plies: None,
+        }
+    }
+
+    /// set decimal comma and return self
+    pub fn decimal_comma(mut self, value: bool) -> Self {
+        self.decimal_comma = value;
+
+        self
+    }
+
+    /// set score unit and return self
+    pub fn unit(mut self, unit: ScoreUnit) -> Self {
+        self.unit = unit;
+
+        self
+    }
+
+    /// set number of decimals of pawn scores and return self
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+
+        self
+    }
+
+    /// truncate displayed pvs of mate scores at the mating move and return self
+    pub fn mate_pv(mut self, value: bool) -> Self {
+        self.mate_pv = value;
+
+        self
+    }
+
+    /// truncate displayed pvs after plies and return self
+    pub fn max_pv_plies(mut self, plies: usize) -> Self {
+        self.max_pv_plies = Some(plies);
+
+        self
+    }
+
+    /// number with given decimals and the configured decimal separator
+    fn decimal(&self, value: f64, decimals: usize) -> String {
+        let formatted = format!("{:.*}", decimals, value);
+
+        match self.decimal_comma {
+            true => formatted.replace('.', ","),
+            _ => formatted,
+        }
+    }
+
+    /// count scaled to a unit prefix ( k, M, G ), one decimal below 100 of the prefix
+    fn scaled(&self, value: u64) -> (String, &'static str) {
+        let prefixes = [(1e9, "G"), (1e6, "M"), (1e3, "k")];
+
+        for (scale, prefix) in prefixes.iter() {
+            let scaled = value as f64 / scale;
+
+            if scaled >= 1.0 {
+                let decimals = if scaled < 100.0 { 1 } else { 0 };
+
+                return (self.decimal(scaled, decimals), prefix);
+            }
+        }
+
+        (format!("{}", value), "")
+    }
+
+    /// format score, positive scores are signed, mate scores are "#5" / "#-5"
+    pub fn score(&self, score: Score) -> String {
+        match score {
+            Score::Mate(moves) if moves < 0 => format!("#-{}", -moves),
+            Score::Mate(moves) => format!("#{}", moves),
+            Score::Cp(cp) => {
+                let value = match self.unit {
+                    ScoreUnit::Pawns => self.decimal(cp as f64 / 100.0, self.precision),
+                    ScoreUnit::Centipawns => format!("{}", cp),
+                };
+
+                // no sign for scores that round to zero ( "0.0", not "-0.0" )
+                let zero = !value.contains(|c: char| c.is_ascii_digit() && c != '0');
+
+                match zero {
+                    true => value.trim_start_matches('-').to_string(),
+                    _ if cp > 0 => format!("+{}", value),
+                    _ => value,
+                }
+            }
+        }
+    }
+
+    /// format node count ( "1.2M" )
+    pub fn nodes(&self, nodes: u64) -> String {
+        let (value, prefix) = self.scaled(nodes);
+
+        format!("{}{}", value, prefix)
+    }
+
+    /// format nodes per second ( "12.4 Mn/s" )
+    pub fn nps(&self, nps: u64) -> String {
+        let (value, prefix) = self.scaled(nps);
+
+        format!("{} {}n/s", value, prefix)
+    }
+
+    /// format pv of analysis info for display, truncated as configured,
+    /// the pv of the info is left intact
+    pub fn pv(&self, ai: &AnalysisInfo) -> String {
+        let pv = ai.pv().unwrap_or_default();
+
+        let mut plies = pv.split_whitespace().count();
+
+        if self.mate_pv {
+            // the side to move mates with its nth move, or is mated after the nth reply
+            match ai.score {
+                Score::Mate(moves) if moves > 0 => plies = plies.min(2 * moves as usize - 1),
+                Score::Mate(moves) => plies = plies.min(2 * moves.unsigned_abs() as usize),
+                _ => {}
+            }
+        }
+
+        if let Some(max_pv_plies) = self.max_pv_plies {
+            plies = plies.min(max_pv_plies);
+        }
+
+        pv.split_whitespace()
+            .take(plies)
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// format time given in milliseconds as m:ss or h:mm:ss
+    pub fn time(&self, ms: u64) -> String {
+        let secs = ms / 1000;
+
+        match secs / 3600 {
+            0 => format!("{}:{:02}", secs / 60, secs % 60),
+            hours => format!("{}:{:02}:{:02}", hours, secs / 60 % 60, secs % 60),
+        }
+    }
+}
+
+/// implement Default for FormatOptions
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn format_values() {
+    let options = FormatOptions::new();
+
+    assert_eq!(options.score(Score::Cp(130)), "+1.3");
+    assert_eq!(options.score(Score::Cp(-4)), "0.0");
+    assert_eq!(options.score(Score::Mate(5)), "#5");
+    assert_eq!(options.score(Score::Mate(-5)), "#-5");
+    assert_eq!(options.nps(12_400_000), "12.4 Mn/s");
+    assert_eq!(options.nps(512), "512 n/s");
+    assert_eq!(options.nodes(1_234_567_890), "1.2G");
+    assert_eq!(options.time(83_000), "1:23");
+    assert_eq!(options.time(3_723_000), "1:02:03");
+
+    let options = options.decimal_comma(true).precision(2);
+
+    assert_eq!(options.score(Score::Cp(-130)), "-1,30");
+    assert_eq!(
+        options.unit(ScoreUnit::Centipawns).score(Score::Cp(130)),
+        "+130"
+    );
+
+    let mut ai = AnalysisInfo::new();
+
+    ai.parse("info depth 5 score mate 1 pv h5f7 e8f7").unwrap();
+
+    assert_eq!(FormatOptions::new().pv(&ai), "h5f7 e8f7");
+    assert_eq!(FormatOptions::new().mate_pv(true).pv(&ai), "h5f7");
+    assert_eq!(FormatOptions::new().max_pv_plies(0).pv(&ai), "");
+    assert_eq!(ai.pv(), Some("h5f7 e8f7".to_string()));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn format_search_pv() {
+    use crate::uciengine::*;
+
+    let path = fake_engine(
+        "format-pv",
+        "    go*) echo 'info depth 9 score mate 1 pv e2e4 e7e5 g1f3'; echo 'bestmove e2e4';;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let ai = engine
+        .try_go(GoJob::new().pos_startpos().go_opt("depth", 9))
+        .await
+        .unwrap()
+        .ai;
+
+    // the pv of the result ends at the mating move when displayed
+    assert_eq!(FormatOptions::new().mate_pv(true).pv(&ai), "e2e4");
+    assert_eq!(FormatOptions::new().pv(&ai), "e2e4 e7e5");
+    assert_eq!(ai.pv().as_deref(), Some("e2e4 e7e5"));
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+/// game result
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameResult {
+    /// white won
+    WhiteWins,
+    /// black won
+    BlackWins,
+    /// draw
+    Draw,
+    /// game not finished
+    Unfinished,
+}
+
+/// game result implementation
+impl GameResult {
+    /// pgn result string
+    pub fn to_pgn(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unfinished => "*",
+        }
+    }
+
+    /// from pgn result string, anything unknown is unfinished
+    pub fn from_pgn<T: AsRef<str>>(result: T) -> Self {
+        match result.as_ref() {
+            "1-0" => GameResult::WhiteWins,
+            "0-1" => GameResult::BlackWins,
+            "1/2-1/2" => GameResult::Draw,
+            _ => GameResult::Unfinished,
+        }
+    }
+}
+
+/// kind of violation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ViolationKind {
+    /// engine used more time than it had on its clock
+    FlagFall,
+    /// engine played an illegal move
+    IllegalMove,
+    /// engine sent bestmove while no search was running
+    UnsolicitedBestmove,
+    /// engine did not send bestmove at all
+    MissingBestmove,
+    /// engine process exited during the game
+    EngineCrash,
+    /// engine did not answer isready with readyok before the game
+    MissingReadyok,
+}
+
+/// rule or protocol violation recorded during a game
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+    /// ply at which the violation occurred, starting from 0
+    pub ply: usize,
+    /// name of the engine that committed the violation
+    pub engine: String,
+    /// kind of violation
+    pub kind: ViolationKind,
+    /// details ( offending line, move or timing )
+    pub detail: String,
+}
+
+/// record of a finished match / tournament game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    /// name of the engine playing white
+    pub white: String,
+    /// name of the engine playing black
+    pub black: String,
+    /// opening name or eco code, if known
+    pub opening: Option<String>,
+    /// starting fen, None for the standard starting position
+    pub start_fen: Option<String>,
+    /// time control in human readable form ( "3+2", "40/90,30" )
+    pub time_control: String,
+    /// moves played in uci notation
+    pub moves: Vec<String>,
+    /// thinking time of each move in milliseconds
+    #[serde(default)]
+    pub move_times: Vec<u64>,
+    /// result
+    pub result: GameResult,
+    /// reason for the result ( "checkmate", "time forfeit", ... )
+    pub termination: String,
+    /// rule and protocol violations
+    #[serde(default)]
+    pub violations: Vec<Violation>,
+}
+
+/// win / draw / loss record from the point of view of one engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct WdlRecord {
+    /// games won
+    pub wins: u64,
+    /// games drawn
+    pub draws: u64,
+    /// games lost
+    pub losses: u64,
+}
+
+/// win / draw / loss record implementation
+impl WdlRecord {
+    /// number of games
+    pub fn games(&self) -> u64 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// score as a fraction ( win counts one, draw counts half ), None if no games
+    pub fn score(&self) -> Option<f64> {
+        let games = self.games();
+
+        if games == 0 {
+            return None;
+        }
+
+        Some((self.wins as f64 + self.draws as f64 / 2.0) / games as f64)
+    }
+
+    /// add result of a game played by engine
+    pub fn add(&mut self, game: &GameRecord, engine: &str) {
+        match game.result {
+            GameResult::Draw => self.draws += 1,
+            GameResult::WhiteWins if game.white == engine => self.wins += 1,
+            GameResult::BlackWins if game.black == engine => self.wins += 1,
+            GameResult::WhiteWins | GameResult::BlackWins => self.losses += 1,
+            GameResult::Unfinished => {}
+        }
+    }
+}
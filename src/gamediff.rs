@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+
+use crate::session::*;
+
+/// ply of a line from the divergence on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffPly {
+    /// move played
+    pub played: String,
+    /// score of the position after the move in centipawns from the point of view
+    /// of white ( mates clamped ), None if the position was not analysed
+    pub white_cp: Option<i32>,
+    /// best line of the engine in the position after the move, None if unknown
+    pub pv: Option<String>,
+}
+
+/// difference of two analysed move sequences of the same game, for example the
+/// game as played and an engine correspondence line, from the ply they diverge at
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameDiff {
+    /// plies both sequences share before they diverge
+    pub common: usize,
+    /// full move number of the diverging move
+    pub move_number: usize,
+    /// true if white makes the diverging move
+    pub white_to_move: bool,
+    /// score of the position before the divergence, None if it was not analysed
+    /// ( or the sequences diverge at the first ply )
+    pub common_cp: Option<i32>,
+    /// best line of the engine in the position before the divergence, None if unknown
+    pub common_pv: Option<String>,
+    /// plies of the first sequence from the divergence on
+    pub a: Vec<DiffPly>,
+    /// plies of the second sequence from the divergence on
+    pub b: Vec<DiffPly>,
+}
+
+/// game diff implementation
+impl GameDiff {
+    /// true if both sequences go on after their common plies, false if they are
+    /// the same or one continues the other
+    pub fn diverged(&self) -> bool {
+        !self.a.is_empty() && !self.b.is_empty()
+    }
+
+    /// scores right after the diverging moves of the first and the second sequence
+    pub fn divergence_cp(&self) -> (Option<i32>, Option<i32>) {
+        let first = |plies: &[DiffPly]| plies.first().and_then(|ply| ply.white_cp);
+
+        (first(&self.a), first(&self.b))
+    }
+
+    /// last analysed scores of the first and the second sequence
+    pub fn final_cp(&self) -> (Option<i32>, Option<i32>) {
+        let last = |plies: &[DiffPly]| {
+            plies
+                .iter()
+                .rev()
+                .find_map(|ply| ply.white_cp)
+                .or(self.common_cp)
+        };
+
+        (last(&self.a), last(&self.b))
+    }
+
+    /// final score of the second sequence minus the final score of the first one,
+    /// from the point of view of white, None if either is unknown
+    pub fn swing(&self) -> Option<i32> {
+        match self.final_cp() {
+            (Some(a), Some(b)) => Some(b - a),
+            _ => None,
+        }
+    }
+
+    /// description of the divergence for post-mortems
+    /// ( "diverge at 5. Nf3 ( 0.30 ) / 5. c4 ( -0.10 ), engine line g1f3 d7d5, final 0.45 / -1.20" )
+    pub fn describe(&self) -> String {
+        let eval = |cp: Option<i32>| match cp {
+            Some(cp) => format!("{:.2}", cp as f64 / 100.0),
+            _ => "?".to_string(),
+        };
+
+        let prefix = match self.white_to_move {
+            true => format!("{}.", self.move_number),
+            _ => format!("{}...", self.move_number),
+        };
+
+        let first = |plies: &[DiffPly]| match plies.first() {
+            Some(ply) => format!("{} {} ( {} )", prefix, ply.played, eval(ply.white_cp)),
+            _ => "end".to_string(),
+        };
+
+        let mut description = format!("diverge at {} / {}", first(&self.a), first(&self.b));
+
+        if let Some(pv) = &self.common_pv {
+            description = description + &format!(", engine line {}", pv);
+        }
+
+        let (a, b) = self.final_cp();
+
+        description + &format!(", final {} / {}", eval(a), eval(b))
+    }
+}
+
+/// analysed plies of a session, the score and line of positions searched to depth 0
+/// count as unknown
+fn diff_plies(session: &AnalysisSession) -> Vec<DiffPly> {
+    session
+        .moves()
+        .into_iter()
+        .map(|(played, ai, white_moved)| {
+            let analysed = ai.depth > 0;
+
+            // the score is from the point of view of the side to move after the move
+            let white_score = match white_moved {
+                true => ai.score.flip(),
+                _ => ai.score,
+            };
+
+            DiffPly {
+                played: played.to_string(),
+                white_cp: analysed.then(|| white_score.cp_equivalent()),
+                pv: ai.pv().filter(|_| analysed),
+            }
+        })
+        .collect()
+}
+
+/// compare the evals and best lines of two analysed move sequences of the same game,
+/// both sessions have to start from the same position
+pub fn diff_games(a: &AnalysisSession, b: &AnalysisSession) -> GameDiff {
+    let a_plies = diff_plies(a);
+    let b_plies = diff_plies(b);
+
+    let common = a_plies
+        .iter()
+        .zip(b_plies.iter())
+        .take_while(|(a, b)| a.played == b.played)
+        .count();
+
+    // the position before the divergence is the same in both sequences, the
+    // first one that analysed it is taken
+    let before = common
+        .checked_sub(1)
+        .map(|index| (&a_plies[index], &b_plies[index]));
+
+    let (common_cp, common_pv) = match before {
+        Some((a, b)) => (
+            a.white_cp.or(b.white_cp),
+            a.pv.to_owned().or_else(|| b.pv.to_owned()),
+        ),
+        _ => (None, None),
+    };
+
+    GameDiff {
+        common,
+        move_number: a.move_number(common),
+        white_to_move: a.white_moved(common),
+        common_cp,
+        common_pv,
+        a: a_plies[common..].to_vec(),
+        b: b_plies[common..].to_vec(),
+    }
+}
+
+#[test]
+fn game_diff() {
+    let session = |lines: &[(&str, &str)]| {
+        let mut session = AnalysisSession::new();
+
+        for (played, info) in lines {
+            let mut ai = crate::analysis::AnalysisInfo::new();
+
+            ai.parse(*info).unwrap();
+
+            session.record(played, ai);
+        }
+
+        session
+    };
+
+    let game = session(&[
+        ("e4", "info depth 20 score cp -30 pv e7e5 g1f3"),
+        ("e5", "info depth 20 score cp 35 pv g1f3 b8c6"),
+        ("Bc4", "info depth 20 score cp -10 pv g8f6"),
+        ("Nf6", "info depth 20 score cp -20 pv d2d3"),
+    ]);
+
+    let line = session(&[
+        ("e4", "info depth 22 score cp -30 pv e7e5 g1f3"),
+        ("e5", "info depth 22 score cp 35 pv g1f3 b8c6"),
+        ("Nf3", "info depth 22 score cp -40 pv b8c6"),
+    ]);
+
+    let diff = diff_games(&game, &line);
+
+    assert!(diff.diverged());
+    assert_eq!(diff.common, 2);
+    assert_eq!(diff.move_number, 2);
+    assert!(diff.white_to_move);
+    assert_eq!(diff.common_cp, Some(35));
+    assert_eq!(diff.divergence_cp(), (Some(10), Some(40)));
+    assert_eq!(diff.final_cp(), (Some(-20), Some(40)));
+    assert_eq!(diff.swing(), Some(60));
+    assert_eq!(
+        diff.describe(),
+        "diverge at 2. Bc4 ( 0.10 ) / 2. Nf3 ( 0.40 ), engine line g1f3 b8c6, final -0.20 / 0.40"
+    );
+
+    assert!(!diff_games(&game, &game).diverged());
+}
@@ -0,0 +1,646 @@
+use log::{debug, info, log_enabled, Level};
+
+use std::collections::HashMap;
+
+use shakmaty::fen::Fen;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, Position};
+
+use thiserror::Error;
+
+use crate::analysis::*;
+use crate::book::*;
+use crate::position::*;
+use crate::timecontrol::*;
+use crate::uciengine::*;
+
+/// GameSessionError captures possible game session errors
+#[derive(Error, Debug)]
+pub enum GameSessionError {
+    #[error("invalid fen : {0}")]
+    InvalidFen(String),
+    #[error("illegal move : {0}")]
+    IllegalMove(String),
+    #[error("engine returned no move : {0}")]
+    NoMove(String),
+    #[error("engine search failed : {0}")]
+    Engine(String),
+    #[error("not pondering")]
+    NotPondering,
+}
+
+/// move played by a game session
+#[derive(Debug, Clone)]
+pub struct SessionMove {
+    /// move in uci notation
+    pub uci: String,
+    /// source of the move, book for moves played from the session book
+    pub source: ResultSource,
+    /// final analysis info of the search, None for book moves
+    pub ai: Option<AnalysisInfo>,
+}
+
+/// game played move by move against an engine, early moves come from the book
+/// without searching as long as it has a move, then the engine takes over
+pub struct GameSession {
+    /// engine searching the moves
+    engine: std::sync::Arc<UciEngine>,
+    /// position command of the game so far
+    pos_command: PositionCommand,
+    /// current position
+    pos: Chess,
+    /// number of plies played
+    plies: usize,
+    /// opening book
+    book: Option<std::sync::Arc<PolyglotBook>>,
+    /// book moves are played up to this many plies of the game
+    book_plies: usize,
+    /// minimum weight of a book move
+    book_min_weight: u16,
+    /// time control of the searches
+    tc: Option<Timecontrol>,
+    /// go options of the searches
+    go_options: Vec<(String, String)>,
+    /// moves played by the session
+    history: Vec<SessionMove>,
+    /// number of times each position occurred, by polyglot hash
+    seen: HashMap<u64, usize>,
+    /// avoid repeating a position for the third time when the score is at least
+    /// this many centipawns
+    avoid_repetition_cp: Option<i32>,
+    /// game clock of both sides, overrides tc when set
+    clock: Option<GameClock>,
+    /// expected opponent move of the last search, from its ponder move
+    expected: Option<String>,
+    /// opponent move the running ponder search is on
+    pondering: Option<String>,
+}
+
+/// game session implementation
+impl GameSession {
+    /// create new game session from the starting position
+    pub fn new(engine: std::sync::Arc<UciEngine>) -> Self {
+        Self {
+            engine,
+            pos_command: PositionCommand::startpos(),
+            pos: Chess::default(),
+            plies: 0,
+            book: None,
+            book_plies: 0,
+            book_min_weight: 0,
+            tc: None,
+            go_options: vec![],
+            history: vec![],
+            seen: vec![(PolyglotBook::key(&Chess::default()), 1)]
+                .into_iter()
+                .collect(),
+            avoid_repetition_cp: None,
+            clock: None,
+            expected: None,
+            pondering: None,
+        }
+    }
+
+    /// set starting fen and return self
+    pub fn fen<T>(mut self, fen: T) -> Result<Self, GameSessionError>
+    where
+        T: core::fmt::Display,
+    {
+        let fen = fen.to_string();
+
+        self.pos = Fen::from_ascii(fen.as_bytes())
+            .ok()
+            .and_then(|parsed| parsed.into_position(CastlingMode::Standard).ok())
+            .ok_or_else(|| GameSessionError::InvalidFen(fen.clone()))?;
+        self.pos_command = PositionCommand::fen(fen);
+        self.seen = vec![(PolyglotBook::key(&self.pos), 1)]
+            .into_iter()
+            .collect();
+
+        Ok(self)
+    }
+
+    /// set book, played up to max plies of the game with moves
+    /// of at least min weight, and return self
+    pub fn book(
+        mut self,
+        book: std::sync::Arc<PolyglotBook>,
+        max_plies: usize,
+        min_weight: u16,
+    ) -> Self {
+        self.book = Some(book);
+        self.book_plies = max_plies;
+        self.book_min_weight = min_weight;
+
+        self
+    }
+
+    /// avoid repeating a position for the third time when the engine score is at least
+    /// ahead_cp centipawns, by searching again without the repeating moves, and return self
+    pub fn avoid_repetition(mut self, ahead_cp: i32) -> Self {
+        self.avoid_repetition_cp = Some(ahead_cp);
+
+        self
+    }
+
+    /// set time control of the searches and return self
+    pub fn tc(mut self, tc: Timecontrol) -> Self {
+        self.tc = Some(tc);
+
+        self
+    }
+
+    /// set game clock and return self, the searches are sent its time control and the
+    /// session moves are charged to it, opponent moves are charged by push_move_timed,
+    /// ponderhit and pondermiss
+    pub fn clock(mut self, clock: GameClock) -> Self {
+        self.clock = Some(clock);
+
+        self
+    }
+
+    /// set go option of the searches as key value pair and return self
+    pub fn go_opt<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.go_options.push((key.to_string(), value.to_string()));
+
+        self
+    }
+
+    /// update time control of the next searches
+    pub fn set_tc(&mut self, tc: Timecontrol) {
+        self.tc = Some(tc);
+    }
+
+    /// game clock, None if not set
+    pub fn game_clock(&self) -> Option<&GameClock> {
+        self.clock.as_ref()
+    }
+
+    /// whether a ponder search is running
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.is_some()
+    }
+
+    /// current position
+    pub fn position(&self) -> &Chess {
+        &self.pos
+    }
+
+    /// number of plies played
+    pub fn plies(&self) -> usize {
+        self.plies
+    }
+
+    /// moves played by the session
+    pub fn history(&self) -> &[SessionMove] {
+        &self.history
+    }
+
+    /// final analysis infos of the searched moves in game order,
+    /// for should_resign and should_accept_draw
+    pub fn analysis(&self) -> Vec<AnalysisInfo> {
+        self.history
+            .iter()
+            .filter_map(|session_move| session_move.ai)
+            .collect()
+    }
+
+    /// play uci move on the current position
+    fn play(&mut self, uci: &str) -> Result<(), GameSessionError> {
+        let m = uci
+            .parse::<UciMove>()
+            .ok()
+            .and_then(|uci_move| uci_move.to_move(&self.pos).ok())
+            .ok_or_else(|| GameSessionError::IllegalMove(uci.to_string()))?;
+
+        self.pos.play_unchecked(m);
+        self.pos_command.push_move(uci);
+        self.plies += 1;
+
+        *self.seen.entry(PolyglotBook::key(&self.pos)).or_insert(0) += 1;
+
+        Ok(())
+    }
+
+    /// play opponent move
+    pub fn push_move<T>(&mut self, uci: T) -> Result<(), GameSessionError>
+    where
+        T: AsRef<str>,
+    {
+        self.play(uci.as_ref())
+    }
+
+    /// play opponent move that took elapsed milliseconds, charged to the game clock
+    pub fn push_move_timed<T>(&mut self, uci: T, elapsed: u64) -> Result<(), GameSessionError>
+    where
+        T: AsRef<str>,
+    {
+        let side = self.side();
+
+        self.play(uci.as_ref())?;
+
+        if let Some(clock) = self.clock.as_mut() {
+            clock.charge(side, elapsed);
+        }
+
+        Ok(())
+    }
+
+    /// clock index of the side to move
+    fn side(&self) -> usize {
+        match self.pos.turn() {
+            shakmaty::Color::White => 0,
+            _ => 1,
+        }
+    }
+
+    /// time control of the next search, from the game clock if set
+    fn timecontrol(&self) -> Option<Timecontrol> {
+        match &self.clock {
+            Some(clock) => Some(clock.timecontrol()),
+            _ => self.tc,
+        }
+    }
+
+    /// ponder on the expected opponent move of the last search while the opponent
+    /// thinks, returns the move pondered on, None if the engine gave no ponder move
+    pub fn ponder(&mut self) -> Result<Option<String>, GameSessionError> {
+        let expected = match self.expected.take() {
+            Some(expected) => expected,
+            _ => return Ok(None),
+        };
+
+        let mut pos_command = self.pos_command.clone();
+
+        pos_command.push_move(&expected);
+
+        let mut go_job = GoJob::new().pos_command(&pos_command).ponder();
+
+        if let Some(tc) = self.timecontrol() {
+            go_job = go_job.tc(tc);
+        }
+
+        for (key, value) in self.go_options.iter() {
+            go_job = go_job.go_opt(key, value);
+        }
+
+        // the ponder result is the bestmove the search is ended with, to be discarded
+        drop(self.engine.try_go(go_job));
+
+        let side = self.side();
+
+        if let Some(clock) = self.clock.as_mut() {
+            clock.start_ponder(1 - side);
+        }
+
+        if log_enabled!(Level::Debug) {
+            debug!("pondering on {} at ply {}", expected, self.plies);
+        }
+
+        self.pondering = Some(expected.clone());
+
+        Ok(Some(expected))
+    }
+
+    /// the opponent played the pondered move after opponent_elapsed milliseconds, play it
+    /// and the reply of the engine, the search is restarted with the time control corrected
+    /// for the ponder hit ( uci ponderhit carries no clock, so the engine would go on with the
+    /// clock of go ponder ), the hash keeps what the ponder search found
+    pub async fn ponderhit(
+        &mut self,
+        opponent_elapsed: u64,
+    ) -> Result<SessionMove, GameSessionError> {
+        let expected = self
+            .pondering
+            .take()
+            .ok_or(GameSessionError::NotPondering)?;
+
+        let tc = self
+            .clock
+            .as_mut()
+            .and_then(|clock| clock.ponderhit(opponent_elapsed))
+            .map(|hit| hit.timecontrol)
+            .or(self.tc);
+
+        let _ = self.engine.pondermiss().await;
+
+        self.play(&expected)?;
+
+        let go_result = self.search(&[], tc).await?;
+
+        if let Some(clock) = self.clock.as_mut() {
+            clock.charge_ponder_move();
+        }
+
+        self.play_result(go_result)
+    }
+
+    /// the opponent played another move than the pondered one after opponent_elapsed
+    /// milliseconds, the ponder search is discarded and the move is played
+    pub async fn pondermiss<T>(
+        &mut self,
+        uci: T,
+        opponent_elapsed: u64,
+    ) -> Result<(), GameSessionError>
+    where
+        T: AsRef<str>,
+    {
+        self.pondering
+            .take()
+            .ok_or(GameSessionError::NotPondering)?;
+
+        let _ = self.engine.pondermiss().await;
+
+        if let Some(clock) = self.clock.as_mut() {
+            clock.pondermiss(opponent_elapsed);
+        }
+
+        self.play(uci.as_ref())
+    }
+
+    /// legal moves of the current position that would repeat a position for the third time
+    pub fn repeating_moves(&self) -> Vec<String> {
+        self.pos
+            .legal_moves()
+            .into_iter()
+            .filter(|m| {
+                let mut pos = self.pos.clone();
+
+                pos.play_unchecked(*m);
+
+                self.seen
+                    .get(&PolyglotBook::key(&pos))
+                    .is_some_and(|seen| *seen >= 2)
+            })
+            .map(|m| UciMove::from_move(m, CastlingMode::Standard).to_string())
+            .collect()
+    }
+
+    /// search the current position with time control tc, restricted to searchmoves if not empty
+    async fn search(
+        &self,
+        searchmoves: &[String],
+        tc: Option<Timecontrol>,
+    ) -> Result<GoResult, GameSessionError> {
+        let mut go_job = GoJob::new().pos_command(&self.pos_command);
+
+        if let Some(tc) = tc {
+            go_job = go_job.tc(tc);
+        }
+
+        if let Some(movestogo) = self
+            .clock
+            .as_ref()
+            .and_then(|clock| clock.movestogo(self.side()))
+        {
+            go_job = go_job.go_opt("movestogo", movestogo);
+        }
+
+        for (key, value) in self.go_options.iter() {
+            go_job = go_job.go_opt(key, value);
+        }
+
+        go_job = go_job.searchmoves(searchmoves).go();
+
+        self.engine
+            .try_go(go_job)
+            .await
+            .map_err(|err| GameSessionError::Engine(err.to_string()))
+    }
+
+    /// book move of the current position, None once out of book
+    fn book_move(&self) -> Option<String> {
+        if self.plies >= self.book_plies {
+            return None;
+        }
+
+        self.book
+            .as_ref()?
+            .best_move(&self.pos, self.book_min_weight)
+    }
+
+    /// play the next move of the session, from the book if it has one,
+    /// otherwise from an engine search
+    pub async fn next_move(&mut self) -> Result<SessionMove, GameSessionError> {
+        if let Some(uci) = self.book_move() {
+            if log_enabled!(Level::Debug) {
+                debug!("book move {} at ply {}", uci, self.plies);
+            }
+
+            let side = self.side();
+
+            self.play(&uci)?;
+
+            if let Some(clock) = self.clock.as_mut() {
+                clock.charge(side, 0);
+            }
+
+            self.expected = None;
+
+            let session_move = SessionMove {
+                uci,
+                source: ResultSource::Book,
+                ai: None,
+            };
+
+            self.history.push(session_move.clone());
+
+            return Ok(session_move);
+        }
+
+        let side = self.side();
+        let tc = self.timecontrol();
+        let started = std::time::Instant::now();
+
+        let mut go_result = self.search(&[], tc).await?;
+
+        if let Some(ahead_cp) = self.avoid_repetition_cp {
+            let repeating = self.repeating_moves();
+
+            let repeats =
+                matches!(&go_result.bestmove, Some(bestmove) if repeating.contains(bestmove));
+
+            if repeats && go_result.ai.score.cp_equivalent() >= ahead_cp {
+                let searchmoves: Vec<String> = self
+                    .pos
+                    .legal_moves()
+                    .into_iter()
+                    .map(|m| UciMove::from_move(m, CastlingMode::Standard).to_string())
+                    .filter(|uci| !repeating.contains(uci))
+                    .collect();
+
+                // with every move repeating the draw can not be avoided
+                if !searchmoves.is_empty() {
+                    if log_enabled!(Level::Info) {
+                        info!(
+                            "avoiding repetition by {:?} at ply {}",
+                            go_result.bestmove, self.plies
+                        );
+                    }
+
+                    go_result = self.search(&searchmoves, tc).await?;
+                }
+            }
+        }
+
+        if let Some(clock) = self.clock.as_mut() {
+            clock.charge(side, started.elapsed().as_millis() as u64);
+        }
+
+        self.play_result(go_result)
+    }
+
+    /// play the bestmove of a search result
+    fn play_result(&mut self, go_result: GoResult) -> Result<SessionMove, GameSessionError> {
+        let uci = match go_result.bestmove {
+            Some(bestmove) if bestmove != "(none)" => bestmove,
+            _ => return Err(GameSessionError::NoMove(self.pos_command.to_string())),
+        };
+
+        self.play(&uci)?;
+
+        self.expected = go_result.ponder;
+
+        let session_move = SessionMove {
+            uci,
+            source: go_result.source,
+            ai: Some(go_result.ai),
+        };
+
+        self.history.push(session_move.clone());
+
+        Ok(session_move)
+    }
+}
+
+#[tokio::test]
+async fn book_first_session() {
+    let start = Chess::default();
+
+    let mut bytes = vec![];
+
+    // e2e4 with weight 1
+    bytes.extend_from_slice(&PolyglotBook::key(&start).to_be_bytes());
+    bytes.extend_from_slice(&(4u16 | (3 << 3) | (4 << 6) | (1 << 9)).to_be_bytes());
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+    bytes.extend_from_slice(&0u32.to_be_bytes());
+
+    // lazy, the engine is never launched while in book
+    let engine = EngineBuilder::new("no-such-engine").lazy(true).build();
+
+    let mut session =
+        GameSession::new(engine).book(std::sync::Arc::new(PolyglotBook::from_bytes(&bytes)), 8, 1);
+
+    let session_move = session.next_move().await.unwrap();
+
+    assert_eq!(session_move.uci, "e2e4");
+    assert_eq!(session_move.source, ResultSource::Book);
+    assert!(session_move.ai.is_none());
+
+    assert!(session.push_move("e2e5").is_err());
+
+    session.push_move("e7e5").unwrap();
+
+    assert_eq!(session.plies(), 2);
+    assert_eq!(session.history().len(), 1);
+
+    let engine = EngineBuilder::new("no-such-engine").lazy(true).build();
+
+    let mut session = GameSession::new(engine).avoid_repetition(100);
+
+    for uci in "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1".split_whitespace() {
+        session.push_move(uci).unwrap();
+    }
+
+    assert_eq!(session.repeating_moves(), vec!["f6g8".to_string()]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn ponderhit_session() {
+    let path = fake_engine(
+        "session-ponder",
+        "    position\\ startpos) m='e2e4 ponder e7e5';;\n    position*) m='g1f3 ponder b8c6';;\n    go*ponder*) ;;\n    go*) echo \"bestmove $m\";;\n    stop) echo 'bestmove d2d4';;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let clock = GameClock::new(&Timecontrol::new(60000, 0, 60000, 0).into());
+
+    let mut session = GameSession::new(engine).clock(clock);
+
+    assert!(matches!(
+        session.ponderhit(0).await,
+        Err(GameSessionError::NotPondering)
+    ));
+
+    assert_eq!(session.next_move().await.unwrap().uci, "e2e4");
+    assert_eq!(session.ponder().unwrap(), Some("e7e5".to_string()));
+    assert!(session.is_pondering());
+
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    assert_eq!(session.ponderhit(500).await.unwrap().uci, "g1f3");
+    assert!(!session.is_pondering());
+    assert_eq!(session.plies(), 3);
+
+    let gos: Vec<String> = fake_engine_log(&path)
+        .into_iter()
+        .filter(|line| line.starts_with("go"))
+        .collect();
+
+    assert_eq!(gos.len(), 3);
+    assert!(gos[1].contains("btime 60000") && gos[1].ends_with("ponder"));
+
+    // the restarted search has black charged for e7e5 and white credited the ponder time
+    let wtime = |go: &str| -> usize {
+        let tokens: Vec<&str> = go.split_whitespace().collect();
+        let index = tokens.iter().position(|token| *token == "wtime").unwrap();
+
+        tokens[index + 1].parse().unwrap()
+    };
+
+    assert!(gos[2].contains("btime 59500") && !gos[2].contains("ponder"));
+    assert!(wtime(&gos[2]) >= wtime(&gos[1]) + 200);
+
+    let clock = session.game_clock().unwrap();
+
+    assert_eq!(clock.remaining(1), 59500);
+    assert!(clock.remaining(0) <= 60000);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn avoid_repetition_research() {
+    let path = fake_engine(
+        "session-repetition",
+        "    go*searchmoves*) echo 'info depth 1 score cp 150 pv e7e5'; echo 'bestmove e7e5';;\n    go*) echo 'info depth 1 score cp 200 pv f6g8'; echo 'bestmove f6g8';;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let mut session = GameSession::new(engine).avoid_repetition(100);
+
+    for uci in "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1".split_whitespace() {
+        session.push_move(uci).unwrap();
+    }
+
+    let session_move = session.next_move().await.unwrap();
+
+    assert_eq!(session_move.uci, "e7e5");
+    assert_eq!(session_move.ai.unwrap().score.cp_equivalent(), 150);
+
+    let gos: Vec<String> = fake_engine_log(&path)
+        .into_iter()
+        .filter(|line| line.starts_with("go"))
+        .collect();
+
+    assert_eq!(gos.len(), 2);
+    assert!(!gos[0].contains("searchmoves"));
+    assert!(gos[1].contains(" searchmoves ") && gos[1].contains("e7e5"));
+    assert!(!gos[1].contains("f6g8"));
+}
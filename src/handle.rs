@@ -0,0 +1,164 @@
+use futures_sink::Sink;
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::sync::mpsc;
+
+use crate::uciengine::*;
+
+/// engine handle for synchronous contexts ( gui main threads, ffi callbacks ),
+/// results are delivered on standard channels, the handle keeps the runtime
+/// it was created in to forward them
+#[derive(Clone)]
+pub struct EngineHandle {
+    engine: std::sync::Arc<UciEngine>,
+    runtime: tokio::runtime::Handle,
+}
+
+/// engine handle implementation
+impl EngineHandle {
+    /// create new engine handle, has to be called from within a tokio runtime
+    pub fn new(engine: std::sync::Arc<UciEngine>) -> Self {
+        Self {
+            engine,
+            runtime: tokio::runtime::Handle::current(),
+        }
+    }
+
+    /// create new engine handle using the given runtime
+    pub fn with_runtime(
+        engine: std::sync::Arc<UciEngine>,
+        runtime: tokio::runtime::Handle,
+    ) -> Self {
+        Self { engine, runtime }
+    }
+
+    /// forward result of pending go to standard channel
+    fn forward(&self, pending: PendingGo, tx: std::sync::mpsc::Sender<GoResult>) {
+        self.runtime.spawn(async move {
+            if let Ok(go_result) = pending.await {
+                let _ = tx.send(go_result);
+            }
+        });
+    }
+
+    /// submit go job, the result is sent on the returned standard channel
+    pub fn submit(&self, go_job: GoJob) -> std::sync::mpsc::Receiver<GoResult> {
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        self.forward(self.engine.try_go(go_job), tx);
+
+        rx
+    }
+
+    /// submit go job and block the current thread until the result,
+    /// panics if called from within an async context
+    pub fn submit_blocking(&self, go_job: GoJob) -> Result<GoResult, EngineError> {
+        self.engine.try_go(go_job).blocking_result()
+    }
+
+    /// snapshot of the current analysis, does not block, so it can be polled
+    /// from a gui thread ( 10 times a second is fine )
+    pub fn snapshot(&self) -> AnalysisSnapshot {
+        self.engine.snapshot()
+    }
+
+    /// create sink of go jobs, the results are sent on the returned standard channel
+    /// in the order the jobs were sent
+    pub fn sink(&self) -> (EngineSink, std::sync::mpsc::Receiver<GoResult>) {
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        // a single task awaits the pending jobs one after the other,
+        // so results can not overtake each other
+        let (pending_tx, mut pending_rx) = mpsc::unbounded_channel::<PendingGo>();
+
+        self.runtime.spawn(async move {
+            while let Some(pending) = pending_rx.recv().await {
+                if let Ok(go_result) = pending.await {
+                    if tx.send(go_result).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        (
+            EngineSink {
+                handle: self.clone(),
+                pending_tx,
+            },
+            rx,
+        )
+    }
+}
+
+/// sink of go jobs, jobs are queued as they are sent, so the sink is always ready
+pub struct EngineSink {
+    handle: EngineHandle,
+    pending_tx: mpsc::UnboundedSender<PendingGo>,
+}
+
+/// engine sink as futures sink
+impl Sink<GoJob> for EngineSink {
+    type Error = std::convert::Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, go_job: GoJob) -> Result<(), Self::Error> {
+        let pending = self.handle.engine.try_go(go_job);
+
+        // the forwarding task only ends when the sink is dropped
+        let _ = self.pending_tx.send(pending);
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn engine_handle() {
+    let path = fake_engine(
+        "handle",
+        "    go\\ depth\\ 1) sleep 0.2; echo 'bestmove d2d4';;
+    go*) echo 'bestmove e2e4';;",
+    );
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let engine = runtime.block_on(async { EngineBuilder::new(&path).build() });
+    let handle = EngineHandle::with_runtime(engine, runtime.handle().clone());
+
+    let bestmove = |go_result: GoResult| go_result.bestmove.unwrap();
+
+    let rx = handle.submit(GoJob::new().pos_startpos().go_opt("depth", 2));
+
+    assert_eq!(bestmove(rx.recv().unwrap()), "e2e4");
+
+    let go_result = handle
+        .submit_blocking(GoJob::new().pos_startpos().go_opt("depth", 1))
+        .unwrap();
+
+    assert_eq!(bestmove(go_result), "d2d4");
+
+    let (mut sink, rx) = handle.sink();
+
+    for depth in [1, 2, 1] {
+        Pin::new(&mut sink)
+            .start_send(GoJob::new().pos_startpos().go_opt("depth", depth))
+            .unwrap();
+    }
+
+    let bestmoves: Vec<String> = (0..3).map(|_| bestmove(rx.recv().unwrap())).collect();
+
+    assert_eq!(bestmoves, vec!["d2d4", "e2e4", "d2d4"]);
+}
@@ -0,0 +1,350 @@
+use log::warn;
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use thiserror::Error;
+
+use crate::profile::*;
+
+/// ImportError captures possible engine configuration import errors
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("engine configuration could not be read : {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid engine configuration json : {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("engine '{0}' has no command")]
+    NoCommand(String),
+    #[error("invalid engine argument '{0}'")]
+    InvalidArgument(String),
+}
+
+/// engine of a cutechess engines.json
+#[derive(Debug, Deserialize)]
+struct CutechessEngine {
+    name: String,
+    #[serde(default)]
+    command: String,
+    #[serde(rename = "workingDirectory", default)]
+    working_directory: Option<String>,
+    #[serde(default)]
+    protocol: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(rename = "initStrings", default)]
+    init_strings: Vec<String>,
+    #[serde(default)]
+    options: Vec<CutechessOption>,
+}
+
+/// option of a cutechess engine
+#[derive(Debug, Deserialize)]
+struct CutechessOption {
+    name: String,
+    #[serde(default)]
+    value: Option<serde_json::Value>,
+}
+
+/// engine path from command and working directory,
+/// a relative command is resolved against the working directory
+fn engine_path(command: &str, dir: Option<&str>) -> String {
+    match dir {
+        Some(dir) if !dir.is_empty() && Path::new(command).is_relative() => {
+            Path::new(dir).join(command).to_string_lossy().to_string()
+        }
+        _ => command.to_string(),
+    }
+}
+
+/// engine profile from the settings of a cutechess engine,
+/// None if it is not a uci engine
+fn cutechess_profile(engine: CutechessEngine) -> Result<Option<EngineProfile>, ImportError> {
+    if engine.command.is_empty() {
+        return Err(ImportError::NoCommand(engine.name));
+    }
+
+    match engine.protocol.as_deref() {
+        None | Some("uci") => {}
+        Some(protocol) => {
+            warn!("skipping {} engine {}", protocol, engine.name);
+
+            return Ok(None);
+        }
+    }
+
+    if !engine.args.is_empty() || !engine.init_strings.is_empty() {
+        warn!(
+            "arguments and init strings of engine {} are not supported",
+            engine.name
+        );
+    }
+
+    let path = engine_path(&engine.command, engine.working_directory.as_deref());
+
+    let mut profile = EngineProfile::new(&engine.name, path);
+
+    for option in engine.options {
+        // options without value keep the engine default
+        let value = match option.value {
+            Some(serde_json::Value::String(value)) => value,
+            Some(serde_json::Value::Null) | None => continue,
+            Some(value) => value.to_string(),
+        };
+
+        profile = profile.uci_opt(option.name, value);
+    }
+
+    Ok(Some(profile))
+}
+
+/// engine profiles from a cutechess engines.json, engines that do not speak uci are skipped
+pub fn profiles_from_cutechess_json(json: &str) -> Result<Vec<EngineProfile>, ImportError> {
+    let engines: Vec<CutechessEngine> = serde_json::from_str(json)?;
+
+    let mut profiles = vec![];
+
+    for engine in engines {
+        if let Some(profile) = cutechess_profile(engine)? {
+            profiles.push(profile);
+        }
+    }
+
+    Ok(profiles)
+}
+
+/// engine profiles from a cutechess engines.json file
+pub fn load_cutechess_json<P: AsRef<Path>>(path: P) -> Result<Vec<EngineProfile>, ImportError> {
+    profiles_from_cutechess_json(&std::fs::read_to_string(path)?)
+}
+
+/// engine profiles from cutechess-cli arguments, as the shell split them
+/// ( -engine name=sf cmd=stockfish dir=/engines option.Hash=128 -each option.Threads=2 ),
+/// the -each settings apply to every engine, other arguments are ignored
+pub fn profiles_from_cutechess_args<I, T>(args: I) -> Result<Vec<EngineProfile>, ImportError>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<str>,
+{
+    // settings of each engine, the -each settings come first
+    let mut each: Vec<(String, String)> = vec![];
+    let mut engines: Vec<Vec<(String, String)>> = vec![];
+
+    // true while reading the settings of -each, false for -engine, None outside
+    let mut block: Option<bool> = None;
+
+    for arg in args {
+        let arg = arg.as_ref();
+
+        match arg {
+            "-engine" => {
+                engines.push(vec![]);
+
+                block = Some(false);
+            }
+            "-each" => block = Some(true),
+            _ if arg.starts_with('-') => block = None,
+            _ => {
+                let settings = match block {
+                    Some(true) => &mut each,
+                    Some(false) => engines.last_mut().unwrap(),
+                    _ => continue,
+                };
+
+                let (key, value) = arg
+                    .split_once('=')
+                    .ok_or_else(|| ImportError::InvalidArgument(arg.to_string()))?;
+
+                settings.push((key.to_string(), value.to_string()));
+            }
+        }
+    }
+
+    let mut profiles = vec![];
+
+    for (i, settings) in engines.into_iter().enumerate() {
+        let mut engine = CutechessEngine {
+            name: format!("engine {}", i + 1),
+            command: String::new(),
+            working_directory: None,
+            protocol: None,
+            args: vec![],
+            init_strings: vec![],
+            options: vec![],
+        };
+
+        for (key, value) in each.iter().cloned().chain(settings) {
+            match key.as_str() {
+                "name" => engine.name = value,
+                "cmd" => engine.command = value,
+                "dir" => engine.working_directory = Some(value),
+                "proto" => engine.protocol = Some(value),
+                "arg" => engine.args.push(value),
+                "initstr" => engine.init_strings.push(value),
+                _ => match key.strip_prefix("option.") {
+                    Some(name) => engine.options.push(CutechessOption {
+                        name: name.to_string(),
+                        value: Some(serde_json::Value::String(value)),
+                    }),
+                    _ => warn!("ignoring engine setting {}={}", key, value),
+                },
+            }
+        }
+
+        if let Some(profile) = cutechess_profile(engine)? {
+            profiles.push(profile);
+        }
+    }
+
+    Ok(profiles)
+}
+
+/// engine profiles from an arena style ini engine list, one section per engine
+/// ( name, command, directory and type keys, uci options as option.<name> keys ),
+/// sections without command and engines that do not speak uci are skipped
+pub fn profiles_from_arena_ini(ini: &str) -> Result<Vec<EngineProfile>, ImportError> {
+    let mut engines: Vec<CutechessEngine> = vec![];
+
+    for line in ini.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(section) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            engines.push(CutechessEngine {
+                name: section.trim().to_string(),
+                command: String::new(),
+                working_directory: None,
+                protocol: None,
+                args: vec![],
+                init_strings: vec![],
+                options: vec![],
+            });
+
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| ImportError::InvalidArgument(line.to_string()))?;
+
+        let engine = match engines.last_mut() {
+            Some(engine) => engine,
+            _ => {
+                warn!("ignoring engine list line outside of a section {}", line);
+
+                continue;
+            }
+        };
+
+        let (key, value) = (key.trim(), value.trim().to_string());
+
+        match key.to_ascii_lowercase().as_str() {
+            "name" => engine.name = value,
+            "command" | "cmd" | "path" | "exe" => engine.command = value,
+            "directory" | "dir" | "workingdirectory" => engine.working_directory = Some(value),
+            "type" | "protocol" => engine.protocol = Some(value.to_ascii_lowercase()),
+            "parameters" | "args" => engine.args.push(value),
+            lower if lower.starts_with("option.") => engine.options.push(CutechessOption {
+                // option names keep their case
+                name: key["option.".len()..].to_string(),
+                value: Some(serde_json::Value::String(value)),
+            }),
+            _ => {}
+        }
+    }
+
+    let mut profiles = vec![];
+
+    for engine in engines {
+        if engine.command.is_empty() {
+            warn!(
+                "skipping engine list section {} without command",
+                engine.name
+            );
+
+            continue;
+        }
+
+        if let Some(profile) = cutechess_profile(engine)? {
+            profiles.push(profile);
+        }
+    }
+
+    Ok(profiles)
+}
+
+/// engine profiles from an arena style ini engine list file
+pub fn load_arena_ini<P: AsRef<Path>>(path: P) -> Result<Vec<EngineProfile>, ImportError> {
+    profiles_from_arena_ini(&std::fs::read_to_string(path)?)
+}
+
+#[test]
+fn arena_import() {
+    let ini = "; arena engine list
+[General]
+Version=3.5
+
+[Stockfish 16]
+Command=stockfish.exe
+Directory=C:/Engines/Stockfish
+Type=UCI
+Option.Hash=256
+Option.Threads=2
+
+[Crafty]
+Command=crafty.exe
+Type=WB2
+";
+
+    let profiles = profiles_from_arena_ini(ini).unwrap();
+
+    assert_eq!(profiles.len(), 1);
+    assert_eq!(profiles[0].name, "Stockfish 16");
+    assert_eq!(profiles[0].path, "C:/Engines/Stockfish/stockfish.exe");
+    assert_eq!(profiles[0].hash_mb(), 256);
+    assert_eq!(profiles[0].threads(), 2);
+}
+
+#[test]
+fn cutechess_import() {
+    let json = r#"[
+        {
+            "name": "Stockfish 16",
+            "command": "stockfish",
+            "workingDirectory": "/engines",
+            "protocol": "uci",
+            "options": [
+                { "name": "Hash", "type": "spin", "value": 128 },
+                { "name": "Ponder", "type": "check", "value": false },
+                { "name": "Clear Hash", "type": "button" }
+            ]
+        },
+        { "name": "Crafty", "command": "crafty", "protocol": "xboard" }
+    ]"#;
+
+    let profiles = profiles_from_cutechess_json(json).unwrap();
+
+    assert_eq!(profiles.len(), 1);
+    assert_eq!(profiles[0].path, "/engines/stockfish");
+    assert_eq!(profiles[0].hash_mb(), 128);
+    assert_eq!(profiles[0].option("Ponder"), Some("false"));
+    assert_eq!(profiles[0].options.len(), 2);
+
+    let args =
+        "-engine name=sf cmd=./sf option.Hash=64 -engine cmd=lc0 -each option.Threads=4 -games 2";
+
+    let profiles = profiles_from_cutechess_args(args.split_whitespace()).unwrap();
+
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(profiles[0].name, "sf");
+    assert_eq!(profiles[0].hash_mb(), 64);
+    assert_eq!(profiles[1].name, "engine 2");
+    assert_eq!(profiles[1].threads(), 4);
+}
@@ -0,0 +1,301 @@
+use std::io::BufRead;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::*;
+
+/// engine output line of a recorded session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedLine {
+    /// milliseconds since the recording started
+    pub at_ms: u64,
+    /// engine output line
+    pub line: String,
+}
+
+/// recorder of the engine output of a session, saved as one json line per output line
+#[derive(Debug, Clone)]
+pub struct SessionRecorder {
+    started: Instant,
+    lines: Vec<RecordedLine>,
+}
+
+/// session recorder implementation
+impl SessionRecorder {
+    /// create new recorder, timestamps count from now
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            lines: vec![],
+        }
+    }
+
+    /// record engine output line
+    pub fn record<T: AsRef<str>>(&mut self, line: T) {
+        self.lines.push(RecordedLine {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            line: line.as_ref().to_string(),
+        });
+    }
+
+    /// record parsed info ( from go_stream ), the final info is recorded as bestmove
+    pub fn record_info(&mut self, ai: &AnalysisInfo) {
+        match ai.done {
+            true => self.record(format!(
+                "bestmove {}",
+                ai.bestmove().unwrap_or_else(|| "(none)".to_string())
+            )),
+            _ => self.record(ai.to_info_line()),
+        }
+    }
+
+    /// recorded lines
+    pub fn lines(&self) -> &[RecordedLine] {
+        &self.lines
+    }
+
+    /// recording as newline delimited json
+    pub fn to_ndjson(&self) -> String {
+        self.lines
+            .iter()
+            .filter_map(|line| serde_json::to_string(line).ok())
+            .map(|json| format!("{}\n", json))
+            .collect()
+    }
+
+    /// save recording to file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_ndjson())
+    }
+}
+
+/// implement Default for SessionRecorder
+impl Default for SessionRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// kind of a recorded engine output line
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    /// info line
+    Info,
+    /// bestmove line with the move
+    Bestmove(String),
+    /// any other line
+    Other,
+}
+
+/// parsed event of a recorded session with the analysis state after it
+#[derive(Debug, Clone)]
+pub struct InspectorEvent {
+    /// time since the recording started
+    pub at: Duration,
+    /// engine output line
+    pub line: String,
+    /// kind of line
+    pub kind: EventKind,
+    /// analysis state after the line
+    pub state: AnalysisInfo,
+}
+
+/// inspector of a recorded session, steps through its events and queries
+/// the analysis state at any point in time
+#[derive(Debug, Clone)]
+pub struct SessionInspector {
+    events: Vec<InspectorEvent>,
+    /// index of the current event, None before the first
+    cursor: Option<usize>,
+}
+
+/// session inspector implementation
+impl SessionInspector {
+    /// create new inspector from recorded lines, the state is rebuilt as the engine
+    /// would, a new search starts with the first info after a bestmove
+    pub fn new(lines: Vec<RecordedLine>) -> Self {
+        let mut state = AnalysisInfo::new();
+
+        let events = lines
+            .into_iter()
+            .map(|recorded| {
+                let line = recorded.line.trim().to_string();
+
+                let kind = match line.split_whitespace().collect::<Vec<&str>>()[..] {
+                    ["bestmove", bestmove, ..] => EventKind::Bestmove(bestmove.to_string()),
+                    ["info", ..] => EventKind::Info,
+                    _ => EventKind::Other,
+                };
+
+                match kind {
+                    EventKind::Info => {
+                        if state.done {
+                            state = AnalysisInfo::new();
+                        }
+
+                        // unparsable lines leave the state as it is
+                        let _ = state.parse(&line);
+                    }
+                    EventKind::Bestmove(_) => state.done = true,
+                    _ => {}
+                }
+
+                InspectorEvent {
+                    at: Duration::from_millis(recorded.at_ms),
+                    line,
+                    kind,
+                    state,
+                }
+            })
+            .collect();
+
+        Self {
+            events,
+            cursor: None,
+        }
+    }
+
+    /// create new inspector from a newline delimited json recording
+    pub fn from_ndjson(ndjson: &str) -> Result<Self, serde_json::Error> {
+        let lines = ndjson
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<RecordedLine>, serde_json::Error>>()?;
+
+        Ok(Self::new(lines))
+    }
+
+    /// load recording file
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+
+        let mut lines = vec![];
+
+        for line in file.lines() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            lines.push(
+                serde_json::from_str(&line)
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?,
+            );
+        }
+
+        Ok(Self::new(lines))
+    }
+
+    /// parsed events
+    pub fn events(&self) -> &[InspectorEvent] {
+        &self.events
+    }
+
+    /// current event, None before the first step
+    pub fn current(&self) -> Option<&InspectorEvent> {
+        self.events.get(self.cursor?)
+    }
+
+    /// step to the next event and return it, None at the end
+    pub fn step(&mut self) -> Option<&InspectorEvent> {
+        let next = self.cursor.map_or(0, |cursor| cursor + 1);
+
+        if next >= self.events.len() {
+            return None;
+        }
+
+        self.cursor = Some(next);
+
+        self.current()
+    }
+
+    /// step back to the previous event and return it, None before the first
+    pub fn step_back(&mut self) -> Option<&InspectorEvent> {
+        self.cursor = self.cursor.and_then(|cursor| cursor.checked_sub(1));
+
+        self.current()
+    }
+
+    /// move to the last event at or before time and return it
+    pub fn seek(&mut self, at: Duration) -> Option<&InspectorEvent> {
+        self.cursor = self
+            .events
+            .partition_point(|event| event.at <= at)
+            .checked_sub(1);
+
+        self.current()
+    }
+
+    /// analysis state at time, a fresh state before the first event
+    pub fn state_at(&self, at: Duration) -> AnalysisInfo {
+        self.events
+            .iter()
+            .take_while(|event| event.at <= at)
+            .last()
+            .map_or_else(AnalysisInfo::new, |event| event.state)
+    }
+
+    /// human readable changes of the analysis state between two points in time,
+    /// empty if it did not change
+    pub fn diff(&self, from: Duration, to: Duration) -> Vec<String> {
+        let (a, b) = (self.state_at(from), self.state_at(to));
+
+        let mut changes = vec![];
+
+        let mut compare = |name: &str, a: String, b: String| {
+            if a != b {
+                changes.push(format!("{} {} -> {}", name, a, b));
+            }
+        };
+
+        compare("depth", a.depth.to_string(), b.depth.to_string());
+        compare("seldepth", a.seldepth.to_string(), b.seldepth.to_string());
+        compare("score", format!("{:?}", a.score), format!("{:?}", b.score));
+        compare("nodes", a.nodes.to_string(), b.nodes.to_string());
+        compare(
+            "bestmove",
+            format!("{:?}", a.bestmove()),
+            format!("{:?}", b.bestmove()),
+        );
+        compare("pv", format!("{:?}", a.pv()), format!("{:?}", b.pv()));
+        compare("done", a.done.to_string(), b.done.to_string());
+
+        changes
+    }
+}
+
+#[test]
+fn inspect_session() {
+    let ndjson = r#"{"at_ms":0,"line":"info depth 1 score cp 20 nodes 20 pv e2e4 e7e5"}
+{"at_ms":40,"line":"info depth 2 score cp 35 nodes 400 pv d2d4 d7d5"}
+{"at_ms":90,"line":"bestmove d2d4 ponder d7d5"}
+{"at_ms":200,"line":"info depth 1 score cp -10 nodes 30 pv g8f6 c2c4"}
+"#;
+
+    let mut inspector = SessionInspector::from_ndjson(ndjson).unwrap();
+
+    assert_eq!(inspector.events().len(), 4);
+    assert_eq!(inspector.step().unwrap().state.depth, 1);
+    assert_eq!(
+        inspector.step().unwrap().state.bestmove(),
+        Some("d2d4".to_string())
+    );
+    assert_eq!(
+        inspector.seek(Duration::from_millis(100)).unwrap().kind,
+        EventKind::Bestmove("d2d4".to_string())
+    );
+    assert_eq!(inspector.step_back().unwrap().at, Duration::from_millis(40));
+
+    assert_eq!(inspector.state_at(Duration::from_millis(250)).nodes, 30);
+    assert_eq!(
+        inspector.diff(Duration::from_millis(10), Duration::from_millis(50))[0],
+        "depth 1 -> 2"
+    );
+    assert!(inspector
+        .diff(Duration::from_millis(40), Duration::from_millis(89))
+        .is_empty());
+}
@@ -0,0 +1,385 @@
+use serde::{Deserialize, Serialize};
+
+use thiserror::Error;
+
+#[cfg(unix)]
+use crate::daemon::*;
+use crate::profile::*;
+use crate::uciengine::*;
+
+/// version of the job description format written by this crate,
+/// descriptions of a newer version are rejected
+pub const JOB_SPEC_VERSION: u32 = 1;
+
+/// label holding the id of the position a job was made from
+pub const POSITION_ID_LABEL: &str = "position";
+
+/// JobSpecError captures possible job description errors
+#[derive(Error, Debug)]
+pub enum JobSpecError {
+    #[error("invalid job description json : {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("unsupported job description version : {0}")]
+    UnsupportedVersion(u32),
+    #[error("job description could not be read : {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// position of a job description
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobPosition {
+    /// id of the position, reported back in the position label
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// fen, starting position if None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fen: Option<String>,
+    /// space separated uci moves played from the fen
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moves: Option<String>,
+}
+
+/// search budget of every position of a job description,
+/// limits that are not set are not sent
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct JobBudget {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nodes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub movetime_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multipv: Option<usize>,
+}
+
+/// engine a job description has to run on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineRequirements {
+    /// part of the engine name, case insensitive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// minimum number of threads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_threads: Option<usize>,
+    /// minimum hash size in MB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_hash_mb: Option<usize>,
+    /// uci options set for the job, applied in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<(String, String)>,
+}
+
+/// output format the executing tool writes results in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// one json document with all results
+    Json,
+    /// one json result per line
+    Jsonl,
+    /// epd with the bestmove and score as operations
+    Epd,
+    /// raw uci info lines
+    Uci,
+}
+
+/// portable description of analysis work, accepted by the library, the cli,
+/// the daemon and the workers, so one tool can create a job another executes
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "positions": [
+///     { "id": "game1-ply20", "fen": "...", "moves": "e2e4 e7e5" }
+///   ],
+///   "budget": { "depth": 20, "movetime_ms": 1000, "multipv": 3 },
+///   "engine": { "name": "stockfish", "min_threads": 4, "options": [["Hash", "256"]] },
+///   "output": "jsonl",
+///   "labels": { "user": "alice" }
+/// }
+/// ```
+///
+/// every field but version and positions is optional, budget limits are sent as
+/// go options, engine options as uci options, labels and the position id as job labels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobSpec {
+    /// format version
+    pub version: u32,
+    /// positions to analyse
+    pub positions: Vec<JobPosition>,
+    /// search budget per position
+    #[serde(default = "JobBudget::new")]
+    pub budget: JobBudget,
+    /// engine requirements
+    #[serde(default = "EngineRequirements::new")]
+    pub engine: EngineRequirements,
+    /// output format
+    #[serde(default = "OutputFormat::default_format")]
+    pub output: OutputFormat,
+    /// labels of every job
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
+}
+
+/// job budget implementation
+impl JobBudget {
+    /// create new budget without limits
+    pub fn new() -> Self {
+        Self {
+            depth: None,
+            nodes: None,
+            movetime_ms: None,
+            multipv: None,
+        }
+    }
+}
+
+/// implement Default for JobBudget
+impl Default for JobBudget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// engine requirements implementation
+impl EngineRequirements {
+    /// create new requirements any engine satisfies
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            min_threads: None,
+            min_hash_mb: None,
+            options: vec![],
+        }
+    }
+
+    /// true if an engine of profile satisfies the requirements
+    pub fn satisfied_by(&self, profile: &EngineProfile) -> bool {
+        self.name.as_ref().is_none_or(|name| {
+            profile
+                .name
+                .to_ascii_lowercase()
+                .contains(&name.to_ascii_lowercase())
+        }) && self
+            .min_threads
+            .is_none_or(|threads| profile.threads() >= threads)
+            && self
+                .min_hash_mb
+                .is_none_or(|hash_mb| profile.hash_mb() >= hash_mb)
+    }
+}
+
+/// implement Default for EngineRequirements
+impl Default for EngineRequirements {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// output format implementation
+impl OutputFormat {
+    /// format of descriptions that do not name one
+    fn default_format() -> Self {
+        OutputFormat::Jsonl
+    }
+}
+
+/// job spec implementation
+impl JobSpec {
+    /// create new job description without positions
+    pub fn new() -> Self {
+        Self {
+            version: JOB_SPEC_VERSION,
+            positions: vec![],
+            budget: JobBudget::new(),
+            engine: EngineRequirements::new(),
+            output: OutputFormat::default_format(),
+            labels: Labels::new(),
+        }
+    }
+
+    /// add position and return self
+    pub fn position(mut self, position: JobPosition) -> Self {
+        self.positions.push(position);
+
+        self
+    }
+
+    /// set budget and return self
+    pub fn budget(mut self, budget: JobBudget) -> Self {
+        self.budget = budget;
+
+        self
+    }
+
+    /// set engine requirements and return self
+    pub fn engine(mut self, engine: EngineRequirements) -> Self {
+        self.engine = engine;
+
+        self
+    }
+
+    /// set output format and return self
+    pub fn output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+
+        self
+    }
+
+    /// set label and return self
+    pub fn label<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.labels.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    /// parse job description json, rejecting newer versions
+    pub fn from_json(json: &str) -> Result<Self, JobSpecError> {
+        let spec: Self = serde_json::from_str(json)?;
+
+        if spec.version > JOB_SPEC_VERSION {
+            return Err(JobSpecError::UnsupportedVersion(spec.version));
+        }
+
+        Ok(spec)
+    }
+
+    /// load job description file
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, JobSpecError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// job description as json
+    pub fn to_json(&self) -> Result<String, JobSpecError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// go options of the budget
+    fn go_options(&self) -> Vec<(String, String)> {
+        let budget = &self.budget;
+
+        [
+            ("depth", budget.depth.map(|depth| depth as u64)),
+            ("nodes", budget.nodes),
+            ("movetime", budget.movetime_ms),
+        ]
+        .iter()
+        .filter_map(|(key, value)| value.map(|value| (key.to_string(), value.to_string())))
+        .collect()
+    }
+
+    /// uci options of the job, the engine options with the multipv of the budget
+    fn uci_options(&self) -> Vec<(String, String)> {
+        let mut options = self.engine.options.clone();
+
+        if let Some(multipv) = self.budget.multipv {
+            options.push(("MultiPV".to_string(), multipv.to_string()));
+        }
+
+        options
+    }
+
+    /// labels of the job of position
+    fn position_labels(&self, position: &JobPosition) -> Labels {
+        let mut labels = self.labels.clone();
+
+        if let Some(id) = &position.id {
+            labels.insert(POSITION_ID_LABEL.to_string(), id.to_string());
+        }
+
+        labels
+    }
+
+    /// go jobs of the positions, in order
+    pub fn to_go_jobs(&self) -> Vec<GoJob> {
+        let go_options = self.go_options();
+        let uci_options = self.uci_options();
+
+        self.positions
+            .iter()
+            .map(|position| {
+                let mut go_job = match &position.fen {
+                    Some(fen) => GoJob::new().pos_fen(fen),
+                    _ => GoJob::new().pos_startpos(),
+                };
+
+                if let Some(moves) = &position.moves {
+                    go_job = go_job.pos_moves(moves);
+                }
+
+                for (key, value) in uci_options.iter() {
+                    go_job = go_job.uci_opt(key, value);
+                }
+
+                for (key, value) in go_options.iter() {
+                    go_job = go_job.go_opt(key, value);
+                }
+
+                for (key, value) in self.position_labels(position) {
+                    go_job = go_job.label(key, value);
+                }
+
+                go_job
+            })
+            .collect()
+    }
+
+    /// daemon requests of the positions, in order
+    #[cfg(unix)]
+    pub fn to_daemon_requests(&self) -> Vec<DaemonRequest> {
+        self.positions
+            .iter()
+            .map(|position| DaemonRequest {
+                fen: position.fen.clone(),
+                moves: position.moves.clone(),
+                uci_options: self.uci_options(),
+                go_options: self.go_options(),
+                labels: self.position_labels(position),
+                deadline_ms: None,
+            })
+            .collect()
+    }
+}
+
+/// implement Default for JobSpec
+impl Default for JobSpec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn job_spec_round_trip() {
+    let json = r#"{
+        "version": 1,
+        "positions": [
+            { "id": "p1" },
+            { "fen": "8/8/8/8/8/8/k7/K7 w - - 0 1", "moves": "a1b1" }
+        ],
+        "budget": { "depth": 12, "multipv": 2 },
+        "engine": { "name": "stock", "min_threads": 2, "options": [["Hash", "64"]] }
+    }"#;
+
+    let spec = JobSpec::from_json(json).unwrap();
+
+    assert_eq!(spec.output, OutputFormat::Jsonl);
+    assert_eq!(spec.to_go_jobs().len(), 2);
+    assert_eq!(JobSpec::from_json(&spec.to_json().unwrap()).unwrap(), spec);
+
+    let profile = EngineProfile::new("Stockfish 16", "sf").uci_opt("Threads", 4);
+
+    assert!(spec.engine.satisfied_by(&profile));
+    assert!(!spec
+        .engine
+        .satisfied_by(&EngineProfile::new("Stockfish 16", "sf")));
+
+    assert!(matches!(
+        JobSpec::from_json(r#"{ "version": 2, "positions": [] }"#),
+        Err(JobSpecError::UnsupportedVersion(2))
+    ));
+}
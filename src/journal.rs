@@ -0,0 +1,221 @@
+use log::{info, log_enabled, warn, Level};
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// journal entry, one json object per line
+#[derive(Debug, Serialize, Deserialize)]
+enum JournalEntry<T> {
+    /// job accepted, before it is executed
+    Accepted { id: u64, job: T },
+    /// job completed
+    Completed { id: u64 },
+}
+
+/// recovery report of a journal
+#[derive(Debug, Clone)]
+pub struct RecoveryReport<T> {
+    /// number of accepted jobs in the journal
+    pub accepted: usize,
+    /// number of completed jobs in the journal
+    pub completed: usize,
+    /// jobs accepted but not completed, in order of acceptance, to be run again
+    pub incomplete: Vec<(u64, T)>,
+    /// number of lines that could not be read ( a line torn by a crash )
+    pub corrupt_lines: usize,
+}
+
+/// append-only job journal for crash safe services, accepted jobs are written
+/// ( and synced ) before they are executed and marked completed afterwards,
+/// after a crash the jobs not completed are recovered and run again
+///
+/// let mut journal = JobJournal::open("jobs.journal")?;
+/// for (id, request) in journal.recover()?.incomplete { ... }
+/// let id = journal.accept(&request)?;
+/// ...
+/// journal.complete(id)?;
+pub struct JobJournal<T> {
+    path: PathBuf,
+    file: std::fs::File,
+    /// id of the next accepted job
+    next_id: u64,
+    _jobs: std::marker::PhantomData<fn(T)>,
+}
+
+/// job journal implementation
+impl<T> JobJournal<T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+{
+    /// open journal at path, created if missing
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+
+        let mut journal = Self {
+            path,
+            file,
+            next_id: 1,
+            _jobs: std::marker::PhantomData,
+        };
+
+        // continues the ids of the jobs in the journal
+        journal.recover()?;
+
+        Ok(journal)
+    }
+
+    /// append entry and sync it to disk
+    fn append(&mut self, entry: &JournalEntry<T>) -> std::io::Result<()> {
+        let json = serde_json::to_string(entry)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+
+        self.file.write_all(format!("{}\n", json).as_bytes())?;
+
+        self.file.sync_data()
+    }
+
+    /// record accepted job before executing it, returns its id
+    pub fn accept(&mut self, job: &T) -> std::io::Result<u64> {
+        let id = self.next_id;
+
+        self.append(&JournalEntry::Accepted {
+            id,
+            job: job.clone(),
+        })?;
+
+        self.next_id += 1;
+
+        Ok(id)
+    }
+
+    /// record completion of job
+    pub fn complete(&mut self, id: u64) -> std::io::Result<()> {
+        self.append(&JournalEntry::Completed { id })
+    }
+
+    /// read the journal and report the jobs to run again
+    pub fn recover(&mut self) -> std::io::Result<RecoveryReport<T>> {
+        let reader = std::io::BufReader::new(std::fs::File::open(&self.path)?);
+
+        let mut report = RecoveryReport {
+            accepted: 0,
+            completed: 0,
+            incomplete: vec![],
+            corrupt_lines: 0,
+        };
+
+        let mut incomplete: BTreeMap<u64, T> = BTreeMap::new();
+
+        for line in reader.lines() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<JournalEntry<T>>(&line) {
+                Ok(JournalEntry::Accepted { id, job }) => {
+                    report.accepted += 1;
+
+                    self.next_id = self.next_id.max(id + 1);
+
+                    incomplete.insert(id, job);
+                }
+                Ok(JournalEntry::Completed { id }) => {
+                    report.completed += 1;
+
+                    incomplete.remove(&id);
+                }
+                Err(err) => {
+                    warn!("corrupt journal line {} : {:?}", line, err);
+
+                    report.corrupt_lines += 1;
+                }
+            }
+        }
+
+        report.incomplete = incomplete.into_iter().collect();
+
+        if log_enabled!(Level::Info) {
+            info!(
+                "journal {:?} : {} accepted, {} completed, {} incomplete, {} corrupt lines",
+                self.path,
+                report.accepted,
+                report.completed,
+                report.incomplete.len(),
+                report.corrupt_lines
+            );
+        }
+
+        Ok(report)
+    }
+
+    /// compact the journal, only the incomplete jobs are kept,
+    /// the compacted journal replaces the old one atomically
+    pub fn compact(&mut self) -> std::io::Result<()> {
+        let report = self.recover()?;
+
+        let tmp = self.path.with_extension("compact");
+
+        {
+            let mut file = std::fs::File::create(&tmp)?;
+
+            for (id, job) in report.incomplete {
+                let json = serde_json::to_string(&JournalEntry::Accepted { id, job })
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+
+                file.write_all(format!("{}\n", json).as_bytes())?;
+            }
+
+            file.sync_all()?;
+        }
+
+        std::fs::rename(&tmp, &self.path)?;
+
+        self.file = std::fs::OpenOptions::new().append(true).open(&self.path)?;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn job_journal() {
+    let path = std::env::temp_dir().join(format!("uciengine-journal-{}", std::process::id()));
+
+    let _ = std::fs::remove_file(&path);
+
+    {
+        let mut journal: JobJournal<String> = JobJournal::open(&path).unwrap();
+
+        let first = journal.accept(&"startpos depth 10".to_string()).unwrap();
+
+        journal.accept(&"startpos depth 20".to_string()).unwrap();
+        journal.complete(first).unwrap();
+    }
+
+    // crash
+    let mut journal: JobJournal<String> = JobJournal::open(&path).unwrap();
+
+    let report = journal.recover().unwrap();
+
+    assert_eq!(
+        report.incomplete,
+        vec![(2, "startpos depth 20".to_string())]
+    );
+
+    journal.compact().unwrap();
+
+    assert_eq!(journal.accept(&"fen".to_string()).unwrap(), 3);
+    assert_eq!(journal.recover().unwrap().accepted, 2);
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -17,12 +17,7 @@
 //!        .uci_opt("Threads", 4)
 //!        .pos_fen("k7/8/8/8/8/8/R7/7K w - - 0 1")
 //!        .pos_moves("h1h2")
-//!        .tc(Timecontrol {
-//!            wtime: 15000,
-//!            winc: 0,
-//!            btime: 15000,
-//!            binc: 0,
-//!        });
+//!        .tc(Timecontrol::new(15000, 0, 15000, 0));
 //!
 //!    let go_job2 = GoJob::new()
 //!        .uci_opt("UCI_Variant", "chess")
@@ -36,13 +31,13 @@
 //!
 //!    // issue two parallel async go commands, to demonstrate that they will be queued and processed one a time
 //!    tokio::spawn(async move {
-//!        let go_result1 = engine_clone1.go(go_job1).await;
+//!        let go_result1 = engine_clone1.try_go(go_job1).await;
 //!
 //!        println!("go result 1 {:?}", go_result1);
 //!    });
 //!
 //!    tokio::spawn(async move {
-//!        let go_result2 = engine_clone2.go(go_job2).await;
+//!        let go_result2 = engine_clone2.try_go(go_job2).await;
 //!
 //!        println!("go result 2 {:?}", go_result2);
 //!    });
@@ -61,5 +56,73 @@
 //!```
 
 // lib
+pub mod adaptive;
 pub mod analysis;
+pub mod backfill;
+#[cfg(feature = "chess")]
+pub mod benchmark;
+pub mod bloom;
+#[cfg(feature = "chess")]
+pub mod book;
+pub mod buildinfo;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod completeness;
+#[cfg(unix)]
+pub mod daemon;
+pub mod decision;
+pub mod diagnostics;
+pub mod difficulty;
+pub mod elo;
+pub mod evalbar;
+pub mod evalbook;
+pub mod fingerprint;
+pub mod format;
+pub mod game;
+pub mod gamediff;
+#[cfg(feature = "chess")]
+pub mod gamesession;
+pub mod handle;
+pub mod import;
+pub mod inspector;
+pub mod jobspec;
+pub mod journal;
+#[cfg(feature = "sqlite")]
+pub mod matchdb;
+pub mod multiplexer;
+pub mod multipv;
+pub mod notation;
+pub mod options;
+pub mod pipeline;
+pub mod pool;
+pub mod position;
+pub mod prior;
+pub mod profile;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod recheck;
+pub mod sampling;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+pub mod server_side;
+pub mod service;
+pub mod session;
+pub mod slo;
+pub mod slots;
+pub mod speed;
+pub mod split;
+pub mod telemetry;
+pub mod timecontrol;
+#[cfg(feature = "chess")]
+pub mod tournament;
+pub mod trend;
 pub mod uciengine;
+pub mod variant;
+#[cfg(feature = "chess")]
+pub mod verify;
+pub mod warmup;
+pub mod webhook;
+
+pub use buildinfo::build_info;
@@ -0,0 +1,209 @@
+use rusqlite::{params, Connection, Result};
+
+use crate::game::*;
+
+/// win / draw / loss columns from the point of view of engine ?1
+const WDL_COLUMNS: &str = "
+    COALESCE(SUM((white = ?1 AND result = '1-0') OR (black = ?1 AND result = '0-1')), 0),
+    COALESCE(SUM(result = '1/2-1/2'), 0),
+    COALESCE(SUM((white = ?1 AND result = '0-1') OR (black = ?1 AND result = '1-0')), 0)";
+
+/// sqlite database of finished match / tournament games
+pub struct MatchDb {
+    conn: Connection,
+}
+
+/// match database implementation
+impl MatchDb {
+    /// open database at path, creating the schema if needed
+    pub fn open<T: AsRef<std::path::Path>>(path: T) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// open in memory database
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    /// create schema on connection
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS games (
+                id INTEGER PRIMARY KEY,
+                tournament TEXT NOT NULL,
+                white TEXT NOT NULL,
+                black TEXT NOT NULL,
+                opening TEXT,
+                start_fen TEXT,
+                time_control TEXT NOT NULL,
+                moves TEXT NOT NULL,
+                move_times TEXT NOT NULL DEFAULT '',
+                result TEXT NOT NULL,
+                termination TEXT NOT NULL,
+                violations TEXT NOT NULL DEFAULT '[]'
+            );
+            CREATE INDEX IF NOT EXISTS games_white ON games (white);
+            CREATE INDEX IF NOT EXISTS games_black ON games (black);",
+        )?;
+
+        Ok(Self { conn })
+    }
+
+    /// insert game of tournament, returns the id of the game
+    pub fn insert_game(&self, tournament: &str, game: &GameRecord) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO games
+                (tournament, white, black, opening, start_fen, time_control, moves, move_times,
+                    result, termination, violations)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                tournament,
+                game.white,
+                game.black,
+                game.opening,
+                game.start_fen,
+                game.time_control,
+                game.moves.join(" "),
+                game.move_times
+                    .iter()
+                    .map(|time| time.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                game.result.to_pgn(),
+                game.termination,
+                serde_json::to_string(&game.violations).unwrap_or_default(),
+            ],
+        )?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// games of tournament in insertion order
+    pub fn games(&self, tournament: &str) -> Result<Vec<GameRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT white, black, opening, start_fen, time_control, moves, move_times,
+                    result, termination, violations
+                FROM games WHERE tournament = ?1 ORDER BY id",
+        )?;
+
+        let games = stmt.query_map(params![tournament], |row| {
+            let moves: String = row.get(5)?;
+            let move_times: String = row.get(6)?;
+            let result: String = row.get(7)?;
+            let violations: String = row.get(9)?;
+
+            Ok(GameRecord {
+                white: row.get(0)?,
+                black: row.get(1)?,
+                opening: row.get(2)?,
+                start_fen: row.get(3)?,
+                time_control: row.get(4)?,
+                moves: moves.split_whitespace().map(|m| m.to_string()).collect(),
+                move_times: move_times
+                    .split_whitespace()
+                    .filter_map(|time| time.parse::<u64>().ok())
+                    .collect(),
+                result: GameResult::from_pgn(result),
+                termination: row.get(8)?,
+                violations: serde_json::from_str(&violations).unwrap_or_default(),
+            })
+        })?;
+
+        games.collect()
+    }
+
+    /// head to head record of engine against opponent
+    pub fn head_to_head(&self, engine: &str, opponent: &str) -> Result<WdlRecord> {
+        self.conn.query_row(
+            &format!(
+                "SELECT {} FROM games
+                    WHERE (white = ?1 AND black = ?2) OR (white = ?2 AND black = ?1)",
+                WDL_COLUMNS
+            ),
+            params![engine, opponent],
+            |row| {
+                Ok(WdlRecord {
+                    wins: row.get(0)?,
+                    draws: row.get(1)?,
+                    losses: row.get(2)?,
+                })
+            },
+        )
+    }
+
+    /// records of engine grouped by column
+    fn grouped(&self, engine: &str, column: &str) -> Result<Vec<(String, WdlRecord)>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT COALESCE({0}, '?'), {1} FROM games
+                WHERE white = ?1 OR black = ?1 GROUP BY {0} ORDER BY {0}",
+            column, WDL_COLUMNS
+        ))?;
+
+        let records = stmt.query_map(params![engine], |row| {
+            Ok((
+                row.get(0)?,
+                WdlRecord {
+                    wins: row.get(1)?,
+                    draws: row.get(2)?,
+                    losses: row.get(3)?,
+                },
+            ))
+        })?;
+
+        records.collect()
+    }
+
+    /// performance of engine by opening ( unknown openings are grouped under '?' )
+    pub fn by_opening(&self, engine: &str) -> Result<Vec<(String, WdlRecord)>> {
+        self.grouped(engine, "opening")
+    }
+
+    /// performance of engine by time control
+    pub fn by_time_control(&self, engine: &str) -> Result<Vec<(String, WdlRecord)>> {
+        self.grouped(engine, "time_control")
+    }
+}
+
+#[test]
+fn queries() {
+    let db = MatchDb::open_in_memory().unwrap();
+
+    let game = |white: &str, black: &str, opening: &str, tc: &str, result| GameRecord {
+        white: white.to_string(),
+        black: black.to_string(),
+        opening: Some(opening.to_string()),
+        start_fen: None,
+        time_control: tc.to_string(),
+        moves: vec!["e2e4".to_string(), "e7e5".to_string()],
+        move_times: vec![],
+        result,
+        termination: "adjudication".to_string(),
+        violations: vec![],
+    };
+
+    db.insert_game("t", &game("a", "b", "C20", "3+2", GameResult::WhiteWins))
+        .unwrap();
+    db.insert_game("t", &game("b", "a", "C20", "3+2", GameResult::Draw))
+        .unwrap();
+    db.insert_game("t", &game("b", "a", "B01", "1+0", GameResult::WhiteWins))
+        .unwrap();
+    db.insert_game("t", &game("a", "c", "B01", "1+0", GameResult::BlackWins))
+        .unwrap();
+
+    assert_eq!(db.games("t").unwrap().len(), 4);
+    assert_eq!(db.games("t").unwrap()[1].moves, vec!["e2e4", "e7e5"]);
+
+    let h2h = db.head_to_head("a", "b").unwrap();
+
+    assert_eq!((h2h.wins, h2h.draws, h2h.losses), (1, 1, 1));
+
+    let by_opening = db.by_opening("a").unwrap();
+
+    assert_eq!(by_opening[0].0, "B01");
+    assert_eq!(by_opening[0].1.losses, 2);
+
+    let by_tc = db.by_time_control("a").unwrap();
+
+    assert_eq!(by_tc[1].0, "3+2");
+    assert_eq!(by_tc[1].1.score(), Some(0.75));
+}
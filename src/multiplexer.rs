@@ -0,0 +1,378 @@
+use log::{debug, log_enabled, warn, Level};
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::time::Duration;
+
+use tokio::sync::{oneshot, Notify};
+
+use crate::uciengine::*;
+
+/// job queued by a client
+struct MuxJob {
+    go_job: GoJob,
+    rtx: oneshot::Sender<GoResult>,
+}
+
+/// virtual session of a client
+struct MuxSession {
+    /// uci options of the session ( set on the client or by its jobs ),
+    /// applied whenever the engine switches to the session
+    uci_options: BTreeMap<String, String>,
+    /// queued jobs
+    jobs: VecDeque<MuxJob>,
+}
+
+/// multiplexer state shared with the clients
+struct MuxShared {
+    sessions: std::sync::Mutex<Vec<MuxSession>>,
+    notify: Notify,
+}
+
+/// engine multiplexer, shares one engine among several clients,
+/// jobs of the clients are served round robin, each client has a virtual session
+/// ( its uci options are re-applied, options only other sessions set are reset to
+/// the defaults the engine declared and ucinewgame is sent when the engine switches
+/// to its session ), a search running longer than the time slice is stopped
+/// and its result returned as is while other clients are waiting
+pub struct Multiplexer {
+    shared: std::sync::Arc<MuxShared>,
+}
+
+/// multiplexer client, a handle to a virtual session
+pub struct MuxClient {
+    id: usize,
+    shared: std::sync::Arc<MuxShared>,
+}
+
+/// multiplexer implementation
+impl Multiplexer {
+    /// create new multiplexer for engine with given time slice, the engine should be
+    /// idle, it is sent uci for its option defaults unless it did the handshake
+    pub fn new(engine: std::sync::Arc<UciEngine>, slice: Duration) -> Self {
+        let shared = std::sync::Arc::new(MuxShared {
+            sessions: std::sync::Mutex::new(vec![]),
+            notify: Notify::new(),
+        });
+
+        tokio::spawn(Self::schedule(engine, slice, shared.clone()));
+
+        Self { shared }
+    }
+
+    /// create new client with an empty virtual session
+    pub fn client(&self) -> MuxClient {
+        let mut sessions = self.shared.sessions.lock().unwrap();
+
+        sessions.push(MuxSession {
+            uci_options: BTreeMap::new(),
+            jobs: VecDeque::new(),
+        });
+
+        MuxClient {
+            id: sessions.len() - 1,
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// next job round robin starting after the last served session
+    fn next_job(shared: &MuxShared, last: Option<usize>) -> Option<(usize, MuxJob)> {
+        let mut sessions = shared.sessions.lock().unwrap();
+
+        let len = sessions.len();
+        let start = last.map(|last| last + 1).unwrap_or(0);
+
+        (0..len)
+            .map(|i| (start + i) % len)
+            .find(|id| !sessions[*id].jobs.is_empty())
+            .and_then(|id| sessions[id].jobs.pop_front().map(|job| (id, job)))
+    }
+
+    /// true if a session other than id has queued jobs
+    fn others_waiting(shared: &MuxShared, id: usize) -> bool {
+        let sessions = shared.sessions.lock().unwrap();
+
+        sessions
+            .iter()
+            .enumerate()
+            .any(|(other, session)| other != id && !session.jobs.is_empty())
+    }
+
+    /// serve jobs of the sessions one at a time
+    async fn schedule(
+        engine: std::sync::Arc<UciEngine>,
+        slice: Duration,
+        shared: std::sync::Arc<MuxShared>,
+    ) {
+        let mut last: Option<usize> = None;
+
+        if engine.options().is_empty() {
+            if let Err(err) = engine.handshake().await {
+                warn!("multiplexer got no option defaults of the engine : {}", err);
+            }
+        }
+
+        let defaults = engine.options();
+
+        // options set on the engine by any session so far
+        let mut touched: BTreeSet<String> = BTreeSet::new();
+
+        loop {
+            let (id, job) = match Self::next_job(&shared, last) {
+                Some(next) => next,
+                _ => {
+                    shared.notify.notified().await;
+
+                    continue;
+                }
+            };
+
+            let mut go_job = job.go_job;
+
+            if last != Some(id) {
+                if log_enabled!(Level::Debug) {
+                    debug!("multiplexer switching to session {}", id);
+                }
+
+                // the session switch is queued before the job, so it can not
+                // interleave with the search of another session
+                engine.try_go(GoJob::new().new_game(true));
+
+                let sessions = shared.sessions.lock().unwrap();
+
+                let options = &sessions[id].uci_options;
+
+                // every option set so far, as the session left it or reset
+                for key in touched.iter().chain(options.keys()) {
+                    if go_job.uci_option(key).is_some() {
+                        continue;
+                    }
+
+                    let value = options.get(key).cloned().or_else(|| {
+                        defaults
+                            .get(key)
+                            .and_then(|option| option.default.to_owned())
+                    });
+
+                    match value {
+                        Some(value) => go_job = go_job.uci_opt(key, value),
+                        _ => warn!("option {} of another session can not be reset", key),
+                    }
+                }
+            }
+
+            {
+                let mut sessions = shared.sessions.lock().unwrap();
+
+                for (key, value) in go_job.uci_options() {
+                    touched.insert(key.to_owned());
+
+                    sessions[id]
+                        .uci_options
+                        .insert(key.to_owned(), value.to_owned());
+                }
+            }
+
+            last = Some(id);
+
+            let mut pending = engine.try_go(go_job);
+
+            let go_result = loop {
+                match tokio::time::timeout(slice, &mut pending).await {
+                    Ok(go_result) => break go_result,
+                    _ => {
+                        // a job applying its options is not searching yet,
+                        // it is preempted once its go was written
+                        if Self::others_waiting(&shared, id)
+                            && engine.running_job() == Some(pending.id())
+                        {
+                            if log_enabled!(Level::Debug) {
+                                debug!("multiplexer preempting session {}", id);
+                            }
+
+                            engine.issue_command("stop");
+
+                            break (&mut pending).await;
+                        }
+                    }
+                }
+            };
+
+            match go_result {
+                Ok(go_result) => {
+                    let _ = job.rtx.send(go_result);
+                }
+                // engine is gone
+                _ => return,
+            }
+        }
+    }
+}
+
+/// multiplexer client implementation
+impl MuxClient {
+    /// set uci option of the virtual session and return self
+    pub fn uci_opt<K, V>(self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        {
+            let mut sessions = self.shared.sessions.lock().unwrap();
+
+            sessions[self.id]
+                .uci_options
+                .insert(key.to_string(), value.to_string());
+        }
+
+        self
+    }
+
+    /// queue go job in the virtual session
+    pub fn go(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        let (rtx, rrx) = oneshot::channel();
+
+        {
+            let mut sessions = self.shared.sessions.lock().unwrap();
+
+            sessions[self.id].jobs.push_back(MuxJob { go_job, rtx });
+        }
+
+        self.shared.notify.notify_one();
+
+        rrx
+    }
+}
+
+/// go commands of a fake engine log with the options set before each of them, sorted
+#[cfg(all(test, unix))]
+fn searches(log: &[String]) -> Vec<(String, Vec<String>)> {
+    let mut options = vec![];
+
+    log.iter()
+        .filter_map(|command| match command.strip_prefix("setoption name ") {
+            Some(option) => {
+                options.push(option.to_string());
+
+                None
+            }
+            _ if command.starts_with("go") => {
+                options.sort();
+
+                Some((command.to_owned(), std::mem::take(&mut options)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn mux_round_robin() {
+    let path = fake_engine("mux-round-robin", "    go*) echo 'bestmove e2e4';;");
+
+    let mux = Multiplexer::new(EngineBuilder::new(&path).build(), Duration::from_secs(5));
+
+    let a = mux.client();
+    let b = mux.client();
+
+    let a1 = a.go(GoJob::new().pos_startpos().go_opt("depth", 1));
+    let a2 = a.go(GoJob::new().pos_startpos().go_opt("depth", 2));
+    let b1 = b.go(GoJob::new().pos_startpos().go_opt("depth", 3));
+
+    for rrx in [a1, a2, b1] {
+        assert_eq!(rrx.await.unwrap().bestmove.as_deref(), Some("e2e4"));
+    }
+
+    // the sessions take turns
+    assert_eq!(
+        searches(&fake_engine_log(&path))
+            .into_iter()
+            .map(|(go, _)| go)
+            .collect::<Vec<String>>(),
+        ["go depth 1", "go depth 3", "go depth 2"]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn mux_preemption() {
+    // readyok of the option fence comes late, a stop written before it ends nothing
+    let path = fake_engine(
+        "mux-preemption",
+        "    isready) sleep 0.3; echo readyok;;
+    go\\ infinite) ;;
+    stop) echo 'bestmove a2a3';;
+    go*) echo 'bestmove e2e4';;",
+    );
+
+    let mux = Multiplexer::new(
+        EngineBuilder::new(&path).build(),
+        Duration::from_millis(100),
+    );
+
+    let a = mux.client();
+    let b = mux.client();
+
+    let a1 = a.go(GoJob::new().uci_opt("Hash", 64).pos_startpos().infinite());
+    let b1 = b.go(GoJob::new().pos_startpos().go_opt("depth", 1));
+
+    // the infinite search is stopped for the waiting session
+    assert_eq!(a1.await.unwrap().bestmove.as_deref(), Some("a2a3"));
+    assert_eq!(b1.await.unwrap().bestmove.as_deref(), Some("e2e4"));
+
+    let log = fake_engine_log(&path);
+
+    let go = log.iter().position(|command| command == "go infinite");
+    let stop = log.iter().position(|command| command == "stop");
+
+    assert!(go.is_some() && go < stop);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn mux_option_isolation() {
+    let path = fake_engine(
+        "mux-options",
+        "    uci) echo 'id name Fake'; echo 'option name Hash type spin default 16 min 1 max 1024'; echo 'option name MultiPV type spin default 1 min 1 max 500'; echo uciok;;
+    go*) echo 'bestmove e2e4';;",
+    );
+
+    let mux = Multiplexer::new(EngineBuilder::new(&path).build(), Duration::from_secs(5));
+
+    let a = mux.client().uci_opt("Hash", 64);
+    let b = mux.client();
+
+    let a1 = a.go(GoJob::new().pos_startpos().go_opt("depth", 1));
+    let b1 = b.go(GoJob::new()
+        .uci_opt("MultiPV", 3)
+        .pos_startpos()
+        .go_opt("depth", 2));
+    let a2 = a.go(GoJob::new().pos_startpos().go_opt("depth", 3));
+    let b2 = b.go(GoJob::new().pos_startpos().go_opt("depth", 4));
+
+    for rrx in [a1, b1, a2, b2] {
+        assert_eq!(rrx.await.unwrap().bestmove.as_deref(), Some("e2e4"));
+    }
+
+    // each session searches with its own options only
+    let options = |options: &[&str]| options.iter().map(|option| option.to_string()).collect();
+
+    assert_eq!(
+        searches(&fake_engine_log(&path)),
+        [
+            ("go depth 1".to_string(), options(&["Hash value 64"])),
+            (
+                "go depth 2".to_string(),
+                options(&["Hash value 16", "MultiPV value 3"])
+            ),
+            (
+                "go depth 3".to_string(),
+                options(&["Hash value 64", "MultiPV value 1"])
+            ),
+            (
+                "go depth 4".to_string(),
+                options(&["Hash value 16", "MultiPV value 3"])
+            ),
+        ]
+    );
+}
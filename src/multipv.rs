@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, HashSet};
+
+#[cfg(feature = "chess")]
+use shakmaty::{fen::Epd, fen::Fen, CastlingMode, Chess, EnPassantMode};
+
+use crate::analysis::*;
+#[cfg(feature = "chess")]
+use crate::notation::*;
+
+/// how near duplicate multipv lines are merged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DedupMode {
+    /// keep all lines
+    None,
+    /// keep only the best line per first move
+    FirstMove,
+    /// keep only the best line per position reached after the given number of plies,
+    /// merges transposed lines ( lines shorter than that are compared at their end )
+    #[cfg(feature = "chess")]
+    Position(usize),
+}
+
+/// aggregates the multipv lines of an analysis over time,
+/// each slot keeps the latest info received for it
+#[derive(Debug, Clone)]
+pub struct MultiPvLines {
+    /// dedup mode
+    mode: DedupMode,
+    /// fen of the analysed position, startpos if None
+    #[cfg(feature = "chess")]
+    root_fen: Option<String>,
+    /// latest info by multipv slot
+    slots: BTreeMap<usize, AnalysisInfo>,
+}
+
+/// multipv lines implementation
+impl MultiPvLines {
+    /// create new empty multipv lines without dedup
+    pub fn new() -> Self {
+        Self {
+            mode: DedupMode::None,
+            #[cfg(feature = "chess")]
+            root_fen: None,
+            slots: BTreeMap::new(),
+        }
+    }
+
+    /// set dedup mode and return self
+    pub fn dedup(mut self, mode: DedupMode) -> Self {
+        self.mode = mode;
+
+        self
+    }
+
+    /// set fen of the analysed position and return self,
+    /// needed for position dedup of positions other than startpos
+    #[cfg(feature = "chess")]
+    pub fn root_fen<T>(mut self, fen: T) -> Self
+    where
+        T: core::fmt::Display,
+    {
+        self.root_fen = Some(fen.to_string());
+
+        self
+    }
+
+    /// observe analysis info, infos without pv are ignored
+    /// ( multipv 0 is taken as slot 1, engines omit multipv with a single line )
+    pub fn observe(&mut self, ai: &AnalysisInfo) {
+        if ai.pv().is_none() {
+            return;
+        }
+
+        self.slots.insert(ai.multipv.max(1), *ai);
+    }
+
+    /// forget all lines ( new position or changed multipv )
+    pub fn clear(&mut self) {
+        self.slots.clear();
+    }
+
+    /// dedup key of a line, None if the line can not be keyed
+    fn key(&self, ai: &AnalysisInfo) -> Option<String> {
+        let pv = ai.pv()?;
+
+        match self.mode {
+            DedupMode::None => None,
+            DedupMode::FirstMove => pv.split_whitespace().next().map(|uci| uci.to_string()),
+            #[cfg(feature = "chess")]
+            DedupMode::Position(plies) => self.position_key(&pv, plies),
+        }
+    }
+
+    /// epd of the position reached after playing up to plies moves of pv,
+    /// together with the number of plies played
+    #[cfg(feature = "chess")]
+    fn position_key(&self, pv: &str, plies: usize) -> Option<String> {
+        let mut pos = match &self.root_fen {
+            Some(fen) => Fen::from_ascii(fen.as_bytes())
+                .ok()?
+                .into_position::<Chess>(CastlingMode::Standard)
+                .ok()?,
+            _ => Chess::default(),
+        };
+
+        let mut played = 0;
+
+        for uci in pv.split_whitespace().take(plies) {
+            pos = play_uci(pos, uci)?;
+
+            played += 1;
+        }
+
+        Some(format!(
+            "{} {}",
+            played,
+            Epd::from_position(&pos, EnPassantMode::Legal)
+        ))
+    }
+
+    /// lines in multipv order with near duplicates merged into the better ranked line,
+    /// the remaining lines are renumbered from 1
+    pub fn lines(&self) -> Vec<AnalysisInfo> {
+        let mut seen = HashSet::new();
+
+        let mut lines: Vec<AnalysisInfo> = self
+            .slots
+            .values()
+            .filter(|ai| match self.key(ai) {
+                Some(key) => seen.insert(key),
+                _ => true,
+            })
+            .copied()
+            .collect();
+
+        for (i, ai) in lines.iter_mut().enumerate() {
+            ai.multipv = i + 1;
+        }
+
+        lines
+    }
+}
+
+/// implement Default for MultiPvLines
+impl Default for MultiPvLines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// multipv analysis of a search, the lines grouped by depth and multipv index,
+/// each line with its own score, depth and pv
+#[derive(Debug, Clone)]
+pub struct MultiPvAnalysis {
+    /// lines by depth, then by multipv index
+    depths: BTreeMap<usize, BTreeMap<usize, AnalysisInfo>>,
+    /// highest multipv index seen
+    k: usize,
+}
+
+/// multipv analysis implementation
+impl MultiPvAnalysis {
+    /// create new empty multipv analysis
+    pub fn new() -> Self {
+        Self {
+            depths: BTreeMap::new(),
+            k: 0,
+        }
+    }
+
+    /// observe analysis info, infos without pv are ignored
+    /// ( multipv 0 is taken as index 1, engines omit multipv with a single line )
+    pub fn observe(&mut self, ai: &AnalysisInfo) {
+        if ai.pv().is_none() {
+            return;
+        }
+
+        let index = ai.multipv.max(1);
+
+        self.k = self.k.max(index);
+
+        self.depths.entry(ai.depth).or_default().insert(index, *ai);
+    }
+
+    /// forget all lines ( new search )
+    pub fn clear(&mut self) {
+        self.depths.clear();
+
+        self.k = 0;
+    }
+
+    /// number of lines, the highest multipv index seen
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// depths with lines, ascending
+    pub fn depths(&self) -> Vec<usize> {
+        self.depths.keys().copied().collect()
+    }
+
+    /// lines of depth in multipv order
+    pub fn at_depth(&self, depth: usize) -> Vec<AnalysisInfo> {
+        self.depths
+            .get(&depth)
+            .map(|lines| lines.values().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// deepest depth all k lines were reported at, None if there is none
+    pub fn complete_depth(&self) -> Option<usize> {
+        self.depths
+            .iter()
+            .rev()
+            .find(|(_, lines)| lines.len() >= self.k)
+            .map(|(depth, _)| *depth)
+    }
+
+    /// lines of the complete depth, their scores are comparable
+    pub fn complete_lines(&self) -> Vec<AnalysisInfo> {
+        self.complete_depth()
+            .map(|depth| self.at_depth(depth))
+            .unwrap_or_default()
+    }
+
+    /// current best k lines, the deepest line of every multipv index, in multipv order
+    pub fn lines(&self) -> Vec<AnalysisInfo> {
+        let mut lines: BTreeMap<usize, AnalysisInfo> = BTreeMap::new();
+
+        for depth_lines in self.depths.values() {
+            for (index, ai) in depth_lines {
+                lines.insert(*index, *ai);
+            }
+        }
+
+        lines.into_values().collect()
+    }
+
+    /// current best line, None if there was none
+    pub fn best(&self) -> Option<AnalysisInfo> {
+        self.lines().first().copied()
+    }
+}
+
+/// implement Default for MultiPvAnalysis
+impl Default for MultiPvAnalysis {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn dedup_first_move() {
+    let mut lines = MultiPvLines::new();
+
+    for info in [
+        "info depth 10 multipv 1 score cp 30 pv e2e4 e7e5",
+        "info depth 10 multipv 2 score cp 25 pv e2e4 c7c5",
+        "info depth 10 multipv 3 score cp 20 pv d2d4 d7d5",
+    ] {
+        let mut ai = AnalysisInfo::new();
+
+        ai.parse(info).unwrap();
+
+        lines.observe(&ai);
+    }
+
+    assert_eq!(lines.lines().len(), 3);
+
+    let lines = lines.dedup(DedupMode::FirstMove).lines();
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1].pv().unwrap(), "d2d4 d7d5");
+    assert_eq!(lines[1].multipv, 2);
+}
+
+#[cfg(feature = "chess")]
+#[test]
+fn dedup_transposition() {
+    let lines = MultiPvLines::new().dedup(DedupMode::Position(3));
+
+    assert_eq!(
+        lines.position_key("e2e4 e7e5 g1f3 b8c6", 3),
+        lines.position_key("g1f3 e7e5 e2e4 g8f6", 3)
+    );
+    assert_ne!(
+        lines.position_key("e2e4 e7e5 g1f3", 3),
+        lines.position_key("e2e4 e7e5", 3)
+    );
+}
+
+#[test]
+fn multipv_analysis() {
+    let mut analysis = MultiPvAnalysis::new();
+
+    for info in [
+        "info depth 9 multipv 1 score cp 28 pv e2e4 e7e5",
+        "info depth 9 multipv 2 score cp 24 pv d2d4 d7d5",
+        "info depth 9 multipv 3 score cp 15 pv g1f3 g8f6",
+        "info depth 10 multipv 1 score cp 31 pv d2d4 g8f6",
+        "info depth 10 multipv 2 score cp 26 pv e2e4 c7c5",
+        "info depth 10 currmove c2c4 currmovenumber 3",
+    ] {
+        let mut ai = AnalysisInfo::new();
+
+        ai.parse(info).unwrap();
+
+        analysis.observe(&ai);
+    }
+
+    assert_eq!(analysis.k(), 3);
+    assert_eq!(analysis.depths(), vec![9, 10]);
+    assert_eq!(analysis.complete_depth(), Some(9));
+    assert_eq!(analysis.complete_lines()[2].pv().unwrap(), "g1f3 g8f6");
+
+    let lines = analysis.lines();
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0].pv().unwrap(), "d2d4 g8f6");
+    assert_eq!(lines[2].depth, 9);
+    assert_eq!(analysis.best().map(|ai| ai.depth), Some(10));
+}
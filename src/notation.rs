@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "chess")]
+use shakmaty::{san::SanPlus, uci::UciMove, Chess, Position};
+
+/// uci null move, the side to move passes
+pub const NULL_MOVE: &str = "0000";
+
+/// san of the null move ( pgn convention )
+pub const NULL_MOVE_SAN: &str = "--";
+
+/// notation of the promotion piece of uci moves
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PromotionStyle {
+    /// e7e8q ( uci )
+    Lowercase,
+    /// e7e8Q
+    Uppercase,
+    /// e7e8=Q
+    Equals,
+}
+
+/// true if s is a square in uci notation
+fn is_square(s: &[u8]) -> bool {
+    matches!(s, [file, rank] if (b'a'..=b'h').contains(file) && (b'1'..=b'8').contains(rank))
+}
+
+/// squares and promotion piece of a uci move in any promotion notation
+/// ( e7e8q, e7e8Q, e7e8=Q ), None if it is not a promotion
+fn split_promotion(m: &str) -> Option<(&str, char)> {
+    let bytes = m.as_bytes();
+
+    if bytes.len() < 5
+        || !m.is_char_boundary(4)
+        || !is_square(&bytes[..2])
+        || !is_square(&bytes[2..4])
+    {
+        return None;
+    }
+
+    let piece = match &m[4..] {
+        piece if piece.len() == 1 => piece,
+        piece => piece.strip_prefix('=').filter(|piece| piece.len() == 1)?,
+    };
+
+    let piece = piece.chars().next()?.to_ascii_lowercase();
+
+    // kings promote in antichess
+    match "qrbnk".contains(piece) {
+        true => Some((&m[..4], piece)),
+        _ => None,
+    }
+}
+
+/// promotion style implementation
+impl PromotionStyle {
+    /// move in this notation, moves that are not promotions are returned as they are
+    pub fn format(&self, m: &str) -> String {
+        let (squares, piece) = match split_promotion(m) {
+            Some(promotion) => promotion,
+            _ => return m.to_string(),
+        };
+
+        match self {
+            PromotionStyle::Lowercase => format!("{}{}", squares, piece),
+            PromotionStyle::Uppercase => format!("{}{}", squares, piece.to_ascii_uppercase()),
+            PromotionStyle::Equals => format!("{}={}", squares, piece.to_ascii_uppercase()),
+        }
+    }
+
+    /// moves separated by whitespace in this notation
+    pub fn format_moves(&self, moves: &str) -> String {
+        moves
+            .split_whitespace()
+            .map(|m| self.format(m))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+/// move in uci notation ( e7e8q ), accepts any promotion notation
+pub fn normalize_promotion(m: &str) -> String {
+    PromotionStyle::Lowercase.format(m)
+}
+
+/// true if m is the null move
+pub fn is_null_move(m: &str) -> bool {
+    m == NULL_MOVE
+}
+
+/// move of a pv
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PvMove {
+    /// uci move
+    Move(String),
+    /// null move ( 0000 ), the side to move passes
+    Null,
+}
+
+/// moves of a pv, null moves kept apart from the moves
+pub fn pv_moves(pv: &str) -> Vec<PvMove> {
+    pv.split_whitespace()
+        .map(|m| match is_null_move(m) {
+            true => PvMove::Null,
+            _ => PvMove::Move(m.to_string()),
+        })
+        .collect()
+}
+
+/// position after the side to move of pos passed, None if it can not pass
+/// ( its opponent would be in check )
+#[cfg(feature = "chess")]
+pub fn play_null_move(pos: Chess) -> Option<Chess> {
+    pos.swap_turn().ok()
+}
+
+/// position after uci move m, the null move included, None if m is not legal in pos
+#[cfg(feature = "chess")]
+pub fn play_uci(pos: Chess, m: &str) -> Option<Chess> {
+    if is_null_move(m) {
+        return play_null_move(pos);
+    }
+
+    let m = m.parse::<UciMove>().ok()?.to_move(&pos).ok()?;
+
+    let mut pos = pos;
+
+    pos.play_unchecked(m);
+
+    Some(pos)
+}
+
+/// san of the moves of pv played from pos, null moves are written as -- and never
+/// converted, the line ends at the first move that can not be played
+#[cfg(feature = "chess")]
+pub fn san_pv(pos: &Chess, pv: &str) -> Vec<String> {
+    let mut pos = pos.to_owned();
+    let mut sans = vec![];
+
+    for m in pv_moves(pv) {
+        match m {
+            PvMove::Null => match play_null_move(pos.to_owned()) {
+                Some(next) => {
+                    pos = next;
+
+                    sans.push(NULL_MOVE_SAN.to_string());
+                }
+                _ => break,
+            },
+            PvMove::Move(uci) => {
+                let m = match uci
+                    .parse::<UciMove>()
+                    .ok()
+                    .and_then(|uci| uci.to_move(&pos).ok())
+                {
+                    Some(m) => m,
+                    _ => break,
+                };
+
+                sans.push(SanPlus::from_move_and_play_unchecked(&mut pos, m).to_string());
+            }
+        }
+    }
+
+    sans
+}
+
+#[test]
+fn promotion_notation() {
+    for m in ["e7e8q", "e7e8Q", "e7e8=Q", "e7e8=q"] {
+        assert_eq!(normalize_promotion(m), "e7e8q");
+    }
+
+    assert_eq!(PromotionStyle::Uppercase.format("b2a1n"), "b2a1N");
+    assert_eq!(PromotionStyle::Equals.format("b2a1n"), "b2a1=N");
+    assert_eq!(
+        PromotionStyle::Lowercase.format_moves("e2e4  a7a8=R 0000"),
+        "e2e4 a7a8r 0000"
+    );
+
+    // not promotions
+    for m in ["e2e4", "e7e8x", "e7e8==Q", "z7e8q", "e1g1"] {
+        assert_eq!(normalize_promotion(m), m);
+    }
+}
+
+#[test]
+fn null_moves() {
+    assert_eq!(
+        pv_moves("e2e4 0000 d2d4"),
+        vec![
+            PvMove::Move("e2e4".to_string()),
+            PvMove::Null,
+            PvMove::Move("d2d4".to_string())
+        ]
+    );
+    assert_eq!(normalize_promotion(NULL_MOVE), NULL_MOVE);
+}
+
+#[cfg(feature = "chess")]
+#[test]
+fn null_move_san() {
+    assert_eq!(
+        san_pv(&Chess::default(), "e2e4 0000 d2d4"),
+        vec!["e4", "--", "d4"]
+    );
+
+    // white can not pass out of check
+    assert_eq!(
+        san_pv(&Chess::default(), "f2f3 e7e5 g2g4 d8h4 0000 e2e4"),
+        vec!["f3", "e5", "g4", "Qh4#"]
+    );
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use thiserror::Error;
+
+/// OptionError captures possible engine option errors
+#[derive(Error, Debug, PartialEq)]
+pub enum OptionError {
+    #[error("invalid option line : {0}")]
+    InvalidLine(String),
+    #[error("unknown option : {0}")]
+    UnknownOption(String),
+    #[error("invalid option value : {0}")]
+    InvalidValue(String),
+}
+
+/// type of an engine option
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OptionType {
+    Check,
+    Spin,
+    Combo,
+    Button,
+    String,
+}
+
+/// option an engine declares in its uci banner
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineOption {
+    /// option name
+    pub name: String,
+    /// option type
+    pub option_type: OptionType,
+    /// default value, None for buttons
+    pub default: Option<String>,
+    /// minimum of spin options
+    pub min: Option<i64>,
+    /// maximum of spin options
+    pub max: Option<i64>,
+    /// values of combo options
+    pub vars: Vec<String>,
+}
+
+/// engine options by name, as declared by the engine
+pub type EngineOptions = HashMap<String, EngineOption>;
+
+/// engine option implementation
+impl EngineOption {
+    /// parse option line, with or without the leading "option "
+    /// ( "option name Hash type spin default 16 min 1 max 33554432" )
+    pub fn parse<T: AsRef<str>>(line: T) -> Result<Self, OptionError> {
+        let line = line.as_ref().trim();
+        let invalid = || OptionError::InvalidLine(line.to_string());
+
+        let tokens: Vec<&str> = line
+            .strip_prefix("option ")
+            .unwrap_or(line)
+            .split_whitespace()
+            .collect();
+
+        // values of each keyword, names and values may contain spaces
+        let mut fields: Vec<(&str, Vec<&str>)> = vec![];
+
+        for token in tokens {
+            match token {
+                "name" | "type" | "default" | "min" | "max" | "var" => fields.push((token, vec![])),
+                _ => fields.last_mut().ok_or_else(invalid)?.1.push(token),
+            }
+        }
+
+        let mut option = EngineOption {
+            name: String::new(),
+            option_type: OptionType::Button,
+            default: None,
+            min: None,
+            max: None,
+            vars: vec![],
+        };
+
+        let mut has_type = false;
+
+        for (keyword, values) in fields {
+            let value = values.join(" ");
+
+            match keyword {
+                "name" => option.name = value,
+                "type" => {
+                    option.option_type = match value.as_str() {
+                        "check" => OptionType::Check,
+                        "spin" => OptionType::Spin,
+                        "combo" => OptionType::Combo,
+                        "button" => OptionType::Button,
+                        "string" => OptionType::String,
+                        _ => return Err(invalid()),
+                    };
+
+                    has_type = true;
+                }
+                "default" => option.default = Some(value),
+                "min" => option.min = Some(value.parse().map_err(|_| invalid())?),
+                "max" => option.max = Some(value.parse().map_err(|_| invalid())?),
+                _ => option.vars.push(value),
+            }
+        }
+
+        if option.name.is_empty() || !has_type {
+            return Err(invalid());
+        }
+
+        Ok(option)
+    }
+
+    /// check value before it is sent with setoption, None for buttons
+    pub fn validate(&self, value: Option<&str>) -> Result<(), OptionError> {
+        let invalid = |value: &str| {
+            OptionError::InvalidValue(format!(
+                "{} for {} {:?}",
+                value, self.name, self.option_type
+            ))
+        };
+
+        let value = match (self.option_type, value) {
+            (OptionType::Button, None) => return Ok(()),
+            (OptionType::Button, Some(value)) => return Err(invalid(value)),
+            (_, None) => return Err(invalid("no value")),
+            (_, Some(value)) => value,
+        };
+
+        match self.option_type {
+            OptionType::Check if value != "true" && value != "false" => Err(invalid(value)),
+            OptionType::Spin => {
+                let spin: i64 = value.parse().map_err(|_| invalid(value))?;
+
+                if self.min.is_some_and(|min| spin < min) || self.max.is_some_and(|max| spin > max)
+                {
+                    return Err(invalid(value));
+                }
+
+                Ok(())
+            }
+            OptionType::Combo if !self.vars.iter().any(|var| var.eq_ignore_ascii_case(value)) => {
+                Err(invalid(value))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// engine options from option lines, lines that can not be parsed are skipped
+pub fn parse_options<I, T>(lines: I) -> EngineOptions
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<str>,
+{
+    lines
+        .into_iter()
+        .filter_map(|line| EngineOption::parse(line).ok())
+        .map(|option| (option.name.to_owned(), option))
+        .collect()
+}
+
+/// check option value against the declared options ( names are case insensitive in uci )
+pub fn validate_option(
+    options: &EngineOptions,
+    name: &str,
+    value: Option<&str>,
+) -> Result<(), OptionError> {
+    options
+        .values()
+        .find(|option| option.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| OptionError::UnknownOption(name.to_string()))?
+        .validate(value)
+}
+
+#[test]
+fn engine_options() {
+    let options = parse_options([
+        "option name Hash type spin default 16 min 1 max 33554432",
+        "option name Ponder type check default false",
+        "option name Analysis Contempt type combo default Both var Off var White var Black var Both",
+        "option name Clear Hash type button",
+        "option name SyzygyPath type string default <empty>",
+        "option name Broken",
+    ]);
+
+    assert_eq!(options.len(), 5);
+    assert_eq!(options["Hash"].max, Some(33554432));
+    assert_eq!(options["Analysis Contempt"].vars.len(), 4);
+
+    assert!(validate_option(&options, "hash", Some("128")).is_ok());
+    assert!(validate_option(&options, "Hash", Some("0")).is_err());
+    assert!(validate_option(&options, "Ponder", Some("yes")).is_err());
+    assert!(validate_option(&options, "Analysis Contempt", Some("white")).is_ok());
+    assert!(validate_option(&options, "Clear Hash", None).is_ok());
+    assert_eq!(
+        validate_option(&options, "Threads", Some("2")),
+        Err(OptionError::UnknownOption("Threads".to_string()))
+    );
+}
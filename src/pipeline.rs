@@ -0,0 +1,289 @@
+use log::warn;
+
+use std::future::Future;
+use std::pin::Pin;
+
+use tokio::sync::{mpsc, Semaphore};
+
+use crate::pool::*;
+use crate::uciengine::*;
+
+/// capacity of the channels between stages
+const STAGE_CHANNEL_CAPACITY: usize = 64;
+
+/// future returned by a stage, resolves to the items produced from one input item
+pub type StageFuture<T> = Pin<Box<dyn Future<Output = Vec<T>> + Send>>;
+
+/// pipeline stage, turns each input item into zero or more output items
+pub trait Stage: Send + Sync + 'static {
+    /// input item
+    type In: Send + 'static;
+    /// output item
+    type Out: Send + 'static;
+
+    /// process one item
+    fn process(&self, item: Self::In) -> StageFuture<Self::Out>;
+
+    /// number of items processed at the same time ( 1 keeps the order of the items )
+    fn concurrency(&self) -> usize {
+        1
+    }
+}
+
+/// stage mapping items with a function
+pub struct MapStage<I, O, F> {
+    f: std::sync::Arc<F>,
+    _items: std::marker::PhantomData<fn(I) -> O>,
+}
+
+/// map stage as stage
+impl<I, O, F> Stage for MapStage<I, O, F>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+    F: Fn(I) -> O + Send + Sync + 'static,
+{
+    type In = I;
+    type Out = O;
+
+    fn process(&self, item: I) -> StageFuture<O> {
+        let out = (self.f)(item);
+
+        Box::pin(async move { vec![out] })
+    }
+}
+
+/// stage keeping the items a predicate holds for
+pub struct FilterStage<T, F> {
+    f: F,
+    _items: std::marker::PhantomData<fn(T)>,
+}
+
+/// filter stage as stage
+impl<T, F> Stage for FilterStage<T, F>
+where
+    T: Send + 'static,
+    F: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    type In = T;
+    type Out = T;
+
+    fn process(&self, item: T) -> StageFuture<T> {
+        let keep = (self.f)(&item);
+
+        Box::pin(async move {
+            match keep {
+                true => vec![item],
+                _ => vec![],
+            }
+        })
+    }
+}
+
+/// stage analysing items on an engine pool, each item is turned into a go job
+/// and passed on together with its result, as many items are analysed
+/// at the same time as the pool has engines
+pub struct AnalyseStage<T, F> {
+    pool: std::sync::Arc<EnginePool>,
+    make_job: F,
+    _items: std::marker::PhantomData<fn(T)>,
+}
+
+/// analyse stage implementation
+impl<T, F> AnalyseStage<T, F>
+where
+    F: Fn(&T) -> GoJob,
+{
+    /// create new analyse stage on pool
+    pub fn new(pool: std::sync::Arc<EnginePool>, make_job: F) -> Self {
+        Self {
+            pool,
+            make_job,
+            _items: std::marker::PhantomData,
+        }
+    }
+}
+
+/// analyse stage as stage
+impl<T, F> Stage for AnalyseStage<T, F>
+where
+    T: Send + 'static,
+    F: Fn(&T) -> GoJob + Send + Sync + 'static,
+{
+    type In = T;
+    type Out = (T, GoResult);
+
+    fn process(&self, item: T) -> StageFuture<(T, GoResult)> {
+        let pending = self.pool.go((self.make_job)(&item));
+
+        Box::pin(async move {
+            match pending {
+                Ok(pending) => match pending.await {
+                    Ok(go_result) => vec![(item, go_result)],
+                    Err(err) => {
+                        warn!("pipeline analysis failed {:?}", err);
+
+                        vec![]
+                    }
+                },
+                Err(err) => {
+                    warn!("pipeline analysis not submitted {:?}", err);
+
+                    vec![]
+                }
+            }
+        })
+    }
+
+    fn concurrency(&self) -> usize {
+        self.pool.len().max(1)
+    }
+}
+
+/// run stage on the items of the input channel, returns the output channel
+fn connect<S: Stage>(stage: S, mut input: mpsc::Receiver<S::In>) -> mpsc::Receiver<S::Out> {
+    let (tx, rx) = mpsc::channel(STAGE_CHANNEL_CAPACITY);
+
+    let stage = std::sync::Arc::new(stage);
+
+    tokio::spawn(async move {
+        let permits = std::sync::Arc::new(Semaphore::new(stage.concurrency().max(1)));
+
+        while let Some(item) = input.recv().await {
+            let permit = match permits.clone().acquire_owned().await {
+                Ok(permit) => permit,
+                _ => break,
+            };
+
+            let out = stage.process(item);
+            let tx = tx.clone();
+
+            tokio::spawn(async move {
+                for item in out.await {
+                    if tx.send(item).await.is_err() {
+                        break;
+                    }
+                }
+
+                drop(permit);
+            });
+        }
+    });
+
+    rx
+}
+
+/// pipeline of stages, items flow through bounded channels, so a slow stage
+/// holds back the stages before it
+///
+/// let pipeline = Pipeline::new()
+///     .filter(|fen: &String| !fen.is_empty())
+///     .stage(AnalyseStage::new(pool, |fen: &String| GoJob::new().pos_fen(fen).go_opt("depth", 12)))
+///     .map(|(fen, go_result)| (fen, go_result.ai.score));
+pub struct Pipeline<I, O> {
+    connect: Box<dyn FnOnce(mpsc::Receiver<I>) -> mpsc::Receiver<O> + Send>,
+}
+
+/// pipeline implementation
+impl<I> Pipeline<I, I>
+where
+    I: Send + 'static,
+{
+    /// create new empty pipeline
+    pub fn new() -> Self {
+        Self {
+            connect: Box::new(|input| input),
+        }
+    }
+}
+
+/// implement Default for Pipeline
+impl<I> Default for Pipeline<I, I>
+where
+    I: Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// pipeline implementation
+impl<I, O> Pipeline<I, O>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    /// append stage and return the extended pipeline
+    pub fn stage<S>(self, stage: S) -> Pipeline<I, S::Out>
+    where
+        S: Stage<In = O>,
+    {
+        let prev = self.connect;
+
+        Pipeline {
+            connect: Box::new(move |input| connect(stage, prev(input))),
+        }
+    }
+
+    /// append map stage and return the extended pipeline
+    pub fn map<T, F>(self, f: F) -> Pipeline<I, T>
+    where
+        T: Send + 'static,
+        F: Fn(O) -> T + Send + Sync + 'static,
+    {
+        self.stage(MapStage {
+            f: std::sync::Arc::new(f),
+            _items: std::marker::PhantomData,
+        })
+    }
+
+    /// append filter stage and return the extended pipeline
+    pub fn filter<F>(self, f: F) -> Pipeline<I, O>
+    where
+        F: Fn(&O) -> bool + Send + Sync + 'static,
+    {
+        self.stage(FilterStage {
+            f,
+            _items: std::marker::PhantomData,
+        })
+    }
+
+    /// start the pipeline on the items of the input channel, returns the output channel
+    pub fn run(self, input: mpsc::Receiver<I>) -> mpsc::Receiver<O> {
+        (self.connect)(input)
+    }
+
+    /// run the pipeline on items and collect the output
+    pub async fn collect(self, items: Vec<I>) -> Vec<O> {
+        let (tx, input) = mpsc::channel(STAGE_CHANNEL_CAPACITY);
+
+        let mut output = self.run(input);
+
+        tokio::spawn(async move {
+            for item in items {
+                if tx.send(item).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut collected = vec![];
+
+        while let Some(item) = output.recv().await {
+            collected.push(item);
+        }
+
+        collected
+    }
+}
+
+#[tokio::test]
+async fn pipeline_stages() {
+    let collected = Pipeline::new()
+        .filter(|n: &u32| *n > 2)
+        .map(|n| n * 10)
+        .collect(vec![1, 2, 3, 4])
+        .await;
+
+    assert_eq!(collected, vec![30, 40]);
+}
@@ -0,0 +1,1085 @@
+use log::{debug, info, log_enabled, warn, Level};
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use thiserror::Error;
+
+use tokio::sync::{mpsc, oneshot, Notify, Semaphore};
+
+use crate::profile::*;
+use crate::uciengine::*;
+
+/// interval of stop commands once a drain deadline passed
+const DRAIN_STOP_INTERVAL: Duration = Duration::from_millis(100);
+/// time a new pool member has to pass its health check
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// default time a session stays bound to its engine after its last job
+pub const DEFAULT_AFFINITY_TIMEOUT: Duration = Duration::from_secs(60);
+/// pending jobs a session engine may have more than the least loaded engine,
+/// before the session is moved to the least loaded engine
+const AFFINITY_SLACK: usize = 1;
+
+/// PoolError captures possible engine pool errors
+#[derive(Error, Debug)]
+pub enum PoolError {
+    #[error("engine pool is draining and does not accept jobs")]
+    Draining,
+    #[error("engine pool has no engines")]
+    Empty,
+    #[error("replacement of pool member {0} failed its health check")]
+    HealthCheckFailed(usize),
+    #[error("go job rejected by pool middleware : {0}")]
+    Rejected(String),
+    #[error("go job failed : {0}")]
+    Engine(EngineError),
+}
+
+/// shutdown hook of the pool
+type ShutdownHook = Box<dyn FnOnce() + Send>;
+
+/// middleware of the pool, inspects and modifies go jobs before they are dispatched,
+/// so services encode policy ( depth caps of free tiers, multipv minimums, options
+/// every job needs ) in one place instead of in every submission path
+pub trait JobMiddleware: Send + Sync {
+    /// modify go job of session ( None for jobs outside sessions ), an error rejects
+    /// the job ( PoolError::Rejected )
+    fn apply(&self, session: Option<&str>, go_job: GoJob) -> Result<GoJob, PoolError>;
+}
+
+/// job middleware implementation for closures
+impl<F> JobMiddleware for F
+where
+    F: Fn(Option<&str>, GoJob) -> Result<GoJob, PoolError> + Send + Sync,
+{
+    fn apply(&self, session: Option<&str>, go_job: GoJob) -> Result<GoJob, PoolError> {
+        self(session, go_job)
+    }
+}
+
+/// middleware of the pool, invoked on the result of every completed job before
+/// it is sent to the submitter ( scrub pvs, normalize scores, write caches, push metrics )
+pub trait ResultMiddleware: Send + Sync {
+    /// modify result of a job of session ( None for jobs outside sessions )
+    fn apply(&self, session: Option<&str>, go_result: GoResult) -> GoResult;
+}
+
+/// result middleware implementation for closures
+impl<F> ResultMiddleware for F
+where
+    F: Fn(Option<&str>, GoResult) -> GoResult + Send + Sync,
+{
+    fn apply(&self, session: Option<&str>, go_result: GoResult) -> GoResult {
+        self(session, go_result)
+    }
+}
+
+/// middleware capping the depth of every search, infinite searches included
+pub struct DepthCap(pub usize);
+
+/// job middleware implementation for depth cap
+impl JobMiddleware for DepthCap {
+    fn apply(&self, _session: Option<&str>, go_job: GoJob) -> Result<GoJob, PoolError> {
+        Ok(go_job.cap_depth(self.0))
+    }
+}
+
+/// middleware raising the MultiPV option of every search to a minimum
+pub struct MultiPvMin(pub usize);
+
+/// job middleware implementation for multipv minimum
+impl JobMiddleware for MultiPvMin {
+    fn apply(&self, _session: Option<&str>, go_job: GoJob) -> Result<GoJob, PoolError> {
+        if !go_job.searches() {
+            return Ok(go_job);
+        }
+
+        let multipv = go_job
+            .uci_option("MultiPV")
+            .and_then(|multipv| multipv.parse::<usize>().ok())
+            .unwrap_or(1);
+
+        match multipv < self.0 {
+            true => Ok(go_job.uci_opt("MultiPV", self.0)),
+            _ => Ok(go_job),
+        }
+    }
+}
+
+/// state of a pool job
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum JobState {
+    /// waiting behind other jobs of its engine, or for the engine to apply its options
+    Queued,
+    /// searching, its go was written
+    Running,
+}
+
+/// snapshot of a pool job, for admin tooling
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    /// job id
+    pub id: u64,
+    /// index of the engine the job went to
+    pub engine: usize,
+    /// position command of the job
+    pub position: Option<String>,
+    /// go command of the job ( the search budget )
+    pub budget: Option<String>,
+    /// time since the job was submitted
+    pub elapsed: Duration,
+    /// queued or running
+    pub state: JobState,
+}
+
+/// status of a pool member, for admin tooling
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineStatus {
+    /// index of the engine
+    pub index: usize,
+    /// process id
+    pub pid: Option<u32>,
+    /// labels of the engine
+    pub labels: Labels,
+    /// jobs pending on the engine
+    pub pending: usize,
+    /// error that killed the engine, None while it is alive
+    pub error: Option<String>,
+}
+
+/// job tracked by the pool
+struct TrackedJob {
+    engine: std::sync::Arc<UciEngine>,
+    /// id of the job on its engine
+    engine_job: u64,
+    index: usize,
+    position: Option<String>,
+    budget: Option<String>,
+    submitted: Instant,
+    cancelled: std::sync::Arc<AtomicBool>,
+}
+
+/// tracked job implementation
+impl TrackedJob {
+    /// running once its engine runs its search
+    fn state(&self) -> JobState {
+        match self.engine.running_job() == Some(self.engine_job) {
+            true => JobState::Running,
+            _ => JobState::Queued,
+        }
+    }
+}
+
+/// pool state shared with the result forwarding tasks
+struct PoolShared {
+    /// jobs pending by id
+    jobs: std::sync::Mutex<BTreeMap<u64, TrackedJob>>,
+    /// id of the next job
+    next_id: AtomicUsize,
+    /// jobs pending per engine, a replaced engine gets a new counter,
+    /// the jobs left on the old engine count on the old one
+    pending: std::sync::Mutex<Vec<std::sync::Arc<AtomicUsize>>>,
+    /// jobs pending in total
+    in_flight: AtomicUsize,
+    /// notified when the pool becomes empty
+    empty: Notify,
+}
+
+/// engine pool, distributes go jobs across engines, each job goes to the engine
+/// with the fewest pending jobs
+pub struct EnginePool {
+    /// pool members, replaced in place by rolling_replace
+    engines: std::sync::Mutex<Vec<std::sync::Arc<UciEngine>>>,
+    shared: std::sync::Arc<PoolShared>,
+    /// false once draining started
+    accepting: AtomicBool,
+    /// hooks run on shutdown, in order of registration
+    shutdown_hooks: std::sync::Mutex<Vec<ShutdownHook>>,
+    /// engine index and time of the last job by session
+    affinity: std::sync::Mutex<HashMap<String, (usize, Instant)>>,
+    /// time a session stays bound to its engine after its last job
+    affinity_timeout: Duration,
+    /// middleware applied to every job, in order of registration
+    middleware: Vec<Box<dyn JobMiddleware>>,
+    /// middleware applied to every result, in order of registration
+    result_middleware: Vec<std::sync::Arc<dyn ResultMiddleware>>,
+}
+
+/// engine pool implementation
+impl EnginePool {
+    /// create new pool of engines
+    pub fn new(engines: Vec<std::sync::Arc<UciEngine>>) -> Self {
+        let pending = engines
+            .iter()
+            .map(|_| std::sync::Arc::new(AtomicUsize::new(0)))
+            .collect();
+
+        Self {
+            engines: std::sync::Mutex::new(engines),
+            shared: std::sync::Arc::new(PoolShared {
+                jobs: std::sync::Mutex::new(BTreeMap::new()),
+                next_id: AtomicUsize::new(1),
+                pending: std::sync::Mutex::new(pending),
+                in_flight: AtomicUsize::new(0),
+                empty: Notify::new(),
+            }),
+            accepting: AtomicBool::new(true),
+            shutdown_hooks: std::sync::Mutex::new(vec![]),
+            affinity: std::sync::Mutex::new(HashMap::new()),
+            affinity_timeout: DEFAULT_AFFINITY_TIMEOUT,
+            middleware: vec![],
+            result_middleware: vec![],
+        }
+    }
+
+    /// add middleware and return self, middleware runs in order of registration,
+    /// each one gets the job as the ones before left it
+    pub fn layer<M>(mut self, middleware: M) -> Self
+    where
+        M: JobMiddleware + 'static,
+    {
+        self.middleware.push(Box::new(middleware));
+
+        self
+    }
+
+    /// add result middleware and return self, result middleware runs in order of
+    /// registration, each one gets the result as the ones before left it
+    pub fn result_layer<M>(mut self, middleware: M) -> Self
+    where
+        M: ResultMiddleware + 'static,
+    {
+        self.result_middleware.push(std::sync::Arc::new(middleware));
+
+        self
+    }
+
+    /// go job of session as the middleware leaves it
+    fn apply_middleware(&self, session: Option<&str>, go_job: GoJob) -> Result<GoJob, PoolError> {
+        self.middleware
+            .iter()
+            .try_fold(go_job, |go_job, middleware| {
+                middleware.apply(session, go_job)
+            })
+    }
+
+    /// set session affinity timeout and return self
+    pub fn affinity_timeout(mut self, timeout: Duration) -> Self {
+        self.affinity_timeout = timeout;
+
+        self
+    }
+
+    /// create new pool of n engines spawned from path
+    pub fn spawn<T>(path: T, n: usize) -> Self
+    where
+        T: core::fmt::Display,
+    {
+        let path = path.to_string();
+
+        Self::new((0..n).map(|_| UciEngine::new(&path)).collect())
+    }
+
+    /// create new pool of n lazy engines spawned from path,
+    /// the process of an engine is spawned when it gets its first job
+    pub fn spawn_lazy<T>(path: T, n: usize) -> Self
+    where
+        T: core::fmt::Display,
+    {
+        let path = path.to_string();
+
+        Self::new(
+            (0..n)
+                .map(|_| EngineBuilder::new(&path).lazy(true).build())
+                .collect(),
+        )
+    }
+
+    /// number of engines
+    pub fn len(&self) -> usize {
+        self.engines().len()
+    }
+
+    /// true if the pool has no engines
+    pub fn is_empty(&self) -> bool {
+        self.engines().is_empty()
+    }
+
+    /// current pool members
+    pub fn engines(&self) -> Vec<std::sync::Arc<UciEngine>> {
+        let engines = self.engines.lock().unwrap();
+
+        engines.clone()
+    }
+
+    /// counter of the jobs pending on the engine at index
+    fn pending(&self, index: usize) -> std::sync::Arc<AtomicUsize> {
+        let pending = self.shared.pending.lock().unwrap();
+
+        pending[index].clone()
+    }
+
+    /// number of jobs pending ( queued or searching )
+    pub fn in_flight(&self) -> usize {
+        self.shared.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// true once draining started
+    pub fn is_draining(&self) -> bool {
+        !self.accepting.load(Ordering::SeqCst)
+    }
+
+    /// submit go job to the engine with the fewest pending jobs
+    pub fn go(&self, go_job: GoJob) -> Result<PendingGo, PoolError> {
+        self.submit(None, go_job)
+    }
+
+    /// submit go job of a session ( for example the successive positions of a game
+    /// a client analyses ), the jobs of a session go to the same engine to reuse
+    /// its hash, unless the session was idle for the affinity timeout or its engine
+    /// got busier than the least loaded one
+    pub fn go_session(&self, session: &str, go_job: GoJob) -> Result<PendingGo, PoolError> {
+        self.submit(Some(session), go_job)
+    }
+
+    /// end session, its next job may go to any engine
+    pub fn end_session(&self, session: &str) {
+        let mut affinity = self.affinity.lock().unwrap();
+
+        affinity.remove(session);
+    }
+
+    /// engine index for the next job of session
+    fn session_engine(&self, session: &str, least_loaded: usize) -> usize {
+        let pending = |index: usize| self.pending(index).load(Ordering::SeqCst);
+
+        let mut affinity = self.affinity.lock().unwrap();
+
+        affinity.retain(|_, (_, last)| last.elapsed() < self.affinity_timeout);
+
+        let index = match affinity.get(session) {
+            Some((index, _)) if pending(*index) <= pending(least_loaded) + AFFINITY_SLACK => *index,
+            Some((index, _)) => {
+                if log_enabled!(Level::Debug) {
+                    debug!(
+                        "rebalancing session {} from engine {} to {}",
+                        session, index, least_loaded
+                    );
+                }
+
+                least_loaded
+            }
+            _ => least_loaded,
+        };
+
+        affinity.insert(session.to_string(), (index, Instant::now()));
+
+        index
+    }
+
+    /// go result of session as the result middleware leaves it
+    fn apply_result_middleware(
+        result_middleware: &[std::sync::Arc<dyn ResultMiddleware>],
+        session: Option<&str>,
+        go_result: GoResult,
+    ) -> GoResult {
+        result_middleware
+            .iter()
+            .fold(go_result, |go_result, middleware| {
+                middleware.apply(session, go_result)
+            })
+    }
+
+    /// submit go job, of a session if any, the id of the pending go identifies
+    /// the job in jobs and cancel, its error is the one the job failed with
+    pub fn submit(&self, session: Option<&str>, go_job: GoJob) -> Result<PendingGo, PoolError> {
+        if self.is_draining() {
+            return Err(PoolError::Draining);
+        }
+
+        let go_job = self.apply_middleware(session, go_job)?;
+
+        let commands = go_job.to_commands();
+
+        let find = |prefix: &str| {
+            commands
+                .iter()
+                .find(|command| command.starts_with(prefix))
+                .cloned()
+        };
+
+        let id = self.shared.next_id.fetch_add(1, Ordering::SeqCst) as u64;
+        let cancelled = std::sync::Arc::new(AtomicBool::new(false));
+        let go_job = go_job.cancel_flag(cancelled.clone());
+
+        let rrx;
+        let index;
+        let pending;
+
+        {
+            let engines = self.engines.lock().unwrap();
+
+            let least_loaded = (0..engines.len())
+                .min_by_key(|i| self.pending(*i).load(Ordering::SeqCst))
+                .ok_or(PoolError::Empty)?;
+
+            index = match session {
+                Some(session) => self.session_engine(session, least_loaded),
+                _ => least_loaded,
+            };
+
+            pending = self.pending(index);
+
+            pending.fetch_add(1, Ordering::SeqCst);
+            self.shared.in_flight.fetch_add(1, Ordering::SeqCst);
+
+            rrx = engines[index].try_go(go_job);
+
+            {
+                let mut jobs = self.shared.jobs.lock().unwrap();
+
+                jobs.insert(
+                    id,
+                    TrackedJob {
+                        engine: engines[index].clone(),
+                        engine_job: rrx.id(),
+                        index,
+                        position: find("position "),
+                        budget: find("go"),
+                        submitted: Instant::now(),
+                        cancelled,
+                    },
+                );
+            }
+        }
+
+        let (tx, rx) = oneshot::channel();
+
+        let error = std::sync::Arc::new(std::sync::Mutex::new(None));
+
+        let shared = self.shared.clone();
+        let result_middleware = self.result_middleware.clone();
+        let session = session.map(|session| session.to_string());
+        let job_error = error.clone();
+
+        tokio::spawn(async move {
+            match rrx.await {
+                Ok(go_result) => {
+                    let go_result = Self::apply_result_middleware(
+                        &result_middleware,
+                        session.as_deref(),
+                        go_result,
+                    );
+
+                    let _ = tx.send(go_result);
+                }
+                Err(err) => {
+                    *job_error.lock().unwrap() = Some(err);
+
+                    // dropping the sender fails the pending go with the error
+                    drop(tx);
+                }
+            }
+
+            {
+                let mut jobs = shared.jobs.lock().unwrap();
+
+                jobs.remove(&id);
+            }
+
+            pending.fetch_sub(1, Ordering::SeqCst);
+
+            if shared.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+                shared.empty.notify_waiters();
+            }
+        });
+
+        Ok(PendingGo::new(id, rx, error))
+    }
+
+    /// analyse go jobs, at most queue jobs are pending at a time ( the number of engines
+    /// keeps every engine busy ), further jobs are taken from jobs as results come in,
+    /// results are streamed in order of completion with the index of their job, the
+    /// stream ends after the last result, dropping it stops the submission of jobs
+    pub fn analyse<I>(
+        self: &std::sync::Arc<Self>,
+        jobs: I,
+        queue: usize,
+    ) -> mpsc::Receiver<(usize, Result<GoResult, PoolError>)>
+    where
+        I: IntoIterator<Item = GoJob>,
+        I::IntoIter: Send + 'static,
+    {
+        let queue = queue.max(1);
+
+        let (tx, rx) = mpsc::channel(queue);
+
+        let pool = self.clone();
+        let jobs = jobs.into_iter();
+
+        tokio::spawn(async move {
+            let permits = std::sync::Arc::new(Semaphore::new(queue));
+
+            for (index, go_job) in jobs.enumerate() {
+                let permit = match permits.clone().acquire_owned().await {
+                    Ok(permit) => permit,
+                    _ => break,
+                };
+
+                if tx.is_closed() {
+                    if log_enabled!(Level::Debug) {
+                        debug!("analysis stream dropped, {} jobs submitted", index);
+                    }
+
+                    break;
+                }
+
+                let submitted = pool.go(go_job);
+                let tx = tx.clone();
+
+                tokio::spawn(async move {
+                    let result = match submitted {
+                        Ok(pending) => pending.await.map_err(PoolError::Engine),
+                        Err(err) => Err(err),
+                    };
+
+                    let _ = tx.send((index, result)).await;
+
+                    drop(permit);
+                });
+            }
+        });
+
+        rx
+    }
+
+    /// jobs in flight, in order of submission, a job is running once its engine
+    /// started its search
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        let jobs = self.shared.jobs.lock().unwrap();
+
+        jobs.iter()
+            .map(|(id, job)| JobInfo {
+                id: *id,
+                engine: job.index,
+                position: job.position.to_owned(),
+                budget: job.budget.to_owned(),
+                elapsed: job.submitted.elapsed(),
+                state: job.state(),
+            })
+            .collect()
+    }
+
+    /// cancel job by id, a queued job is dropped ( its result fails ) once it comes up,
+    /// a running job is stopped ( its result is the search so far ),
+    /// returns false if there is no such job
+    pub fn cancel(&self, id: u64) -> bool {
+        let jobs = self.shared.jobs.lock().unwrap();
+
+        let job = match jobs.get(&id) {
+            Some(job) => job,
+            _ => return false,
+        };
+
+        // set before the state is taken, a job starting in between sees the flag
+        job.cancelled.store(true, Ordering::SeqCst);
+
+        let state = job.state();
+
+        if state == JobState::Running {
+            job.engine.issue_command("stop");
+        }
+
+        if log_enabled!(Level::Info) {
+            info!("cancelled {:?} job {}", state, id);
+        }
+
+        true
+    }
+
+    /// status of the pool members
+    pub fn engine_status(&self) -> Vec<EngineStatus> {
+        self.engines()
+            .iter()
+            .enumerate()
+            .map(|(index, engine)| EngineStatus {
+                index,
+                pid: engine.pid(),
+                labels: engine.labels().clone(),
+                pending: self.pending(index).load(Ordering::SeqCst),
+                error: engine.error().map(|error| error.to_string()),
+            })
+            .collect()
+    }
+
+    /// spawn engine of profile and check that it configures and searches,
+    /// returns None if it failed the health check ( the engine is quit then )
+    async fn healthy_engine(profile: &EngineProfile) -> Option<std::sync::Arc<UciEngine>> {
+        let engine = profile.spawn();
+
+        let check = async {
+            engine.try_go(profile.apply(GoJob::new())).await.ok()?;
+
+            engine
+                .try_go(GoJob::new().pos_startpos().go_opt("depth", 1))
+                .await
+                .ok()?
+                .bestmove
+        };
+
+        match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check).await {
+            Ok(Some(_)) if !engine.is_dead() => Some(engine),
+            _ => {
+                engine.quit();
+
+                None
+            }
+        }
+    }
+
+    /// replace pool members one at a time with engines of a new profile
+    /// ( new binary or config ) while the pool keeps serving jobs, each new member
+    /// has to pass a health check before it takes the place of the old one,
+    /// the old member is quit once the jobs queued on it are done,
+    /// stops at the first member failing its health check ( it keeps the old engine ),
+    /// returns the number of replaced members
+    pub async fn rolling_replace(&self, new_profile: &EngineProfile) -> Result<usize, PoolError> {
+        for index in 0..self.len() {
+            let engine = match Self::healthy_engine(new_profile).await {
+                Some(engine) => engine,
+                _ => {
+                    warn!(
+                        "pool member {} replacement {} failed its health check",
+                        index, new_profile.name
+                    );
+
+                    return Err(PoolError::HealthCheckFailed(index));
+                }
+            };
+
+            let old = {
+                let mut engines = self.engines.lock().unwrap();
+
+                // the jobs queued on the old member do not count on the new one
+                self.shared.pending.lock().unwrap()[index] =
+                    std::sync::Arc::new(AtomicUsize::new(0));
+
+                std::mem::replace(&mut engines[index], engine)
+            };
+
+            if log_enabled!(Level::Info) {
+                info!("pool member {} replaced by {}", index, new_profile.name);
+            }
+
+            // quit is queued behind the jobs already submitted to the old member
+            old.quit();
+        }
+
+        Ok(self.len())
+    }
+
+    /// register hook to run on shutdown, after the pool drained
+    pub fn on_shutdown<F>(&self, hook: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut shutdown_hooks = self.shutdown_hooks.lock().unwrap();
+
+        shutdown_hooks.push(Box::new(hook));
+    }
+
+    /// wait until no jobs are pending
+    async fn wait_empty(&self, timeout: Option<Duration>) -> bool {
+        loop {
+            // created before the check, so a notification in between is not lost
+            let empty = self.shared.empty.notified();
+
+            if self.in_flight() == 0 {
+                return true;
+            }
+
+            match timeout {
+                Some(timeout) => {
+                    if tokio::time::timeout(timeout, empty).await.is_err() {
+                        return self.in_flight() == 0;
+                    }
+                }
+                _ => empty.await,
+            }
+        }
+    }
+
+    /// stop accepting jobs and resolve once all pending jobs are done,
+    /// running searches may finish until the deadline, after it they are stopped
+    /// ( queued jobs still run, but are stopped as they start )
+    pub async fn drain(&self, deadline: Option<Duration>) {
+        self.accepting.store(false, Ordering::SeqCst);
+
+        if log_enabled!(Level::Info) {
+            info!("draining engine pool, {} jobs pending", self.in_flight());
+        }
+
+        if self.wait_empty(deadline).await {
+            return;
+        }
+
+        if log_enabled!(Level::Info) {
+            info!(
+                "drain deadline passed, stopping {} pending jobs",
+                self.in_flight()
+            );
+        }
+
+        loop {
+            for engine in self.engines().iter() {
+                engine.issue_command("stop");
+            }
+
+            if self.wait_empty(Some(DRAIN_STOP_INTERVAL)).await {
+                return;
+            }
+        }
+    }
+
+    /// drain the pool, quit the engines and run the shutdown hooks
+    pub async fn shutdown(&self, deadline: Option<Duration>) {
+        self.drain(deadline).await;
+
+        for engine in self.engines().iter() {
+            engine.quit();
+        }
+
+        let hooks: Vec<ShutdownHook> = {
+            let mut shutdown_hooks = self.shutdown_hooks.lock().unwrap();
+
+            shutdown_hooks.drain(..).collect()
+        };
+
+        if log_enabled!(Level::Debug) {
+            debug!("running {} shutdown hooks", hooks.len());
+        }
+
+        for hook in hooks {
+            hook();
+        }
+    }
+}
+
+#[test]
+fn job_middleware() {
+    let pool = EnginePool::new(vec![])
+        .layer(DepthCap(20))
+        .layer(MultiPvMin(3))
+        .layer(|session: Option<&str>, go_job: GoJob| match session {
+            Some("banned") => Err(PoolError::Rejected("banned session".to_string())),
+            _ => Ok(go_job.uci_opt("SyzygyPath", "/tb")),
+        });
+
+    let go_job = pool
+        .apply_middleware(None, GoJob::new().pos_startpos().infinite())
+        .unwrap();
+
+    assert_eq!(go_job.go_option("depth"), Some("20"));
+    assert_eq!(go_job.uci_option("MultiPV"), Some("3"));
+    assert_eq!(go_job.uci_option("SyzygyPath"), Some("/tb"));
+    assert!(!go_job.to_commands().iter().any(|c| c.contains("infinite")));
+
+    let go_job = pool
+        .apply_middleware(None, GoJob::new().go_opt("depth", 12).uci_opt("MultiPV", 5))
+        .unwrap();
+
+    assert_eq!(go_job.go_option("depth"), Some("12"));
+    assert_eq!(go_job.uci_option("MultiPV"), Some("5"));
+    // jobs that do not search are not turned into searches
+    assert!(!pool
+        .apply_middleware(None, GoJob::new())
+        .unwrap()
+        .searches());
+    assert!(matches!(
+        pool.apply_middleware(Some("banned"), GoJob::new()),
+        Err(PoolError::Rejected(_))
+    ));
+}
+
+#[tokio::test]
+async fn analyse_stream() {
+    let pool = std::sync::Arc::new(EnginePool::new(vec![]));
+
+    let mut results = pool.analyse((0..3).map(|_| GoJob::new().go_opt("depth", 1)), 2);
+
+    let mut indices = vec![];
+
+    while let Some((index, result)) = results.recv().await {
+        assert!(matches!(result, Err(PoolError::Empty)));
+
+        indices.push(index);
+    }
+
+    indices.sort();
+
+    assert_eq!(indices, vec![0, 1, 2]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn analyse_stream_results() {
+    // the first job is the slow one
+    let path = fake_engine(
+        "pool-analyse",
+        "    go\\ depth\\ 1) sleep 1; echo 'bestmove e2e4';;
+    go\\ depth\\ 2) sleep 0.1; echo 'bestmove d2d4';;
+    go\\ depth\\ 3) sleep 0.1; echo 'bestmove c2c4';;
+    go*) echo 'bestmove g1f3';;",
+    );
+
+    let pool = std::sync::Arc::new(EnginePool::new(vec![
+        EngineBuilder::new(&path).build(),
+        EngineBuilder::new(&path).build(),
+    ]));
+
+    let jobs = (1..=4).map(|depth| GoJob::new().pos_startpos().go_opt("depth", depth));
+
+    let mut results = pool.analyse(jobs, 2);
+
+    let mut indices = vec![];
+
+    while let Some((index, result)) = results.recv().await {
+        let bestmove = ["e2e4", "d2d4", "c2c4", "g1f3"][index];
+
+        assert_eq!(result.unwrap().bestmove.as_deref(), Some(bestmove));
+
+        indices.push(index);
+
+        let searches = fake_engine_log(&path)
+            .iter()
+            .filter(|line| line.starts_with("go"))
+            .count();
+
+        // a job is only submitted once a result came in, no more than queue jobs
+        // are pending at a time
+        assert!(searches <= indices.len() + 2);
+    }
+
+    // in order of completion, the later jobs run one by one beside the slow one
+    assert_eq!(indices, vec![1, 2, 3, 0]);
+}
+
+#[test]
+fn result_middleware() {
+    use crate::analysis::*;
+    use crate::multipv::*;
+    use crate::telemetry::*;
+
+    let searches = std::sync::Arc::new(AtomicUsize::new(0));
+    let counted = searches.clone();
+
+    let pool = EnginePool::new(vec![])
+        .result_layer(move |_: Option<&str>, go_result: GoResult| {
+            counted.fetch_add(1, Ordering::SeqCst);
+
+            go_result
+        })
+        .result_layer(|session: Option<&str>, mut go_result: GoResult| {
+            go_result
+                .labels
+                .insert("session".to_string(), session.unwrap_or("none").to_string());
+
+            go_result
+        });
+
+    let go_result = GoResult {
+        bestmove: Some("e2e4".to_string()),
+        ponder: None,
+        ai: AnalysisInfo::new(),
+        is_ready: false,
+        no_info: true,
+        telemetry: SearchTelemetry::new(),
+        completeness: 1.0,
+        labels: Labels::new(),
+        source: ResultSource::Search,
+        anomalies: vec![],
+        lines: FullLines::new(),
+        multipv: MultiPvAnalysis::new(),
+    };
+
+    let go_result =
+        EnginePool::apply_result_middleware(&pool.result_middleware, Some("game-1"), go_result);
+
+    assert_eq!(searches.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        go_result.labels.get("session").map(|s| s.as_str()),
+        Some("game-1")
+    );
+    assert_eq!(go_result.bestmove.as_deref(), Some("e2e4"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn drain_and_shutdown() {
+    // infinite searches only end on stop
+    let path = fake_engine(
+        "drain",
+        "    go\\ infinite) ;;
+    go*) sleep 0.2; echo 'bestmove e2e4';;
+    stop) echo 'bestmove d2d4';;",
+    );
+
+    let pool = EnginePool::new(vec![EngineBuilder::new(&path).build()]);
+
+    let rx = pool
+        .go(GoJob::new().pos_startpos().go_opt("depth", 1))
+        .unwrap();
+
+    // without a deadline the pending search finishes on its own
+    pool.drain(None).await;
+
+    assert!(pool.is_draining());
+    assert_eq!(pool.in_flight(), 0);
+    assert_eq!(rx.await.unwrap().bestmove.as_deref(), Some("e2e4"));
+    assert!(matches!(
+        pool.go(GoJob::new().go_opt("depth", 1)),
+        Err(PoolError::Draining)
+    ));
+
+    let pool = EnginePool::new(vec![EngineBuilder::new(&path).build()]);
+
+    let hook_ran = std::sync::Arc::new(AtomicBool::new(false));
+    let ran = hook_ran.clone();
+
+    pool.on_shutdown(move || ran.store(true, Ordering::SeqCst));
+
+    let rx = pool.go(GoJob::new().pos_startpos().infinite()).unwrap();
+
+    // past the deadline the running search is stopped
+    pool.shutdown(Some(Duration::from_millis(100))).await;
+
+    assert_eq!(rx.await.unwrap().bestmove.as_deref(), Some("d2d4"));
+    assert!(hook_ran.load(Ordering::SeqCst));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn go_error() {
+    let path = fake_engine("pool-error", "    go*) exit 1;;");
+
+    let pool = EnginePool::new(vec![EngineBuilder::new(&path).build()]);
+
+    let pending = pool
+        .go(GoJob::new().pos_startpos().go_opt("depth", 1))
+        .unwrap();
+
+    // the error that killed the engine reaches the submitter
+    assert!(matches!(pending.await, Err(EngineError::EngineExited(_))));
+    assert_eq!(pool.in_flight(), 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn rolling_replace_pending() {
+    let old_path = fake_engine(
+        "replace-old",
+        "    go\\ infinite) ;;
+    stop) echo 'bestmove d2d4';;",
+    );
+    let new_path = fake_engine("replace-new", "    go*) echo 'bestmove e2e4';;");
+
+    let pool = EnginePool::new(vec![EngineBuilder::new(&old_path).build()]);
+
+    let old = pool.engines()[0].clone();
+
+    let running = pool.go(GoJob::new().pos_startpos().infinite()).unwrap();
+
+    assert_eq!(
+        pool.rolling_replace(&EngineProfile::new("new", &new_path))
+            .await
+            .unwrap(),
+        1
+    );
+
+    // the job left on the old member does not count on the new one
+    assert_eq!(pool.engine_status()[0].pending, 0);
+    assert_eq!(pool.in_flight(), 1);
+
+    let go_result = pool
+        .go(GoJob::new().pos_startpos().go_opt("depth", 1))
+        .unwrap()
+        .await
+        .unwrap();
+
+    assert_eq!(go_result.bestmove.as_deref(), Some("e2e4"));
+
+    old.issue_command("stop");
+
+    assert_eq!(running.await.unwrap().bestmove.as_deref(), Some("d2d4"));
+
+    pool.drain(None).await;
+
+    assert_eq!(pool.engine_status()[0].pending, 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn jobs_and_cancel() {
+    // options are confirmed late, a stop written before readyok ends nothing
+    let path = fake_engine(
+        "pool-cancel",
+        "    isready) sleep 0.3; echo readyok;;
+    go\\ infinite) ;;
+    stop) echo 'bestmove d2d4';;
+    go*) echo 'bestmove e2e4';;",
+    );
+
+    let pool = EnginePool::new(vec![EngineBuilder::new(&path).build()]);
+
+    let running = pool.go(GoJob::new().pos_startpos().infinite()).unwrap();
+    let queued = pool
+        .go(GoJob::new().pos_startpos().go_opt("depth", 1))
+        .unwrap();
+
+    while pool.jobs()[0].state != JobState::Running {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let state = |id: u64| {
+        pool.jobs()
+            .into_iter()
+            .find(|job| job.id == id)
+            .map(|job| job.state)
+    };
+
+    assert_eq!(state(queued.id()), Some(JobState::Queued));
+
+    // the queued job is dropped, the running one stopped
+    assert!(pool.cancel(queued.id()));
+    assert!(pool.cancel(running.id()));
+    assert!(!pool.cancel(0));
+
+    assert_eq!(running.await.unwrap().bestmove.as_deref(), Some("d2d4"));
+    assert!(matches!(queued.await, Err(EngineError::NoResult(_))));
+
+    // applying its options the job is queued, cancelling it does not send stop
+    let fenced = pool
+        .go(GoJob::new().uci_opt("Hash", 64).pos_startpos().infinite())
+        .unwrap();
+
+    while !fake_engine_log(&path).contains(&"isready".to_string()) {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    assert_eq!(state(fenced.id()), Some(JobState::Queued));
+    assert!(pool.cancel(fenced.id()));
+    assert!(matches!(fenced.await, Err(EngineError::NoResult(_))));
+    assert_eq!(
+        fake_engine_log(&path),
+        [
+            "position startpos",
+            "go infinite",
+            "stop",
+            "setoption name Hash value 64",
+            "isready"
+        ]
+    );
+}
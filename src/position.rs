@@ -0,0 +1,232 @@
+use log::warn;
+
+#[cfg(feature = "chess")]
+use shakmaty::{fen::Fen, CastlingMode, Chess, EnPassantMode};
+
+use crate::notation::*;
+
+/// initial capacity of the position command buffer
+/// ( enough for a fen and about a hundred moves )
+const POS_COMMAND_CAPACITY: usize = 640;
+
+/// position command, maintained incrementally as moves are played,
+/// so that the full move list does not have to be formatted again every turn
+#[derive(Debug, Clone)]
+pub struct PositionCommand {
+    /// position command built so far
+    command: String,
+    /// number of moves in the command
+    num_moves: usize,
+    /// accept null moves ( 0000 ) in the move list
+    null_moves: bool,
+    /// switch to position fen once the number of moves exceeds this threshold
+    #[cfg(feature = "chess")]
+    fen_threshold: Option<usize>,
+    /// current position, None if it could not be tracked
+    #[cfg(feature = "chess")]
+    pos: Option<Chess>,
+}
+
+/// position command implementation
+impl PositionCommand {
+    /// create new position command with given base ( startpos or fen ... )
+    fn with_base(base: &str) -> Self {
+        let mut command = String::with_capacity(POS_COMMAND_CAPACITY);
+
+        command.push_str("position ");
+        command.push_str(base);
+
+        Self {
+            command,
+            num_moves: 0,
+            null_moves: false,
+            #[cfg(feature = "chess")]
+            fen_threshold: None,
+            #[cfg(feature = "chess")]
+            pos: None,
+        }
+    }
+
+    /// create new position command from starting position
+    pub fn startpos() -> Self {
+        #[allow(unused_mut)]
+        let mut pos_command = Self::with_base("startpos");
+
+        #[cfg(feature = "chess")]
+        {
+            pos_command.pos = Some(Chess::default());
+        }
+
+        pos_command
+    }
+
+    /// create new position command from fen
+    pub fn fen<T>(fen: T) -> Self
+    where
+        T: core::fmt::Display,
+    {
+        let fen = fen.to_string();
+
+        #[allow(unused_mut)]
+        let mut pos_command = Self::with_base(&format!("fen {}", fen));
+
+        #[cfg(feature = "chess")]
+        {
+            pos_command.pos = Fen::from_ascii(fen.as_bytes())
+                .ok()
+                .and_then(|fen| fen.into_position(CastlingMode::Standard).ok());
+
+            if pos_command.pos.is_none() {
+                warn!("could not track position of fen {}", fen);
+            }
+        }
+
+        pos_command
+    }
+
+    /// set fen threshold and return self,
+    /// once the number of moves exceeds the threshold,
+    /// the command is collapsed to a position fen of the current position
+    #[cfg(feature = "chess")]
+    pub fn fen_threshold(mut self, threshold: usize) -> Self {
+        self.fen_threshold = Some(threshold);
+
+        self
+    }
+
+    /// set whether null moves ( 0000 ) are accepted and return self, engines that do
+    /// not support them may reject the position, so they are ignored unless enabled
+    pub fn null_moves(mut self, null_moves: bool) -> Self {
+        self.null_moves = null_moves;
+
+        self
+    }
+
+    /// append uci move
+    pub fn push_move<T>(&mut self, uci: T)
+    where
+        T: AsRef<str>,
+    {
+        let uci = uci.as_ref();
+
+        if uci.is_empty() {
+            warn!("ignoring empty move");
+
+            return;
+        }
+
+        if is_null_move(uci) && !self.null_moves {
+            warn!("ignoring null move, null moves are not enabled");
+
+            return;
+        }
+
+        if self.num_moves == 0 {
+            self.command.push_str(" moves");
+        }
+
+        self.command.push(' ');
+        self.command.push_str(uci);
+
+        self.num_moves += 1;
+
+        #[cfg(feature = "chess")]
+        self.track_move(uci);
+    }
+
+    /// append space separated uci moves
+    pub fn push_moves<T>(&mut self, moves: T)
+    where
+        T: AsRef<str>,
+    {
+        for uci in moves.as_ref().split_whitespace() {
+            self.push_move(uci);
+        }
+    }
+
+    /// play move on the tracked position and collapse to fen if needed
+    #[cfg(feature = "chess")]
+    fn track_move(&mut self, uci: &str) {
+        if let Some(pos) = self.pos.take() {
+            self.pos = play_uci(pos, uci);
+
+            if self.pos.is_none() {
+                warn!("could not track move {}, fen threshold disabled", uci);
+            }
+        }
+
+        if let (Some(threshold), Some(pos)) = (self.fen_threshold, &self.pos) {
+            if self.num_moves > threshold {
+                let fen = Fen::from_position(pos, EnPassantMode::Legal).to_string();
+
+                self.command.clear();
+                self.command.push_str("position fen ");
+                self.command.push_str(&fen);
+
+                self.num_moves = 0;
+            }
+        }
+    }
+
+    /// number of moves in the command
+    pub fn num_moves(&self) -> usize {
+        self.num_moves
+    }
+
+    /// position command as str
+    pub fn as_str(&self) -> &str {
+        &self.command
+    }
+}
+
+/// implement Display for PositionCommand
+impl std::fmt::Display for PositionCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.command)
+    }
+}
+
+#[test]
+fn push_moves() {
+    let mut pos_command = PositionCommand::startpos();
+
+    assert_eq!(pos_command.as_str(), "position startpos");
+
+    pos_command.push_moves("e2e4 e7e5");
+    pos_command.push_move("g1f3");
+
+    assert_eq!(pos_command.num_moves(), 3);
+    assert_eq!(
+        pos_command.as_str(),
+        "position startpos moves e2e4 e7e5 g1f3"
+    );
+
+    // null moves are ignored unless enabled
+    pos_command.push_move(NULL_MOVE);
+
+    assert_eq!(pos_command.num_moves(), 3);
+
+    let mut pos_command = PositionCommand::startpos().null_moves(true);
+
+    pos_command.push_moves("e2e4 0000 d2d4");
+
+    assert_eq!(pos_command.num_moves(), 3);
+}
+
+#[cfg(feature = "chess")]
+#[test]
+fn fen_threshold() {
+    let mut pos_command = PositionCommand::startpos().fen_threshold(2);
+
+    pos_command.push_moves("e2e4 e7e5");
+
+    assert_eq!(pos_command.num_moves(), 2);
+
+    pos_command.push_move("g1f3");
+
+    assert_eq!(pos_command.num_moves(), 0);
+    assert_eq!(
+        pos_command.as_str(),
+        "position fen rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+    );
+}
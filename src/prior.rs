@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::*;
+
+/// prior evaluation of a position, from a cache, a cloud service or an earlier search,
+/// scores are seen from the side to move
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalPrior {
+    /// best move of the prior, None if unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bestmove: Option<String>,
+    /// score
+    pub score: Score,
+    /// depth the prior was searched to
+    pub depth: usize,
+}
+
+/// eval prior implementation
+impl EvalPrior {
+    /// create new prior without best move
+    pub fn new(score: Score, depth: usize) -> Self {
+        Self {
+            bestmove: None,
+            score,
+            depth,
+        }
+    }
+
+    /// create new prior from analysis info of an earlier search
+    pub fn from_info(ai: &AnalysisInfo) -> Self {
+        Self {
+            bestmove: ai.bestmove(),
+            score: ai.score,
+            depth: ai.depth,
+        }
+    }
+
+    /// set best move and return self
+    pub fn bestmove<T: core::fmt::Display>(mut self, bestmove: T) -> Self {
+        self.bestmove = Some(bestmove.to_string());
+
+        self
+    }
+}
+
+/// policy deciding how searches use priors
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PriorPolicy {
+    /// priors searched at least this deep with a best move are trusted,
+    /// their positions get a verify search only
+    pub trusted_depth: usize,
+    /// depth of the verify search of trusted positions
+    pub verify_depth: usize,
+    /// a result whose score differs from the prior by more than this many
+    /// centipawns disagrees with it
+    pub disagreement_cp: i32,
+    /// plies a disagreeing result is searched deeper than the deeper of the result
+    /// and the prior
+    pub deepen_plies: usize,
+}
+
+/// prior policy implementation
+impl PriorPolicy {
+    /// create new policy with conservative defaults
+    pub fn new() -> Self {
+        Self {
+            trusted_depth: 20,
+            verify_depth: 8,
+            disagreement_cp: 50,
+            deepen_plies: 4,
+        }
+    }
+
+    /// set depth priors are trusted from and depth of their verify search and return self
+    pub fn trusted(mut self, trusted_depth: usize, verify_depth: usize) -> Self {
+        self.trusted_depth = trusted_depth;
+        self.verify_depth = verify_depth.max(1);
+
+        self
+    }
+
+    /// set score difference in centipawns that counts as disagreement and return self
+    pub fn disagreement_cp(mut self, disagreement_cp: i32) -> Self {
+        self.disagreement_cp = disagreement_cp;
+
+        self
+    }
+
+    /// set plies disagreeing results are searched deeper and return self
+    pub fn deepen_plies(mut self, deepen_plies: usize) -> Self {
+        self.deepen_plies = deepen_plies;
+
+        self
+    }
+
+    /// true if the position of prior is known easy and needs a verify search only
+    pub fn is_trusted(&self, prior: &EvalPrior) -> bool {
+        prior.bestmove.is_some() && prior.depth >= self.trusted_depth
+    }
+
+    /// true if the result of a search disagrees with prior, on the best move
+    /// where both have one or on the score
+    pub fn disagrees(&self, prior: &EvalPrior, ai: &AnalysisInfo) -> bool {
+        let bestmove_differs = match (&prior.bestmove, ai.bestmove()) {
+            (Some(prior_bestmove), Some(bestmove)) => *prior_bestmove != bestmove,
+            _ => false,
+        };
+
+        bestmove_differs
+            || (prior.score.cp_equivalent() - ai.score.cp_equivalent()).abs() > self.disagreement_cp
+    }
+
+    /// depth to search a result disagreeing with prior again to
+    pub fn deeper_depth(&self, prior: &EvalPrior, ai: &AnalysisInfo) -> usize {
+        prior.depth.max(ai.depth) + self.deepen_plies
+    }
+}
+
+/// implement Default for PriorPolicy
+impl Default for PriorPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn eval_priors() {
+    let policy = PriorPolicy::new().trusted(18, 6).disagreement_cp(40);
+
+    let prior = EvalPrior::new(Score::Cp(30), 24).bestmove("e2e4");
+
+    assert!(policy.is_trusted(&prior));
+    assert!(!policy.is_trusted(&EvalPrior::new(Score::Cp(30), 24)));
+    assert!(!policy.is_trusted(&EvalPrior::new(Score::Cp(30), 10).bestmove("e2e4")));
+
+    let info = |line: &str| {
+        let mut ai = AnalysisInfo::new();
+
+        ai.parse(line).unwrap();
+
+        ai
+    };
+
+    let agreeing = info("info depth 6 score cp 45 pv e2e4 e7e5");
+
+    assert!(!policy.disagrees(&prior, &agreeing));
+    assert_eq!(
+        EvalPrior::from_info(&agreeing).bestmove,
+        Some("e2e4".to_string())
+    );
+
+    assert!(policy.disagrees(&prior, &info("info depth 6 score cp 30 pv d2d4 d7d5")));
+    assert!(policy.disagrees(&prior, &info("info depth 6 score cp -20 pv e2e4 e7e5")));
+    assert_eq!(
+        policy.deeper_depth(&prior, &agreeing),
+        24 + policy.deepen_plies
+    );
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use crate::uciengine::*;
+
+/// engine profile, everything needed to spawn and configure an engine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineProfile {
+    /// display name of the engine
+    pub name: String,
+    /// engine executable path
+    pub path: String,
+    /// uci options as key value pairs, applied in order
+    pub options: Vec<(String, String)>,
+}
+
+/// engine profile implementation
+impl EngineProfile {
+    /// create new engine profile with given name and path
+    pub fn new<N, P>(name: N, path: P) -> Self
+    where
+        N: core::fmt::Display,
+        P: core::fmt::Display,
+    {
+        Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            options: vec![],
+        }
+    }
+
+    /// add uci option as key value pair and return self
+    pub fn uci_opt<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.options.push((key.to_string(), value.to_string()));
+
+        self
+    }
+
+    /// limit strength to given elo and return self
+    pub fn fixed_elo(self, elo: usize) -> Self {
+        self.uci_opt("UCI_LimitStrength", true)
+            .uci_opt("UCI_Elo", elo)
+    }
+
+    /// get uci option value ( option names are case insensitive in uci )
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// number of threads the engine uses ( Threads option, 1 if not set )
+    pub fn threads(&self) -> usize {
+        self.option("Threads")
+            .and_then(|threads| threads.parse::<usize>().ok())
+            .unwrap_or(1)
+    }
+
+    /// hash size in MB the engine uses ( Hash option, 16 if not set )
+    pub fn hash_mb(&self) -> usize {
+        self.option("Hash")
+            .and_then(|hash| hash.parse::<usize>().ok())
+            .unwrap_or(16)
+    }
+
+    /// spawn engine
+    pub fn spawn(&self) -> std::sync::Arc<UciEngine> {
+        EngineBuilder::new(&self.path).build()
+    }
+
+    /// apply uci options of the profile to go job and return it
+    pub fn apply(&self, go_job: GoJob) -> GoJob {
+        self.options
+            .iter()
+            .fold(go_job, |go_job, (key, value)| go_job.uci_opt(key, value))
+    }
+}
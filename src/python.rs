@@ -0,0 +1,477 @@
+// the method wrappers pyo3 generates convert the PyErr of a PyResult into PyErr
+#![allow(clippy::useless_conversion)]
+
+use log::warn;
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::analysis::*;
+use crate::pipeline::*;
+use crate::pool::*;
+use crate::uciengine::*;
+
+/// json string as python object
+fn json_to_py(py: Python, json: &str) -> PyResult<PyObject> {
+    Ok(py
+        .import_bound("json")?
+        .call_method1("loads", (json,))?
+        .unbind())
+}
+
+/// analysis info as python dict
+fn info_to_py(py: Python, ai: AnalysisInfo) -> PyResult<PyObject> {
+    let json = ai
+        .to_json()
+        .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+
+    json_to_py(py, &json)
+}
+
+/// go result as json ( bestmove, ponder, info )
+fn result_to_json(go_result: GoResult) -> String {
+    serde_json::json!({
+        "bestmove": go_result.bestmove,
+        "ponder": go_result.ponder,
+        "info": go_result.ai.to_serde(),
+    })
+    .to_string()
+}
+
+/// go job description, converted to a GoJob on submission
+#[pyclass(name = "GoJob")]
+#[derive(Debug, Clone)]
+pub struct PyGoJob {
+    fen: Option<String>,
+    moves: Option<String>,
+    uci_options: Vec<(String, String)>,
+    go_options: Vec<(String, String)>,
+}
+
+/// python go job implementation, setters return a new job so that calls can be chained
+#[pymethods]
+impl PyGoJob {
+    /// create new go job for the starting position
+    #[new]
+    fn new() -> Self {
+        Self {
+            fen: None,
+            moves: None,
+            uci_options: vec![],
+            go_options: vec![],
+        }
+    }
+
+    /// set position fen
+    fn pos_fen(&self, fen: String) -> Self {
+        Self {
+            fen: Some(fen),
+            ..self.clone()
+        }
+    }
+
+    /// set position moves, space separated uci moves
+    fn pos_moves(&self, moves: String) -> Self {
+        Self {
+            moves: Some(moves),
+            ..self.clone()
+        }
+    }
+
+    /// add uci option
+    fn uci_opt(&self, key: String, value: String) -> Self {
+        let mut job = self.clone();
+
+        job.uci_options.push((key, value));
+
+        job
+    }
+
+    /// add go option
+    fn go_opt(&self, key: String, value: String) -> Self {
+        let mut job = self.clone();
+
+        job.go_options.push((key, value));
+
+        job
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// python go job implementation
+impl PyGoJob {
+    /// convert to go job
+    fn to_go_job(&self) -> GoJob {
+        let mut go_job = match &self.fen {
+            Some(fen) => GoJob::new().pos_fen(fen),
+            _ => GoJob::new().pos_startpos(),
+        };
+
+        if let Some(moves) = &self.moves {
+            go_job = go_job.pos_moves(moves);
+        }
+
+        for (key, value) in &self.uci_options {
+            go_job = go_job.uci_opt(key, value);
+        }
+
+        for (key, value) in &self.go_options {
+            go_job = go_job.go_opt(key, value);
+        }
+
+        go_job
+    }
+}
+
+/// uci engine, runs its own tokio runtime
+#[pyclass(name = "UciEngine")]
+pub struct PyUciEngine {
+    runtime: std::sync::Arc<tokio::runtime::Runtime>,
+    engine: std::sync::Arc<UciEngine>,
+}
+
+/// python uci engine implementation
+#[pymethods]
+impl PyUciEngine {
+    /// spawn engine at path
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        let runtime = tokio::runtime::Runtime::new()
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+
+        let engine = {
+            let _guard = runtime.enter();
+
+            UciEngine::new(path)
+        };
+
+        Ok(Self {
+            runtime: std::sync::Arc::new(runtime),
+            engine,
+        })
+    }
+
+    /// search and block until the result, returns dict with bestmove, ponder and info
+    fn go(&self, py: Python, job: &PyGoJob) -> PyResult<PyObject> {
+        let rrx = self.engine.try_go(job.to_go_job());
+
+        let go_result = py
+            .allow_threads(|| self.runtime.block_on(rrx))
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+
+        json_to_py(py, &result_to_json(go_result))
+    }
+
+    /// start search and return an iterator over the analysis info dicts,
+    /// the iterator ends when the search finishes, its result is then available
+    /// through the result method of the iterator
+    fn analyse(&self, job: &PyGoJob) -> AnalysisStream {
+        let arx = self.engine.atx.subscribe();
+
+        AnalysisStream {
+            runtime: self.runtime.clone(),
+            arx,
+            rrx: Some(self.engine.try_go(job.to_go_job())),
+            result: None,
+        }
+    }
+
+    /// stop running search
+    fn stop(&self) {
+        self.engine.issue_command("stop");
+    }
+
+    /// quit engine
+    fn quit(&self) {
+        self.engine.quit();
+    }
+}
+
+/// iterator over the analysis info of a running search
+#[pyclass]
+pub struct AnalysisStream {
+    runtime: std::sync::Arc<tokio::runtime::Runtime>,
+    arx: broadcast::Receiver<AnalysisInfo>,
+    rrx: Option<PendingGo>,
+    /// json of the result once the search finished
+    result: Option<String>,
+}
+
+/// analysis stream implementation
+#[pymethods]
+impl AnalysisStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>, py: Python) -> PyResult<Option<PyObject>> {
+        let stream = &mut *slf;
+
+        let mut rrx = match stream.rrx.take() {
+            Some(rrx) => rrx,
+            _ => return Ok(None),
+        };
+
+        let runtime = stream.runtime.clone();
+        let arx = &mut stream.arx;
+
+        let (ai, go_result) = py.allow_threads(|| {
+            runtime.block_on(async {
+                loop {
+                    tokio::select! {
+                        go_result = &mut rrx => return (None, Some(go_result.ok())),
+                        received = arx.recv() => match received {
+                            Ok(ai) => return (Some(ai), None),
+                            Err(RecvError::Lagged(_)) => {}
+                            Err(RecvError::Closed) => return (None, Some((&mut rrx).await.ok())),
+                        },
+                    }
+                }
+            })
+        });
+
+        match go_result {
+            Some(go_result) => {
+                stream.result = go_result.map(result_to_json);
+
+                Ok(None)
+            }
+            _ => {
+                stream.rrx = Some(rrx);
+
+                ai.map(|ai| info_to_py(py, ai)).transpose()
+            }
+        }
+    }
+
+    /// result of the finished search as dict, None while searching
+    fn result(&self, py: Python) -> PyResult<Option<PyObject>> {
+        self.result
+            .as_ref()
+            .map(|result| json_to_py(py, result))
+            .transpose()
+    }
+}
+
+/// kind of a stage of a python pipeline
+#[derive(Debug, Clone, Copy)]
+enum PyStageKind {
+    /// the function returns the new item
+    Map,
+    /// the item is kept if the function returns a true value
+    Filter,
+    /// the function returns the GoJob of the item, the item is passed on as
+    /// ( item, result dict )
+    Analyse,
+}
+
+/// stage of a python pipeline, items the function raised on are dropped with a warning
+struct PyStage {
+    kind: PyStageKind,
+    f: PyObject,
+    pool: std::sync::Arc<EnginePool>,
+}
+
+/// python stage as stage
+impl Stage for PyStage {
+    type In = PyObject;
+    type Out = PyObject;
+
+    fn process(&self, item: PyObject) -> StageFuture<PyObject> {
+        let out = Python::with_gil(|py| -> PyResult<(Option<PyObject>, Option<PyGoJob>)> {
+            let out = self.f.call1(py, (item.clone_ref(py),))?;
+
+            match self.kind {
+                PyStageKind::Map => Ok((Some(out), None)),
+                PyStageKind::Filter => {
+                    Ok((out.bind(py).is_truthy()?.then(|| item.clone_ref(py)), None))
+                }
+                PyStageKind::Analyse => Ok((None, Some(out.extract::<PyGoJob>(py)?))),
+            }
+        });
+
+        let (out, job) = match out {
+            Ok(out) => out,
+            Err(err) => {
+                warn!("python pipeline stage failed {}", err);
+
+                return Box::pin(async { vec![] });
+            }
+        };
+
+        let job = match job {
+            Some(job) => job,
+            _ => return Box::pin(async move { out.into_iter().collect() }),
+        };
+
+        let pending = self.pool.go(job.to_go_job());
+
+        Box::pin(async move {
+            let go_result = match pending {
+                Ok(pending) => pending.await.ok(),
+                Err(err) => {
+                    warn!("python pipeline analysis not submitted {:?}", err);
+
+                    None
+                }
+            };
+
+            let go_result = match go_result {
+                Some(go_result) => go_result,
+                _ => return vec![],
+            };
+
+            Python::with_gil(|py| {
+                json_to_py(py, &result_to_json(go_result))
+                    .map(|result| vec![(item, result).into_py(py)])
+                    .unwrap_or_default()
+            })
+        })
+    }
+
+    fn concurrency(&self) -> usize {
+        match self.kind {
+            PyStageKind::Analyse => self.pool.len().max(1),
+            _ => 1,
+        }
+    }
+}
+
+/// annotation pipeline over python items, analysed on a pool of engines with its
+/// own tokio runtime, stages are appended in place so that calls can be chained
+///
+/// Pipeline("stockfish", 4).filter(lambda fen: fen).analyse(lambda fen: GoJob().pos_fen(fen).go_opt("depth", "12")).run(fens)
+#[pyclass(name = "Pipeline")]
+pub struct PyPipeline {
+    runtime: std::sync::Arc<tokio::runtime::Runtime>,
+    pool: std::sync::Arc<EnginePool>,
+    stages: Vec<(PyStageKind, PyObject)>,
+}
+
+/// python pipeline implementation
+#[pymethods]
+impl PyPipeline {
+    /// create new pipeline analysing on engines spawned from path
+    #[new]
+    #[pyo3(signature = (path, engines = 1))]
+    fn new(path: &str, engines: usize) -> PyResult<Self> {
+        let runtime = tokio::runtime::Runtime::new()
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+
+        let pool = {
+            let _guard = runtime.enter();
+
+            EnginePool::spawn(path, engines.max(1))
+        };
+
+        Ok(Self {
+            runtime: std::sync::Arc::new(runtime),
+            pool: std::sync::Arc::new(pool),
+            stages: vec![],
+        })
+    }
+
+    /// append map stage, f returns the new item
+    fn map(mut slf: PyRefMut<'_, Self>, f: PyObject) -> PyRefMut<'_, Self> {
+        slf.stages.push((PyStageKind::Map, f));
+
+        slf
+    }
+
+    /// append filter stage, items f returns a false value for are dropped
+    fn filter(mut slf: PyRefMut<'_, Self>, f: PyObject) -> PyRefMut<'_, Self> {
+        slf.stages.push((PyStageKind::Filter, f));
+
+        slf
+    }
+
+    /// append analyse stage, f returns the GoJob of an item, the item is passed on
+    /// as ( item, result dict ), as many items are analysed at the same time as the
+    /// pool has engines
+    fn analyse(mut slf: PyRefMut<'_, Self>, f: PyObject) -> PyRefMut<'_, Self> {
+        slf.stages.push((PyStageKind::Analyse, f));
+
+        slf
+    }
+
+    /// run the stages on items and return the output, blocks until all items passed
+    fn run(&self, py: Python, items: Vec<PyObject>) -> Vec<PyObject> {
+        let mut pipeline = Pipeline::new();
+
+        for (kind, f) in &self.stages {
+            pipeline = pipeline.stage(PyStage {
+                kind: *kind,
+                f: f.clone_ref(py),
+                pool: self.pool.clone(),
+            });
+        }
+
+        py.allow_threads(|| self.runtime.block_on(pipeline.collect(items)))
+    }
+}
+
+/// python module
+#[pymodule]
+fn uciengine(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyGoJob>()?;
+    m.add_class::<PyUciEngine>()?;
+    m.add_class::<AnalysisStream>()?;
+    m.add_class::<PyPipeline>()?;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn python_pipeline() {
+    let path = fake_engine(
+        "python",
+        "    go*) echo 'info depth 3 score cp 25 pv e2e4'; echo 'bestmove e2e4';;",
+    );
+
+    pyo3::prepare_freethreaded_python();
+
+    Python::with_gil(|py| {
+        let pipeline = Py::new(py, PyPipeline::new(&path, 2).unwrap()).unwrap();
+
+        let globals = pyo3::types::PyDict::new_bound(py);
+
+        globals
+            .set_item("GoJob", py.get_type_bound::<PyGoJob>())
+            .unwrap();
+
+        let f = |code: &str| py.eval_bound(code, Some(&globals), None).unwrap().unbind();
+
+        let items: Vec<PyObject> = vec!["", "8/8/8/8/8/8/8/K6k w - - 0 1"]
+            .into_iter()
+            .map(|fen| fen.into_py(py))
+            .collect();
+
+        let out = {
+            let mut pipeline = pipeline.borrow_mut(py);
+
+            pipeline
+                .stages
+                .push((PyStageKind::Filter, f("lambda fen: fen")));
+            pipeline.stages.push((
+                PyStageKind::Analyse,
+                f("lambda fen: GoJob().pos_fen(fen).go_opt('depth', '3')"),
+            ));
+            pipeline
+                .stages
+                .push((PyStageKind::Map, f("lambda item: item[1]['bestmove']")));
+
+            pipeline.run(py, items)
+        };
+
+        let out: Vec<String> = out.iter().map(|out| out.extract(py).unwrap()).collect();
+
+        assert_eq!(out, vec!["e2e4"]);
+    });
+}
@@ -0,0 +1,194 @@
+use log::{info, log_enabled, Level};
+
+use crate::prior::*;
+use crate::uciengine::*;
+
+/// verification status of a search result
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verification {
+    /// result had no anomalies, agreed with its prior if any and was not searched again
+    Unchecked,
+    /// result was anomalous or disagreed with its prior, the search again agreed with it
+    Verified,
+    /// result was anomalous or disagreed with its prior, the search again disagreed or failed
+    Unverified,
+}
+
+/// search result together with its verification
+#[derive(Debug)]
+pub struct VerifiedResult {
+    /// original result
+    pub result: GoResult,
+    /// result of the search again, if the original was anomalous or disagreed with its prior
+    pub recheck: Option<GoResult>,
+    /// verification status
+    pub verification: Verification,
+}
+
+/// verified result implementation
+impl VerifiedResult {
+    /// most trustworthy result, the search again if there was one
+    pub fn best_result(&self) -> &GoResult {
+        self.recheck.as_ref().unwrap_or(&self.result)
+    }
+}
+
+/// verification pass, searches positions with anomalous results again
+/// on a second engine or with a higher budget, results disagreeing with
+/// the prior of their position are searched again deeper
+///
+/// let verifier = Verifier::new().engine(second).go_opt("depth", 30);
+/// let verified = verifier.go(&engine, || GoJob::new().pos_fen(fen).go_opt("depth", 20)).await;
+/// let verified = verifier.verify_prior(&engine, result, Some(&prior), make_job).await;
+pub struct Verifier {
+    /// engine to search again on, the original engine if None
+    engine: Option<std::sync::Arc<UciEngine>>,
+    /// go options overriding the options of the job when searching again
+    go_options: Vec<(String, String)>,
+    /// policy deciding when a result disagrees with its prior
+    prior_policy: PriorPolicy,
+}
+
+/// verifier implementation
+impl Verifier {
+    /// create new verifier searching again on the original engine with the same budget
+    pub fn new() -> Self {
+        Self {
+            engine: None,
+            go_options: vec![],
+            prior_policy: PriorPolicy::new(),
+        }
+    }
+
+    /// set engine to search again on and return self
+    pub fn engine(mut self, engine: std::sync::Arc<UciEngine>) -> Self {
+        self.engine = Some(engine);
+
+        self
+    }
+
+    /// set go option of the search again and return self
+    pub fn go_opt<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.go_options.push((key.to_string(), value.to_string()));
+
+        self
+    }
+
+    /// set policy deciding when a result disagrees with its prior and return self
+    pub fn prior_policy(mut self, prior_policy: PriorPolicy) -> Self {
+        self.prior_policy = prior_policy;
+
+        self
+    }
+
+    /// verify result of a search, make_job creates the job of the original search,
+    /// anomalous results are searched again, the result is verified if the search again
+    /// is not anomalous and agrees on the bestmove
+    pub async fn verify<F>(
+        &self,
+        engine: &UciEngine,
+        result: GoResult,
+        make_job: F,
+    ) -> VerifiedResult
+    where
+        F: Fn() -> GoJob,
+    {
+        self.verify_prior(engine, result, None, make_job).await
+    }
+
+    /// verify result of a search of a position with prior, see verify, results
+    /// disagreeing with the prior are searched again too, beyond the deeper of
+    /// the result and the prior
+    pub async fn verify_prior<F>(
+        &self,
+        engine: &UciEngine,
+        result: GoResult,
+        prior: Option<&EvalPrior>,
+        make_job: F,
+    ) -> VerifiedResult
+    where
+        F: Fn() -> GoJob,
+    {
+        let disagreeing = prior.filter(|prior| self.prior_policy.disagrees(prior, &result.ai));
+
+        if result.anomalies.is_empty() && disagreeing.is_none() {
+            return VerifiedResult {
+                result,
+                recheck: None,
+                verification: Verification::Unchecked,
+            };
+        }
+
+        if log_enabled!(Level::Info) {
+            info!(
+                "searching again result {:?} anomalies {:?} disagreeing prior {:?}",
+                result.bestmove, result.anomalies, disagreeing
+            );
+        }
+
+        let mut go_job = make_job();
+
+        if let Some(prior) = disagreeing {
+            go_job = go_job.go_opt("depth", self.prior_policy.deeper_depth(prior, &result.ai));
+        }
+
+        for (key, value) in self.go_options.iter() {
+            go_job = go_job.go_opt(key, value);
+        }
+
+        let rrx = match &self.engine {
+            Some(second) => second.try_go(go_job),
+            _ => engine.try_go(go_job),
+        };
+
+        let recheck = rrx.await.ok();
+
+        let verification = match &recheck {
+            Some(recheck)
+                if recheck.anomalies.is_empty() && recheck.bestmove == result.bestmove =>
+            {
+                Verification::Verified
+            }
+            _ => Verification::Unverified,
+        };
+
+        VerifiedResult {
+            result,
+            recheck,
+            verification,
+        }
+    }
+
+    /// search on engine and verify the result, see verify
+    pub async fn go<F>(&self, engine: &UciEngine, make_job: F) -> Option<VerifiedResult>
+    where
+        F: Fn() -> GoJob,
+    {
+        let result = engine.try_go(make_job()).await.ok()?;
+
+        Some(self.verify(engine, result, make_job).await)
+    }
+}
+
+/// implement Default for Verifier
+impl Default for Verifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// number of results per verification status ( unchecked, verified, unverified )
+pub fn verification_counts(results: &[VerifiedResult]) -> (usize, usize, usize) {
+    results.iter().fold(
+        (0, 0, 0),
+        |(unchecked, verified, unverified), result| match result.verification {
+            Verification::Unchecked => (unchecked + 1, verified, unverified),
+            Verification::Verified => (unchecked, verified + 1, unverified),
+            Verification::Unverified => (unchecked, verified, unverified + 1),
+        },
+    )
+}
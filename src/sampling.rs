@@ -0,0 +1,197 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::*;
+
+/// sampling policy of the info stream, an update is broadcast if any of the
+/// set conditions holds, with no condition set every update is broadcast,
+/// the final update of a search ( on bestmove ) is always broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InfoSampling {
+    /// broadcast every nth update
+    pub every: Option<usize>,
+    /// broadcast when the depth changed
+    pub on_depth_change: bool,
+    /// broadcast when the score changed by at least this many centipawns
+    pub score_change_cp: Option<i32>,
+    /// broadcast when this much time passed since the last broadcast
+    pub interval: Option<Duration>,
+}
+
+/// info sampling implementation
+impl InfoSampling {
+    /// create new policy broadcasting every update
+    pub fn new() -> Self {
+        Self {
+            every: None,
+            on_depth_change: false,
+            score_change_cp: None,
+            interval: None,
+        }
+    }
+
+    /// broadcast every nth update and return self
+    pub fn every(mut self, n: usize) -> Self {
+        self.every = Some(n.max(1));
+
+        self
+    }
+
+    /// broadcast on depth change and return self
+    pub fn on_depth_change(mut self) -> Self {
+        self.on_depth_change = true;
+
+        self
+    }
+
+    /// broadcast on score change of at least cp centipawns and return self
+    pub fn score_change(mut self, cp: i32) -> Self {
+        self.score_change_cp = Some(cp);
+
+        self
+    }
+
+    /// broadcast when the interval passed since the last broadcast and return self
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+
+        self
+    }
+
+    /// true if no condition is set
+    pub fn is_unsampled(&self) -> bool {
+        self.every.is_none()
+            && !self.on_depth_change
+            && self.score_change_cp.is_none()
+            && self.interval.is_none()
+    }
+}
+
+/// implement Default for InfoSampling
+impl Default for InfoSampling {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// last broadcast update of a line
+#[derive(Debug, Clone, Copy)]
+struct Broadcast {
+    depth: usize,
+    cp: i32,
+}
+
+/// sampler state of the info stream of a search
+#[derive(Debug, Clone)]
+pub struct InfoSampler {
+    /// updates since the last broadcast
+    skipped: usize,
+    /// time of the last broadcast
+    last_at: Option<Instant>,
+    /// last broadcast update by multipv index
+    last: Vec<Option<Broadcast>>,
+}
+
+/// info sampler implementation
+impl InfoSampler {
+    /// create new sampler
+    pub fn new() -> Self {
+        Self {
+            skipped: 0,
+            last_at: None,
+            last: vec![],
+        }
+    }
+
+    /// true if the update should be broadcast under policy,
+    /// the state is reset after the final update of a search
+    pub fn admit(&mut self, policy: &InfoSampling, ai: &AnalysisInfo, now: Instant) -> bool {
+        if ai.done {
+            *self = Self::new();
+
+            return true;
+        }
+
+        if policy.is_unsampled() {
+            return true;
+        }
+
+        let line = ai.multipv.max(1) - 1;
+
+        if self.last.len() <= line {
+            self.last.resize(line + 1, None);
+        }
+
+        let last = self.last[line];
+
+        let admitted = match last {
+            // the first update of a line always goes through
+            None => true,
+            Some(last) => {
+                policy.every.is_some_and(|every| self.skipped + 1 >= every)
+                    || (policy.on_depth_change && ai.depth != last.depth)
+                    || policy
+                        .score_change_cp
+                        .is_some_and(|cp| (ai.score.cp_equivalent() - last.cp).abs() >= cp)
+                    || match (policy.interval, self.last_at) {
+                        (Some(interval), Some(last_at)) => now.duration_since(last_at) >= interval,
+                        _ => false,
+                    }
+            }
+        };
+
+        if admitted {
+            self.skipped = 0;
+            self.last_at = Some(now);
+            self.last[line] = Some(Broadcast {
+                depth: ai.depth,
+                cp: ai.score.cp_equivalent(),
+            });
+        } else {
+            self.skipped += 1;
+        }
+
+        admitted
+    }
+}
+
+/// implement Default for InfoSampler
+impl Default for InfoSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn info_sampling() {
+    let policy = InfoSampling::new().on_depth_change().score_change(30);
+    let mut sampler = InfoSampler::new();
+    let now = Instant::now();
+
+    let admitted: Vec<bool> = [
+        "info depth 10 score cp 20 nodes 100",
+        "info depth 10 score cp 25 nodes 200",
+        "info depth 10 score cp 60 nodes 300",
+        "info depth 11 score cp 60 nodes 400",
+        "info depth 11 score cp 61 nodes 500",
+    ]
+    .iter()
+    .map(|line| {
+        let mut ai = AnalysisInfo::new();
+
+        ai.parse(line).unwrap();
+
+        sampler.admit(&policy, &ai, now)
+    })
+    .collect();
+
+    assert_eq!(admitted, vec![true, false, true, true, false]);
+
+    let mut ai = AnalysisInfo::new();
+
+    ai.done = true;
+
+    assert!(sampler.admit(&policy, &ai, now));
+    assert!(InfoSampler::new().admit(&InfoSampling::new().every(3), &ai, now));
+}
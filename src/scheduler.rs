@@ -0,0 +1,452 @@
+use log::{debug, info, log_enabled, warn, Level};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+/// SchedulerError captures possible scheduler errors
+#[derive(Error, Debug)]
+pub enum SchedulerError {
+    #[error("invalid cron expression : {0}")]
+    InvalidCron(String),
+    #[error("schedules could not be read or written : {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid schedules json : {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// seconds within which a cron expression has to match, expressions that never
+/// match ( like february 31 ) give no next time
+const CRON_HORIZON: u64 = 5 * 366 * 86400;
+
+/// allowed values of a cron field as a bit set
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CronField {
+    bits: u64,
+    /// true for "*", day of month and day of week combine differently then
+    any: bool,
+}
+
+/// cron field implementation
+impl CronField {
+    /// parse field with values between min and max, lists, ranges and steps are
+    /// supported ( "*/15", "1-5", "0,30", "8-18/2" )
+    fn parse(field: &str, min: u64, max: u64) -> Option<Self> {
+        let mut bits = 0;
+
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u64>().ok().filter(|step| *step > 0)?),
+                _ => (part, 1),
+            };
+
+            let (from, to) = match range {
+                "*" => (min, max),
+                _ => match range.split_once('-') {
+                    Some((from, to)) => (from.parse().ok()?, to.parse().ok()?),
+                    _ => {
+                        let value = range.parse().ok()?;
+
+                        // "5/15" runs from 5 to the end
+                        match step > 1 {
+                            true => (value, max),
+                            _ => (value, value),
+                        }
+                    }
+                },
+            };
+
+            if from < min || to > max || from > to {
+                return None;
+            }
+
+            for value in (from..=to).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+
+        Some(Self {
+            bits,
+            any: field == "*",
+        })
+    }
+
+    /// true if value is allowed
+    fn matches(&self, value: u64) -> bool {
+        self.bits & (1 << value) != 0
+    }
+}
+
+/// cron schedule, five fields "minute hour day-of-month month day-of-week",
+/// evaluated in utc, day of week 0 or 7 is sunday, as in cron a day matches
+/// either day field when both are restricted
+///
+/// let nightly = CronSchedule::parse("0 2 * * *")?;
+/// let next = nightly.next_after(SystemTime::now());
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minute: CronField,
+    hour: CronField,
+    day: CronField,
+    month: CronField,
+    weekday: CronField,
+}
+
+/// year, month and day of days since the unix epoch ( proleptic gregorian )
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year as u64, month as u64, day as u64)
+}
+
+/// cron schedule implementation
+impl CronSchedule {
+    /// parse cron expression
+    pub fn parse<T: AsRef<str>>(expression: T) -> Result<Self, SchedulerError> {
+        let expression = expression.as_ref();
+        let invalid = || SchedulerError::InvalidCron(expression.to_string());
+
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+
+        if fields.len() != 5 {
+            return Err(invalid());
+        }
+
+        let mut weekday = CronField::parse(fields[4], 0, 7).ok_or_else(invalid)?;
+
+        // 7 is sunday as well
+        if weekday.matches(7) {
+            weekday.bits |= 1;
+        }
+
+        Ok(Self {
+            minute: CronField::parse(fields[0], 0, 59).ok_or_else(invalid)?,
+            hour: CronField::parse(fields[1], 0, 23).ok_or_else(invalid)?,
+            day: CronField::parse(fields[2], 1, 31).ok_or_else(invalid)?,
+            month: CronField::parse(fields[3], 1, 12).ok_or_else(invalid)?,
+            weekday,
+        })
+    }
+
+    /// true if the schedule runs on days since the unix epoch
+    fn matches_day(&self, days: u64) -> bool {
+        let (_, month, day) = civil_from_days(days);
+        // 1970-01-01 was a thursday
+        let weekday = (days + 4) % 7;
+
+        let day_matches = match (self.day.any, self.weekday.any) {
+            (true, true) => true,
+            (true, false) => self.weekday.matches(weekday),
+            (false, true) => self.day.matches(day),
+            _ => self.day.matches(day) || self.weekday.matches(weekday),
+        };
+
+        self.month.matches(month) && day_matches
+    }
+
+    /// next run in seconds since the unix epoch strictly after secs, None if
+    /// the schedule never runs
+    pub fn next_after_secs(&self, secs: u64) -> Option<u64> {
+        let horizon = secs + CRON_HORIZON;
+
+        // runs are on whole minutes
+        let mut t = (secs / 60 + 1) * 60;
+
+        while t <= horizon {
+            let days = t / 86400;
+
+            if !self.matches_day(days) {
+                t = (days + 1) * 86400;
+
+                continue;
+            }
+
+            if !self.hour.matches(t % 86400 / 3600) {
+                t = (t / 3600 + 1) * 3600;
+
+                continue;
+            }
+
+            if !self.minute.matches(t % 3600 / 60) {
+                t += 60;
+
+                continue;
+            }
+
+            return Some(t);
+        }
+
+        None
+    }
+
+    /// next run strictly after time, None if the schedule never runs
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+        self.next_after_secs(secs)
+            .map(|next| UNIX_EPOCH + Duration::from_secs(next))
+    }
+}
+
+/// recurring job of a scheduler
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledJob<T> {
+    /// id of the schedule
+    pub id: String,
+    /// cron expression
+    pub cron: String,
+    /// job run on every match ( a JobSpec, a DaemonRequest, ... )
+    pub job: T,
+    /// last run in seconds since the unix epoch, None if it never ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<u64>,
+}
+
+/// path of the schedules kept alongside a job journal
+pub fn schedules_path<P: AsRef<Path>>(journal_path: P) -> PathBuf {
+    journal_path.as_ref().with_extension("schedules")
+}
+
+/// lightweight scheduler of recurring jobs, the schedules are persisted as json
+/// ( conventionally alongside the job journal, see schedules_path ) so they
+/// survive restarts, runs missed while the service was down are not made up for
+///
+/// let mut scheduler: Scheduler<JobSpec> = Scheduler::open(schedules_path("jobs.journal"))?;
+/// scheduler.add("nightly", "0 2 * * *", todays_games)?;
+/// scheduler.add("refresh", "30 3 * * 0", stale_cache_entries)?;
+/// scheduler.run(|id, spec| async move { ... }).await;
+pub struct Scheduler<T> {
+    path: PathBuf,
+    jobs: Vec<ScheduledJob<T>>,
+}
+
+/// scheduler implementation
+impl<T> Scheduler<T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+{
+    /// open schedules at path, none if it is missing
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SchedulerError> {
+        let path = path.as_ref().to_path_buf();
+
+        let jobs = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err.into()),
+        };
+
+        let scheduler = Self { path, jobs };
+
+        // schedules edited by hand may be invalid, they are kept but never run
+        for job in scheduler.jobs.iter() {
+            if let Err(err) = CronSchedule::parse(&job.cron) {
+                warn!("schedule {} will not run : {}", job.id, err);
+            }
+        }
+
+        Ok(scheduler)
+    }
+
+    /// write the schedules, the file is replaced atomically
+    fn save(&self) -> Result<(), SchedulerError> {
+        let tmp = self.path.with_extension("schedules-tmp");
+
+        std::fs::write(&tmp, serde_json::to_string_pretty(&self.jobs)?)?;
+
+        std::fs::rename(&tmp, &self.path)?;
+
+        Ok(())
+    }
+
+    /// add or replace schedule with id and persist it
+    pub fn add<I, C>(&mut self, id: I, cron: C, job: T) -> Result<(), SchedulerError>
+    where
+        I: core::fmt::Display,
+        C: core::fmt::Display,
+    {
+        let cron = cron.to_string();
+
+        CronSchedule::parse(&cron)?;
+
+        let id = id.to_string();
+
+        self.jobs.retain(|job| job.id != id);
+
+        self.jobs.push(ScheduledJob {
+            id,
+            cron,
+            job,
+            last_run: None,
+        });
+
+        self.save()
+    }
+
+    /// remove schedule with id and persist it, returns true if there was one
+    pub fn remove(&mut self, id: &str) -> Result<bool, SchedulerError> {
+        let len = self.jobs.len();
+
+        self.jobs.retain(|job| job.id != id);
+
+        if self.jobs.len() == len {
+            return Ok(false);
+        }
+
+        self.save()?;
+
+        Ok(true)
+    }
+
+    /// schedules
+    pub fn schedules(&self) -> &[ScheduledJob<T>] {
+        &self.jobs
+    }
+
+    /// next run of schedule after its last run, at most a minute back from now
+    fn next_run(job: &ScheduledJob<T>, now: u64) -> Option<u64> {
+        let cron = CronSchedule::parse(&job.cron).ok()?;
+
+        // a run missed while down is not made up for, only the latest one counts
+        cron.next_after_secs(job.last_run.unwrap_or(0).max(now.saturating_sub(60)))
+    }
+
+    /// earliest next run of all schedules in seconds since the unix epoch
+    pub fn next_due(&self, now: u64) -> Option<u64> {
+        self.jobs
+            .iter()
+            .filter_map(|job| Self::next_run(job, now))
+            .min()
+    }
+
+    /// jobs due at now ( seconds since the unix epoch ), their last run is recorded
+    /// and persisted before they are handed out
+    pub fn due(&mut self, now: u64) -> Result<Vec<(String, T)>, SchedulerError> {
+        let mut due = vec![];
+
+        for job in self.jobs.iter_mut() {
+            if let Some(next) = Self::next_run(job, now) {
+                if next <= now {
+                    job.last_run = Some(now);
+
+                    due.push((job.id.to_owned(), job.job.clone()));
+                }
+            }
+        }
+
+        if !due.is_empty() {
+            self.save()?;
+        }
+
+        Ok(due)
+    }
+
+    /// run the schedules forever, execute is called with the id and the job of
+    /// every due schedule, one after the other
+    pub async fn run<F, Fut>(&mut self, mut execute: F)
+    where
+        F: FnMut(String, T) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        loop {
+            let now = unix_now();
+
+            let sleep = match self.next_due(now) {
+                Some(next) => Duration::from_secs(next.saturating_sub(now)),
+                // nothing to run, look again later in case of a change on disk
+                _ => Duration::from_secs(60),
+            };
+
+            if log_enabled!(Level::Debug) {
+                debug!("scheduler sleeping {:?}", sleep);
+            }
+
+            tokio::time::sleep(sleep).await;
+
+            let due = match self.due(unix_now()) {
+                Ok(due) => due,
+                Err(err) => {
+                    warn!("scheduler could not record runs : {}", err);
+
+                    continue;
+                }
+            };
+
+            for (id, job) in due {
+                if log_enabled!(Level::Info) {
+                    info!("running scheduled job {}", id);
+                }
+
+                execute(id, job).await;
+            }
+        }
+    }
+}
+
+/// seconds since the unix epoch
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0)
+}
+
+#[test]
+fn cron_schedules() {
+    // 2024-03-15 10:07:00 utc, a friday
+    let friday = 1710497220;
+
+    let nightly = CronSchedule::parse("0 2 * * *").unwrap();
+
+    // 2024-03-16 02:00
+    assert_eq!(nightly.next_after_secs(friday), Some(1710554400));
+
+    let weekdays = CronSchedule::parse("*/15 9-17 * * 1-5").unwrap();
+
+    assert_eq!(weekdays.next_after_secs(friday), Some(friday + 8 * 60));
+    // friday 17:45 is followed by monday 09:00
+    assert_eq!(weekdays.next_after_secs(1710524700), Some(1710752400));
+
+    assert!(CronSchedule::parse("0 0 31 2 *")
+        .unwrap()
+        .next_after_secs(friday)
+        .is_none());
+    assert!(CronSchedule::parse("61 * * * *").is_err());
+    assert!(CronSchedule::parse("* * *").is_err());
+
+    let path = std::env::temp_dir().join(format!("uciengine-schedules-{}", std::process::id()));
+
+    let _ = std::fs::remove_file(&path);
+
+    {
+        let mut scheduler: Scheduler<String> = Scheduler::open(&path).unwrap();
+
+        scheduler
+            .add("nightly", "0 2 * * *", "todays games".to_string())
+            .unwrap();
+
+        assert_eq!(scheduler.next_due(friday), Some(1710554400));
+        assert!(scheduler.due(friday).unwrap().is_empty());
+        assert_eq!(scheduler.due(1710554400).unwrap().len(), 1);
+        assert!(scheduler.due(1710554400).unwrap().is_empty());
+    }
+
+    let mut scheduler: Scheduler<String> = Scheduler::open(&path).unwrap();
+
+    assert_eq!(scheduler.schedules()[0].last_run, Some(1710554400));
+    assert!(scheduler.remove("nightly").unwrap());
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,501 @@
+use log::{debug, log_enabled, warn, Level};
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use thiserror::Error;
+
+use crate::analysis::*;
+use crate::position::*;
+use crate::uciengine::*;
+
+/// ServerError captures possible server side errors
+#[derive(Error, Debug)]
+pub enum ServerError {
+    #[error("invalid gui command : {0}")]
+    InvalidCommand(String),
+    #[error("gui io failed : {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// parameters of a go command of the gui, the mirror image of the go options of GoJob
+#[derive(Debug, Clone)]
+pub struct GoParams {
+    /// clock, None if the gui sent no time ( missing fields are 0 )
+    pub timecontrol: Option<Timecontrol>,
+    /// moves to the next time control
+    pub movestogo: Option<usize>,
+    /// depth limit
+    pub depth: Option<usize>,
+    /// nodes limit
+    pub nodes: Option<u64>,
+    /// search for a mate in moves
+    pub mate: Option<usize>,
+    /// movetime in milliseconds
+    pub movetime: Option<usize>,
+    /// search until stopped
+    pub infinite: bool,
+    /// ponder search
+    pub ponder: bool,
+    /// moves the search is restricted to, empty if all moves
+    pub searchmoves: Vec<String>,
+    /// tokens not understood, in order ( engine specific extensions )
+    pub unknown: Vec<String>,
+}
+
+/// command of the gui, parsed from the gui side of uci
+#[derive(Debug, Clone)]
+pub enum GuiCommand {
+    Uci,
+    Debug(bool),
+    IsReady,
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    UciNewGame,
+    Position(PositionCommand),
+    Go(GoParams),
+    Stop,
+    PonderHit,
+    Quit,
+    /// unknown command, ignored as the protocol requires
+    Unknown(String),
+}
+
+/// parse number of gui command
+fn parse_number<T: std::str::FromStr>(line: &str, token: Option<&str>) -> Result<T, ServerError> {
+    token
+        .and_then(|token| token.parse::<T>().ok())
+        .ok_or_else(|| ServerError::InvalidCommand(line.to_string()))
+}
+
+/// parse clock time of gui command, a negative time ( a gui running over the clock )
+/// is clamped to 0
+fn parse_clock(line: &str, token: Option<&str>) -> Result<usize, ServerError> {
+    parse_number::<i64>(line, token).map(|time| time.max(0) as usize)
+}
+
+/// go params implementation
+impl GoParams {
+    /// create new go params without limits
+    pub fn new() -> Self {
+        Self {
+            timecontrol: None,
+            movestogo: None,
+            depth: None,
+            nodes: None,
+            mate: None,
+            movetime: None,
+            infinite: false,
+            ponder: false,
+            searchmoves: vec![],
+            unknown: vec![],
+        }
+    }
+
+    /// clock, created on the first time field
+    fn clock(&mut self) -> &mut Timecontrol {
+        self.timecontrol.get_or_insert(Timecontrol::new(0, 0, 0, 0))
+    }
+
+    /// parse go command ( the leading go is optional ), unknown tokens are kept
+    /// in unknown, a missing or malformed number is an error, negative clock times
+    /// are clamped to 0
+    pub fn parse(line: &str) -> Result<Self, ServerError> {
+        let mut params = Self::new();
+
+        let mut tokens = line.split_whitespace().peekable();
+
+        if tokens.peek() == Some(&"go") {
+            tokens.next();
+        }
+
+        while let Some(token) = tokens.next() {
+            match token {
+                "wtime" => params.clock().wtime = parse_clock(line, tokens.next())?,
+                "btime" => params.clock().btime = parse_clock(line, tokens.next())?,
+                "winc" => params.clock().winc = parse_clock(line, tokens.next())?,
+                "binc" => params.clock().binc = parse_clock(line, tokens.next())?,
+                "movestogo" => params.movestogo = Some(parse_number(line, tokens.next())?),
+                "depth" => params.depth = Some(parse_number(line, tokens.next())?),
+                "nodes" => params.nodes = Some(parse_number(line, tokens.next())?),
+                "mate" => params.mate = Some(parse_number(line, tokens.next())?),
+                "movetime" => params.movetime = Some(parse_number(line, tokens.next())?),
+                "infinite" => params.infinite = true,
+                "ponder" => params.ponder = true,
+                // searchmoves consumes the rest of the line
+                "searchmoves" => params.searchmoves = tokens.by_ref().map(String::from).collect(),
+                _ => {
+                    warn!("unknown go token {}", token);
+
+                    params.unknown.push(token.to_string());
+                }
+            }
+        }
+
+        Ok(params)
+    }
+
+    /// go job with these params ( no position ), the inverse of parse,
+    /// the job searches even without params, as a plain go does
+    pub fn to_go_job(&self) -> GoJob {
+        let mut go_job = GoJob::new();
+
+        if let Some(timecontrol) = self.timecontrol {
+            go_job = go_job.tc(timecontrol);
+        }
+
+        if let Some(movestogo) = self.movestogo {
+            go_job = go_job.movestogo(movestogo);
+        }
+
+        if let Some(depth) = self.depth {
+            go_job = go_job.go_opt("depth", depth);
+        }
+
+        if let Some(nodes) = self.nodes {
+            go_job = go_job.go_opt("nodes", nodes);
+        }
+
+        if let Some(mate) = self.mate {
+            go_job = go_job.go_opt("mate", mate);
+        }
+
+        if let Some(movetime) = self.movetime {
+            go_job = go_job.go_opt("movetime", movetime);
+        }
+
+        if self.infinite {
+            go_job = go_job.infinite();
+        }
+
+        if self.ponder {
+            go_job = go_job.ponder();
+        }
+
+        go_job.searchmoves(self.searchmoves.iter()).go()
+    }
+}
+
+/// implement Default for GoParams
+impl Default for GoParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// gui command implementation
+impl GuiCommand {
+    /// parse gui command line
+    pub fn parse(line: &str) -> Result<Self, ServerError> {
+        let line = line.trim();
+        let mut tokens = line.split_whitespace();
+
+        let command = match tokens.next() {
+            Some("uci") => GuiCommand::Uci,
+            Some("debug") => GuiCommand::Debug(tokens.next() == Some("on")),
+            Some("isready") => GuiCommand::IsReady,
+            Some("ucinewgame") => GuiCommand::UciNewGame,
+            Some("stop") => GuiCommand::Stop,
+            Some("ponderhit") => GuiCommand::PonderHit,
+            Some("quit") => GuiCommand::Quit,
+            Some("setoption") => {
+                // option names and values may contain spaces
+                let rest = line["setoption".len()..].trim();
+
+                let rest = rest
+                    .strip_prefix("name ")
+                    .ok_or_else(|| ServerError::InvalidCommand(line.to_string()))?;
+
+                match rest.split_once(" value ") {
+                    Some((name, value)) => GuiCommand::SetOption {
+                        name: name.trim().to_string(),
+                        value: Some(value.trim().to_string()),
+                    },
+                    _ => GuiCommand::SetOption {
+                        name: rest.trim().to_string(),
+                        value: None,
+                    },
+                }
+            }
+            Some("position") => {
+                let rest = line["position".len()..].trim();
+
+                let (base, moves) = match rest.split_once("moves") {
+                    Some((base, moves)) => (base.trim(), moves),
+                    _ => (rest, ""),
+                };
+
+                let mut pos_command = match base.strip_prefix("fen ") {
+                    Some(fen) => PositionCommand::fen(fen.trim()),
+                    _ if base == "startpos" => PositionCommand::startpos(),
+                    _ => return Err(ServerError::InvalidCommand(line.to_string())),
+                };
+
+                pos_command.push_moves(moves);
+
+                GuiCommand::Position(pos_command)
+            }
+            Some("go") => GuiCommand::Go(GoParams::parse(line)?),
+            _ => GuiCommand::Unknown(line.to_string()),
+        };
+
+        Ok(command)
+    }
+}
+
+/// reporter of a search, sends the output of the search to the gui
+#[derive(Debug, Clone)]
+pub struct SearchReporter {
+    otx: mpsc::UnboundedSender<String>,
+}
+
+/// search reporter implementation
+impl SearchReporter {
+    /// report analysis info
+    pub fn info(&self, ai: &AnalysisInfo) {
+        let _ = self.otx.send(ai.to_info_line());
+    }
+
+    /// report info string
+    pub fn info_string<T: core::fmt::Display>(&self, text: T) {
+        let _ = self.otx.send(format!("info string {}", text));
+    }
+
+    /// report bestmove, ends the search
+    pub fn bestmove(&self, bestmove: &str, ponder: Option<&str>) {
+        let line = match ponder {
+            Some(ponder) => format!("bestmove {} ponder {}", bestmove, ponder),
+            _ => format!("bestmove {}", bestmove),
+        };
+
+        let _ = self.otx.send(line);
+    }
+}
+
+/// engine served to a gui, implemented by rust engine authors,
+/// the server does the protocol and calls these with typed requests
+pub trait UciEngineImpl: Send {
+    /// engine name and author, answered to uci
+    fn id(&self) -> (String, String);
+
+    /// option lines without the leading "option " ( "name Hash type spin default 16 min 1 max 1024" )
+    fn options(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// set option, value is None for button options
+    fn set_option(&mut self, _name: &str, _value: Option<&str>) {}
+
+    /// the next position is from a new game
+    fn new_game(&mut self) {}
+
+    /// set position to search
+    fn position(&mut self, position: &PositionCommand);
+
+    /// start search of the position, should return right away ( searching on
+    /// a thread or task ) and send bestmove through the reporter when done
+    fn go(&mut self, request: GoParams, reporter: SearchReporter);
+
+    /// stop the running search, it should report bestmove soon
+    fn stop(&mut self);
+
+    /// the gui played the move pondered on, the ponder search goes on as a normal search
+    fn ponderhit(&mut self) {}
+}
+
+/// uci engine server, serves the gui side of uci for an engine implementation
+///
+/// UciEngineServer::new(MyEngine::new()).serve_stdio().await?;
+pub struct UciEngineServer<E> {
+    engine: E,
+}
+
+/// uci engine server implementation
+impl<E> UciEngineServer<E>
+where
+    E: UciEngineImpl,
+{
+    /// create new server for engine
+    pub fn new(engine: E) -> Self {
+        Self { engine }
+    }
+
+    /// handle gui command, returns false on quit
+    fn handle(&mut self, command: GuiCommand, otx: &mpsc::UnboundedSender<String>) -> bool {
+        match command {
+            GuiCommand::Uci => {
+                let (name, author) = self.engine.id();
+
+                let _ = otx.send(format!("id name {}", name));
+                let _ = otx.send(format!("id author {}", author));
+
+                for option in self.engine.options() {
+                    let _ = otx.send(format!("option {}", option));
+                }
+
+                let _ = otx.send("uciok".to_string());
+            }
+            GuiCommand::IsReady => {
+                let _ = otx.send("readyok".to_string());
+            }
+            GuiCommand::SetOption { name, value } => {
+                self.engine.set_option(&name, value.as_deref());
+            }
+            GuiCommand::UciNewGame => self.engine.new_game(),
+            GuiCommand::Position(position) => self.engine.position(&position),
+            GuiCommand::Go(request) => self.engine.go(request, SearchReporter { otx: otx.clone() }),
+            GuiCommand::Stop => self.engine.stop(),
+            GuiCommand::PonderHit => self.engine.ponderhit(),
+            GuiCommand::Quit => {
+                self.engine.stop();
+
+                return false;
+            }
+            GuiCommand::Debug(_) => {}
+            GuiCommand::Unknown(line) => warn!("ignoring unknown gui command {}", line),
+        }
+
+        true
+    }
+
+    /// serve gui commands read from reader, engine output is written to writer,
+    /// returns on quit or when the gui closes its side
+    pub async fn serve<R, W>(mut self, reader: R, mut writer: W) -> Result<(), ServerError>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let (otx, mut orx) = mpsc::unbounded_channel::<String>();
+
+        let mut lines = reader.lines();
+
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let line = match line? {
+                        Some(line) => line,
+                        _ => break,
+                    };
+
+                    if log_enabled!(Level::Debug) {
+                        debug!("gui in : {}", line);
+                    }
+
+                    let command = match GuiCommand::parse(&line) {
+                        Ok(command) => command,
+                        Err(err) => {
+                            warn!("{}", err);
+
+                            continue;
+                        }
+                    };
+
+                    if !self.handle(command, &otx) {
+                        break;
+                    }
+                }
+                Some(output) = orx.recv() => {
+                    if log_enabled!(Level::Debug) {
+                        debug!("gui out : {}", output);
+                    }
+
+                    writer.write_all(format!("{}\n", output).as_bytes()).await?;
+                    writer.flush().await?;
+                }
+            }
+        }
+
+        // output of the stopped search
+        drop(otx);
+
+        while let Ok(output) = orx.try_recv() {
+            writer.write_all(format!("{}\n", output).as_bytes()).await?;
+        }
+
+        writer.flush().await?;
+
+        Ok(())
+    }
+
+    /// serve gui on stdin and stdout
+    pub async fn serve_stdio(self) -> Result<(), ServerError> {
+        self.serve(
+            tokio::io::BufReader::new(tokio::io::stdin()),
+            tokio::io::stdout(),
+        )
+        .await
+    }
+}
+
+#[test]
+fn go_params() {
+    let params = GoParams::parse(
+        "go wtime 1000 binc 10 movestogo 40 depth 3 multicut searchmoves e7e5 c7c5",
+    )
+    .unwrap();
+
+    assert_eq!(params.timecontrol.unwrap().wtime, 1000);
+    assert_eq!(params.movestogo, Some(40));
+    assert_eq!(params.searchmoves, vec!["e7e5", "c7c5"]);
+    assert_eq!(params.unknown, vec!["multicut"]);
+    assert!(GoParams::parse("go depth").is_err());
+
+    let flagged = GoParams::parse("go wtime -50 btime 2000").unwrap();
+
+    assert_eq!(flagged.timecontrol.unwrap().wtime, 0);
+    assert_eq!(flagged.timecontrol.unwrap().btime, 2000);
+
+    // encoded by go job and parsed back
+    let commands = params.to_go_job().to_commands();
+    let reparsed = GoParams::parse(commands.last().unwrap()).unwrap();
+
+    assert_eq!(reparsed.timecontrol.unwrap().binc, 10);
+    assert_eq!(reparsed.depth, Some(3));
+    assert_eq!(reparsed.searchmoves, params.searchmoves);
+    assert!(reparsed.unknown.is_empty());
+}
+
+#[tokio::test]
+async fn engine_server() {
+    struct FirstMove;
+
+    impl UciEngineImpl for FirstMove {
+        fn id(&self) -> (String, String) {
+            ("FirstMove".to_string(), "test".to_string())
+        }
+
+        fn position(&mut self, _position: &PositionCommand) {}
+
+        fn go(&mut self, request: GoParams, reporter: SearchReporter) {
+            let mut ai = AnalysisInfo::new();
+
+            ai.depth = request.depth.unwrap_or(1);
+            ai.set_pv("e7e5 g1f3");
+
+            reporter.info(&ai);
+            reporter.bestmove("e7e5", Some("g1f3"));
+        }
+
+        fn stop(&mut self) {}
+    }
+
+    match GuiCommand::parse("position startpos moves e2e4 e7e5").unwrap() {
+        GuiCommand::Position(position) => assert_eq!(position.num_moves(), 2),
+        command => panic!("parsed as {:?}", command),
+    }
+
+    let gui = "uci\nisready\nposition startpos moves e2e4\ngo depth 3\nquit\n";
+    let mut output: Vec<u8> = vec![];
+
+    UciEngineServer::new(FirstMove)
+        .serve(gui.as_bytes(), &mut output)
+        .await
+        .unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(output.starts_with("id name FirstMove\nid author test\nuciok\nreadyok\n"));
+    assert!(output.ends_with("bestmove e7e5 ponder g1f3\n"));
+}
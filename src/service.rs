@@ -0,0 +1,62 @@
+use tokio::sync::{broadcast, oneshot};
+
+use crate::analysis::*;
+use crate::position::*;
+use crate::uciengine::*;
+
+/// engine service, the high level operations a gui needs from an engine backend,
+/// implementors provide job submission, immediate commands and the info stream,
+/// the session operations are built on top of these
+pub trait EngineService: Send + Sync {
+    /// submit go job, the result is sent on the returned receiver
+    fn submit(&self, go_job: GoJob) -> oneshot::Receiver<GoResult>;
+
+    /// issue command immediately, bypassing queued jobs
+    fn issue(&self, command: &str);
+
+    /// subscribe to the analysis info stream
+    fn stream(&self) -> broadcast::Receiver<AnalysisInfo>;
+
+    /// start new session ( the following positions are from a different game )
+    fn start_session(&self) {
+        self.submit(GoJob::new().new_game(true));
+    }
+
+    /// stop the running search, its result is sent to the job that started it
+    fn stop(&self) {
+        self.issue("stop");
+    }
+
+    /// set number of principal variations for the following searches
+    fn set_multipv(&self, multipv: usize) {
+        self.submit(GoJob::new().uci_opt("MultiPV", multipv));
+    }
+
+    /// update position, stops the running search and starts infinite analysis
+    /// of the new position, the result is sent once the analysis is stopped
+    fn update_position(&self, pos_command: &PositionCommand) -> oneshot::Receiver<GoResult> {
+        self.stop();
+
+        self.submit(GoJob::new().pos_command(pos_command).infinite())
+    }
+
+    /// quit engine
+    fn shutdown(&self) {
+        self.submit(GoJob::new().custom("quit"));
+    }
+}
+
+/// uci engine as engine service
+impl EngineService for UciEngine {
+    fn submit(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        self.queue(go_job)
+    }
+
+    fn issue(&self, command: &str) {
+        self.issue_command(command);
+    }
+
+    fn stream(&self) -> broadcast::Receiver<AnalysisInfo> {
+        self.atx.subscribe()
+    }
+}
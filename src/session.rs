@@ -0,0 +1,242 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::*;
+
+/// export format of an analysis session
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    /// pgn movetext with lichess style [%eval] comments
+    PgnEval,
+    /// csv rows, one per move, with a header row
+    Csv,
+    /// newline delimited json events, one per move
+    Ndjson,
+}
+
+/// retained analysis of a move
+#[derive(Debug, Clone)]
+struct SessionEntry {
+    /// move as played ( san for pgn export )
+    played: String,
+    /// final analysis of the position after the move
+    ai: AnalysisInfo,
+}
+
+/// analysis event of a move, as exported to ndjson
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionEvent {
+    /// ply of the move, starting from 1
+    ply: usize,
+    /// move as played
+    played: String,
+    /// score from the point of view of white
+    white_score: Score,
+    /// final analysis of the position after the move
+    ai: AnalysisInfoSerde,
+}
+
+/// analysis session of a game, retains the final analysis of every position
+/// played and exports the score history to several formats in one pass
+#[derive(Debug, Clone)]
+pub struct AnalysisSession {
+    /// true if white made the first move of the session
+    white_starts: bool,
+    /// full move number of the first move
+    first_move_number: usize,
+    /// retained history
+    history: Vec<SessionEntry>,
+}
+
+/// analysis session implementation
+impl AnalysisSession {
+    /// create new session starting with a white move at move 1
+    pub fn new() -> Self {
+        Self {
+            white_starts: true,
+            first_move_number: 1,
+            history: vec![],
+        }
+    }
+
+    /// set side and full move number of the first move and return self
+    pub fn start(mut self, white_starts: bool, first_move_number: usize) -> Self {
+        self.white_starts = white_starts;
+        self.first_move_number = first_move_number.max(1);
+
+        self
+    }
+
+    /// record move played and the final analysis of the position after it
+    /// ( score from the point of view of the side to move after the move )
+    pub fn record<T: AsRef<str>>(&mut self, played: T, ai: AnalysisInfo) {
+        self.history.push(SessionEntry {
+            played: played.as_ref().to_string(),
+            ai,
+        });
+    }
+
+    /// number of recorded moves
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// true if no move was recorded
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// true if white made the move at index of the history
+    pub fn white_moved(&self, index: usize) -> bool {
+        (index & 1 == 0) == self.white_starts
+    }
+
+    /// full move number of the move at index of the history
+    pub fn move_number(&self, index: usize) -> usize {
+        self.first_move_number + (index + !self.white_starts as usize) / 2
+    }
+
+    /// recorded moves in order, each with the final analysis of the position after it
+    /// and true if white made it
+    pub fn moves(&self) -> Vec<(&str, &AnalysisInfo, bool)> {
+        self.history
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (entry.played.as_str(), &entry.ai, self.white_moved(index)))
+            .collect()
+    }
+
+    /// export the history to each of the sinks in one pass
+    pub fn export_to(&self, sinks: &mut [(ExportFormat, &mut dyn Write)]) -> std::io::Result<()> {
+        for (format, sink) in sinks.iter_mut() {
+            if *format == ExportFormat::Csv {
+                writeln!(sink, "ply,move,depth,white_cp,white_mate,bestmove")?;
+            }
+        }
+
+        for (index, entry) in self.history.iter().enumerate() {
+            let played = &entry.played;
+            let white_moved = self.white_moved(index);
+
+            // the score is from the point of view of the side to move after the move
+            let white_score = match white_moved {
+                true => entry.ai.score.flip(),
+                _ => entry.ai.score,
+            };
+
+            let move_number = self.move_number(index);
+
+            for (format, sink) in sinks.iter_mut() {
+                match format {
+                    ExportFormat::PgnEval => {
+                        let eval = match white_score {
+                            Score::Mate(moves) => format!("#{}", moves),
+                            Score::Cp(cp) => format!("{:.2}", cp as f64 / 100.0),
+                        };
+
+                        match (white_moved, index) {
+                            (true, _) => write!(sink, "{}. ", move_number)?,
+                            (false, 0) => write!(sink, "{}... ", move_number)?,
+                            _ => {}
+                        }
+
+                        write!(sink, "{} {{ [%eval {}] }} ", played, eval)?;
+                    }
+                    ExportFormat::Csv => {
+                        let (cp, mate) = match white_score {
+                            Score::Cp(cp) => (cp.to_string(), String::new()),
+                            Score::Mate(moves) => (String::new(), moves.to_string()),
+                        };
+
+                        writeln!(
+                            sink,
+                            "{},{},{},{},{},{}",
+                            index + 1,
+                            played,
+                            entry.ai.depth,
+                            cp,
+                            mate,
+                            entry.ai.bestmove().unwrap_or_default()
+                        )?;
+                    }
+                    ExportFormat::Ndjson => {
+                        let event = SessionEvent {
+                            ply: index + 1,
+                            played: played.to_string(),
+                            white_score,
+                            ai: entry.ai.to_serde(),
+                        };
+
+                        let json = serde_json::to_string(&event).map_err(|err| {
+                            std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+                        })?;
+
+                        writeln!(sink, "{}", json)?;
+                    }
+                }
+            }
+        }
+
+        for (_, sink) in sinks.iter_mut() {
+            sink.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// export the history to each of the formats in one pass, returns one string per format
+    pub fn export(&self, formats: &[ExportFormat]) -> Vec<String> {
+        let mut buffers: Vec<Vec<u8>> = formats.iter().map(|_| vec![]).collect();
+
+        {
+            let mut sinks: Vec<(ExportFormat, &mut dyn Write)> = formats
+                .iter()
+                .zip(buffers.iter_mut())
+                .map(|(format, buffer)| (*format, buffer as &mut dyn Write))
+                .collect();
+
+            // writing to memory does not fail
+            let _ = self.export_to(&mut sinks);
+        }
+
+        buffers
+            .into_iter()
+            .map(|buffer| String::from_utf8_lossy(&buffer).trim_end().to_string())
+            .collect()
+    }
+}
+
+/// implement Default for AnalysisSession
+impl Default for AnalysisSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn session_export() {
+    let mut session = AnalysisSession::new();
+
+    let mut ai = AnalysisInfo::new();
+
+    // black to move, black is worse
+    ai.parse("info depth 20 score cp -35 pv e7e5 g1f3").unwrap();
+
+    session.record("e4", ai);
+
+    ai.parse("info depth 20 score mate -3 pv g1f3 b8c6")
+        .unwrap();
+
+    session.record("e5", ai);
+
+    let exported = session.export(&[
+        ExportFormat::PgnEval,
+        ExportFormat::Csv,
+        ExportFormat::Ndjson,
+    ]);
+
+    assert_eq!(exported[0], "1. e4 { [%eval 0.35] } e5 { [%eval #-3] }");
+    assert_eq!(exported[1].lines().nth(1).unwrap(), "1,e4,20,35,,e7e5");
+    assert_eq!(exported[2].lines().count(), 2);
+}
@@ -0,0 +1,314 @@
+use log::{debug, log_enabled, Level};
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::analysis::*;
+use crate::prior::*;
+use crate::uciengine::*;
+
+/// default number of recent samples the controller fits its model to
+pub const DEFAULT_SLO_WINDOW: usize = 200;
+/// depth used while there are not enough samples
+const FALLBACK_DEPTH: usize = 12;
+/// minimum depth of a budget
+const MIN_DEPTH: usize = 1;
+/// maximum depth of a budget
+const MAX_DEPTH: usize = 64;
+
+/// search budget fitting a latency target
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchBudget {
+    /// depth limit
+    pub depth: usize,
+    /// nodes limit, None while the speed is unknown
+    pub nodes: Option<u64>,
+    /// movetime limit in milliseconds, the latency target as safety net
+    pub movetime: usize,
+}
+
+/// search budget controller, keeps searches within a target latency as hardware and
+/// load vary, fits time to depth ( exponential in depth ) and nodes per second to the
+/// recent searches and turns the target into depth and nodes budgets, harder positions
+/// get lower depth for the same latency
+///
+/// let mut controller = BudgetController::new(Duration::from_secs(2));
+/// let go_job = controller.apply(GoJob::new().pos_fen(fen), difficulty.score);
+/// // feed every info of the search back
+/// controller.observe(&ai);
+#[derive(Debug, Clone)]
+pub struct BudgetController {
+    /// latency target
+    target: Duration,
+    /// number of samples kept
+    window: usize,
+    /// recent ( depth, time in milliseconds ) samples
+    samples: VecDeque<(usize, usize)>,
+    /// recent nodes per second samples
+    nps: VecDeque<u64>,
+    /// policy for positions with priors
+    prior_policy: PriorPolicy,
+}
+
+/// budget controller implementation
+impl BudgetController {
+    /// create new controller for latency target
+    pub fn new(target: Duration) -> Self {
+        Self {
+            target,
+            window: DEFAULT_SLO_WINDOW,
+            samples: VecDeque::new(),
+            nps: VecDeque::new(),
+            prior_policy: PriorPolicy::new(),
+        }
+    }
+
+    /// set number of recent samples the model is fitted to and return self
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window.max(2);
+
+        self
+    }
+
+    /// set policy for positions with priors and return self
+    pub fn prior_policy(mut self, prior_policy: PriorPolicy) -> Self {
+        self.prior_policy = prior_policy;
+
+        self
+    }
+
+    /// set latency target
+    pub fn set_target(&mut self, target: Duration) {
+        self.target = target;
+    }
+
+    /// observe analysis info of a search run with a budget of this controller
+    pub fn observe(&mut self, ai: &AnalysisInfo) {
+        if ai.multipv > 1 || ai.depth == 0 || ai.time == 0 {
+            return;
+        }
+
+        self.samples.push_back((ai.depth, ai.time));
+
+        if self.samples.len() > self.window {
+            self.samples.pop_front();
+        }
+
+        if ai.nps > 0 {
+            self.nps.push_back(ai.nps);
+
+            if self.nps.len() > self.window {
+                self.nps.pop_front();
+            }
+        }
+    }
+
+    /// least squares fit of ln time = a + b * depth, None without enough samples
+    fn fit(&self) -> Option<(f64, f64)> {
+        let n = self.samples.len() as f64;
+
+        if self.samples.len() < 2 {
+            return None;
+        }
+
+        let (sx, sy, sxx, sxy) =
+            self.samples
+                .iter()
+                .fold((0.0, 0.0, 0.0, 0.0), |(sx, sy, sxx, sxy), (depth, time)| {
+                    let (x, y) = (*depth as f64, (*time as f64).ln());
+
+                    (sx + x, sy + y, sxx + x * x, sxy + x * y)
+                });
+
+        let denominator = n * sxx - sx * sx;
+
+        if denominator.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let b = (n * sxy - sx * sy) / denominator;
+        let a = (sy - b * sx) / n;
+
+        // time has to grow with depth for the model to be usable
+        match b > 0.0 {
+            true => Some((a, b)),
+            _ => None,
+        }
+    }
+
+    /// estimated time in milliseconds to reach depth, None while unknown
+    pub fn time_to_depth(&self, depth: usize) -> Option<f64> {
+        self.fit().map(|(a, b)| (a + b * depth as f64).exp())
+    }
+
+    /// search budget for a position of given difficulty between 0 and 1
+    /// ( see difficulty::difficulty, 0 if unknown ), hard positions take up to
+    /// twice as long per depth
+    pub fn budget(&self, difficulty: f64) -> SearchBudget {
+        let target_ms = self.target.as_millis() as f64;
+        let factor = 1.0 + difficulty.clamp(0.0, 1.0);
+
+        let depth = match self.fit() {
+            Some((a, b)) => {
+                let depth = ((target_ms / factor).ln() - a) / b;
+
+                (depth.floor().max(0.0) as usize).clamp(MIN_DEPTH, MAX_DEPTH)
+            }
+            _ => FALLBACK_DEPTH,
+        };
+
+        let nodes = match self.nps.len() {
+            0 => None,
+            len => {
+                let nps = self.nps.iter().sum::<u64>() as f64 / len as f64;
+
+                Some((nps * target_ms / 1000.0 / factor) as u64)
+            }
+        };
+
+        let budget = SearchBudget {
+            depth,
+            nodes,
+            movetime: target_ms as usize,
+        };
+
+        if log_enabled!(Level::Debug) {
+            debug!("search budget for difficulty {} : {:?}", difficulty, budget);
+        }
+
+        budget
+    }
+
+    /// search budget for a position of given difficulty with prior, positions
+    /// with a trusted prior get a verify search of at most the verify depth
+    pub fn budget_with_prior(&self, difficulty: f64, prior: Option<&EvalPrior>) -> SearchBudget {
+        let budget = self.budget(difficulty);
+
+        match prior {
+            Some(prior) if self.prior_policy.is_trusted(prior) => {
+                let depth = budget.depth.min(self.prior_policy.verify_depth);
+
+                // nodes scale with the estimated time to the lower depth
+                let nodes = match (self.time_to_depth(depth), self.time_to_depth(budget.depth)) {
+                    (Some(verify_ms), Some(full_ms)) if full_ms > 0.0 => budget
+                        .nodes
+                        .map(|nodes| ((nodes as f64 * verify_ms / full_ms) as u64).max(1)),
+                    _ => budget.nodes,
+                };
+
+                let budget = SearchBudget {
+                    depth,
+                    nodes,
+                    movetime: budget.movetime,
+                };
+
+                if log_enabled!(Level::Debug) {
+                    debug!("verify budget for trusted prior {:?} : {:?}", prior, budget);
+                }
+
+                budget
+            }
+            _ => budget,
+        }
+    }
+
+    /// search budget for a search again of a result that disagrees with prior,
+    /// None if it agrees, the depth goes beyond the deeper of the result and the prior
+    /// and the latency target is doubled
+    pub fn deeper_budget(&self, prior: &EvalPrior, ai: &AnalysisInfo) -> Option<SearchBudget> {
+        if !self.prior_policy.disagrees(prior, ai) {
+            return None;
+        }
+
+        let depth = self.prior_policy.deeper_depth(prior, ai).min(MAX_DEPTH);
+
+        let budget = SearchBudget {
+            depth,
+            nodes: None,
+            movetime: self.target.as_millis() as usize * 2,
+        };
+
+        if log_enabled!(Level::Debug) {
+            debug!(
+                "deeper budget for result {:?} {:?} disagreeing with prior {:?} : {:?}",
+                ai.bestmove(),
+                ai.score,
+                prior,
+                budget
+            );
+        }
+
+        Some(budget)
+    }
+
+    /// apply search budget for a position of given difficulty to go job and return it
+    pub fn apply(&self, go_job: GoJob, difficulty: f64) -> GoJob {
+        Self::apply_budget(go_job, self.budget(difficulty))
+    }
+
+    /// apply search budget for a position of given difficulty with prior to go job
+    /// and return it
+    pub fn apply_with_prior(
+        &self,
+        go_job: GoJob,
+        difficulty: f64,
+        prior: Option<&EvalPrior>,
+    ) -> GoJob {
+        Self::apply_budget(go_job, self.budget_with_prior(difficulty, prior))
+    }
+
+    /// apply search budget to go job and return it
+    pub fn apply_budget(go_job: GoJob, budget: SearchBudget) -> GoJob {
+        let go_job = go_job
+            .go_opt("depth", budget.depth)
+            .go_opt("movetime", budget.movetime);
+
+        match budget.nodes {
+            Some(nodes) => go_job.go_opt("nodes", nodes),
+            _ => go_job,
+        }
+    }
+}
+
+#[test]
+fn budget_controller() {
+    let mut controller = BudgetController::new(Duration::from_millis(1000));
+
+    assert_eq!(controller.budget(0.0).depth, FALLBACK_DEPTH);
+
+    // time doubles per depth, depth 10 takes 1000 ms
+    for depth in 1..=10 {
+        let mut ai = AnalysisInfo::new();
+
+        ai.parse(format!(
+            "info depth {} time {} nps 1000000 score cp 20 pv e2e4 e7e5",
+            depth,
+            1000 >> (10 - depth)
+        ))
+        .unwrap();
+
+        controller.observe(&ai);
+    }
+
+    let easy = controller.budget(0.0);
+    let hard = controller.budget(1.0);
+
+    assert!((9..=10).contains(&easy.depth));
+    assert_eq!(hard.depth, easy.depth - 1);
+    assert_eq!(easy.nodes, Some(1_000_000));
+
+    let controller = controller.prior_policy(PriorPolicy::new().trusted(20, 5));
+    let prior = EvalPrior::new(Score::Cp(20), 30).bestmove("e2e4");
+
+    let verify = controller.budget_with_prior(0.0, Some(&prior));
+
+    assert_eq!(verify.depth, 5);
+    assert!(verify.nodes.unwrap() < easy.nodes.unwrap());
+    assert_eq!(controller.budget_with_prior(0.0, None), easy);
+
+    let mut ai = AnalysisInfo::new();
+
+    ai.parse("info depth 5 score cp -150 pv d2d4 d7d5").unwrap();
+
+    assert_eq!(controller.deeper_budget(&prior, &ai).unwrap().depth, 34);
+}
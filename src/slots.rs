@@ -0,0 +1,208 @@
+use log::{debug, log_enabled, warn, Level};
+
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+
+use crate::multiplexer::*;
+use crate::pool::*;
+use crate::uciengine::*;
+
+/// search backend, anything that runs go jobs and knows how many searches
+/// it can run concurrently without them queueing behind each other
+pub trait SearchBackend: Send + Sync {
+    /// number of independent searches the backend runs concurrently
+    fn search_slots(&self) -> usize;
+
+    /// submit go job, the result is sent on the returned receiver,
+    /// the receiver fails if the backend refused the job
+    fn submit(&self, go_job: GoJob) -> oneshot::Receiver<GoResult>;
+}
+
+/// a uci engine process runs one search at a time
+impl SearchBackend for UciEngine {
+    fn search_slots(&self) -> usize {
+        1
+    }
+
+    fn submit(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        self.queue(go_job)
+    }
+}
+
+/// a pool runs one search per engine
+impl SearchBackend for EnginePool {
+    fn search_slots(&self) -> usize {
+        self.len()
+    }
+
+    fn submit(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        match self.go(go_job) {
+            Ok(pending) => pending.into_receiver(),
+            Err(err) => {
+                warn!("pool refused slot job : {}", err);
+
+                // dropping the sender fails the receiver
+                oneshot::channel().1
+            }
+        }
+    }
+}
+
+/// the clients of a multiplexer share one search
+impl SearchBackend for MuxClient {
+    fn search_slots(&self) -> usize {
+        1
+    }
+
+    fn submit(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        self.go(go_job)
+    }
+}
+
+/// slot bookkeeping shared with the sessions
+struct SlotsShared {
+    semaphore: std::sync::Arc<Semaphore>,
+    /// indices of the free slots
+    free: std::sync::Mutex<Vec<usize>>,
+}
+
+/// concurrent search slots of a backend, the slot count is detected from the
+/// backend, each session holds one slot and its searches run independently
+/// of the searches of the other sessions
+///
+/// let slots = SearchSlots::new(std::sync::Arc::new(EnginePool::spawn(path, 4)));
+/// let session = slots.session().await;
+/// let go_result = session.go(go_job).await;
+pub struct SearchSlots {
+    backend: std::sync::Arc<dyn SearchBackend>,
+    slots: usize,
+    shared: std::sync::Arc<SlotsShared>,
+}
+
+/// session holding a search slot, the slot is released when the session is dropped
+pub struct SlotSession {
+    slot: usize,
+    backend: std::sync::Arc<dyn SearchBackend>,
+    shared: std::sync::Arc<SlotsShared>,
+    _permit: OwnedSemaphorePermit,
+}
+
+/// search slots implementation
+impl SearchSlots {
+    /// create new search slots for backend, at least one
+    pub fn new(backend: std::sync::Arc<dyn SearchBackend>) -> Self {
+        let slots = backend.search_slots().max(1);
+
+        if log_enabled!(Level::Debug) {
+            debug!("backend serves {} concurrent searches", slots);
+        }
+
+        Self {
+            backend,
+            slots,
+            shared: std::sync::Arc::new(SlotsShared {
+                semaphore: std::sync::Arc::new(Semaphore::new(slots)),
+                free: std::sync::Mutex::new((0..slots).rev().collect()),
+            }),
+        }
+    }
+
+    /// number of slots
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// number of free slots
+    pub fn available(&self) -> usize {
+        self.shared.semaphore.available_permits()
+    }
+
+    /// session on the permit
+    fn session_with(&self, permit: OwnedSemaphorePermit) -> SlotSession {
+        let slot = {
+            let mut free = self.shared.free.lock().unwrap();
+
+            free.pop().expect("permit without free slot")
+        };
+
+        SlotSession {
+            slot,
+            backend: self.backend.clone(),
+            shared: self.shared.clone(),
+            _permit: permit,
+        }
+    }
+
+    /// wait for a free slot and return a session holding it
+    pub async fn session(&self) -> SlotSession {
+        let permit = self
+            .shared
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("slot semaphore closed");
+
+        self.session_with(permit)
+    }
+
+    /// session holding a free slot, None if all slots are taken
+    pub fn try_session(&self) -> Option<SlotSession> {
+        self.shared
+            .semaphore
+            .clone()
+            .try_acquire_owned()
+            .ok()
+            .map(|permit| self.session_with(permit))
+    }
+}
+
+/// slot session implementation
+impl SlotSession {
+    /// index of the slot held
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// submit go job in the slot, the result is sent on the returned receiver
+    pub fn go(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        self.backend.submit(go_job.label("slot", self.slot))
+    }
+}
+
+/// slot session drop implementation, frees the slot
+impl Drop for SlotSession {
+    fn drop(&mut self) {
+        let mut free = self.shared.free.lock().unwrap();
+
+        free.push(self.slot);
+    }
+}
+
+#[tokio::test]
+async fn search_slots() {
+    struct Refusing;
+
+    impl SearchBackend for Refusing {
+        fn search_slots(&self) -> usize {
+            2
+        }
+
+        fn submit(&self, _go_job: GoJob) -> oneshot::Receiver<GoResult> {
+            oneshot::channel().1
+        }
+    }
+
+    let slots = SearchSlots::new(std::sync::Arc::new(Refusing));
+
+    let first = slots.session().await;
+    let second = slots.try_session().unwrap();
+
+    assert_eq!((first.slot(), second.slot()), (0, 1));
+    assert!(slots.try_session().is_none());
+    assert!(second.go(GoJob::new()).await.is_err());
+
+    drop(first);
+
+    assert_eq!(slots.available(), 1);
+    assert_eq!(slots.session().await.slot(), 0);
+}
@@ -0,0 +1,263 @@
+use log::{debug, log_enabled, warn, Level};
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::uciengine::*;
+
+/// timings of one position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionTimings {
+    /// fen of the position
+    pub fen: String,
+    /// time in milliseconds to reach each requested depth, None if not reached
+    pub time_to_depth: Vec<Option<usize>>,
+    /// time in milliseconds from which the final bestmove stayed on top
+    pub time_to_stable_move: Option<usize>,
+    /// depth reached
+    pub depth: usize,
+}
+
+/// speed report of one engine config on a position set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeedReport {
+    /// label of the run ( engine version, hardware, instance type )
+    pub label: String,
+    /// time per position in milliseconds
+    pub movetime: usize,
+    /// depths timed
+    pub depths: Vec<usize>,
+    /// timings by position
+    pub timings: Vec<PositionTimings>,
+}
+
+/// distribution of times in milliseconds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TimeDistribution {
+    /// number of positions the time was measured for
+    pub count: usize,
+    /// median time
+    pub median: Option<usize>,
+    /// 90th percentile time
+    pub p90: Option<usize>,
+}
+
+/// time distribution implementation
+impl TimeDistribution {
+    /// create new distribution of times
+    pub fn new(mut times: Vec<usize>) -> Self {
+        times.sort_unstable();
+
+        let percentile = |percentile: f64| match times.len() {
+            0 => None,
+            len => Some(times[((len - 1) as f64 * percentile).round() as usize]),
+        };
+
+        Self {
+            count: times.len(),
+            median: percentile(0.5),
+            p90: percentile(0.9),
+        }
+    }
+}
+
+/// speed summary, comparable across engine configs and hardware
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeedSummary {
+    /// label of the run
+    pub label: String,
+    /// number of positions
+    pub positions: usize,
+    /// time to depth distributions, one per depth
+    pub time_to_depth: Vec<(usize, TimeDistribution)>,
+    /// time to stable bestmove distribution
+    pub time_to_stable_move: TimeDistribution,
+}
+
+/// speed report implementation
+impl SpeedReport {
+    /// speed summary
+    pub fn summary(&self) -> SpeedSummary {
+        let time_to_depth = self
+            .depths
+            .iter()
+            .enumerate()
+            .map(|(i, depth)| {
+                let times = self
+                    .timings
+                    .iter()
+                    .filter_map(|timings| timings.time_to_depth.get(i).copied().flatten())
+                    .collect();
+
+                (*depth, TimeDistribution::new(times))
+            })
+            .collect();
+
+        SpeedSummary {
+            label: self.label.to_owned(),
+            positions: self.timings.len(),
+            time_to_depth,
+            time_to_stable_move: TimeDistribution::new(
+                self.timings
+                    .iter()
+                    .filter_map(|timings| timings.time_to_stable_move)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// format summaries as a plain text comparison table, one row per run,
+/// median / p90 milliseconds per depth and to stable bestmove
+pub fn speed_table(summaries: &[SpeedSummary]) -> String {
+    let ms = |distribution: &TimeDistribution| match (distribution.median, distribution.p90) {
+        (Some(median), Some(p90)) => format!("{}/{}", median, p90),
+        _ => "-".to_string(),
+    };
+
+    let depths: Vec<usize> = summaries
+        .first()
+        .map(|summary| {
+            summary
+                .time_to_depth
+                .iter()
+                .map(|(depth, _)| *depth)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut table = format!("{:<24} {:>9}", "label", "positions");
+
+    for depth in depths.iter() {
+        table += &format!(" {:>13}", format!("depth {}", depth));
+    }
+
+    table += &format!(" {:>13}\n", "stable move");
+
+    for summary in summaries {
+        table += &format!("{:<24} {:>9}", summary.label, summary.positions);
+
+        for (_, distribution) in summary.time_to_depth.iter() {
+            table += &format!(" {:>13}", ms(distribution));
+        }
+
+        table += &format!(" {:>13}\n", ms(&summary.time_to_stable_move));
+    }
+
+    table
+}
+
+/// run position set on engine with given time per position in milliseconds,
+/// timing when each of the depths is reached and when the bestmove settled,
+/// the engine should be configured ( options, hash, threads ) beforehand
+pub async fn run_speed<T>(
+    engine: &UciEngine,
+    fens: &[String],
+    movetime: usize,
+    depths: &[usize],
+    label: T,
+) -> SpeedReport
+where
+    T: core::fmt::Display,
+{
+    let mut report = SpeedReport {
+        label: label.to_string(),
+        movetime,
+        depths: depths.to_vec(),
+        timings: vec![],
+    };
+
+    for fen in fens {
+        let mut arx = engine.atx.subscribe();
+
+        let mut rrx = engine.try_go(GoJob::new().pos_fen(fen).go_opt("movetime", movetime));
+
+        let mut time_to_depth: Vec<Option<usize>> = vec![None; depths.len()];
+        // top move and the time it got on top
+        let mut top: Option<(String, usize)> = None;
+
+        let go_result = loop {
+            tokio::select! {
+                go_result = &mut rrx => break go_result.ok(),
+                received = arx.recv() => match received {
+                    Ok(ai) if ai.multipv <= 1 => {
+                        for (i, depth) in depths.iter().enumerate() {
+                            if ai.depth >= *depth && time_to_depth[i].is_none() {
+                                time_to_depth[i] = Some(ai.time);
+                            }
+                        }
+
+                        if let Some(bestmove) = ai.bestmove() {
+                            match &top {
+                                Some((top_move, _)) if *top_move == bestmove => {}
+                                _ => top = Some((bestmove, ai.time)),
+                            }
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break rrx.await.ok(),
+                },
+            }
+        };
+
+        let go_result = match go_result {
+            Some(go_result) => go_result,
+            _ => {
+                warn!("no result for speed position {}", fen);
+
+                continue;
+            }
+        };
+
+        let time_to_stable_move = match (&top, &go_result.bestmove) {
+            (Some((top_move, since)), Some(bestmove)) if top_move == bestmove => Some(*since),
+            _ => None,
+        };
+
+        let timings = PositionTimings {
+            fen: fen.to_owned(),
+            time_to_depth,
+            time_to_stable_move,
+            depth: go_result.ai.depth,
+        };
+
+        if log_enabled!(Level::Debug) {
+            debug!("speed position timings {:?}", timings);
+        }
+
+        report.timings.push(timings);
+    }
+
+    report
+}
+
+#[test]
+fn speed_summary() {
+    let report = SpeedReport {
+        label: "c6i.large".to_string(),
+        movetime: 1000,
+        depths: vec![10, 20],
+        timings: vec![
+            PositionTimings {
+                fen: "a".to_string(),
+                time_to_depth: vec![Some(10), Some(400)],
+                time_to_stable_move: Some(200),
+                depth: 22,
+            },
+            PositionTimings {
+                fen: "b".to_string(),
+                time_to_depth: vec![Some(30), None],
+                time_to_stable_move: None,
+                depth: 18,
+            },
+        ],
+    };
+
+    let summary = report.summary();
+
+    assert_eq!(summary.time_to_depth[0].1.count, 2);
+    assert_eq!(summary.time_to_depth[1].1.median, Some(400));
+    assert_eq!(summary.time_to_stable_move.p90, Some(200));
+    assert!(speed_table(&[summary]).contains("400/400"));
+}
@@ -0,0 +1,148 @@
+use log::warn;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "chess")]
+use shakmaty::{CastlingMode, Position};
+
+use crate::analysis::*;
+use crate::pool::*;
+use crate::uciengine::*;
+#[cfg(feature = "chess")]
+use crate::verify::*;
+
+/// root split line, the result of searching a single root move
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootSplitLine {
+    /// rank of the move among all root moves, starting from 1
+    pub multipv: usize,
+    /// root move
+    pub uci: String,
+    /// score of the root move ( from the point of view of the side to move )
+    pub score: Score,
+    /// depth reached
+    pub depth: usize,
+    /// pv starting with the root move
+    pub pv: Option<String>,
+}
+
+/// legal moves of the position of go job in uci, None if the job has no position
+/// or it can not be replayed
+#[cfg(feature = "chess")]
+pub fn root_moves(go_job: &GoJob) -> Option<Vec<String>> {
+    let pos = go_job
+        .to_commands()
+        .iter()
+        .find(|command| command.starts_with("position "))
+        .and_then(|command| position_from_command(command))?;
+
+    Some(
+        pos.legal_moves()
+            .iter()
+            .map(|m| m.to_uci(CastlingMode::Standard).to_string())
+            .collect(),
+    )
+}
+
+/// root split implementation of the engine pool
+impl EnginePool {
+    /// distribute the root moves of a position across the engines of the pool,
+    /// each search restricted to a single root move via searchmoves,
+    /// then merge the per move scores into a multipv like result, best move first
+    ///
+    /// make_job should return a go job with position and search limits set,
+    /// it is called once per root move, searches that got no result are left out
+    pub async fn root_split<F, T>(
+        &self,
+        root_moves: &[T],
+        make_job: F,
+    ) -> Result<Vec<RootSplitLine>, PoolError>
+    where
+        F: Fn() -> GoJob,
+        T: core::fmt::Display,
+    {
+        // jobs are queued right away, each engine works through its share
+        let mut pending: Vec<(String, PendingGo)> = vec![];
+
+        for uci in root_moves {
+            let uci = uci.to_string();
+
+            let go_job = make_job().searchmoves(vec![uci.to_owned()]);
+
+            pending.push((uci, self.go(go_job)?));
+        }
+
+        let mut lines: Vec<RootSplitLine> = vec![];
+
+        for (uci, pending) in pending {
+            match pending.await {
+                Ok(go_result) => lines.push(RootSplitLine {
+                    multipv: 0,
+                    uci,
+                    score: go_result.ai.score,
+                    depth: go_result.ai.depth,
+                    pv: go_result.ai.pv(),
+                }),
+                Err(err) => warn!("root split search for {} failed {:?}", uci, err),
+            }
+        }
+
+        lines.sort_by_key(|line| -line.score.sort_key());
+
+        for (i, line) in lines.iter_mut().enumerate() {
+            line.multipv = i + 1;
+        }
+
+        Ok(lines)
+    }
+
+    /// root split of all legal moves of the position of the jobs, see root_split,
+    /// an empty result if the position can not be replayed or has no legal moves
+    #[cfg(feature = "chess")]
+    pub async fn distributed_root_split<F>(
+        &self,
+        make_job: F,
+    ) -> Result<Vec<RootSplitLine>, PoolError>
+    where
+        F: Fn() -> GoJob,
+    {
+        let root_moves = match root_moves(&make_job()) {
+            Some(root_moves) => root_moves,
+            _ => {
+                warn!("root split of a job without a position that can be replayed");
+
+                return Ok(vec![]);
+            }
+        };
+
+        self.root_split(&root_moves, make_job).await
+    }
+}
+
+#[cfg(all(feature = "chess", unix))]
+#[tokio::test]
+async fn root_split() {
+    // every root move scores 0 except e2e4
+    let path = fake_engine(
+        "root-split",
+        "    *searchmoves\\ e2e4) echo 'info depth 5 score cp 40 pv e2e4 e7e5'; echo 'bestmove e2e4';;
+    go*) echo \"info depth 5 score cp 0 pv ${line##* }\"; echo \"bestmove ${line##* }\";;",
+    );
+
+    let pool = EnginePool::new(vec![
+        EngineBuilder::new(&path).build(),
+        EngineBuilder::new(&path).build(),
+    ]);
+
+    let make_job = || GoJob::new().pos_startpos().go_opt("depth", 5);
+
+    assert_eq!(root_moves(&make_job()).map(|moves| moves.len()), Some(20));
+
+    let lines = pool.distributed_root_split(make_job).await.unwrap();
+
+    assert_eq!(lines.len(), 20);
+    assert_eq!(lines[0].uci, "e2e4");
+    assert_eq!(lines[0].pv.as_deref(), Some("e2e4 e7e5"));
+    assert_eq!(lines[19].multipv, 20);
+    assert!(root_moves(&GoJob::new().go_opt("depth", 5)).is_none());
+}
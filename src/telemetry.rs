@@ -0,0 +1,166 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::*;
+use crate::completeness::*;
+use crate::uciengine::Labels;
+
+/// per search telemetry, measures the wrapper's own overhead
+/// so that engine slowness can be told apart from wrapper slowness
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SearchTelemetry {
+    /// time from go issued to first info line received
+    pub first_info: Option<Duration>,
+    /// time from go issued to bestmove line received
+    pub bestmove: Option<Duration>,
+    /// time the bestmove line spent in the internal channel
+    pub channel_latency: Duration,
+    /// total time spent parsing engine output
+    pub parse_time: Duration,
+    /// number of lines parsed during the search
+    pub parsed_lines: usize,
+    /// time from go issued to result sent to the caller
+    pub wall: Duration,
+    /// time to spawn the process and complete the handshake of a lazy engine,
+    /// only reported with the first result
+    pub startup: Option<Duration>,
+}
+
+/// search telemetry implementation
+impl SearchTelemetry {
+    /// create new empty telemetry
+    pub fn new() -> Self {
+        Self {
+            first_info: None,
+            bestmove: None,
+            channel_latency: Duration::from_secs(0),
+            parse_time: Duration::from_secs(0),
+            parsed_lines: 0,
+            wall: Duration::from_secs(0),
+            startup: None,
+        }
+    }
+
+    /// wrapper overhead ( parse time plus channel latency )
+    pub fn overhead(&self) -> Duration {
+        self.parse_time + self.channel_latency
+    }
+}
+
+/// implement Default for SearchTelemetry
+impl Default for SearchTelemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// search telemetry with labels, for slicing exported metrics by engine and job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabeledTelemetry {
+    /// labels of the result
+    pub labels: Labels,
+    /// search telemetry
+    #[serde(flatten)]
+    pub telemetry: SearchTelemetry,
+}
+
+/// search clock, shared between the reader and the job processor
+#[derive(Debug)]
+pub struct SearchClock {
+    /// when go was issued, None if no search is being timed
+    go_issued: Option<Instant>,
+    /// telemetry collected so far
+    telemetry: SearchTelemetry,
+    /// iterations of the search so far
+    completeness: CompletenessTracker,
+}
+
+/// search clock implementation
+impl SearchClock {
+    /// create new idle search clock
+    pub fn new() -> Self {
+        Self {
+            go_issued: None,
+            telemetry: SearchTelemetry::new(),
+            completeness: CompletenessTracker::new(),
+        }
+    }
+
+    /// start timing a new search
+    pub fn start(&mut self) {
+        self.go_issued = Some(Instant::now());
+        self.telemetry = SearchTelemetry::new();
+        self.completeness = CompletenessTracker::new();
+    }
+
+    /// time the search from now on, called once the go command is written,
+    /// so the time spent applying the options of the job is not counted
+    pub fn go_written(&mut self) {
+        if self.go_issued.is_some() {
+            self.go_issued = Some(Instant::now());
+        }
+    }
+
+    /// true if a search is being timed
+    pub fn is_running(&self) -> bool {
+        self.go_issued.is_some()
+    }
+
+    /// elapsed time since go issued
+    fn elapsed(&self) -> Option<Duration> {
+        self.go_issued.map(|go_issued| go_issued.elapsed())
+    }
+
+    /// record parsing of a line that took the given time
+    pub fn record_parse(&mut self, parse_time: Duration, is_info: bool) {
+        self.telemetry.parse_time += parse_time;
+        self.telemetry.parsed_lines += 1;
+
+        if is_info && self.telemetry.first_info.is_none() {
+            self.telemetry.first_info = self.elapsed();
+        }
+    }
+
+    /// observe parsed analysis info
+    pub fn observe(&mut self, ai: &AnalysisInfo) {
+        self.completeness.observe(ai);
+    }
+
+    /// completeness of the search so far, see CompletenessTracker::score
+    pub fn completeness(&self, budget: Option<usize>) -> f64 {
+        self.completeness.score(budget)
+    }
+
+    /// true if the score flipped sign vs the previous depth,
+    /// see CompletenessTracker::score_sign_flipped
+    pub fn score_sign_flipped(&self) -> bool {
+        self.completeness.score_sign_flipped()
+    }
+
+    /// record bestmove received
+    pub fn record_bestmove(&mut self) {
+        self.telemetry.bestmove = self.elapsed();
+    }
+
+    /// finish timing and return telemetry,
+    /// received_at is the time the bestmove line was put into the channel
+    pub fn finish(&mut self, received_at: Instant) -> SearchTelemetry {
+        self.telemetry.channel_latency = received_at.elapsed();
+
+        if let Some(wall) = self.elapsed() {
+            self.telemetry.wall = wall;
+        }
+
+        self.go_issued = None;
+
+        self.telemetry
+    }
+}
+
+/// implement Default for SearchClock
+impl Default for SearchClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
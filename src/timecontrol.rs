@@ -0,0 +1,587 @@
+use serde::{Deserialize, Serialize};
+
+use std::time::Instant;
+
+use thiserror::Error;
+
+use crate::analysis::Score;
+use crate::uciengine::{ClockSides, Timecontrol};
+
+/// TimecontrolParseError captures possible match time control parsing errors
+#[derive(Error, Debug)]
+pub enum TimecontrolParseError {
+    #[error("empty time control")]
+    Empty,
+    #[error("invalid time control period '{0}'")]
+    InvalidPeriod(String),
+}
+
+/// time control period ( times are in milliseconds )
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Period {
+    /// number of moves to be played in the period, None if it lasts until the end of the game
+    pub moves: Option<usize>,
+    /// time added to the clock at the start of the period
+    pub time: u64,
+    /// increment per move
+    pub inc: u64,
+}
+
+/// match time control, a sequence of periods,
+/// the last period repeats if it has a number of moves
+/// ( "40/90,30" is 40 moves in 90 minutes then 30 minutes for the rest of the game,
+/// "40/120" is 40 moves in 120 minutes repeating, "40/5:00" is 40 moves in 5 minutes
+/// repeating, ':' separates the parts of a clock time as in Timecontrol )
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchTimecontrol {
+    /// periods
+    pub periods: Vec<Period>,
+}
+
+/// match time control implementation
+impl MatchTimecontrol {
+    /// create new match time control without periods
+    pub fn new() -> Self {
+        Self { periods: vec![] }
+    }
+
+    /// create sudden death time control with time and increment in milliseconds
+    pub fn sudden_death(time: u64, inc: u64) -> Self {
+        Self::new().period(None, time, inc)
+    }
+
+    /// add period with number of moves, time and increment in milliseconds and return self
+    pub fn period(mut self, moves: Option<usize>, time: u64, inc: u64) -> Self {
+        self.periods.push(Period { moves, time, inc });
+
+        self
+    }
+
+    /// period with given index, the last period repeats
+    pub fn nth_period(&self, index: usize) -> Option<&Period> {
+        self.periods
+            .get(index)
+            .or_else(|| self.periods.last().filter(|period| period.moves.is_some()))
+    }
+}
+
+/// implement Default for MatchTimecontrol
+impl Default for MatchTimecontrol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// time control of white ( of black if only its clock is set ), sudden death
+/// unless it has moves to go, the period then repeats
+impl From<Timecontrol> for MatchTimecontrol {
+    fn from(tc: Timecontrol) -> Self {
+        let (time, inc) = match tc.sides {
+            ClockSides::Black => (tc.btime, tc.binc),
+            _ => (tc.wtime, tc.winc),
+        };
+
+        Self::new().period(tc.movestogo, time as u64, inc as u64)
+    }
+}
+
+/// display match time control as periods separated by ',',
+/// each period is [moves/]minutes[+increment seconds]
+impl std::fmt::Display for MatchTimecontrol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, period) in self.periods.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+
+            if let Some(moves) = period.moves {
+                write!(f, "{}/", moves)?;
+            }
+
+            write!(f, "{}", period.time as f64 / 60000.0)?;
+
+            if period.inc > 0 || period.moves.is_none() {
+                write!(f, "+{}", period.inc as f64 / 1000.0)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// parse match time control from its display form, the time of a period is in minutes
+/// or a clock ( "40/90,30+30", "40/5:00" )
+impl std::str::FromStr for MatchTimecontrol {
+    type Err = TimecontrolParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(TimecontrolParseError::Empty);
+        }
+
+        let mut tc = Self::new();
+
+        for period in s.split(',').map(|period| period.trim()) {
+            let invalid = || TimecontrolParseError::InvalidPeriod(period.to_string());
+
+            let (moves, rest) = match period.find('/') {
+                Some(index) => (
+                    Some(period[..index].parse::<usize>().map_err(|_| invalid())?),
+                    &period[index + 1..],
+                ),
+                _ => (None, period),
+            };
+
+            let (time, seconds) = match rest.find('+') {
+                Some(index) => (&rest[..index], &rest[index + 1..]),
+                _ => (rest, "0"),
+            };
+
+            let time = parse_clock_time(time.trim()).ok_or_else(invalid)?;
+            let seconds = seconds.trim().parse::<f64>().map_err(|_| invalid())?;
+
+            if moves == Some(0) || seconds < 0.0 {
+                return Err(invalid());
+            }
+
+            tc = tc.period(moves, time, (seconds * 1000.0).round() as u64);
+        }
+
+        Ok(tc)
+    }
+}
+
+/// time in milliseconds of minutes ( "3", "0.5" ) or of a clock ( "5:00", "1:30:00" )
+fn parse_clock_time(time: &str) -> Option<u64> {
+    if !time.contains(':') {
+        return time
+            .parse::<f64>()
+            .ok()
+            .filter(|minutes| *minutes >= 0.0)
+            .map(|minutes| (minutes * 60000.0).round() as u64);
+    }
+
+    let parts: Vec<&str> = time.split(':').collect();
+
+    if parts.len() > 3 {
+        return None;
+    }
+
+    // the last part are seconds, each part before counts 60 times the next one
+    parts.iter().try_fold(0u64, |millis, part| {
+        let value = part.parse::<u64>().ok()?;
+
+        Some(millis * 60 + value * 1000)
+    })
+}
+
+/// parse time control of both sides from [moves/]time[+increment seconds], time is
+/// in minutes or a clock, the moves of a classical control are the moves to go
+/// ( "3+2", "0.5+0", "40/90", "40/5:00" )
+impl std::str::FromStr for Timecontrol {
+    type Err = TimecontrolParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.is_empty() {
+            return Err(TimecontrolParseError::Empty);
+        }
+
+        let invalid = || TimecontrolParseError::InvalidPeriod(s.to_string());
+
+        let (moves, rest) = match s.find('/') {
+            Some(index) => (
+                Some(s[..index].trim().parse::<usize>().map_err(|_| invalid())?),
+                &s[index + 1..],
+            ),
+            _ => (None, s),
+        };
+
+        let (time, inc) = match rest.find('+') {
+            Some(index) => (&rest[..index], &rest[index + 1..]),
+            _ => (rest, "0"),
+        };
+
+        let time = parse_clock_time(time.trim()).ok_or_else(invalid)?;
+        let inc = inc
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|seconds| *seconds >= 0.0)
+            .ok_or_else(invalid)?;
+
+        if moves == Some(0) || time == 0 {
+            return Err(invalid());
+        }
+
+        let inc = (inc * 1000.0).round() as usize;
+
+        let tc = Self {
+            wtime: time as usize,
+            winc: inc,
+            btime: time as usize,
+            binc: inc,
+            movestogo: moves,
+            sides: ClockSides::Both,
+        };
+
+        Ok(tc)
+    }
+}
+
+/// clock of one side
+#[derive(Debug, Clone, Copy)]
+struct SideClock {
+    /// remaining time in milliseconds, negative if the flag fell
+    remaining: i64,
+    /// index of the current period
+    period: usize,
+    /// moves played in the current period
+    moves: usize,
+}
+
+/// ponder search in progress
+#[derive(Debug, Clone, Copy)]
+struct Ponder {
+    /// side pondering
+    side: usize,
+    /// when go ponder was issued
+    started: Instant,
+    /// when ponderhit was issued
+    hit: Option<Instant>,
+}
+
+/// ponder hit bookkeeping
+#[derive(Debug, Clone, Copy)]
+pub struct PonderHit {
+    /// milliseconds the engine searched for free before the ponder hit
+    pub ponder_elapsed: u64,
+    /// clock state corrected for the ponder hit, the opponent is charged for its move,
+    /// the pondering side is credited the time it pondered ( engines measure their time
+    /// from go ponder, so this is the time control to send if the search is restarted )
+    pub timecontrol: Timecontrol,
+}
+
+/// game clock of both sides ( index 0 is white, index 1 is black )
+#[derive(Debug, Clone)]
+pub struct GameClock {
+    tc: MatchTimecontrol,
+    sides: [SideClock; 2],
+    /// ponder search in progress
+    ponder: Option<Ponder>,
+}
+
+/// game clock implementation
+impl GameClock {
+    /// create new game clock with both sides at the start of the first period
+    pub fn new(tc: &MatchTimecontrol) -> Self {
+        let side = SideClock {
+            remaining: tc
+                .nth_period(0)
+                .map(|period| period.time as i64)
+                .unwrap_or(0),
+            period: 0,
+            moves: 0,
+        };
+
+        Self {
+            tc: tc.to_owned(),
+            sides: [side, side],
+            ponder: None,
+        }
+    }
+
+    /// record go ponder issued for side ( its opponent is to move ),
+    /// the side is not charged while pondering
+    pub fn start_ponder(&mut self, side: usize) {
+        self.ponder = Some(Ponder {
+            side,
+            started: Instant::now(),
+            hit: None,
+        });
+    }
+
+    /// record ponderhit, the opponent played the expected move after opponent_elapsed
+    /// milliseconds, returns None if no ponder search was started
+    pub fn ponderhit(&mut self, opponent_elapsed: u64) -> Option<PonderHit> {
+        let ponder = self.ponder.as_mut()?;
+
+        let now = Instant::now();
+
+        ponder.hit = Some(now);
+
+        let ponder_elapsed = now.duration_since(ponder.started).as_millis() as u64;
+
+        Some(self.apply_ponderhit(opponent_elapsed, ponder_elapsed))
+    }
+
+    /// charge opponent for its move and correct the clock for ponder_elapsed
+    fn apply_ponderhit(&mut self, opponent_elapsed: u64, ponder_elapsed: u64) -> PonderHit {
+        let side = self.ponder.map(|ponder| ponder.side).unwrap_or(0);
+
+        self.charge(1 - side, opponent_elapsed);
+
+        let mut timecontrol = self.timecontrol();
+
+        match side {
+            0 => timecontrol.wtime += ponder_elapsed as usize,
+            _ => timecontrol.btime += ponder_elapsed as usize,
+        }
+
+        PonderHit {
+            ponder_elapsed,
+            timecontrol,
+        }
+    }
+
+    /// record pondermiss, the opponent played another move after opponent_elapsed
+    /// milliseconds, the ponder search is discarded and the side searches anew
+    pub fn pondermiss(&mut self, opponent_elapsed: u64) {
+        if let Some(ponder) = self.ponder.take() {
+            self.charge(1 - ponder.side, opponent_elapsed);
+        }
+    }
+
+    /// charge the pondering side for its move after a ponderhit, only the time since
+    /// the ponderhit counts ( charging from go ponder is a common bug that loses on time ),
+    /// returns the milliseconds charged, None if there was no ponderhit
+    pub fn charge_ponder_move(&mut self) -> Option<u64> {
+        let ponder = self.ponder.take()?;
+
+        let elapsed = ponder.hit?.elapsed().as_millis() as u64;
+
+        self.charge(ponder.side, elapsed);
+
+        Some(elapsed)
+    }
+
+    /// remaining time of side in milliseconds
+    pub fn remaining(&self, side: usize) -> i64 {
+        self.sides[side].remaining
+    }
+
+    /// increment of side in the current period in milliseconds
+    pub fn inc(&self, side: usize) -> u64 {
+        self.tc
+            .nth_period(self.sides[side].period)
+            .map(|period| period.inc)
+            .unwrap_or(0)
+    }
+
+    /// moves side has to play until the next time control, None for sudden death
+    pub fn movestogo(&self, side: usize) -> Option<usize> {
+        let clock = self.sides[side];
+
+        self.tc
+            .nth_period(clock.period)
+            .and_then(|period| period.moves)
+            .map(|moves| moves - clock.moves)
+    }
+
+    /// clock state as uci time control
+    pub fn timecontrol(&self) -> Timecontrol {
+        Timecontrol::new(
+            self.remaining(0).max(0) as usize,
+            self.inc(0) as usize,
+            self.remaining(1).max(0) as usize,
+            self.inc(1) as usize,
+        )
+    }
+
+    /// charge side for a move that took elapsed milliseconds,
+    /// adds the increment and the time of the next period once the current one is completed
+    pub fn charge(&mut self, side: usize, elapsed: u64) {
+        let inc = self.inc(side) as i64;
+        let movestogo = self.movestogo(side);
+
+        let clock = &mut self.sides[side];
+
+        clock.remaining += inc - elapsed as i64;
+        clock.moves += 1;
+
+        if movestogo == Some(1) {
+            clock.period += 1;
+            clock.moves = 0;
+
+            if let Some(period) = self.tc.nth_period(clock.period) {
+                clock.remaining += period.time as i64;
+            }
+        }
+    }
+}
+
+/// estimate whether a forced mate can be delivered before the flag falls
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MateTimeEstimate {
+    /// moves the mating side has to play
+    pub moves: usize,
+    /// thinking time per move assumed in milliseconds
+    pub ms_per_move: u64,
+    /// lowest remaining time of the mating side during the mate in milliseconds,
+    /// negative if the flag falls first
+    pub min_remaining: i64,
+}
+
+/// mate time estimate implementation
+impl MateTimeEstimate {
+    /// estimate for a side to move with remaining time and increment in milliseconds
+    /// ( sudden death ), None if the score is not a mate for the side to move
+    pub fn from_clock(score: Score, remaining: u64, inc: u64, ms_per_move: u64) -> Option<Self> {
+        let clock = GameClock::new(&MatchTimecontrol::sudden_death(remaining, inc));
+
+        clock.mate_estimate(0, score, ms_per_move)
+    }
+
+    /// estimate for the side to move of a uci time control
+    pub fn from_timecontrol(
+        tc: &Timecontrol,
+        white: bool,
+        score: Score,
+        ms_per_move: u64,
+    ) -> Option<Self> {
+        match white {
+            true => Self::from_clock(score, tc.wtime as u64, tc.winc as u64, ms_per_move),
+            _ => Self::from_clock(score, tc.btime as u64, tc.binc as u64, ms_per_move),
+        }
+    }
+
+    /// true if the mate can be delivered with time to spare
+    pub fn feasible(&self) -> bool {
+        self.min_remaining > 0
+    }
+}
+
+/// game clock mate estimate
+impl GameClock {
+    /// estimate whether side can deliver the mate of score ( from the point of view
+    /// of side, side to move ) spending ms_per_move on each of its moves,
+    /// increments and later periods are taken into account,
+    /// None if the score is not a mate for side
+    pub fn mate_estimate(
+        &self,
+        side: usize,
+        score: Score,
+        ms_per_move: u64,
+    ) -> Option<MateTimeEstimate> {
+        let moves = match score {
+            Score::Mate(moves) if moves > 0 => moves as usize,
+            _ => return None,
+        };
+
+        let mut clock = self.clone();
+        let mut min_remaining = clock.remaining(side);
+
+        for _ in 0..moves {
+            min_remaining = min_remaining.min(clock.remaining(side) - ms_per_move as i64);
+
+            clock.charge(side, ms_per_move);
+        }
+
+        Some(MateTimeEstimate {
+            moves,
+            ms_per_move,
+            min_remaining,
+        })
+    }
+}
+
+#[test]
+fn repeating_periods() {
+    let tc = "40/90,30+30".parse::<MatchTimecontrol>().unwrap();
+
+    assert_eq!(tc.periods[0].moves, Some(40));
+    assert_eq!(tc.periods[1].inc, 30000);
+    assert_eq!(tc.to_string(), "40/90,30+30");
+
+    // ':' is a clock time, as for Timecontrol
+    let tc = "40/5:00".parse::<MatchTimecontrol>().unwrap();
+
+    assert_eq!(
+        tc.periods,
+        vec![Period {
+            moves: Some(40),
+            time: 300000,
+            inc: 0
+        }]
+    );
+    assert_eq!(tc.to_string(), "40/5");
+    assert!("40/90:30+30".parse::<MatchTimecontrol>().is_ok());
+    assert!("40/90:xx".parse::<MatchTimecontrol>().is_err());
+    assert_eq!(
+        "3+2".parse::<MatchTimecontrol>().unwrap().to_string(),
+        "3+2"
+    );
+
+    let mut clock = GameClock::new(&"2/1".parse::<MatchTimecontrol>().unwrap());
+
+    assert_eq!(clock.movestogo(0), Some(2));
+
+    clock.charge(0, 10000);
+
+    assert_eq!(clock.movestogo(0), Some(1));
+    assert_eq!(clock.movestogo(1), Some(2));
+
+    clock.charge(0, 10000);
+
+    assert_eq!(clock.movestogo(0), Some(2));
+    assert_eq!(clock.remaining(0), 100000);
+}
+
+#[test]
+fn parse_timecontrol() {
+    let tc = "3+2".parse::<Timecontrol>().unwrap();
+
+    assert_eq!(
+        (tc.wtime, tc.winc, tc.btime, tc.binc),
+        (180000, 2000, 180000, 2000)
+    );
+
+    let tc = "40/5:00".parse::<Timecontrol>().unwrap();
+
+    assert_eq!((tc.wtime, tc.winc, tc.movestogo), (300000, 0, Some(40)));
+    assert_eq!(MatchTimecontrol::from(tc).to_string(), "40/5");
+    assert_eq!("1:30:00+30".parse::<Timecontrol>().unwrap().btime, 5400000);
+    assert_eq!("0.5".parse::<Timecontrol>().unwrap().wtime, 30000);
+    assert!(matches!(
+        "".parse::<Timecontrol>(),
+        Err(TimecontrolParseError::Empty)
+    ));
+
+    for malformed in ["3+x", "0/5", "5:xx", "-3+2", "1:2:3:4", "0+0"] {
+        assert!(matches!(
+            malformed.parse::<Timecontrol>(),
+            Err(TimecontrolParseError::InvalidPeriod(_))
+        ));
+    }
+}
+
+#[test]
+fn mate_estimate() {
+    let estimate = MateTimeEstimate::from_clock(Score::Mate(5), 3000, 0, 1000).unwrap();
+
+    assert_eq!(estimate.min_remaining, -2000);
+    assert!(!estimate.feasible());
+
+    let estimate = MateTimeEstimate::from_clock(Score::Mate(5), 3000, 1000, 1000).unwrap();
+
+    assert!(estimate.feasible());
+    assert!(MateTimeEstimate::from_clock(Score::Mate(-5), 3000, 0, 1000).is_none());
+}
+
+#[test]
+fn ponderhit_clock() {
+    let mut clock = GameClock::new(&"1+0".parse::<MatchTimecontrol>().unwrap());
+
+    assert!(clock.ponderhit(1000).is_none());
+
+    // black ponders on the expected white move
+    clock.start_ponder(1);
+
+    let hit = clock.apply_ponderhit(2000, 1500);
+
+    assert_eq!(clock.remaining(0), 58000);
+    assert_eq!(clock.remaining(1), 60000);
+    assert_eq!(hit.timecontrol.wtime, 58000);
+    assert_eq!(hit.timecontrol.btime, 61500);
+}
@@ -0,0 +1,1257 @@
+use log::{info, log_enabled, warn, Level};
+
+use serde::{Deserialize, Serialize};
+
+use shakmaty::fen::{Epd, Fen};
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, Color, EnPassantMode, Position};
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Semaphore;
+
+use crate::fingerprint::*;
+use crate::game::*;
+use crate::profile::*;
+use crate::timecontrol::*;
+use crate::uciengine::*;
+use crate::verify::*;
+use crate::warmup::*;
+use crate::webhook::*;
+
+/// termination of a game won by checkmate
+pub const TERMINATION_CHECKMATE: &str = "checkmate";
+/// termination of a game drawn by stalemate
+pub const TERMINATION_STALEMATE: &str = "stalemate";
+/// termination of a game drawn by insufficient material
+pub const TERMINATION_INSUFFICIENT_MATERIAL: &str = "insufficient material";
+/// termination of a game drawn by threefold repetition
+pub const TERMINATION_REPETITION: &str = "threefold repetition";
+/// termination of a game drawn by the fifty move rule
+pub const TERMINATION_FIFTY_MOVES: &str = "fifty move rule";
+/// termination of a game adjudicated as a draw on reaching the move limit
+pub const TERMINATION_MOVE_LIMIT: &str = "move limit";
+/// termination of a game lost on time
+pub const TERMINATION_TIME_FORFEIT: &str = "time forfeit";
+/// termination of a game lost by an illegal move
+pub const TERMINATION_ILLEGAL_MOVE: &str = "illegal move";
+/// termination of a game lost by an engine crash
+pub const TERMINATION_CRASH: &str = "engine crash";
+/// termination of a game lost by a protocol violation
+pub const TERMINATION_PROTOCOL_VIOLATION: &str = "protocol violation";
+
+/// result of the side to move losing
+fn loss_of(turn: Color) -> GameResult {
+    match turn {
+        Color::White => GameResult::BlackWins,
+        Color::Black => GameResult::WhiteWins,
+    }
+}
+
+/// outcome of a position by the rules of chess, None if the game goes on,
+/// seen is the number of times the position occurred
+pub(crate) fn rules_outcome(pos: &Chess, seen: usize) -> Option<(GameResult, &'static str)> {
+    if pos.is_checkmate() {
+        Some((loss_of(pos.turn()), TERMINATION_CHECKMATE))
+    } else if pos.is_stalemate() {
+        Some((GameResult::Draw, TERMINATION_STALEMATE))
+    } else if pos.is_insufficient_material() {
+        Some((GameResult::Draw, TERMINATION_INSUFFICIENT_MATERIAL))
+    } else if seen >= 3 {
+        Some((GameResult::Draw, TERMINATION_REPETITION))
+    } else if pos.halfmoves() >= 100 {
+        Some((GameResult::Draw, TERMINATION_FIFTY_MOVES))
+    } else {
+        None
+    }
+}
+
+/// grace period after the clock ran out before the bestmove is considered missing
+const MISSING_BESTMOVE_GRACE: Duration = Duration::from_millis(1000);
+/// time an engine has to apply ucinewgame and the options of its profile
+const SETUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// ruleset used to score violations
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Ruleset {
+    /// fide like, a flag fall is a draw if the opponent cannot mate,
+    /// unsolicited bestmoves are recorded but not scored
+    Fide,
+    /// every violation loses the game
+    Strict,
+}
+
+/// settings of a single game
+#[derive(Debug, Clone)]
+pub struct GameSettings {
+    /// time control
+    pub tc: MatchTimecontrol,
+    /// games reaching this many plies are adjudicated as draws
+    pub max_plies: usize,
+    /// ruleset used to score violations
+    pub ruleset: Ruleset,
+    /// time an engine may overstep its clock without losing, in milliseconds
+    pub time_margin: u64,
+}
+
+/// implement Default for GameSettings
+/// ( default time control, move limit 400 plies, fide ruleset, no time margin )
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            tc: Timecontrol::default().into(),
+            max_plies: 400,
+            ruleset: Ruleset::Fide,
+            time_margin: 0,
+        }
+    }
+}
+
+/// play a single game between two engines and return its record,
+/// the engines are spawned for the game and quit afterwards
+pub async fn play_game(
+    white: &EngineProfile,
+    black: &EngineProfile,
+    start_fen: Option<&str>,
+    settings: &GameSettings,
+) -> GameRecord {
+    let engines = [white.spawn(), black.spawn()];
+
+    let record = play_game_on(&engines, white, black, start_fen, settings).await;
+
+    for engine in engines.iter() {
+        // quit immediately, an engine that missed its bestmove still has a job pending
+        engine.issue_command("quit");
+    }
+
+    record
+}
+
+/// true if the engines of a game can not be reused for another game
+/// ( an engine crashed or still has a search pending )
+pub fn engines_unusable(record: &GameRecord) -> bool {
+    record.violations.iter().any(|violation| {
+        matches!(
+            violation.kind,
+            ViolationKind::MissingBestmove
+                | ViolationKind::EngineCrash
+                | ViolationKind::MissingReadyok
+        )
+    })
+}
+
+/// play a single game on running engines ( white first ) and return its record,
+/// the engines are sent ucinewgame and configured with the profiles before the game,
+/// an engine failing to confirm them loses, they are left running after the game
+pub async fn play_game_on(
+    engines: &[std::sync::Arc<UciEngine>; 2],
+    white: &EngineProfile,
+    black: &EngineProfile,
+    start_fen: Option<&str>,
+    settings: &GameSettings,
+) -> GameRecord {
+    let mut record = GameRecord {
+        white: white.name.to_owned(),
+        black: black.name.to_owned(),
+        opening: None,
+        start_fen: start_fen.map(|fen| fen.to_string()),
+        time_control: settings.tc.to_string(),
+        moves: vec![],
+        move_times: vec![],
+        result: GameResult::Unfinished,
+        termination: String::new(),
+        violations: vec![],
+    };
+
+    let mut pos: Chess = match start_fen {
+        Some(fen) => match Fen::from_ascii(fen.as_bytes())
+            .ok()
+            .and_then(|fen| fen.into_position(CastlingMode::Standard).ok())
+        {
+            Some(pos) => pos,
+            _ => {
+                warn!("invalid start fen {}", fen);
+
+                record.termination = format!("invalid start fen {}", fen);
+
+                return record;
+            }
+        },
+        _ => Chess::default(),
+    };
+
+    let profiles = [white, black];
+    let colors = [Color::White, Color::Black];
+
+    // ucinewgame and the options of the profiles are confirmed with readyok before
+    // the game, so applying them ( a large Hash ) is not charged to the clocks
+    let setup = |side: usize| {
+        let go_job = profiles[side].apply(GoJob::new().new_game(true));
+
+        tokio::time::timeout(SETUP_TIMEOUT, engines[side].try_go(go_job))
+    };
+
+    let setups = tokio::join!(setup(0), setup(1));
+
+    for (side, setup) in vec![setups.0, setups.1].into_iter().enumerate() {
+        let (kind, detail, termination) = match setup {
+            Ok(Ok(_)) => continue,
+            Ok(Err(err)) => (
+                ViolationKind::EngineCrash,
+                err.to_string(),
+                TERMINATION_CRASH,
+            ),
+            _ => (
+                ViolationKind::MissingReadyok,
+                format!("no readyok after {} ms", SETUP_TIMEOUT.as_millis()),
+                TERMINATION_PROTOCOL_VIOLATION,
+            ),
+        };
+
+        record.violations.push(Violation {
+            ply: 0,
+            engine: profiles[side].name.to_owned(),
+            kind,
+            detail,
+        });
+
+        if record.termination.is_empty() {
+            record.result = loss_of(colors[side]);
+            record.termination = termination.to_string();
+        }
+    }
+
+    if !record.termination.is_empty() {
+        return record;
+    }
+
+    let mut clock = GameClock::new(&settings.tc);
+
+    let mut repetitions: HashMap<String, usize> = HashMap::new();
+
+    loop {
+        // unsolicited bestmoves of either engine since the last move
+        for side in 0..2 {
+            for line in engines[side].take_unsolicited() {
+                record.violations.push(Violation {
+                    ply: record.moves.len(),
+                    engine: profiles[side].name.to_owned(),
+                    kind: ViolationKind::UnsolicitedBestmove,
+                    detail: line,
+                });
+
+                if settings.ruleset == Ruleset::Strict && record.termination.is_empty() {
+                    record.result = loss_of(colors[side]);
+                    record.termination = TERMINATION_PROTOCOL_VIOLATION.to_string();
+                }
+            }
+        }
+
+        if !record.termination.is_empty() {
+            break;
+        }
+
+        let epd = Epd::from_position(&pos, EnPassantMode::Legal).to_string();
+        let seen = repetitions.entry(epd).or_insert(0);
+
+        *seen += 1;
+
+        let outcome = match rules_outcome(&pos, *seen) {
+            None if record.moves.len() >= settings.max_plies => {
+                Some((GameResult::Draw, TERMINATION_MOVE_LIMIT))
+            }
+            outcome => outcome,
+        };
+
+        if let Some((result, termination)) = outcome {
+            record.result = result;
+            record.termination = termination.to_string();
+
+            break;
+        }
+
+        let side = match pos.turn() {
+            Color::White => 0,
+            Color::Black => 1,
+        };
+
+        let mut go_job = match start_fen {
+            Some(fen) => GoJob::new().pos_fen(fen),
+            _ => GoJob::new().pos_startpos(),
+        };
+
+        if !record.moves.is_empty() {
+            go_job = go_job.pos_moves(record.moves.join(" "));
+        }
+
+        go_job = go_job.tc(clock.timecontrol());
+
+        if let Some(movestogo) = clock.movestogo(side) {
+            go_job = go_job.movestogo(movestogo);
+        }
+
+        let allowed =
+            Duration::from_millis(clock.remaining(side).max(0) as u64 + settings.time_margin);
+
+        let started = Instant::now();
+
+        let go_result = tokio::time::timeout(
+            allowed + MISSING_BESTMOVE_GRACE,
+            engines[side].try_go(go_job),
+        )
+        .await;
+
+        let elapsed = started.elapsed();
+
+        record.move_times.push(elapsed.as_millis() as u64);
+
+        let ply = record.moves.len();
+
+        let violation = |kind: ViolationKind, detail: String| Violation {
+            ply,
+            engine: profiles[side].name.to_owned(),
+            kind,
+            detail,
+        };
+
+        let go_result = match go_result {
+            Ok(Ok(go_result)) => Some(go_result),
+            Ok(_) => {
+                let crash = violation(
+                    ViolationKind::EngineCrash,
+                    "engine exited during search".to_string(),
+                );
+
+                record.violations.push(crash);
+
+                record.result = loss_of(pos.turn());
+                record.termination = TERMINATION_CRASH.to_string();
+
+                break;
+            }
+            _ => {
+                let missing = violation(
+                    ViolationKind::MissingBestmove,
+                    format!("no bestmove after {} ms", elapsed.as_millis()),
+                );
+
+                record.violations.push(missing);
+
+                None
+            }
+        };
+
+        if go_result.is_none() || elapsed > allowed {
+            let flag_fall = violation(
+                ViolationKind::FlagFall,
+                format!(
+                    "used {} ms with {} ms allowed",
+                    elapsed.as_millis(),
+                    allowed.as_millis()
+                ),
+            );
+
+            record.violations.push(flag_fall);
+
+            // under fide rules the game is drawn if the opponent cannot mate
+            record.result = match settings.ruleset {
+                Ruleset::Fide if pos.has_insufficient_material(!pos.turn()) => GameResult::Draw,
+                _ => loss_of(pos.turn()),
+            };
+            record.termination = TERMINATION_TIME_FORFEIT.to_string();
+
+            break;
+        }
+
+        clock.charge(side, elapsed.as_millis() as u64);
+
+        let bestmove = go_result
+            .and_then(|go_result| go_result.bestmove)
+            .unwrap_or_default();
+
+        let m = bestmove
+            .parse::<UciMove>()
+            .ok()
+            .and_then(|uci| uci.to_move(&pos).ok());
+
+        match m {
+            Some(m) => {
+                pos.play_unchecked(m);
+
+                record.moves.push(bestmove);
+            }
+            _ => {
+                let illegal = violation(ViolationKind::IllegalMove, bestmove.to_owned());
+
+                record.violations.push(illegal);
+
+                record.result = loss_of(pos.turn());
+                record.termination = format!("{} {}", TERMINATION_ILLEGAL_MOVE, bestmove);
+
+                break;
+            }
+        }
+    }
+
+    record
+}
+
+/// engine restart policy of a tournament
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RestartPolicy {
+    /// restart an engine after this many games
+    pub every_games: usize,
+    /// restart an engine once its resident memory exceeds this many MB ( linux only )
+    pub max_rss_mb: Option<u64>,
+}
+
+/// restart policy implementation
+impl RestartPolicy {
+    /// create default restart policy ( fresh engines for every game )
+    pub fn new() -> Self {
+        Self {
+            every_games: 1,
+            max_rss_mb: None,
+        }
+    }
+
+    /// set number of games after which an engine is restarted and return self
+    pub fn every_games(mut self, every_games: usize) -> Self {
+        self.every_games = every_games.max(1);
+
+        self
+    }
+
+    /// set resident memory limit in MB and return self
+    pub fn max_rss_mb(mut self, max_rss_mb: u64) -> Self {
+        self.max_rss_mb = Some(max_rss_mb);
+
+        self
+    }
+}
+
+/// implement Default for RestartPolicy
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// resident memory of process in MB, None if not available
+fn rss_mb(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+
+    status
+        .lines()
+        .find(|line| line.starts_with("VmRSS:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| kb / 1024)
+}
+
+/// engine restart recorded during a tournament
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineRestart {
+    /// name of the engine
+    pub engine: String,
+    /// games played by the engine process
+    pub games: usize,
+    /// reason of the restart
+    pub reason: String,
+}
+
+/// warm-up of an engine spawned during a tournament
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineWarmup {
+    /// name of the engine
+    pub engine: String,
+    /// warm-up telemetry
+    pub report: WarmupReport,
+}
+
+/// running engine kept between games
+struct IdleEngine {
+    engine: std::sync::Arc<UciEngine>,
+    games: usize,
+}
+
+/// running engines of a tournament by profile name
+struct EngineCache {
+    policy: RestartPolicy,
+    /// warm-up of every spawned engine, None for none
+    warmup: Option<Warmup>,
+    idle: std::sync::Mutex<HashMap<String, Vec<IdleEngine>>>,
+    /// fingerprint of the first engine spawned per profile name
+    fingerprints: std::sync::Mutex<HashMap<String, EngineFingerprint>>,
+}
+
+/// engine cache implementation
+impl EngineCache {
+    /// take idle engine of profile or spawn one, a spawned engine is fingerprinted
+    /// and a restart is returned if its banner differs from the first engine of the profile
+    /// ( the binary changed underneath, e.g. by a package upgrade ),
+    /// a spawned engine is warmed up and its warm-up is returned
+    async fn checkout(
+        &self,
+        profile: &EngineProfile,
+    ) -> (IdleEngine, Option<EngineRestart>, Option<EngineWarmup>) {
+        let idle = {
+            let mut idle = self.idle.lock().unwrap();
+
+            idle.get_mut(&profile.name)
+                .and_then(|engines| engines.pop())
+        };
+
+        if let Some(idle) = idle {
+            return (idle, None, None);
+        }
+
+        let engine = profile.spawn();
+
+        let fingerprint = engine.fingerprint().await;
+
+        let changes = {
+            let mut fingerprints = self.fingerprints.lock().unwrap();
+
+            match fingerprints.get(&profile.name) {
+                Some(first) => first.changes(&fingerprint),
+                _ => {
+                    fingerprints.insert(profile.name.to_string(), fingerprint);
+
+                    vec![]
+                }
+            }
+        };
+
+        let changed = match changes.is_empty() {
+            true => None,
+            _ => {
+                warn!(
+                    "engine {} changed since the first spawn : {}",
+                    profile.name,
+                    changes.join(" , ")
+                );
+
+                Some(EngineRestart {
+                    engine: profile.name.to_string(),
+                    games: 0,
+                    reason: format!("engine binary changed : {}", changes.join(" , ")),
+                })
+            }
+        };
+
+        let warmup = match &self.warmup {
+            Some(warmup) => Some(EngineWarmup {
+                engine: profile.name.to_string(),
+                report: warmup.run(&engine).await,
+            }),
+            _ => None,
+        };
+
+        (IdleEngine { engine, games: 0 }, changed, warmup)
+    }
+
+    /// return engine after a game, returns the restart if the engine was quit
+    fn checkin(&self, name: &str, mut idle: IdleEngine, unusable: bool) -> Option<EngineRestart> {
+        idle.games += 1;
+
+        let rss = idle.engine.pid().and_then(rss_mb);
+
+        let reason = if unusable {
+            Some("engine failed during the game".to_string())
+        } else if idle.games >= self.policy.every_games {
+            Some(format!("played {} games", idle.games))
+        } else {
+            match (rss, self.policy.max_rss_mb) {
+                (Some(rss), Some(max_rss)) if rss > max_rss => {
+                    Some(format!("resident memory {} MB over {} MB", rss, max_rss))
+                }
+                _ => None,
+            }
+        };
+
+        match reason {
+            Some(reason) => {
+                idle.engine.issue_command("quit");
+
+                Some(EngineRestart {
+                    engine: name.to_string(),
+                    games: idle.games,
+                    reason,
+                })
+            }
+            _ => {
+                let mut engines = self.idle.lock().unwrap();
+
+                engines.entry(name.to_string()).or_default().push(idle);
+
+                None
+            }
+        }
+    }
+
+    /// quit all idle engines
+    fn quit_all(&self) {
+        let mut idle = self.idle.lock().unwrap();
+
+        for (_, engines) in idle.drain() {
+            for idle in engines {
+                idle.engine.issue_command("quit");
+            }
+        }
+    }
+}
+
+/// tournament progress snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TournamentProgress {
+    /// total number of games
+    pub games_total: usize,
+    /// number of finished games
+    pub games_finished: usize,
+    /// number of games being played
+    pub games_running: usize,
+    /// crosstable, records of each engine against each opponent
+    pub crosstable: BTreeMap<String, BTreeMap<String, WdlRecord>>,
+    /// time since the tournament started
+    pub elapsed: Duration,
+    /// estimated time until the tournament finishes, None until a game finished
+    pub eta: Option<Duration>,
+}
+
+/// tournament event, posted to webhooks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TournamentEvent {
+    /// event kind ( "game_finished" or "engine_crash" )
+    pub event: String,
+    /// the game that triggered the event
+    pub game: GameRecord,
+    /// progress after the game
+    pub progress: TournamentProgress,
+}
+
+/// tournament state, shared between the game workers
+#[derive(Debug)]
+struct TournamentState {
+    /// progress
+    progress: TournamentProgress,
+    /// when the tournament started
+    started: Option<Instant>,
+    /// engine restarts so far
+    restarts: Vec<EngineRestart>,
+    /// warm-ups of spawned engines so far
+    warmups: Vec<EngineWarmup>,
+}
+
+/// tournament state implementation
+impl TournamentState {
+    /// progress snapshot with elapsed time and eta
+    fn snapshot(&self) -> TournamentProgress {
+        let mut progress = self.progress.clone();
+
+        if let Some(started) = self.started {
+            progress.elapsed = started.elapsed();
+
+            if progress.games_finished > 0 {
+                let remaining = (progress.games_total - progress.games_finished) as u32;
+
+                progress.eta = Some(progress.elapsed / progress.games_finished as u32 * remaining);
+            }
+        }
+
+        progress
+    }
+}
+
+/// pairing of a tournament game
+#[derive(Debug, Clone)]
+struct Pairing {
+    /// index of the game
+    index: usize,
+    /// white profile
+    white: EngineProfile,
+    /// black profile
+    black: EngineProfile,
+    /// start fen
+    start_fen: Option<String>,
+}
+
+/// round robin tournament, every engine plays every other engine with both colors in each round
+#[derive(Debug)]
+pub struct Tournament {
+    /// engine profiles
+    engines: Vec<EngineProfile>,
+    /// number of rounds
+    rounds: usize,
+    /// settings of the games
+    settings: GameSettings,
+    /// maximum number of games played at the same time
+    concurrency: usize,
+    /// total engine threads available to concurrent games, None for no limit
+    threads_budget: Option<usize>,
+    /// total engine hash in MB available to concurrent games, None for no limit
+    hash_budget: Option<usize>,
+    /// opening fens, cycled through by round
+    openings: Vec<String>,
+    /// urls notified on game finish and engine crash
+    webhooks: Vec<String>,
+    /// replay finished games to verify moves and result
+    verify: bool,
+    /// engine restart policy
+    restart_policy: RestartPolicy,
+    /// warm-up of every spawned engine
+    warmup: Option<Warmup>,
+    /// shared state
+    state: std::sync::Arc<std::sync::Mutex<TournamentState>>,
+}
+
+/// tournament implementation
+impl Tournament {
+    /// create new tournament with defaults
+    /// ( one round, default game settings, one game at a time )
+    pub fn new() -> Self {
+        Self {
+            engines: vec![],
+            rounds: 1,
+            settings: GameSettings::default(),
+            concurrency: 1,
+            threads_budget: None,
+            hash_budget: None,
+            openings: vec![],
+            webhooks: vec![],
+            verify: false,
+            restart_policy: RestartPolicy::new(),
+            warmup: None,
+            state: std::sync::Arc::new(std::sync::Mutex::new(TournamentState {
+                progress: TournamentProgress {
+                    games_total: 0,
+                    games_finished: 0,
+                    games_running: 0,
+                    crosstable: BTreeMap::new(),
+                    elapsed: Duration::from_secs(0),
+                    eta: None,
+                },
+                started: None,
+                restarts: vec![],
+                warmups: vec![],
+            })),
+        }
+    }
+
+    /// add engine and return self
+    pub fn engine(mut self, profile: EngineProfile) -> Self {
+        self.engines.push(profile);
+
+        self
+    }
+
+    /// set number of rounds and return self
+    pub fn rounds(mut self, rounds: usize) -> Self {
+        self.rounds = rounds;
+
+        self
+    }
+
+    /// set sudden death time control ( time and increment of white ) and return self
+    pub fn tc(mut self, tc: Timecontrol) -> Self {
+        self.settings.tc = tc.into();
+
+        self
+    }
+
+    /// set match time control and return self,
+    /// repeating controls forward movestogo to the engines
+    pub fn match_tc(mut self, tc: MatchTimecontrol) -> Self {
+        self.settings.tc = tc;
+
+        self
+    }
+
+    /// set maximum number of games played at the same time and return self
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+
+        self
+    }
+
+    /// set resource budget and return self,
+    /// games are started concurrently ( up to the concurrency limit ) only while
+    /// the Threads and Hash settings of the engines playing fit into the budget
+    pub fn resource_budget(mut self, threads: usize, hash_mb: usize) -> Self {
+        self.threads_budget = Some(threads.max(1));
+        self.hash_budget = Some(hash_mb.max(1));
+
+        self
+    }
+
+    /// add opening fen and return self
+    pub fn opening<T>(mut self, fen: T) -> Self
+    where
+        T: core::fmt::Display,
+    {
+        self.openings.push(fen.to_string());
+
+        self
+    }
+
+    /// set move limit in plies and return self
+    pub fn max_plies(mut self, max_plies: usize) -> Self {
+        self.settings.max_plies = max_plies;
+
+        self
+    }
+
+    /// set ruleset used to score violations and return self
+    pub fn ruleset(mut self, ruleset: Ruleset) -> Self {
+        self.settings.ruleset = ruleset;
+
+        self
+    }
+
+    /// set time margin in milliseconds and return self
+    pub fn time_margin(mut self, time_margin: u64) -> Self {
+        self.settings.time_margin = time_margin;
+
+        self
+    }
+
+    /// add webhook url ( plain http, https is not supported ) and return self,
+    /// a tournament event is posted to it on every game finish and engine crash,
+    /// posts run beside the games and are given up after WEBHOOK_TIMEOUT
+    pub fn webhook<T>(mut self, url: T) -> Self
+    where
+        T: core::fmt::Display,
+    {
+        self.webhooks.push(url.to_string());
+
+        self
+    }
+
+    /// set verification and return self,
+    /// if set every finished game is replayed to verify its moves and result,
+    /// issues are logged as warnings
+    pub fn verify(mut self, value: bool) -> Self {
+        self.verify = value;
+
+        self
+    }
+
+    /// set engine restart policy and return self,
+    /// engines are kept running between games until the policy restarts them
+    /// ( the options of the profile are reapplied in every game )
+    pub fn restart_policy(mut self, restart_policy: RestartPolicy) -> Self {
+        self.restart_policy = restart_policy;
+
+        self
+    }
+
+    /// set warm-up run on every engine spawned before its first game and return self,
+    /// the warm-up telemetry is kept apart from the games ( see warmups )
+    pub fn warmup(mut self, warmup: Warmup) -> Self {
+        self.warmup = Some(warmup);
+
+        self
+    }
+
+    /// warm-ups of the engines spawned so far
+    pub fn warmups(&self) -> Vec<EngineWarmup> {
+        let state = self.state.lock().unwrap();
+
+        state.warmups.to_owned()
+    }
+
+    /// engine restarts so far, restarts after every game are only recorded
+    /// if the policy keeps engines for more than one game,
+    /// engines whose binary changed since the first spawn are recorded as well
+    pub fn restarts(&self) -> Vec<EngineRestart> {
+        let state = self.state.lock().unwrap();
+
+        state.restarts.to_owned()
+    }
+
+    /// progress snapshot, can be called while the tournament runs
+    pub fn progress(&self) -> TournamentProgress {
+        let state = self.state.lock().unwrap();
+
+        state.snapshot()
+    }
+
+    /// pairings of all games
+    fn pairings(&self) -> Vec<Pairing> {
+        let mut pairings = vec![];
+
+        for round in 0..self.rounds {
+            let start_fen = match self.openings.len() {
+                0 => None,
+                len => Some(self.openings[round % len].to_owned()),
+            };
+
+            for i in 0..self.engines.len() {
+                for j in (i + 1)..self.engines.len() {
+                    for (white, black) in [(i, j), (j, i)].iter() {
+                        pairings.push(Pairing {
+                            index: pairings.len(),
+                            white: self.engines[*white].clone(),
+                            black: self.engines[*black].clone(),
+                            start_fen: start_fen.to_owned(),
+                        });
+                    }
+                }
+            }
+        }
+
+        pairings
+    }
+
+    /// permits needed for a resource, clamped to the budget
+    /// so that an oversized game still runs, alone
+    fn permits(needed: usize, budget: usize, resource: &str) -> u32 {
+        if needed > budget {
+            warn!(
+                "game needs {} {} which exceeds the budget of {}",
+                needed, resource, budget
+            );
+        }
+
+        needed.min(budget).max(1) as u32
+    }
+
+    /// run tournament and return the game records in pairing order
+    pub async fn run(&self) -> Vec<GameRecord> {
+        let pairings = self.pairings();
+
+        {
+            let mut state = self.state.lock().unwrap();
+
+            state.progress.games_total = pairings.len();
+            state.progress.games_finished = 0;
+            state.progress.crosstable = BTreeMap::new();
+            state.started = Some(Instant::now());
+            state.restarts = vec![];
+            state.warmups = vec![];
+        }
+
+        let cache = std::sync::Arc::new(EngineCache {
+            policy: self.restart_policy,
+            warmup: self.warmup.clone(),
+            idle: std::sync::Mutex::new(HashMap::new()),
+            fingerprints: std::sync::Mutex::new(HashMap::new()),
+        });
+
+        let games = std::sync::Arc::new(Semaphore::new(self.concurrency));
+
+        let threads = self
+            .threads_budget
+            .map(|budget| (budget, std::sync::Arc::new(Semaphore::new(budget))));
+
+        let hash = self
+            .hash_budget
+            .map(|budget| (budget, std::sync::Arc::new(Semaphore::new(budget))));
+
+        let mut handles = vec![];
+
+        // games are started in pairing order as soon as the budget allows
+        for pairing in pairings {
+            let mut permits = vec![games.clone().acquire_owned().await.unwrap()];
+
+            if let Some((budget, threads)) = &threads {
+                let needed = pairing.white.threads() + pairing.black.threads();
+
+                permits.push(
+                    threads
+                        .clone()
+                        .acquire_many_owned(Self::permits(needed, *budget, "threads"))
+                        .await
+                        .unwrap(),
+                );
+            }
+
+            if let Some((budget, hash)) = &hash {
+                let needed = pairing.white.hash_mb() + pairing.black.hash_mb();
+
+                permits.push(
+                    hash.clone()
+                        .acquire_many_owned(Self::permits(needed, *budget, "MB hash"))
+                        .await
+                        .unwrap(),
+                );
+            }
+
+            let state = self.state.clone();
+            let webhooks = self.webhooks.clone();
+            let settings = self.settings.clone();
+            let verify = self.verify;
+            let cache = cache.clone();
+
+            handles.push(tokio::spawn(async move {
+                {
+                    let mut state = state.lock().unwrap();
+
+                    state.progress.games_running += 1;
+                }
+
+                let (white, white_changed, white_warmup) = cache.checkout(&pairing.white).await;
+                let (black, black_changed, black_warmup) = cache.checkout(&pairing.black).await;
+
+                {
+                    let mut state = state.lock().unwrap();
+
+                    state
+                        .restarts
+                        .extend(white_changed.into_iter().chain(black_changed));
+                    state
+                        .warmups
+                        .extend(white_warmup.into_iter().chain(black_warmup));
+                }
+
+                let idle = [white, black];
+
+                let engines = [idle[0].engine.clone(), idle[1].engine.clone()];
+
+                let record = play_game_on(
+                    &engines,
+                    &pairing.white,
+                    &pairing.black,
+                    pairing.start_fen.as_deref(),
+                    &settings,
+                )
+                .await;
+
+                let unusable = engines_unusable(&record);
+
+                let [white, black] = idle;
+
+                for (name, idle) in [(&record.white, white), (&record.black, black)] {
+                    if let Some(restart) = cache.checkin(name, idle, unusable) {
+                        if log_enabled!(Level::Info) {
+                            info!(
+                                "restarting {} after {} games : {}",
+                                restart.engine, restart.games, restart.reason
+                            );
+                        }
+
+                        if cache.policy.every_games > 1 || restart.games < cache.policy.every_games
+                        {
+                            let mut state = state.lock().unwrap();
+
+                            state.restarts.push(restart);
+                        }
+                    }
+                }
+
+                // release the budget of the game
+                drop(permits);
+
+                if verify {
+                    for issue in verify_game(&record).issues {
+                        warn!("game {} verification issue {:?}", pairing.index, issue);
+                    }
+                }
+
+                if log_enabled!(Level::Info) {
+                    info!(
+                        "game {} {} - {} {} ( {} )",
+                        pairing.index,
+                        record.white,
+                        record.black,
+                        record.result.to_pgn(),
+                        record.termination
+                    );
+                }
+
+                let progress = {
+                    let mut state = state.lock().unwrap();
+
+                    state.progress.games_running -= 1;
+                    state.progress.games_finished += 1;
+
+                    for (engine, opponent) in [
+                        (&record.white, &record.black),
+                        (&record.black, &record.white),
+                    ]
+                    .iter()
+                    {
+                        state
+                            .progress
+                            .crosstable
+                            .entry(engine.to_string())
+                            .or_default()
+                            .entry(opponent.to_string())
+                            .or_default()
+                            .add(&record, engine);
+                    }
+
+                    state.snapshot()
+                };
+
+                // posted off the game path, a slow endpoint does not hold up the game
+                let notification = match webhooks.is_empty() {
+                    true => None,
+                    _ => {
+                        let event = TournamentEvent {
+                            event: match record.termination.as_str() {
+                                TERMINATION_CRASH => "engine_crash",
+                                _ => "game_finished",
+                            }
+                            .to_string(),
+                            game: record.clone(),
+                            progress,
+                        };
+
+                        Some(tokio::spawn(async move {
+                            notify_all(&webhooks, &event).await;
+                        }))
+                    }
+                };
+
+                (pairing.index, record, notification)
+            }));
+        }
+
+        let mut records: Vec<(usize, GameRecord)> = vec![];
+        let mut notifications = vec![];
+
+        for handle in handles {
+            match handle.await {
+                Ok((index, record, notification)) => {
+                    records.push((index, record));
+                    notifications.extend(notification);
+                }
+                Err(err) => warn!("tournament game failed {:?}", err),
+            }
+        }
+
+        // every post is bounded by the webhook timeout
+        for notification in notifications {
+            let _ = notification.await;
+        }
+
+        cache.quit_all();
+
+        records.sort_by_key(|(index, _)| *index);
+
+        records.into_iter().map(|(_, record)| record).collect()
+    }
+}
+
+/// implement Default for Tournament
+impl Default for Tournament {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn engine_cache_restarts() {
+    let cache = |policy: RestartPolicy| EngineCache {
+        policy,
+        warmup: None,
+        idle: std::sync::Mutex::new(HashMap::new()),
+        fingerprints: std::sync::Mutex::new(HashMap::new()),
+    };
+
+    let profile = EngineProfile::new("fake", fake_engine("engine-cache", ""));
+
+    // the engine is kept for every_games games
+    let games = cache(RestartPolicy::new().every_games(2));
+
+    let (idle, restart, _) = games.checkout(&profile).await;
+    let engine = idle.engine.clone();
+
+    assert!(restart.is_none());
+    assert!(games.checkin("fake", idle, false).is_none());
+
+    let (idle, _, _) = games.checkout(&profile).await;
+
+    assert!(std::sync::Arc::ptr_eq(&engine, &idle.engine));
+
+    let restart = games.checkin("fake", idle, false).unwrap();
+
+    assert_eq!(restart.games, 2);
+    assert_eq!(restart.reason, "played 2 games");
+
+    // an engine that failed is never reused
+    let (idle, _, _) = games.checkout(&profile).await;
+
+    assert!(!std::sync::Arc::ptr_eq(&engine, &idle.engine));
+    assert_eq!(
+        games.checkin("fake", idle, true).unwrap().reason,
+        "engine failed during the game"
+    );
+
+    games.quit_all();
+
+    // the fake engine grows by 8 MB on uci
+    #[cfg(target_os = "linux")]
+    {
+        let path = fake_engine(
+            "engine-cache-rss",
+            "    uci) pad=$(head -c 8000000 /dev/zero | tr '\\0' a); echo 'id name Fake'; echo uciok;;",
+        );
+
+        let profile = EngineProfile::new("fake", path);
+
+        let rss = cache(RestartPolicy::new().every_games(100).max_rss_mb(4));
+
+        let (idle, _, _) = rss.checkout(&profile).await;
+
+        assert!(rss_mb(idle.engine.pid().unwrap()).unwrap() > 4);
+        assert!(rss
+            .checkin("fake", idle, false)
+            .unwrap()
+            .reason
+            .starts_with("resident memory"));
+
+        let unlimited = cache(RestartPolicy::new().every_games(100));
+
+        let (idle, _, _) = unlimited.checkout(&profile).await;
+
+        assert!(unlimited.checkin("fake", idle, false).is_none());
+
+        unlimited.quit_all();
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn time_forfeit() {
+    // white never answers go
+    let white = EngineProfile::new("silent", fake_engine("forfeit-white", "    go*) ;;"))
+        .uci_opt("Hash", 64);
+    let black = EngineProfile::new(
+        "fake",
+        fake_engine("forfeit-black", "    go*) echo 'bestmove e7e5';;"),
+    );
+
+    let settings = GameSettings {
+        tc: Timecontrol::new(200, 0, 200, 0).into(),
+        ..GameSettings::default()
+    };
+
+    let record = play_game(&white, &black, None, &settings).await;
+
+    assert_eq!(record.result, GameResult::BlackWins);
+    assert_eq!(record.termination, TERMINATION_TIME_FORFEIT);
+    assert_eq!(
+        record
+            .violations
+            .iter()
+            .map(|violation| violation.kind)
+            .collect::<Vec<ViolationKind>>(),
+        [ViolationKind::MissingBestmove, ViolationKind::FlagFall]
+    );
+    assert!(engines_unusable(&record));
+
+    // the options are applied before the clock of the first move runs
+    assert_eq!(
+        fake_engine_log(&white.path)[..4],
+        [
+            "setoption name Hash value 64",
+            "ucinewgame",
+            "isready",
+            "position startpos"
+        ]
+    );
+}
@@ -0,0 +1,313 @@
+use log::{debug, log_enabled, Level};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::analysis::*;
+use crate::evalbar::DEFAULT_MATE_CP;
+use crate::uciengine::*;
+
+/// kind of a score trend, seen from the side to move
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TrendKind {
+    /// the score rises at least at the improving slope
+    Improving,
+    /// the score falls at least at the collapsing slope
+    Collapsing,
+    /// an advantage holds without collapsing
+    Consolidating,
+}
+
+/// event of a score trend crossing a slope of its policy
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrendEvent {
+    /// kind of the trend
+    pub kind: TrendKind,
+    /// slope in centipawns per depth over the window
+    pub slope: f64,
+    /// depth the trend was classified at
+    pub depth: usize,
+    /// score at that depth in centipawns ( mates clamped )
+    pub cp: i32,
+    /// true if the side to move is white, None if unknown
+    pub white: Option<bool>,
+}
+
+/// trend event implementation
+impl TrendEvent {
+    /// description of the event for broadcasts ( "position collapsing for White" )
+    pub fn describe(&self) -> String {
+        let side = match self.white {
+            Some(true) => "White",
+            Some(false) => "Black",
+            _ => "the side to move",
+        };
+
+        match self.kind {
+            TrendKind::Improving => format!("position improving for {}", side),
+            TrendKind::Collapsing => format!("position collapsing for {}", side),
+            TrendKind::Consolidating => format!("advantage consolidating for {}", side),
+        }
+    }
+}
+
+/// policy classifying score trends over the last depths
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrendPolicy {
+    /// number of depths the slope is fitted over
+    pub window: usize,
+    /// slope in centipawns per depth from which the score is improving
+    pub improving_slope: f64,
+    /// slope in centipawns per depth, taken negative, from which the score is collapsing
+    pub collapsing_slope: f64,
+    /// score in centipawns every depth of the window has to keep to consolidate
+    pub advantage_cp: i32,
+}
+
+/// trend policy implementation
+impl TrendPolicy {
+    /// create new policy over 4 depths, improving or collapsing at 15 cp per depth,
+    /// consolidating from 150 cp
+    pub fn new() -> Self {
+        Self {
+            window: 4,
+            improving_slope: 15.0,
+            collapsing_slope: 15.0,
+            advantage_cp: 150,
+        }
+    }
+
+    /// set number of depths the slope is fitted over and return self
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window.max(2);
+
+        self
+    }
+
+    /// set improving and collapsing slopes in centipawns per depth and return self
+    pub fn slopes(mut self, improving_slope: f64, collapsing_slope: f64) -> Self {
+        self.improving_slope = improving_slope;
+        self.collapsing_slope = collapsing_slope;
+
+        self
+    }
+
+    /// set advantage that consolidates and return self
+    pub fn advantage_cp(mut self, advantage_cp: i32) -> Self {
+        self.advantage_cp = advantage_cp;
+
+        self
+    }
+}
+
+/// implement Default for TrendPolicy
+impl Default for TrendPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// least squares slope of scores by depth
+fn slope(scores: &BTreeMap<usize, i32>) -> f64 {
+    let n = scores.len() as f64;
+
+    let mean_depth = scores.keys().map(|depth| *depth as f64).sum::<f64>() / n;
+    let mean_cp = scores.values().map(|cp| *cp as f64).sum::<f64>() / n;
+
+    let (covariance, variance) =
+        scores
+            .iter()
+            .fold((0.0, 0.0), |(covariance, variance), (depth, cp)| {
+                let dx = *depth as f64 - mean_depth;
+
+                (covariance + dx * (*cp as f64 - mean_cp), variance + dx * dx)
+            });
+
+    match variance > 0.0 {
+        true => covariance / variance,
+        _ => 0.0,
+    }
+}
+
+/// tracks the top line score over the depths of a search and emits an event
+/// whenever the trend changes into one of the kinds
+#[derive(Debug, Clone)]
+pub struct TrendTracker {
+    /// policy
+    policy: TrendPolicy,
+    /// true if the side to move is white, None if unknown
+    white: Option<bool>,
+    /// score of the last window depths
+    scores: BTreeMap<usize, i32>,
+    /// current trend, None if there is none
+    current: Option<TrendKind>,
+}
+
+/// trend tracker implementation
+impl TrendTracker {
+    /// create new tracker with policy
+    pub fn new(policy: TrendPolicy) -> Self {
+        Self {
+            policy,
+            white: None,
+            scores: BTreeMap::new(),
+            current: None,
+        }
+    }
+
+    /// set side to move and return self, events name the side
+    pub fn white_to_move(mut self, white: bool) -> Self {
+        self.white = Some(white);
+
+        self
+    }
+
+    /// clear the tracker ( new position )
+    pub fn reset(&mut self) {
+        self.scores.clear();
+        self.current = None;
+    }
+
+    /// current trend, None if there is none
+    pub fn current(&self) -> Option<TrendKind> {
+        self.current
+    }
+
+    /// slope over the window, None until the window is full
+    pub fn slope(&self) -> Option<f64> {
+        match self.scores.len() >= self.policy.window {
+            true => Some(slope(&self.scores)),
+            _ => None,
+        }
+    }
+
+    /// observe analysis info, only exact top line scores count, returns the event
+    /// of a trend that just started
+    pub fn observe(&mut self, ai: &AnalysisInfo) -> Option<TrendEvent> {
+        if ai.multipv > 1
+            || ai.depth == 0
+            || ai.pv().is_none()
+            || !matches!(ai.scoretype, ScoreType::Exact)
+        {
+            return None;
+        }
+
+        let cp = match ai.score {
+            Score::Cp(cp) => cp.clamp(-DEFAULT_MATE_CP, DEFAULT_MATE_CP),
+            Score::Mate(moves) if moves > 0 => DEFAULT_MATE_CP,
+            Score::Mate(_) => -DEFAULT_MATE_CP,
+        };
+
+        self.scores.insert(ai.depth, cp);
+
+        while self.scores.len() > self.policy.window {
+            self.scores.pop_first();
+        }
+
+        let slope = self.slope()?;
+
+        let kind = if slope >= self.policy.improving_slope {
+            Some(TrendKind::Improving)
+        } else if slope <= -self.policy.collapsing_slope {
+            Some(TrendKind::Collapsing)
+        } else if self
+            .scores
+            .values()
+            .all(|cp| *cp >= self.policy.advantage_cp)
+        {
+            Some(TrendKind::Consolidating)
+        } else {
+            None
+        };
+
+        if kind == self.current {
+            return None;
+        }
+
+        self.current = kind;
+
+        let event = TrendEvent {
+            kind: kind?,
+            slope,
+            depth: ai.depth,
+            cp,
+            white: self.white,
+        };
+
+        if log_enabled!(Level::Debug) {
+            debug!("{} ( {:.1} cp per depth )", event.describe(), event.slope);
+        }
+
+        Some(event)
+    }
+}
+
+/// search go job and stop the search once a trend of one of kinds starts, returns
+/// the result with the event that stopped it, None if the search ended on its own
+pub async fn analyse_until_trend(
+    engine: &UciEngine,
+    go_job: GoJob,
+    mut tracker: TrendTracker,
+    kinds: &[TrendKind],
+) -> Result<(GoResult, Option<TrendEvent>), EngineError> {
+    let mut arx = engine.atx.subscribe();
+
+    let mut rrx = engine.try_go(go_job);
+
+    loop {
+        let event = tokio::select! {
+            go_result = &mut rrx => return go_result.map(|go_result| (go_result, None)),
+            received = arx.recv() => match received {
+                Ok(ai) => tracker.observe(&ai),
+                Err(RecvError::Lagged(_)) => None,
+                Err(RecvError::Closed) => return rrx.await.map(|go_result| (go_result, None)),
+            },
+        };
+
+        if let Some(event) = event.filter(|event| kinds.contains(&event.kind)) {
+            engine.issue_command("stop");
+
+            return rrx.await.map(|go_result| (go_result, Some(event)));
+        }
+    }
+}
+
+#[test]
+fn score_trends() {
+    let mut tracker = TrendTracker::new(TrendPolicy::new().window(3)).white_to_move(true);
+
+    let mut observe = |line: &str| {
+        let mut ai = AnalysisInfo::new();
+
+        ai.parse(line).unwrap();
+
+        tracker.observe(&ai).map(|event| event.describe())
+    };
+
+    assert_eq!(observe("info depth 10 score cp 40 pv e2e4"), None);
+    assert_eq!(observe("info depth 11 score cp 35 pv e2e4"), None);
+    assert_eq!(
+        observe("info depth 11 currmove d2d4 currmovenumber 2"),
+        None
+    );
+    assert_eq!(observe("info depth 12 score cp 38 pv e2e4"), None);
+    assert_eq!(observe("info depth 13 score cp 10 pv d2d4"), None);
+    assert_eq!(
+        observe("info depth 14 score cp -30 pv d2d4"),
+        Some("position collapsing for White".to_string())
+    );
+    assert_eq!(observe("info depth 15 score cp -80 pv d2d4"), None);
+    assert_eq!(
+        observe("info depth 16 score cp 120 pv c2c4"),
+        Some("position improving for White".to_string())
+    );
+    assert_eq!(observe("info depth 17 score cp 200 pv c2c4"), None);
+    assert_eq!(observe("info depth 18 score cp 210 pv c2c4"), None);
+    assert_eq!(
+        observe("info depth 19 score cp 190 pv c2c4"),
+        Some("advantage consolidating for White".to_string())
+    );
+}
@@ -1,14 +1,137 @@
-use log::{debug, error, info, log_enabled, Level};
+use log::{debug, error, info, log_enabled, warn, Level};
 
 use envor::envor::env_true;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::Command;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, ChildStdout, Command};
 use tokio::sync::*;
 
+use thiserror::Error;
+
 use crate::analysis::*;
+#[cfg(feature = "chaos")]
+use crate::chaos::*;
+use crate::diagnostics::*;
+use crate::fingerprint::*;
+use crate::multipv::*;
+use crate::notation::*;
+use crate::options::*;
+use crate::position::*;
+use crate::sampling::*;
+use crate::telemetry::*;
+#[cfg(feature = "chess")]
+use crate::verify::*;
+
+/// EngineError captures possible engine errors
+#[derive(Error, Debug, Clone)]
+pub enum EngineError {
+    #[error("engine could not be spawned : {0}")]
+    SpawnFailed(String),
+    #[error("engine exited : {0}")]
+    EngineExited(std::process::ExitStatus),
+    #[error("engine command could not be written : {0}")]
+    WriteFailed(String),
+    #[error("engine output could not be read : {0}")]
+    Io(String),
+    #[error("engine timed out : {0}")]
+    Timeout(String),
+    #[error("engine violated the protocol : {0}")]
+    ProtocolError(String),
+    #[error("go job ended without a result : {0}")]
+    NoResult(String),
+}
+
+/// GoLimitsError captures possible go limits errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum GoLimitsError {
+    #[error("conflicting go limits : {0}")]
+    Conflict(String),
+    #[error("go limit out of range : {0}")]
+    OutOfRange(String),
+}
+
+/// line ending of commands written to the engine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    /// "\n"
+    Lf,
+    /// "\r\n"
+    CrLf,
+}
+
+/// policy of restarting engines whose process died, the job searching when it died
+/// fails, later jobs go to the restarted engine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EngineRestartPolicy {
+    /// restarts allowed within window, an engine dying more often stays dead
+    pub max_restarts: usize,
+    /// window restarts are counted in
+    pub window: Duration,
+    /// delay before a restart
+    pub backoff: Duration,
+}
+
+/// restart policy implementation
+impl EngineRestartPolicy {
+    /// create new restart policy of up to 3 restarts a minute, 100 ms apart
+    pub fn new() -> Self {
+        Self {
+            max_restarts: 3,
+            window: Duration::from_secs(60),
+            backoff: Duration::from_millis(100),
+        }
+    }
+
+    /// set restarts allowed within window and return self
+    pub fn max_restarts(mut self, max_restarts: usize) -> Self {
+        self.max_restarts = max_restarts;
+
+        self
+    }
+
+    /// set window restarts are counted in and return self
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+
+        self
+    }
+
+    /// set delay before a restart and return self
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+
+        self
+    }
+
+    /// true if another restart is allowed at now, given the times of the restarts
+    /// so far, which are updated
+    fn allows(&self, restarts: &mut std::collections::VecDeque<Instant>, now: Instant) -> bool {
+        while restarts
+            .front()
+            .is_some_and(|restart| now.duration_since(*restart) >= self.window)
+        {
+            restarts.pop_front();
+        }
+
+        if restarts.len() >= self.max_restarts {
+            return false;
+        }
+
+        restarts.push_back(now);
+
+        true
+    }
+}
+
+/// implement Default for EngineRestartPolicy
+impl Default for EngineRestartPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 /// enum of possible position specifiers
 #[derive(Debug)]
@@ -17,12 +140,17 @@ pub enum PosSpec {
     Startpos,
     /// position from fen
     Fen,
+    /// position from incrementally maintained position command
+    Command,
     /// position not specified
     No,
 }
 
 use PosSpec::*;
 
+/// key value labels of engines and jobs ( "gpu" : "0", "game_id" : "abc" )
+pub type Labels = BTreeMap<String, String>;
+
 /// go command job
 #[derive(Debug)]
 pub struct GoJob {
@@ -34,23 +162,54 @@ pub struct GoJob {
     pos_fen: Option<String>,
     /// position moves
     pos_moves: Option<String>,
+    /// send null moves ( 0000 ) in the position moves
+    null_moves: bool,
+    /// position command
+    pos_command: Option<String>,
     /// go command options as key value pairs
     go_options: HashMap<String, String>,
+    /// search moves ( go option, always sent last )
+    searchmoves: Option<String>,
     /// custom command
     custom_command: Option<String>,
+    /// new game ( ucinewgame before the position, the hash is cleared )
+    new_game: bool,
     /// ponder ( go option )
     ponder: bool,
+    /// infinite ( go option, explicit intent to search until stopped )
+    infinite: bool,
     /// ponderhit ( ponderhit uci commend )
     ponderhit: bool,
     /// pondermiss ( alias to awaited stop )
     pondermiss: bool,
+    /// labels, carried to the result
+    labels: Labels,
+    /// cancel flag, a job cancelled before it starts is dropped
+    cancelled: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
+    /// id given by the engine the job is queued on
+    id: u64,
     /// result sender
     rtx: Option<oneshot::Sender<GoResult>>,
+    /// info sender, every info of the search is sent while it runs
+    itx: Option<mpsc::UnboundedSender<AnalysisInfo>>,
     should_go: bool,
 }
 
-/// time control ( all values are in milliseconds )
-#[derive(Debug)]
+/// clocks of a time control sent with go
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockSides {
+    /// clocks of both sides
+    Both,
+    /// clock of white only
+    White,
+    /// clock of black only
+    Black,
+}
+
+/// time control ( all values are in milliseconds ), created with its constructors,
+/// so that fields can be added
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
 pub struct Timecontrol {
     /// white time
     pub wtime: usize,
@@ -60,6 +219,138 @@ pub struct Timecontrol {
     pub btime: usize,
     /// black increment
     pub binc: usize,
+    /// moves to go until the next time control, None for sudden death
+    pub movestogo: Option<usize>,
+    /// clocks sent with go, for analysis only the clock of the side to move matters
+    pub sides: ClockSides,
+}
+
+/// typed search limits of a go command, the search stops at the first limit reached,
+/// an infinite search has no other limit
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GoLimits {
+    /// depth in plies
+    pub depth: Option<usize>,
+    /// nodes
+    pub nodes: Option<u64>,
+    /// mate in moves
+    pub mate: Option<usize>,
+    /// time of the search
+    pub movetime: Option<Duration>,
+    /// search until stopped
+    pub infinite: bool,
+}
+
+/// go limits implementation
+impl GoLimits {
+    /// create new go limits without any limit
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// set depth and return self
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+
+        self
+    }
+
+    /// set nodes and return self
+    pub fn nodes(mut self, nodes: u64) -> Self {
+        self.nodes = Some(nodes);
+
+        self
+    }
+
+    /// set mate in moves and return self
+    pub fn mate(mut self, mate: usize) -> Self {
+        self.mate = Some(mate);
+
+        self
+    }
+
+    /// set movetime and return self
+    pub fn movetime(mut self, movetime: Duration) -> Self {
+        self.movetime = Some(movetime);
+
+        self
+    }
+
+    /// set infinite and return self
+    pub fn infinite(mut self) -> Self {
+        self.infinite = true;
+
+        self
+    }
+
+    /// true if no limit is set and the search is not infinite
+    pub fn is_empty(&self) -> bool {
+        self.go_options().is_empty() && !self.infinite
+    }
+
+    /// go options of the limits in command order
+    pub fn go_options(&self) -> Vec<(&'static str, String)> {
+        let mut options = vec![];
+
+        if let Some(depth) = self.depth {
+            options.push(("depth", depth.to_string()));
+        }
+
+        if let Some(nodes) = self.nodes {
+            options.push(("nodes", nodes.to_string()));
+        }
+
+        if let Some(mate) = self.mate {
+            options.push(("mate", mate.to_string()));
+        }
+
+        if let Some(movetime) = self.movetime {
+            options.push(("movetime", movetime.as_millis().to_string()));
+        }
+
+        options
+    }
+
+    /// check the limits, zero limits are out of range, an infinite search
+    /// conflicts with every limit
+    pub fn validate(&self) -> Result<(), GoLimitsError> {
+        let options = self.go_options();
+
+        if let Some((key, _)) = options.iter().find(|(_, value)| value == "0") {
+            return Err(GoLimitsError::OutOfRange(format!("{} 0", key)));
+        }
+
+        if self.infinite && !options.is_empty() {
+            let keys: Vec<&str> = options.iter().map(|(key, _)| *key).collect();
+
+            return Err(GoLimitsError::Conflict(format!(
+                "infinite with {}",
+                keys.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// go command of the limits ( "go depth 18 nodes 1000000" )
+    pub fn to_command(&self) -> Result<String, GoLimitsError> {
+        self.validate()?;
+
+        let mut command = "go".to_string();
+
+        for (key, value) in self.go_options() {
+            command.push(' ');
+            command.push_str(key);
+            command.push(' ');
+            command.push_str(&value);
+        }
+
+        if self.infinite {
+            command.push_str(" infinite");
+        }
+
+        Ok(command)
+    }
 }
 
 /// implementation of time control
@@ -67,12 +358,98 @@ impl Timecontrol {
     /// create default time control
     /// ( one minute thinking time for both sides, no increment )
     pub fn default() -> Self {
+        Self::new(60000, 0, 60000, 0)
+    }
+
+    /// create time control from times and increments in milliseconds
+    pub fn new(wtime: usize, winc: usize, btime: usize, binc: usize) -> Self {
+        Self {
+            wtime,
+            winc,
+            btime,
+            binc,
+            movestogo: None,
+            sides: ClockSides::Both,
+        }
+    }
+
+    /// create time control from durations
+    pub fn from_durations(
+        wtime: Duration,
+        winc: Duration,
+        btime: Duration,
+        binc: Duration,
+    ) -> Self {
+        Self::new(
+            wtime.as_millis() as usize,
+            winc.as_millis() as usize,
+            btime.as_millis() as usize,
+            binc.as_millis() as usize,
+        )
+    }
+
+    /// create time control of white only from durations ( analysis with white to move )
+    pub fn white(time: Duration, inc: Duration) -> Self {
         Self {
-            wtime: 60000,
-            winc: 0,
-            btime: 60000,
-            binc: 0,
+            sides: ClockSides::White,
+            ..Self::from_durations(time, inc, Duration::ZERO, Duration::ZERO)
+        }
+    }
+
+    /// create time control of black only from durations ( analysis with black to move )
+    pub fn black(time: Duration, inc: Duration) -> Self {
+        Self {
+            sides: ClockSides::Black,
+            ..Self::from_durations(Duration::ZERO, Duration::ZERO, time, inc)
+        }
+    }
+
+    /// set moves to go until the next time control and return self
+    pub fn movestogo(mut self, movestogo: usize) -> Self {
+        self.movestogo = Some(movestogo);
+
+        self
+    }
+
+    /// go options of the time control in command order
+    pub fn go_options(&self) -> Vec<(&'static str, usize)> {
+        let mut options = vec![];
+
+        if self.sides != ClockSides::Black {
+            options.push(("wtime", self.wtime));
+            options.push(("winc", self.winc));
+        }
+
+        if self.sides != ClockSides::White {
+            options.push(("btime", self.btime));
+            options.push(("binc", self.binc));
+        }
+
+        if let Some(movestogo) = self.movestogo {
+            options.push(("movestogo", movestogo));
         }
+
+        options
+    }
+
+    /// white time as duration
+    pub fn wtime_duration(&self) -> Duration {
+        Duration::from_millis(self.wtime as u64)
+    }
+
+    /// white increment as duration
+    pub fn winc_duration(&self) -> Duration {
+        Duration::from_millis(self.winc as u64)
+    }
+
+    /// black time as duration
+    pub fn btime_duration(&self) -> Duration {
+        Duration::from_millis(self.btime as u64)
+    }
+
+    /// black increment as duration
+    pub fn binc_duration(&self) -> Duration {
+        Duration::from_millis(self.binc as u64)
     }
 }
 
@@ -84,17 +461,53 @@ impl GoJob {
             pos_spec: No,
             pos_fen: None,
             pos_moves: None,
+            null_moves: false,
+            pos_command: None,
             uci_options: HashMap::new(),
             go_options: HashMap::new(),
+            searchmoves: None,
             rtx: None,
+            itx: None,
             custom_command: None,
+            new_game: false,
             ponder: false,
+            infinite: false,
             ponderhit: false,
             pondermiss: false,
+            labels: Labels::new(),
+            cancelled: None,
+            id: 0,
             should_go: false,
         }
     }
 
+    /// set cancel flag and return self, if the flag is set before the job starts
+    /// the job is dropped ( its result fails ) instead of being sent to the engine
+    pub fn cancel_flag(mut self, cancelled: std::sync::Arc<std::sync::atomic::AtomicBool>) -> Self {
+        self.cancelled = Some(cancelled);
+
+        self
+    }
+
+    /// true if the job was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+            .as_ref()
+            .map(|cancelled| cancelled.load(std::sync::atomic::Ordering::SeqCst))
+            .unwrap_or(false)
+    }
+
+    /// set label and return self
+    pub fn label<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.labels.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
     /// set custom command and return self,
     /// if set, other settings will be ignored
     /// and only this single command will be sent,
@@ -108,6 +521,15 @@ impl GoJob {
         self
     }
 
+    /// set new game flag and return self, if set ucinewgame is sent before the position
+    /// and the engine is synchronized with isready, so the hash of an unrelated game or
+    /// position does not carry over
+    pub fn new_game(mut self, value: bool) -> Self {
+        self.new_game = value;
+
+        self
+    }
+
     /// convert go job to commands
     pub fn to_commands(&self) -> Vec<String> {
         let mut commands: Vec<String> = vec![];
@@ -134,21 +556,20 @@ impl GoJob {
             commands.push(format!("setoption name {} value {}", key, value));
         }
 
-        let mut pos_command_moves = "".to_string();
-
-        if let Some(pos_moves) = &self.pos_moves {
-            pos_command_moves = format!(" moves {}", pos_moves)
+        if self.new_game {
+            commands.push("ucinewgame".to_string());
         }
 
         let pos_command: Option<String> = match self.pos_spec {
-            Startpos => Some(format!("position startpos{}", pos_command_moves)),
+            Startpos => Some(self.build_pos_command(&["startpos"])),
             Fen => {
                 let fen = match &self.pos_fen {
                     Some(fen) => fen,
                     _ => "",
                 };
-                Some(format!("position fen {}{}", fen, pos_command_moves))
+                Some(self.build_pos_command(&["fen ", fen]))
             }
+            Command => self.pos_command.clone(),
             _ => None,
         };
 
@@ -167,8 +588,16 @@ impl GoJob {
                 go_command = go_command + &format!(" {}", "ponder");
             }
 
-            commands.push(go_command);
+            if self.infinite {
+                go_command = go_command + &format!(" {}", "infinite");
+            }
+
+            // searchmoves consumes the rest of the line, so it has to come last
+            if let Some(searchmoves) = &self.searchmoves {
+                go_command = go_command + &format!(" searchmoves {}", searchmoves);
+            }
 
+            commands.push(go_command);
         } else {
             commands.push("isready".to_string());
         }
@@ -176,28 +605,110 @@ impl GoJob {
         commands
     }
 
+    /// build position command from base parts and moves in a pre-sized buffer
+    fn build_pos_command(&self, base: &[&str]) -> String {
+        let base_len: usize = base.iter().map(|part| part.len()).sum();
+
+        // null moves are left out unless enabled, as PositionCommand does
+        let pos_moves = self.pos_moves.as_ref().and_then(|pos_moves| {
+            let moves: Vec<&str> = pos_moves
+                .split(' ')
+                .filter(|uci| {
+                    let skip = is_null_move(uci) && !self.null_moves;
+
+                    if skip {
+                        warn!("ignoring null move, null moves are not enabled");
+                    }
+
+                    !skip
+                })
+                .collect();
+
+            match moves.is_empty() {
+                true => None,
+                _ => Some(moves.join(" ")),
+            }
+        });
+
+        let moves_len = match &pos_moves {
+            Some(pos_moves) => pos_moves.len() + 7,
+            _ => 0,
+        };
+
+        let mut pos_command = String::with_capacity(9 + base_len + moves_len);
+
+        pos_command.push_str("position ");
+
+        for part in base {
+            pos_command.push_str(part);
+        }
+
+        if let Some(pos_moves) = &pos_moves {
+            pos_command.push_str(" moves ");
+            pos_command.push_str(pos_moves);
+        }
+
+        pos_command
+    }
+
     /// set ponder and return self
     pub fn set_ponder(mut self, value: bool) -> Self {
+        self.should_go = self.should_go || value;
         self.ponder = value;
 
         self
     }
 
-    /// set ponder to true and return self
+    /// set ponder to true and return self, the job issues go ponder, its result is
+    /// awaited through UciEngine::ponderhit or UciEngine::pondermiss
     pub fn ponder(mut self) -> Self {
+        self.should_go = true;
         self.ponder = true;
 
         self
     }
 
-    /// set ponderhit and return self
+    /// make the job search and return self, a job without go options sends a plain go
+    /// ( searched with the default movetime of the engine, see EngineBuilder::default_movetime )
+    pub fn go(mut self) -> Self {
+        self.should_go = true;
+
+        self
+    }
+
+    /// set infinite and return self,
+    /// the search runs until stopped ( issue_command("stop") )
+    pub fn infinite(mut self) -> Self {
+        self.should_go = true;
+        self.infinite = true;
+
+        self
+    }
+
+    /// true if the job sends a go command without any search limit
+    /// and without ponder or infinite, many engines search forever on such a go
+    pub fn is_unbounded(&self) -> bool {
+        let limits = [
+            "wtime", "btime", "depth", "nodes", "mate", "movetime", "infinite",
+        ];
+
+        self.should_go
+            && self.custom_command.is_none()
+            && !self.ponder
+            && !self.infinite
+            && !limits
+                .iter()
+                .any(|limit| self.go_options.contains_key(*limit))
+    }
+
+    /// set ponderhit and return self, the job ends a ponder search ( see UciEngine::ponderhit )
     pub fn ponderhit(mut self) -> Self {
         self.ponderhit = true;
 
         self
     }
 
-    /// set pondermiss and return self
+    /// set pondermiss and return self, the job ends a ponder search ( see UciEngine::pondermiss )
     pub fn pondermiss(mut self) -> Self {
         self.pondermiss = true;
 
@@ -222,9 +733,31 @@ impl GoJob {
         self
     }
 
+    /// set position from incrementally maintained position command and return self,
+    /// overrides position fen, startpos and moves
+    ///
+    /// ### Example
+    /// ```
+    /// use uciengine::position::PositionCommand;
+    /// use uciengine::uciengine::GoJob;
+    ///
+    /// let mut pos_command = PositionCommand::startpos();
+    ///
+    /// pos_command.push_move("e2e4");
+    ///
+    /// let go_job = GoJob::new().pos_command(&pos_command);
+    /// ```
+    pub fn pos_command(mut self, pos_command: &PositionCommand) -> Self {
+        self.pos_spec = Command;
+        self.pos_command = Some(pos_command.to_string());
+
+        self
+    }
+
     /// set position moves and return self,
     /// moves should be a space separated string of uci moves,
-    /// as described by the UCI protocol
+    /// as described by the UCI protocol ( promotions in any notation
+    /// are sent as e7e8q, null moves only if enabled, see null_moves )
     ///
     /// ### Example
     /// ```
@@ -238,7 +771,16 @@ impl GoJob {
     where
         T: core::fmt::Display,
     {
-        self.pos_moves = Some(format!("{}", moves));
+        self.pos_moves = Some(PromotionStyle::Lowercase.format_moves(&moves.to_string()));
+
+        self
+    }
+
+    /// set whether null moves ( 0000 ) are sent in the position moves and return self,
+    /// engines that do not support them may reject the position, so they are left out
+    /// unless enabled
+    pub fn null_moves(mut self, null_moves: bool) -> Self {
+        self.null_moves = null_moves;
 
         self
     }
@@ -255,6 +797,21 @@ impl GoJob {
         self
     }
 
+    /// value of uci option set by the job, None if the job does not set it
+    pub fn uci_option(&self, key: &str) -> Option<&str> {
+        self.uci_options.get(key).map(|value| value.as_str())
+    }
+
+    /// uci options set by the job
+    pub(crate) fn uci_options(&self) -> &HashMap<String, String> {
+        &self.uci_options
+    }
+
+    /// value of go option of the job, None if the job does not set it
+    pub fn go_option(&self, key: &str) -> Option<&str> {
+        self.go_options.get(key).map(|value| value.as_str())
+    }
+
     /// set go option as key value pair and return self
     pub fn go_opt<K, V>(mut self, key: K, value: V) -> Self
     where
@@ -268,331 +825,3305 @@ impl GoJob {
         self
     }
 
-    /// set time control and return self
-    pub fn tc(mut self, tc: Timecontrol) -> Self {
-        self.go_options
-            .insert("wtime".to_string(), format!("{}", tc.wtime));
-        self.go_options
-            .insert("winc".to_string(), format!("{}", tc.winc));
-        self.go_options
-            .insert("btime".to_string(), format!("{}", tc.btime));
-        self.go_options
-            .insert("binc".to_string(), format!("{}", tc.binc));
+    /// restrict search to the given uci moves and return self, the score of the result
+    /// is then the eval of the best of these moves ( of a single move, for annotation ),
+    /// no moves lift the restriction and do not make the job search
+    pub fn searchmoves<I, T>(mut self, moves: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: core::fmt::Display,
+    {
+        let moves: Vec<String> = moves
+            .into_iter()
+            .map(|m| normalize_promotion(m.to_string().trim()))
+            .filter(|m| !m.is_empty())
+            .collect();
+
+        self.searchmoves = match moves.is_empty() {
+            true => None,
+            _ => {
+                self.should_go = true;
+
+                Some(moves.join(" "))
+            }
+        };
 
         self
     }
-}
 
-/// go command result
-#[derive(Debug)]
-pub struct GoResult {
-    /// best move if any
-    pub bestmove: Option<String>,
-    /// ponder if any
-    pub ponder: Option<String>,
-    /// analysis info
-    pub ai: AnalysisInfo,
-    pub is_ready: bool,
-}
+    /// set time control and return self, replaces the clocks and moves to go
+    /// of a time control set before
+    pub fn tc(mut self, tc: Timecontrol) -> Self {
+        self.should_go = true;
 
-/// uci engine
-pub struct UciEngine {
-    gtx: mpsc::UnboundedSender<GoJob>,
-    pub ai: std::sync::Arc<std::sync::Mutex<AnalysisInfo>>,
-    pub atx: std::sync::Arc<broadcast::Sender<AnalysisInfo>>,
-}
+        for key in ["wtime", "winc", "btime", "binc", "movestogo"] {
+            self.go_options.remove(key);
+        }
 
-/// uci engine implementation
-impl UciEngine {
-    /// create new uci engine
-    pub fn new<T>(path: T) -> std::sync::Arc<UciEngine>
-    where
-        T: core::fmt::Display,
-    {
-        // you can use anything that can be converted to string as path
-        let path = path.to_string();
+        for (key, value) in tc.go_options() {
+            self.go_options
+                .insert(key.to_string(), format!("{}", value));
+        }
 
-        // spawn engine process
-        let mut child = Command::new(path.as_str())
-            .stdout(Stdio::piped())
-            .stdin(Stdio::piped())
-            .spawn()
-            .expect("failed to spawn engine");
+        self
+    }
 
-        // obtain process stdout
-        let stdout = child
-            .stdout
-            .take()
-            .expect("child did not have a handle to stdout");
+    /// set movetime and return self
+    pub fn movetime(self, movetime: Duration) -> Self {
+        self.go_opt("movetime", movetime.as_millis())
+    }
 
-        // obtain process stdin
-        let stdin = child
-            .stdin
-            .take()
-            .expect("child did not have a handle to stdin");
+    /// fit the search into a deadline and return self, the movetime is capped at
+    /// the deadline minus the overhead margin ( round trip, queueing, parsing ),
+    /// an infinite search becomes a timed one, so the search never outlives
+    /// whoever asked for it
+    pub fn deadline(mut self, deadline: Duration, margin: Duration) -> Self {
+        let budget = deadline
+            .saturating_sub(margin)
+            .max(Duration::from_millis(1));
 
-        // stdout reader
-        let reader = BufReader::new(stdout).lines();
+        let budget = match self
+            .go_options
+            .get("movetime")
+            .and_then(|movetime| movetime.parse::<u64>().ok())
+        {
+            Some(movetime) => budget.min(Duration::from_millis(movetime)),
+            _ => budget,
+        };
 
-        // channel for receiving bestmove result
-        let (tx, rx) = mpsc::unbounded_channel::<String>();
+        self.infinite = false;
 
-        tokio::spawn(async move {
-            // run engine process and wait for exit code
-            let status = child
-                .wait()
-                .await
-                .expect("engine process encountered an error");
-
-            if log_enabled!(Level::Info) {
-                info!("engine process exit status : {}", status);
-            }
-        });
+        self.movetime(budget)
+    }
 
-        let ai = std::sync::Arc::new(std::sync::Mutex::new(AnalysisInfo::new()));
-        let is_ready = std::sync::Arc::new(std::sync::Mutex::new(false));
+    /// set typed search limits and return self, fails if the limits conflict with
+    /// each other or with the limits already set ( an infinite search with a time
+    /// control or a depth, limits for an infinite search )
+    pub fn limits(mut self, limits: GoLimits) -> Result<Self, GoLimitsError> {
+        limits.validate()?;
 
-        let ai_clone = ai.clone();
+        let timed = ["wtime", "btime", "depth", "nodes", "mate", "movetime"]
+            .iter()
+            .any(|limit| self.go_options.contains_key(*limit));
 
-        let (atx, _) = broadcast::channel::<AnalysisInfo>(20);
+        if limits.infinite && timed {
+            return Err(GoLimitsError::Conflict(
+                "infinite with the limits of the job".to_string(),
+            ));
+        }
 
-        let atx = std::sync::Arc::new(atx);
+        if self.infinite && !limits.go_options().is_empty() {
+            return Err(GoLimitsError::Conflict(
+                "limits for an infinite job".to_string(),
+            ));
+        }
 
-        let atx_clone = atx.clone();
+        for (key, value) in limits.go_options() {
+            self = self.go_opt(key, value);
+        }
 
-        tokio::spawn(async move {
-            let mut reader = reader;
-            let ai = ai_clone;
-            let atx = atx_clone;
+        match limits.infinite {
+            true => Ok(self.infinite()),
+            _ => Ok(self),
+        }
+    }
 
-            let test_parse_info = env_true("TEST_PARSE_INFO");
-            let mut num_lines: usize = 0;
-            let mut ok_lines: usize = 0;
-            let mut failed_lines: usize = 0;
+    /// true if the job starts a search ( not a custom command, ponderhit or isready job )
+    pub fn searches(&self) -> bool {
+        self.should_go && self.custom_command.is_none() && !self.ponderhit && !self.pondermiss
+    }
 
-            loop {
-                match reader.next_line().await {
-                    Ok(line_opt) => {
-                        if let Some(line) = line_opt {
-                            num_lines += 1;
+    /// cap depth and return self, a deeper search is limited to depth, an infinite
+    /// search becomes a search to depth, jobs that do not search are left as they are
+    pub fn cap_depth(mut self, depth: usize) -> Self {
+        if !self.searches() {
+            return self;
+        }
 
-                            if log_enabled!(Level::Debug) {
-                                debug!("uci engine out ( {} ) : {}", num_lines, line);
-                            }
+        let depth = match self
+            .go_option("depth")
+            .and_then(|current| current.parse::<usize>().ok())
+        {
+            Some(current) => current.min(depth),
+            _ => depth,
+        };
 
-                            let mut is_bestmove = line.len() >= 8;
-                            let mut is_ready = line == "readyok";
+        self.infinite = false;
 
-                            if is_bestmove {
-                                is_bestmove = &line[0..8] == "bestmove";
-                            }
+        self.go_opt("depth", depth)
+    }
 
-                            {
-                                let mut ai = ai.lock().unwrap();
+    /// set moves to go until the next time control and return self
+    pub fn movestogo(mut self, movestogo: usize) -> Self {
+        self.should_go = true;
+        self.go_options
+            .insert("movestogo".to_string(), format!("{}", movestogo));
 
-                                let parse_result = ai.parse(line.to_owned());
+        self
+    }
+}
 
-                                if is_bestmove {
-                                    ai.done = true;
-                                }
+/// go command result
+#[derive(Debug)]
+pub struct GoResult {
+    /// best move if any
+    pub bestmove: Option<String>,
+    /// ponder if any
+    pub ponder: Option<String>,
+    /// analysis info
+    pub ai: AnalysisInfo,
+    pub is_ready: bool,
+    /// true if the engine sent bestmove without any info line ( minimal engines ),
+    /// ai is then a new analysis info with only done set, the move is in bestmove
+    pub no_info: bool,
+    /// search telemetry
+    pub telemetry: SearchTelemetry,
+    /// completeness of the search between 0 and 1, low values mark results
+    /// worth analysing deeper
+    pub completeness: f64,
+    /// labels of the engine merged with the labels of the job ( job labels win )
+    pub labels: Labels,
+    /// source of the result ( search, tablebase or book )
+    pub source: ResultSource,
+    /// anomalies of the result, empty if it looks sound
+    pub anomalies: Vec<Anomaly>,
+    /// full pvs and currlines, the pv of ai is bounded
+    pub lines: FullLines,
+    /// lines of every multipv index by depth
+    pub multipv: MultiPvAnalysis,
+}
 
-                                debug!("parse result {:?} , ai {:?}", parse_result, ai);
+/// live search of go_stream
+#[derive(Debug)]
+pub struct GoStream {
+    /// every info of the search as it is parsed, the final info ( done ) comes last,
+    /// closed once the search finished, the final info is sent even if the engine
+    /// sent no info at all ( it is then the only one, see GoResult::no_info )
+    pub infos: mpsc::UnboundedReceiver<AnalysisInfo>,
+    /// result of the search
+    pub result: PendingGo,
+}
 
-                                if parse_result.is_ok() {
-                                    ok_lines += 1;
+/// go job queued by UciEngine::try_go, resolves to its result or the error
+/// that ended the job without one
+#[derive(Debug)]
+pub struct PendingGo {
+    /// id of the job
+    id: u64,
+    rrx: oneshot::Receiver<GoResult>,
+    /// error of the engine
+    error: std::sync::Arc<std::sync::Mutex<Option<EngineError>>>,
+}
 
-                                    let send_result = atx.send(*ai);
+/// pending go implementation
+impl PendingGo {
+    /// create new pending go of job id from the result receiver and the error
+    /// reported once the receiver fails
+    pub(crate) fn new(
+        id: u64,
+        rrx: oneshot::Receiver<GoResult>,
+        error: std::sync::Arc<std::sync::Mutex<Option<EngineError>>>,
+    ) -> Self {
+        Self { id, rrx, error }
+    }
 
-                                    debug!("send ai result {:?}", send_result);
-                                } else {
-                                    failed_lines += 1;
+    /// id of the job
+    pub fn id(&self) -> u64 {
+        self.id
+    }
 
-                                    println!(
-                                        "parsing failed on {} with error {:?}",
-                                        line, parse_result
-                                    );
-                                }
+    /// the result receiver, for apis built on receivers
+    pub(crate) fn into_receiver(self) -> oneshot::Receiver<GoResult> {
+        self.rrx
+    }
 
-                                if test_parse_info {
-                                    println!(
-                                        "read {} , parsed ok {} , failed {}",
-                                        num_lines, ok_lines, failed_lines
-                                    );
-                                }
-                            }
+    /// error of a job that ended without a result, the error that killed the engine,
+    /// or NoResult for jobs that end without a result ( cancelled jobs, custom commands )
+    fn failure(error: &std::sync::Mutex<Option<EngineError>>) -> EngineError {
+        let error = error.lock().unwrap();
 
-                            if is_bestmove || is_ready {
-                                let send_result = tx.send(line);
+        error
+            .to_owned()
+            .unwrap_or_else(|| EngineError::NoResult("the job was dropped".to_string()))
+    }
 
-                                if log_enabled!(Level::Debug) {
-                                    debug!("send bestmove result {:?}", send_result);
-                                }
-                            }
-                        } else {
-                            if log_enabled!(Level::Debug) {
-                                debug!("engine returned empty line option");
-                            }
+    /// result of the job, None while it is pending, does not block
+    pub fn try_result(&mut self) -> Option<Result<GoResult, EngineError>> {
+        match self.rrx.try_recv() {
+            Ok(go_result) => Some(Ok(go_result)),
+            Err(oneshot::error::TryRecvError::Empty) => None,
+            Err(oneshot::error::TryRecvError::Closed) => Some(Err(Self::failure(&self.error))),
+        }
+    }
 
-                            break;
-                        }
-                    }
-                    Err(err) => {
-                        if log_enabled!(Level::Error) {
-                            error!("engine read error {:?}", err);
-                        }
+    /// block the current thread until the result,
+    /// panics if called from within an async context
+    pub fn blocking_result(self) -> Result<GoResult, EngineError> {
+        match self.rrx.blocking_recv() {
+            Ok(go_result) => Ok(go_result),
+            _ => Err(Self::failure(&self.error)),
+        }
+    }
+}
 
-                        break;
-                    }
-                }
-            }
+/// pending go as future of the result
+impl std::future::Future for PendingGo {
+    type Output = Result<GoResult, EngineError>;
 
-            if log_enabled!(Level::Debug) {
-                debug!("engine read terminated");
-            }
-        });
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        match std::pin::Pin::new(&mut self.rrx).poll(cx) {
+            std::task::Poll::Ready(Ok(go_result)) => std::task::Poll::Ready(Ok(go_result)),
+            std::task::Poll::Ready(_) => std::task::Poll::Ready(Err(Self::failure(&self.error))),
+            std::task::Poll::Pending => std::task::Poll::Pending,
+        }
+    }
+}
 
-        // channel for sending go jobs
-        let (gtx, grx) = mpsc::unbounded_channel::<GoJob>();
+/// snapshot of the current analysis, for guis polling instead of subscribing
+#[derive(Debug, Clone)]
+pub struct AnalysisSnapshot {
+    /// true while a search is running
+    pub searching: bool,
+    /// depth of the lines
+    pub depth: usize,
+    /// score of the best line
+    pub score: Score,
+    /// nodes searched so far
+    pub nodes: u64,
+    /// nodes per second
+    pub nps: u64,
+    /// time searched so far in milliseconds
+    pub time: usize,
+    /// top lines in multipv order, all of the same depth so their scores compare
+    pub lines: Vec<AnalysisInfo>,
+}
 
-        let ai_clone = ai.clone();
-        let is_ready_clone = is_ready.clone();
+/// go result implementation
+impl GoResult {
+    /// telemetry of the search with the labels of the result
+    pub fn labeled_telemetry(&self) -> LabeledTelemetry {
+        LabeledTelemetry {
+            labels: self.labels.clone(),
+            telemetry: self.telemetry,
+        }
+    }
+}
+
+/// write command to engine stdin
+async fn write_command(stdin: &mut ChildStdin, command: &str) -> std::io::Result<()> {
+    if log_enabled!(Level::Debug) {
+        debug!("issuing engine command : {}", command);
+    }
+
+    let write_result = stdin.write_all(command.as_bytes()).await;
+
+    if log_enabled!(Level::Debug) {
+        debug!("write result {:?}", write_result);
+    }
+
+    write_result
+}
+
+/// decode line of engine output, invalid utf8 is replaced
+/// and the line ending ( lf or crlf ) is stripped
+fn decode_line(bytes: &[u8]) -> String {
+    let line = String::from_utf8_lossy(bytes);
+
+    line.trim_end_matches(['\n', '\r']).to_string()
+}
+
+/// capacity the line buffer is shrunk back to after a longer line
+const RETAINED_LINE_CAPACITY: usize = 64 * 1024;
+
+/// read line of engine output, None at the end of the output, lines are read
+/// whole however long they are ( multipv pvs at high depth run to many kilobytes ),
+/// the buffer grows as needed and is shrunk back after an exceptionally long line
+async fn read_line<R>(reader: &mut R, buf: &mut Vec<u8>) -> std::io::Result<Option<String>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    buf.clear();
+
+    let line = match reader.read_until(b'\n', buf).await? {
+        0 => None,
+        _ => Some(decode_line(buf)),
+    };
+
+    if buf.capacity() > RETAINED_LINE_CAPACITY {
+        buf.clear();
+        buf.shrink_to(RETAINED_LINE_CAPACITY);
+    }
+
+    Ok(line)
+}
+
+/// exit of the engine process, shared by the process task and the engine
+struct ProcessExit {
+    /// notified once quit was written, the process is killed if it did not exit
+    /// within QUIT_TIMEOUT
+    quit: Notify,
+    /// true once the process exited
+    exited: std::sync::atomic::AtomicBool,
+    /// exit status, None while running or if it could not be obtained
+    status: std::sync::Mutex<Option<std::process::ExitStatus>>,
+    /// last lines the process wrote to stderr
+    stderr: std::sync::Mutex<std::collections::VecDeque<String>>,
+    /// notified once the process exited
+    done: Notify,
+}
+
+/// process exit implementation
+impl ProcessExit {
+    /// create new process exit
+    fn new() -> Self {
+        Self {
+            quit: Notify::new(),
+            exited: std::sync::atomic::AtomicBool::new(false),
+            status: std::sync::Mutex::new(None),
+            stderr: std::sync::Mutex::new(std::collections::VecDeque::new()),
+            done: Notify::new(),
+        }
+    }
+
+    /// true once the process exited
+    fn has_exited(&self) -> bool {
+        self.exited.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// exit status, waiting up to timeout for the process to exit,
+    /// None if it did not exit in time or the status could not be obtained
+    async fn status(&self, timeout: Duration) -> Option<std::process::ExitStatus> {
+        // created before the check, so the notification can not be missed
+        let done = self.done.notified();
+
+        if !self.has_exited() {
+            let _ = tokio::time::timeout(timeout, done).await;
+        }
+
+        *self.status.lock().unwrap()
+    }
+}
+
+/// output of an engine process handed over to the reader, its stdout with the sender
+/// of the bestmove and readyok lines awaited by the jobs
+type EngineOutput = (
+    BufReader<ChildStdout>,
+    mpsc::UnboundedSender<(String, Instant)>,
+);
+
+/// spawn engine process at path and return its stdin and stdout,
+/// the process is killed if it does not exit in time after quit, or once the runtime
+/// shuts down
+fn launch(
+    path: &str,
+    pid: &std::sync::Mutex<Option<u32>>,
+    exit: std::sync::Arc<ProcessExit>,
+) -> Result<(ChildStdin, BufReader<ChildStdout>), EngineError> {
+    let mut child = Command::new(path)
+        .stdout(Stdio::piped())
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| EngineError::SpawnFailed(format!("{} : {}", path, err)))?;
+
+    {
+        let mut pid = pid.lock().unwrap();
+
+        *pid = child.id();
+    }
+
+    // obtain process stdout and stdin, piped above
+    let (stdout, stdin) = match (child.stdout.take(), child.stdin.take()) {
+        (Some(stdout), Some(stdin)) => (stdout, stdin),
+        _ => {
+            return Err(EngineError::SpawnFailed(
+                "engine process has no stdout or stdin".to_string(),
+            ))
+        }
+    };
+
+    // stderr is logged and its last lines kept, to explain an engine that failed to start
+    let stderr_reader = child.stderr.take().map(|stderr| {
+        let exit = exit.clone();
 
         tokio::spawn(async move {
-            let mut stdin = stdin;
-            let mut grx = grx;
-            let mut rx = rx;
-            let ai = ai_clone;
-            let is_ready = is_ready_clone;
+            let mut lines = BufReader::new(stderr).lines();
 
-            while let Some(go_job) = grx.recv().await {
-                if log_enabled!(Level::Debug) {
-                    debug!("received go job {:?}", go_job);
+            while let Ok(Some(line)) = lines.next_line().await {
+                warn!("engine stderr : {}", line);
+
+                let mut stderr = exit.stderr.lock().unwrap();
+
+                if stderr.len() >= MAX_STDERR_LINES {
+                    stderr.pop_front();
                 }
 
-                for command in go_job.to_commands() {
-                    let command = format!("{}\n", command);
+                stderr.push_back(line);
+            }
+        })
+    });
+
+    tokio::spawn(async move {
+        let quit_timeout = async {
+            exit.quit.notified().await;
 
-                    if log_enabled!(Level::Debug) {
-                        debug!("issuing engine command : {}", command);
-                    }
+            tokio::time::sleep(QUIT_TIMEOUT).await;
+        };
 
-                    let write_result = stdin.write_all(command.as_bytes()).await;
+        // run engine process and wait for exit code
+        let status = tokio::select! {
+            status = child.wait() => status,
+            _ = quit_timeout => {
+                warn!("engine did not exit within {:?} of quit, killing it", QUIT_TIMEOUT);
 
-                    if log_enabled!(Level::Debug) {
-                        debug!("write result {:?}", write_result);
-                    }
+                let _ = child.kill().await;
+
+                child.wait().await
+            }
+        };
+
+        match status {
+            Ok(status) => {
+                if log_enabled!(Level::Info) {
+                    info!("engine process exit status : {}", status);
                 }
 
-                if go_job.custom_command.is_none() && (!go_job.ponder) {
-                    {
-                        let mut ai = ai.lock().unwrap();
+                *exit.status.lock().unwrap() = Some(status);
+            }
+            Err(err) => error!("engine process encountered an error : {}", err),
+        }
 
-                        *ai = AnalysisInfo::new();
-                    }
+        // the stderr output is complete once the exit is announced
+        if let Some(stderr_reader) = stderr_reader {
+            let _ = tokio::time::timeout(EXIT_STATUS_TIMEOUT, stderr_reader).await;
+        }
 
-                    let recv_result = rx.recv().await.unwrap();
+        exit.exited.store(true, std::sync::atomic::Ordering::SeqCst);
+        exit.done.notify_waiters();
+    });
 
-                    if log_enabled!(Level::Debug) {
-                        debug!("recv result {:?}", recv_result);
-                    }
+    Ok((stdin, BufReader::new(stdout)))
+}
 
-                    let parts: Vec<&str> = recv_result.split(" ").collect();
+/// read next line of engine output, through the fault injector if there is one
+async fn read_next<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    #[cfg(feature = "chaos")] chaos: &mut Option<ChaosLines>,
+) -> std::io::Result<Option<String>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    #[cfg(feature = "chaos")]
+    if let Some(chaos) = chaos.as_mut() {
+        if let Some(held) = chaos.take_held() {
+            return Ok(Some(held));
+        }
 
-                    let send_ai: AnalysisInfo;
+        return match read_line(reader, buf).await? {
+            Some(line) => Ok(chaos.apply(line).await),
+            _ => Ok(None),
+        };
+    }
 
-                    {
-                        let ai = ai.lock().unwrap();
+    read_line(reader, buf).await
+}
 
-                        send_ai = *ai;
-                    }
+/// command writer, once a write failed the engine is taken as dead
+/// and all further commands are kept unsent
+struct CommandWriter {
+    /// stdin of the engine, None if it could not be spawned
+    stdin: Option<ChildStdin>,
+    /// line ending of written commands
+    line_ending: LineEnding,
+    /// true once a write failed
+    dead: bool,
+    /// error that killed the engine
+    error: std::sync::Arc<std::sync::Mutex<Option<EngineError>>>,
+    /// commands not written, in order
+    unsent: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    /// setoption commands with a value, the latest per option, replayed on restart
+    options: Vec<String>,
+    /// true once quit was written, the engine is not restarted then
+    quit: bool,
+}
 
-                    let send_is_ready: bool;
+/// name of the option set by setoption command, None for other commands and buttons
+fn setoption_name(command: &str) -> Option<&str> {
+    command
+        .strip_prefix("setoption name ")?
+        .split_once(" value ")
+        .map(|(name, _)| name)
+}
 
-                    {
-                        let is_ready = is_ready.lock().unwrap();
+/// command writer implementation
+impl CommandWriter {
+    /// mark engine dead with error
+    fn fail(&mut self, err: EngineError) {
+        error!("engine dead, keeping further commands unsent : {}", err);
 
-                        send_is_ready = *is_ready;
-                    }
+        self.dead = true;
 
-                    let mut go_result = GoResult {
-                        bestmove: None,
-                        ponder: None,
-                        ai: send_ai,
-                        is_ready: false,
-                    };
+        let mut error = self.error.lock().unwrap();
 
-                    if parts.len() > 1 {
-                        go_result.bestmove = Some(parts[1].to_string());
-                    }
+        *error = Some(err);
+    }
 
-                    if parts.len() > 3 {
-                        go_result.ponder = Some(parts[3].to_string());
-                    }
+    /// take stdin of a restarted engine, the engine is alive again
+    fn revive(&mut self, stdin: ChildStdin) {
+        self.stdin = Some(stdin);
+        self.dead = false;
 
-                    let send_result = go_job.rtx.unwrap().send(go_result);
+        let mut error = self.error.lock().unwrap();
 
-                    if log_enabled!(Level::Debug) {
-                        debug!("result of send go result {:?}", send_result);
-                    }
-                }
+        *error = None;
+    }
+
+    /// remember options set and quit
+    fn record(&mut self, command: &str) {
+        for line in command.lines() {
+            if line == "quit" {
+                self.quit = true;
             }
-        });
 
-        if log_enabled!(Level::Info) {
-            info!("spawned uci engine : {}", path);
+            if let Some(name) = setoption_name(line) {
+                self.options
+                    .retain(|option| setoption_name(option) != Some(name));
+
+                self.options.push(line.to_string());
+            }
         }
+    }
 
-        std::sync::Arc::new(UciEngine {
-            gtx: gtx,
-            ai: ai,
-            atx: atx,
-        })
+    /// write command, returns true if it was written
+    async fn write(&mut self, command: &str) -> bool {
+        self.record(command);
+
+        if let (false, Some(stdin)) = (self.dead, self.stdin.as_mut()) {
+            let translated;
+
+            let command_bytes = match self.line_ending {
+                LineEnding::Lf => command,
+                LineEnding::CrLf => {
+                    translated = command.replace('\n', "\r\n");
+
+                    translated.as_str()
+                }
+            };
+
+            match write_command(stdin, command_bytes).await {
+                Ok(_) => return true,
+                Err(err) => self.fail(EngineError::WriteFailed(err.to_string())),
+            }
+        }
+
+        let mut unsent = self.unsent.lock().unwrap();
+
+        unsent.push(command.to_string());
+
+        false
     }
+}
 
-    /// get analysis info
-    pub fn get_ai(&self) -> AnalysisInfo {
-        let ai = self.ai.lock().unwrap();
+/// respawn the process of a dead engine, send uci and replay the options set so far,
+/// returns the receiver of the bestmove and readyok lines of the new process
+async fn respawn(
+    path: &str,
+    pid: &std::sync::Mutex<Option<u32>>,
+    process_exit: &std::sync::Mutex<std::sync::Arc<ProcessExit>>,
+    readers: &mpsc::UnboundedSender<EngineOutput>,
+    writer: &mut CommandWriter,
+    uciok: &Notify,
+) -> Result<mpsc::UnboundedReceiver<(String, Instant)>, EngineError> {
+    let dead_exit = process_exit.lock().unwrap().clone();
 
-        *ai
+    // a process that lost its pipes but still runs is killed after QUIT_TIMEOUT
+    if dead_exit.status(EXIT_STATUS_TIMEOUT).await.is_none() {
+        dead_exit.quit.notify_one();
     }
 
-    /// issue go command
-    pub fn go(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
-        let mut go_job = go_job;
+    let exit = std::sync::Arc::new(ProcessExit::new());
 
-        let (rtx, rrx): (oneshot::Sender<GoResult>, oneshot::Receiver<GoResult>) =
-            oneshot::channel();
+    // the stderr of the dead process may tell why it died
+    {
+        let stderr = dead_exit.stderr.lock().unwrap().clone();
 
-        go_job.rtx = Some(rtx);
+        *exit.stderr.lock().unwrap() = stderr;
+    }
 
-        let send_result = self.gtx.send(go_job);
+    *process_exit.lock().unwrap() = exit.clone();
 
-        if log_enabled!(Level::Debug) {
-            debug!("send go job result {:?}", send_result);
-        }
+    let (stdin, reader) = launch(path, pid, exit)?;
 
-        rrx
+    let (tx, rx) = mpsc::unbounded_channel::<(String, Instant)>();
+
+    let _ = readers.send((reader, tx));
+
+    writer.revive(stdin);
+
+    // created before uci is sent, so the notification can not be missed
+    let uciok = uciok.notified();
+
+    writer.write("uci\n").await;
+
+    if tokio::time::timeout(FINGERPRINT_TIMEOUT, uciok)
+        .await
+        .is_err()
+    {
+        return Err(EngineError::Timeout(format!(
+            "restarted {} did not answer uci with uciok",
+            path
+        )));
     }
 
-    pub fn check_ready(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
-        let mut go_job = go_job;
+    for option in writer.options.clone() {
+        writer.write(&format!("{}\n", option)).await;
+    }
 
-        let (rtx, rrx): (oneshot::Sender<GoResult>, oneshot::Receiver<GoResult>) =
-            oneshot::channel();
+    Ok(rx)
+}
 
-        go_job.rtx = Some(rtx);
+/// maximum number of startup output lines kept
+const MAX_STARTUP_LINES: usize = 1000;
+/// maximum number of stderr lines kept
+const MAX_STDERR_LINES: usize = 100;
 
-        let send_result = self.gtx.send(go_job);
+/// time to wait for uciok when fingerprinting an engine
+const FINGERPRINT_TIMEOUT: Duration = Duration::from_secs(5);
+/// time to wait for the readyok fencing option changes before a search,
+/// an engine not answering in time is taken as dead
+#[cfg(not(test))]
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+#[cfg(test)]
+const READY_TIMEOUT: Duration = Duration::from_secs(2);
+/// time to wait for the bestmove of a stopped search
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+/// time the engine process has to exit after quit before it is killed
+pub const QUIT_TIMEOUT: Duration = Duration::from_secs(3);
+/// time to wait for the exit status once the engine output closed
+const EXIT_STATUS_TIMEOUT: Duration = Duration::from_millis(500);
 
-        if log_enabled!(Level::Debug) {
-            debug!("send go job result {:?}", send_result);
+/// default movetime in milliseconds of go jobs without search limits
+pub const DEFAULT_MOVETIME: usize = 1000;
+
+/// uci engine builder
+#[derive(Debug)]
+pub struct EngineBuilder {
+    /// engine executable path
+    path: String,
+    /// latency optimized fast path mode
+    fast_path: bool,
+    /// movetime applied to go jobs without search limits
+    default_movetime: Option<usize>,
+    /// labels of the engine
+    labels: Labels,
+    /// interval of keepalive isready commands while idle
+    keepalive: Option<Duration>,
+    /// line ending of written commands
+    line_ending: LineEnding,
+    /// score convention of the engine
+    score_convention: ScoreConvention,
+    /// spawn the process only when the first job arrives
+    lazy: bool,
+    /// do the uci handshake before the first job
+    handshake: bool,
+    /// sampling policy of the info stream
+    info_sampling: InfoSampling,
+    /// normalization of info string diagnostics
+    diagnostic_table: DiagnosticTable,
+    /// restart of a dead engine, None to leave it dead
+    restart_policy: Option<EngineRestartPolicy>,
+    /// promotion notation of the bestmove and ponder move of results
+    promotion_style: PromotionStyle,
+    /// fault injection of engine output
+    #[cfg(feature = "chaos")]
+    chaos: Option<ChaosConfig>,
+}
+
+/// uci engine builder implementation
+impl EngineBuilder {
+    /// create new engine builder for engine at path
+    pub fn new<T>(path: T) -> Self
+    where
+        T: core::fmt::Display,
+    {
+        Self {
+            path: path.to_string(),
+            fast_path: false,
+            default_movetime: Some(DEFAULT_MOVETIME),
+            labels: Labels::new(),
+            keepalive: None,
+            line_ending: LineEnding::Lf,
+            score_convention: ScoreConvention::stockfish(),
+            lazy: false,
+            handshake: false,
+            info_sampling: InfoSampling::new(),
+            diagnostic_table: DiagnosticTable::new(),
+            restart_policy: None,
+            promotion_style: PromotionStyle::Lowercase,
+            #[cfg(feature = "chaos")]
+            chaos: None,
         }
+    }
 
-        rrx
+    /// inject faults into the engine output and return self ( testing only )
+    #[cfg(feature = "chaos")]
+    pub fn chaos(mut self, config: ChaosConfig) -> Self {
+        self.chaos = Some(config);
+
+        self
     }
 
-    /// quit engine
+    /// set score convention of the engine and return self,
+    /// used to tell tablebase results apart ( default stockfish )
+    pub fn score_convention(mut self, score_convention: ScoreConvention) -> Self {
+        self.score_convention = score_convention;
+
+        self
+    }
+
+    /// set promotion notation of the bestmove and ponder move of results and return self,
+    /// moves of the engine are read in any notation ( default uci, e7e8q )
+    pub fn promotion_style(mut self, promotion_style: PromotionStyle) -> Self {
+        self.promotion_style = promotion_style;
+
+        self
+    }
+
+    /// set line ending of commands written to the engine and return self
+    /// ( engine output is read with either line ending )
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+
+        self
+    }
+
+    /// set keepalive interval and return self,
+    /// isready is sent whenever the engine was idle for the interval
+    /// ( keeps engines run behind ssh or network wrappers from being dropped
+    /// by nat timeouts ), the readyok answers are consumed internally,
+    /// keepalive does not reconnect, a dropped connection ends the wrapper process,
+    /// set a restart policy to respawn it ( uci and the options are replayed )
+    pub fn keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive = Some(interval);
+
+        self
+    }
+
+    /// set label of the engine and return self,
+    /// engine labels are carried to every result of the engine
+    pub fn label<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.labels.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    /// set fast path mode and return self,
+    /// in fast path mode the engine is pre-warmed with isready on spawn,
+    /// option changes and ucinewgame are not fenced with isready before go,
+    /// the commands of a job are written in a single write
+    /// and bestmove is handed over before any parsing or broadcasting
+    pub fn fast_path(mut self, value: bool) -> Self {
+        self.fast_path = value;
+
+        self
+    }
+
+    /// set lazy mode and return self,
+    /// a lazy engine spawns its process when the first job arrives, the uci handshake
+    /// is done before that job and its time is reported in the telemetry of the first
+    /// result ( saves the resources of rarely used engines, fast path warm up is skipped )
+    pub fn lazy(mut self, value: bool) -> Self {
+        self.lazy = value;
+
+        self
+    }
+
+    /// set handshake mode and return self, the engine is sent uci and awaited for uciok
+    /// before the first job, so its identity is known once the first result arrives
+    /// ( lazy engines always do the handshake )
+    pub fn handshake(mut self, value: bool) -> Self {
+        self.handshake = value;
+
+        self
+    }
+
+    /// set table normalizing info string diagnostics and return self
+    /// ( default the table of known builds )
+    pub fn diagnostic_table(mut self, table: DiagnosticTable) -> Self {
+        self.diagnostic_table = table;
+
+        self
+    }
+
+    /// set restart policy and return self, an engine whose process died is
+    /// respawned, sent uci and the options set so far ( default none, a dead
+    /// engine stays dead )
+    pub fn restart_policy(mut self, policy: EngineRestartPolicy) -> Self {
+        self.restart_policy = Some(policy);
+
+        self
+    }
+
+    /// set sampling policy of the info stream and return self,
+    /// infos are sampled before they are broadcast, so slow subscribers are not
+    /// flooded during fast searches ( the result of a job is not affected )
+    pub fn info_sampling(mut self, policy: InfoSampling) -> Self {
+        self.info_sampling = policy;
+
+        self
+    }
+
+    /// set movetime in milliseconds applied to go jobs without search limits and return self,
+    /// None sends such jobs as they are ( the result may never arrive )
+    pub fn default_movetime(mut self, movetime: Option<usize>) -> Self {
+        self.default_movetime = movetime;
+
+        self
+    }
+
+    /// set movetime applied to go jobs without search limits as duration and return self
+    pub fn default_movetime_duration(self, movetime: Option<Duration>) -> Self {
+        self.default_movetime(movetime.map(|movetime| movetime.as_millis() as usize))
+    }
+
+    /// spawn engine
+    pub fn build(self) -> std::sync::Arc<UciEngine> {
+        UciEngine::spawn(self)
+    }
+
+    /// spawn engine right away ( lazy is ignored ) and complete the uci handshake,
+    /// fails with what kept the engine from starting, the os error if it could not
+    /// be launched, the exit status and stderr output if it exited before uciok
+    pub async fn try_build(self) -> Result<std::sync::Arc<UciEngine>, EngineError> {
+        let path = self.path.to_owned();
+
+        let engine = UciEngine::spawn(self.lazy(false).handshake(false));
+
+        if let Some(err) = engine.error() {
+            return Err(err);
+        }
+
+        let process_exit = engine.process_exit();
+
+        let exited = process_exit.status(FINGERPRINT_TIMEOUT);
+
+        let answered = tokio::select! {
+            answered = engine.uci() => answered,
+            _ = exited => false,
+        };
+
+        if answered {
+            return Ok(engine);
+        }
+
+        let status = process_exit.status(EXIT_STATUS_TIMEOUT).await;
+
+        let stderr = engine.stderr().join("\n");
+
+        Err(match (status, stderr.is_empty()) {
+            (Some(status), true) => {
+                EngineError::SpawnFailed(format!("{} exited with {} before uciok", path, status))
+            }
+            (Some(status), _) => EngineError::SpawnFailed(format!(
+                "{} exited with {} before uciok, stderr : {}",
+                path, status, stderr
+            )),
+            _ => EngineError::Timeout(format!("{} did not answer uci with uciok", path)),
+        })
+    }
+}
+
+/// uci engine
+pub struct UciEngine {
+    gtx: mpsc::UnboundedSender<GoJob>,
+    /// immediate command sender, bypasses the job queue
+    ctx: mpsc::UnboundedSender<String>,
+    /// bestmove / readyok lines received while no job awaited a result
+    unsolicited: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    /// output received before the handshake completed
+    startup_output: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    /// process id of the engine, None if it is not spawned yet or already exited
+    pid: std::sync::Arc<std::sync::Mutex<Option<u32>>>,
+    /// labels of the engine
+    labels: Labels,
+    /// banner of the last uci handshake
+    banner: std::sync::Arc<std::sync::Mutex<EngineFingerprint>>,
+    /// notified on uciok
+    uciok: std::sync::Arc<Notify>,
+    /// error that killed the engine, None while it is alive
+    error: std::sync::Arc<std::sync::Mutex<Option<EngineError>>>,
+    /// commands not written since the engine died
+    unsent: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    /// sampling policy of the info stream
+    info_sampling: std::sync::Arc<std::sync::Mutex<InfoSampling>>,
+    /// true while a job awaits its result
+    searching: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    /// notified once a job that awaited its result finished
+    search_done: std::sync::Arc<Notify>,
+    /// id of the next job
+    next_job: std::sync::atomic::AtomicU64,
+    /// id of the job whose commands were written and that awaits its result, 0 if none
+    running_job: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    /// true from go ponder until ponderhit or pondermiss
+    pondering: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    /// full pvs and currlines of the current search
+    full_lines: std::sync::Arc<std::sync::Mutex<FullLines>>,
+    /// multipv lines of the current search
+    multipv: std::sync::Arc<std::sync::Mutex<MultiPvAnalysis>>,
+    /// exit of the engine process, replaced on restart
+    process_exit: std::sync::Arc<std::sync::Mutex<std::sync::Arc<ProcessExit>>>,
+    /// number of restarts
+    restarts: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    /// normalized info string diagnostics
+    dtx: std::sync::Arc<broadcast::Sender<EngineDiagnostic>>,
+    pub ai: std::sync::Arc<std::sync::Mutex<AnalysisInfo>>,
+    pub atx: std::sync::Arc<broadcast::Sender<AnalysisInfo>>,
+}
+
+/// uci engine implementation
+impl UciEngine {
+    /// create new uci engine
+    pub fn new<T>(path: T) -> std::sync::Arc<UciEngine>
+    where
+        T: core::fmt::Display,
+    {
+        // you can use anything that can be converted to string as path
+        EngineBuilder::new(path).build()
+    }
+
+    /// create new uci engine and complete the uci handshake, see EngineBuilder::try_build
+    pub async fn try_new<T>(path: T) -> Result<std::sync::Arc<UciEngine>, EngineError>
+    where
+        T: core::fmt::Display,
+    {
+        EngineBuilder::new(path).try_build().await
+    }
+
+    /// spawn uci engine from builder
+    fn spawn(builder: EngineBuilder) -> std::sync::Arc<UciEngine> {
+        let path = builder.path;
+        let fast_path = builder.fast_path;
+        let default_movetime = builder.default_movetime;
+        let labels = builder.labels;
+        let engine_labels = labels.clone();
+        let keepalive = builder.keepalive;
+        let line_ending = builder.line_ending;
+        let score_convention = builder.score_convention;
+        let promotion_style = builder.promotion_style;
+        #[cfg(feature = "chaos")]
+        let mut chaos = builder.chaos.map(ChaosLines::new);
+
+        let lazy = builder.lazy;
+        let handshake = builder.handshake;
+
+        let pid = std::sync::Arc::new(std::sync::Mutex::new(None));
+
+        let process_exit = std::sync::Arc::new(ProcessExit::new());
+
+        // the reader reads the output of every process the engine spawns, in turn
+        let (readers, mut readers_rx) = mpsc::unbounded_channel::<EngineOutput>();
+
+        // channel for receiving bestmove result, along with the time it was received
+        let (tx, rx) = mpsc::unbounded_channel::<(String, Instant)>();
+
+        let (mut first_tx, stdin) = match lazy {
+            true => (Some(tx), None),
+            _ => (
+                None,
+                Some(
+                    launch(&path, &pid, process_exit.clone()).map(|(stdin, reader)| {
+                        let _ = readers.send((reader, tx));
+
+                        stdin
+                    }),
+                ),
+            ),
+        };
+
+        let process_exit = std::sync::Arc::new(std::sync::Mutex::new(process_exit));
+
+        let ai = std::sync::Arc::new(std::sync::Mutex::new(AnalysisInfo::new()));
+        let full_lines = std::sync::Arc::new(std::sync::Mutex::new(FullLines::new()));
+        let multipv = std::sync::Arc::new(std::sync::Mutex::new(MultiPvAnalysis::new()));
+        let is_ready = std::sync::Arc::new(std::sync::Mutex::new(false));
+
+        let clock = std::sync::Arc::new(std::sync::Mutex::new(SearchClock::new()));
+
+        let unsolicited = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+
+        // number of keepalive readyok answers still to come
+        let keepalive_pending = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let ai_clone = ai.clone();
+        let full_lines_clone = full_lines.clone();
+        let multipv_clone = multipv.clone();
+        let clock_clone = clock.clone();
+        let unsolicited_clone = unsolicited.clone();
+        let startup_output = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+        let startup_output_clone = startup_output.clone();
+        let keepalive_pending_clone = keepalive_pending.clone();
+
+        let banner = std::sync::Arc::new(std::sync::Mutex::new(EngineFingerprint::new()));
+        let uciok = std::sync::Arc::new(Notify::new());
+
+        let banner_clone = banner.clone();
+        let uciok_clone = uciok.clone();
+
+        // info sender of the running job
+        let job_itx = std::sync::Arc::new(std::sync::Mutex::new(
+            None::<mpsc::UnboundedSender<AnalysisInfo>>,
+        ));
+        let job_itx_clone = job_itx.clone();
+
+        // true from go ponder until ponderhit or pondermiss
+        let pondering = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let pondering_clone = pondering.clone();
+
+        let info_sampling = std::sync::Arc::new(std::sync::Mutex::new(builder.info_sampling));
+        let info_sampling_clone = info_sampling.clone();
+
+        let diagnostic_table = builder.diagnostic_table;
+
+        let (dtx, _) = broadcast::channel::<EngineDiagnostic>(20);
+
+        let dtx = std::sync::Arc::new(dtx);
+
+        let dtx_clone = dtx.clone();
+
+        let (atx, _) = broadcast::channel::<AnalysisInfo>(20);
+
+        let atx = std::sync::Arc::new(atx);
+
+        let atx_clone = atx.clone();
+
+        tokio::spawn(async move {
+            let mut buf: Vec<u8> = vec![];
+            let ai = ai_clone;
+            let full_lines = full_lines_clone;
+            let multipv = multipv_clone;
+            let atx = atx_clone;
+            let clock = clock_clone;
+            let unsolicited = unsolicited_clone;
+            let startup_output = startup_output_clone;
+            let keepalive_pending = keepalive_pending_clone;
+            let banner = banner_clone;
+            let uciok = uciok_clone;
+            let job_itx = job_itx_clone;
+            let pondering = pondering_clone;
+
+            let info_sampling = info_sampling_clone;
+            let dtx = dtx_clone;
+            let mut sampler = InfoSampler::new();
+
+            let test_parse_info = env_true("TEST_PARSE_INFO");
+            let mut num_lines: usize = 0;
+            let mut ok_lines: usize = 0;
+            let mut failed_lines: usize = 0;
+            // a restarted engine hands over the output of its new process
+            while let Some((mut reader, tx)) = readers_rx.recv().await {
+                // the handshake completes with the first uciok or readyok, or once a job
+                // awaits a result or ponders, output before it is set aside instead of
+                // being parsed
+                let mut handshake_done = false;
+
+                {
+                    let mut banner = banner.lock().unwrap();
+
+                    *banner = EngineFingerprint::new();
+                }
+
+                loop {
+                    let read_result = read_next(
+                        &mut reader,
+                        &mut buf,
+                        #[cfg(feature = "chaos")]
+                        &mut chaos,
+                    )
+                    .await;
+
+                    match read_result {
+                        Ok(line_opt) => {
+                            if let Some(line) = line_opt {
+                                num_lines += 1;
+
+                                if log_enabled!(Level::Debug) {
+                                    debug!("uci engine out ( {} ) : {}", num_lines, line);
+                                }
+
+                                let mut is_bestmove = line.len() >= 8;
+                                let mut is_ready = line == "readyok";
+
+                                if is_bestmove {
+                                    is_bestmove = &line[0..8] == "bestmove";
+                                }
+
+                                if let Some(diagnostic) = diagnostic_table.normalize(&line) {
+                                    if log_enabled!(Level::Debug) {
+                                        debug!("engine diagnostic {:?}", diagnostic);
+                                    }
+
+                                    let _ = dtx.send(diagnostic);
+                                }
+
+                                if !handshake_done {
+                                    let is_running = {
+                                        let clock = clock.lock().unwrap();
+
+                                        clock.is_running()
+                                    };
+
+                                    let is_pondering =
+                                        pondering.load(std::sync::atomic::Ordering::SeqCst);
+
+                                    if is_running
+                                        || is_pondering
+                                        || line == "uciok"
+                                        || line == "readyok"
+                                    {
+                                        handshake_done = true;
+                                    } else {
+                                        {
+                                            let mut banner = banner.lock().unwrap();
+
+                                            banner.observe(&line);
+                                        }
+
+                                        let mut startup_output = startup_output.lock().unwrap();
+
+                                        if startup_output.len() < MAX_STARTUP_LINES {
+                                            startup_output.push(line);
+                                        }
+
+                                        continue;
+                                    }
+                                }
+
+                                if line == "uciok" {
+                                    uciok.notify_waiters();
+
+                                    continue;
+                                }
+
+                                {
+                                    let mut banner = banner.lock().unwrap();
+
+                                    if banner.observe(&line) {
+                                        continue;
+                                    }
+                                }
+
+                                // diagnostics carry no search info
+                                if line.starts_with("info string") {
+                                    continue;
+                                }
+
+                                // the engine answers isready in order, so the first readyok
+                                // belongs to an outstanding keepalive
+                                if is_ready
+                                    && keepalive_pending
+                                        .fetch_update(
+                                            std::sync::atomic::Ordering::SeqCst,
+                                            std::sync::atomic::Ordering::SeqCst,
+                                            |pending| pending.checked_sub(1),
+                                        )
+                                        .is_ok()
+                                {
+                                    continue;
+                                }
+
+                                if is_bestmove || is_ready {
+                                    let is_running = {
+                                        let clock = clock.lock().unwrap();
+
+                                        clock.is_running()
+                                    };
+
+                                    // with no job awaiting a result this would be taken
+                                    // as the result of the next job, set it aside instead
+                                    if !is_running {
+                                        warn!("unsolicited engine output : {}", line);
+
+                                        let mut unsolicited = unsolicited.lock().unwrap();
+
+                                        unsolicited.push(line);
+
+                                        continue;
+                                    }
+                                }
+
+                                if fast_path && is_bestmove {
+                                    // bestmove carries no info, hand it over right away
+                                    let send_ai: AnalysisInfo;
+
+                                    {
+                                        let mut ai = ai.lock().unwrap();
+
+                                        ai.done = true;
+
+                                        send_ai = *ai;
+                                    }
+
+                                    {
+                                        let mut clock = clock.lock().unwrap();
+
+                                        clock.record_bestmove();
+                                    }
+
+                                    let send_result = tx.send((line, Instant::now()));
+
+                                    if log_enabled!(Level::Debug) {
+                                        debug!("send bestmove result {:?}", send_result);
+                                    }
+
+                                    if let Some(itx) = job_itx.lock().unwrap().as_ref() {
+                                        let _ = itx.send(send_ai);
+                                    }
+
+                                    // resets the sampler, the final update is always sent
+                                    sampler.admit(&InfoSampling::new(), &send_ai, Instant::now());
+
+                                    let _ = atx.send(send_ai);
+
+                                    continue;
+                                }
+
+                                if line.starts_with("info") {
+                                    let mut full_lines = full_lines.lock().unwrap();
+
+                                    full_lines.observe(&line);
+                                }
+
+                                {
+                                    let mut ai = ai.lock().unwrap();
+
+                                    let parse_start = Instant::now();
+
+                                    let parse_result = ai.parse(line.to_owned());
+
+                                    {
+                                        let mut clock = clock.lock().unwrap();
+
+                                        clock.record_parse(
+                                            parse_start.elapsed(),
+                                            line.starts_with("info"),
+                                        );
+
+                                        if is_bestmove {
+                                            clock.record_bestmove();
+                                        }
+                                    }
+
+                                    if is_bestmove {
+                                        ai.done = true;
+                                    }
+
+                                    debug!("parse result {:?} , ai {:?}", parse_result, ai);
+
+                                    if parse_result.is_ok() {
+                                        ok_lines += 1;
+
+                                        {
+                                            let mut clock = clock.lock().unwrap();
+
+                                            clock.observe(&ai);
+                                        }
+
+                                        // other infos keep the pv of the last line
+                                        if line.contains(" pv ") {
+                                            multipv.lock().unwrap().observe(&ai);
+                                        }
+
+                                        // every info goes to the job, sampling only applies
+                                        // to the broadcast
+                                        if let Some(itx) = job_itx.lock().unwrap().as_ref() {
+                                            let _ = itx.send(*ai);
+                                        }
+
+                                        let admitted = {
+                                            let info_sampling = info_sampling.lock().unwrap();
+
+                                            sampler.admit(&info_sampling, &ai, Instant::now())
+                                        };
+
+                                        if admitted {
+                                            let send_result = atx.send(*ai);
+
+                                            debug!("send ai result {:?}", send_result);
+                                        }
+                                    } else {
+                                        failed_lines += 1;
+
+                                        println!(
+                                            "parsing failed on {} with error {:?}",
+                                            line, parse_result
+                                        );
+                                    }
+
+                                    if test_parse_info {
+                                        println!(
+                                            "read {} , parsed ok {} , failed {}",
+                                            num_lines, ok_lines, failed_lines
+                                        );
+                                    }
+                                }
+
+                                if is_bestmove || is_ready {
+                                    let send_result = tx.send((line, Instant::now()));
+
+                                    if log_enabled!(Level::Debug) {
+                                        debug!("send bestmove result {:?}", send_result);
+                                    }
+                                }
+                            } else {
+                                if log_enabled!(Level::Debug) {
+                                    debug!("engine returned empty line option");
+                                }
+
+                                break;
+                            }
+                        }
+                        Err(err) => {
+                            if log_enabled!(Level::Error) {
+                                error!("engine read error {:?}", err);
+                            }
+
+                            break;
+                        }
+                    }
+                }
+
+                if log_enabled!(Level::Debug) {
+                    debug!("engine read terminated");
+                }
+            }
+        });
+
+        // channel for sending go jobs
+        let (gtx, grx) = mpsc::unbounded_channel::<GoJob>();
+
+        // channel for sending immediate commands
+        let (ctx, crx) = mpsc::unbounded_channel::<String>();
+
+        let ai_clone = ai.clone();
+        let full_lines_clone = full_lines.clone();
+        let multipv_clone = multipv.clone();
+        let is_ready_clone = is_ready.clone();
+        let clock_clone = clock.clone();
+
+        // an engine that could not be spawned is known dead right away
+        let error = std::sync::Arc::new(std::sync::Mutex::new(match &stdin {
+            Some(Err(err)) => Some(err.clone()),
+            _ => None,
+        }));
+        let unsent = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+
+        let error_clone = error.clone();
+        let unsent_clone = unsent.clone();
+        let pid_clone = pid.clone();
+        let process_exit_clone = process_exit.clone();
+        let path_clone = path.clone();
+        let uciok_clone = uciok.clone();
+        let job_itx_clone = job_itx.clone();
+
+        let searching = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let search_done = std::sync::Arc::new(Notify::new());
+
+        let running_job = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+
+        let searching_clone = searching.clone();
+        let search_done_clone = search_done.clone();
+        let running_job_clone = running_job.clone();
+        let pondering_clone = pondering.clone();
+
+        let restart_policy = builder.restart_policy;
+        let restarts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let restarts_clone = restarts.clone();
+
+        tokio::spawn(async move {
+            let mut grx = grx;
+            let mut crx = crx;
+            let mut rx = rx;
+            let ai = ai_clone;
+            let full_lines = full_lines_clone;
+            let multipv = multipv_clone;
+            let is_ready = is_ready_clone;
+            let clock = clock_clone;
+            let uciok = uciok_clone;
+            let path = path_clone;
+            let job_itx = job_itx_clone;
+            let searching = searching_clone;
+            let search_done = search_done_clone;
+            let running_job = running_job_clone;
+            let pondering = pondering_clone;
+            let process_exit = process_exit_clone;
+            let restarts = restarts_clone;
+            // times of the restarts within the window of the restart policy
+            let mut restart_times = std::collections::VecDeque::new();
+
+            // first job of a lazy engine, received before the process was spawned
+            let mut first_job: Option<GoJob> = None;
+            // time to spawn the process and complete the handshake, reported with the first result
+            let mut startup: Option<Duration> = None;
+
+            let stdin = match stdin {
+                Some(stdin) => stdin,
+                _ => {
+                    first_job = match grx.recv().await {
+                        Some(go_job) => Some(go_job),
+                        _ => return,
+                    };
+
+                    let started = Instant::now();
+
+                    let launched = launch(&path, &pid_clone, process_exit.lock().unwrap().clone());
+
+                    let stdin = launched.map(|(stdin, reader)| {
+                        if let Some(tx) = first_tx.take() {
+                            let _ = readers.send((reader, tx));
+                        }
+
+                        stdin
+                    });
+
+                    if stdin.is_ok() && log_enabled!(Level::Info) {
+                        info!("spawned lazy uci engine : {}", path);
+                    }
+
+                    startup = Some(started.elapsed());
+
+                    stdin
+                }
+            };
+
+            let mut writer = CommandWriter {
+                stdin: None,
+                line_ending,
+                dead: false,
+                error: error_clone,
+                unsent: unsent_clone,
+                options: vec![],
+                quit: false,
+            };
+
+            // an engine that could not be spawned is dead from the start
+            match stdin {
+                Ok(stdin) => writer.stdin = Some(stdin),
+                Err(err) => writer.fail(err),
+            }
+
+            if (startup.is_some() || handshake) && !writer.dead {
+                // created before uci is sent, so the notification can not be missed
+                let uciok = uciok.notified();
+
+                let started = Instant::now();
+
+                writer.write("uci\n").await;
+
+                if tokio::time::timeout(FINGERPRINT_TIMEOUT, uciok)
+                    .await
+                    .is_err()
+                {
+                    warn!("engine did not answer uci with uciok");
+                }
+
+                startup = startup.map(|spawn| spawn + started.elapsed());
+            }
+
+            loop {
+                let restart = match restart_policy {
+                    Some(policy) if writer.dead && !writer.quit => {
+                        let error = writer.error.lock().unwrap().clone();
+
+                        // an engine that can not be launched is not retried
+                        !matches!(error, Some(EngineError::SpawnFailed(_)))
+                            && policy.allows(&mut restart_times, Instant::now())
+                    }
+                    _ => false,
+                };
+
+                if let (true, Some(policy)) = (restart, restart_policy) {
+                    tokio::time::sleep(policy.backoff).await;
+
+                    match respawn(
+                        &path,
+                        &pid_clone,
+                        &process_exit,
+                        &readers,
+                        &mut writer,
+                        &uciok,
+                    )
+                    .await
+                    {
+                        Ok(new_rx) => {
+                            rx = new_rx;
+
+                            pondering.store(false, std::sync::atomic::Ordering::SeqCst);
+
+                            let restarts =
+                                restarts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+
+                            if log_enabled!(Level::Info) {
+                                info!("restarted uci engine {} ( restart {} )", path, restarts);
+                            }
+                        }
+                        Err(err) => writer.fail(err),
+                    }
+
+                    continue;
+                }
+
+                let dead = writer.dead;
+
+                let idle = async {
+                    match keepalive {
+                        Some(interval) if !dead => tokio::time::sleep(interval).await,
+                        _ => std::future::pending::<()>().await,
+                    }
+                };
+
+                let go_job = match first_job.take() {
+                    Some(go_job) => go_job,
+                    _ => tokio::select! {
+                        go_job = grx.recv() => match go_job {
+                            Some(go_job) => go_job,
+                            _ => break,
+                        },
+                        Some(command) = crx.recv() => {
+                            writer.write(&command).await;
+
+                            continue;
+                        }
+                        // nothing is awaited while idle, the output only closes
+                        // when the process died
+                        None = rx.recv(), if !dead => {
+                            let exit = process_exit.lock().unwrap().clone();
+
+                            writer.fail(match exit.status(EXIT_STATUS_TIMEOUT).await {
+                                Some(status) => EngineError::EngineExited(status),
+                                _ => EngineError::Io("engine output closed".to_string()),
+                            });
+
+                            continue;
+                        }
+                        _ = idle => {
+                            keepalive_pending.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+                            writer.write("isready\n").await;
+
+                            continue;
+                        }
+                    },
+                };
+
+                if log_enabled!(Level::Debug) {
+                    debug!("received go job {:?}", go_job);
+                }
+
+                if go_job.is_cancelled() {
+                    if log_enabled!(Level::Debug) {
+                        debug!("dropping cancelled go job");
+                    }
+
+                    continue;
+                }
+
+                let mut go_job = match default_movetime {
+                    Some(movetime) if go_job.is_unbounded() => {
+                        warn!("go job without search limits, searching {} ms", movetime);
+
+                        go_job.go_opt("movetime", movetime)
+                    }
+                    _ => go_job,
+                };
+
+                let ends_ponder = go_job.ponderhit || go_job.pondermiss;
+
+                // the engine ignores ponderhit without a ponder search, the job would never
+                // get its bestmove
+                if ends_ponder && !pondering.load(std::sync::atomic::Ordering::SeqCst) {
+                    warn!("ponderhit or pondermiss without a ponder search, dropping go job");
+
+                    continue;
+                }
+
+                let starts_ponder =
+                    go_job.ponder && go_job.should_go && go_job.custom_command.is_none();
+
+                if starts_ponder {
+                    {
+                        let mut ai = ai.lock().unwrap();
+
+                        *ai = AnalysisInfo::new();
+                    }
+
+                    full_lines.lock().unwrap().clear();
+                    multipv.lock().unwrap().clear();
+
+                    pondering.store(true, std::sync::atomic::Ordering::SeqCst);
+                }
+
+                let awaits_result = go_job.custom_command.is_none() && (!go_job.ponder);
+
+                // after ponderhit the ponder search goes on as the search of the job,
+                // otherwise the analysis is reset before any command is written, so an
+                // engine answering with bestmove right away gets no stale info
+                if awaits_result && !ends_ponder {
+                    {
+                        let mut ai = ai.lock().unwrap();
+
+                        *ai = AnalysisInfo::new();
+                    }
+
+                    full_lines.lock().unwrap().clear();
+                    multipv.lock().unwrap().clear();
+                }
+
+                if awaits_result {
+                    let mut clock = clock.lock().unwrap();
+
+                    clock.start();
+
+                    searching.store(true, std::sync::atomic::Ordering::SeqCst);
+
+                    // set before the go command is written, so no info is missed
+                    *job_itx.lock().unwrap() = go_job.itx.take();
+                }
+
+                let mut commands: Vec<String> = go_job
+                    .to_commands()
+                    .iter()
+                    .map(|command| format!("{}\n", command))
+                    .collect();
+
+                // the pv of the result is checked against the position of the job
+                #[cfg(feature = "chess")]
+                let position_command = commands
+                    .iter()
+                    .find(|command| command.starts_with("position "))
+                    .cloned();
+
+                // option changes ( Hash, Threads ) and ucinewgame have to be applied before
+                // the search starts, the engine confirms them with readyok, the fast path
+                // skips this optional fence and relies on the engine reading commands in order
+                if awaits_result
+                    && !fast_path
+                    && go_job.should_go
+                    && (!go_job.uci_options.is_empty() || go_job.new_game)
+                {
+                    let (options, rest): (Vec<String>, Vec<String>) =
+                        commands.into_iter().partition(|command| {
+                            command.starts_with("setoption ") || command == "ucinewgame\n"
+                        });
+
+                    commands = rest;
+
+                    for option in options {
+                        writer.write(&option).await;
+                    }
+
+                    if writer.write("isready\n").await {
+                        let timeout = tokio::time::sleep(READY_TIMEOUT);
+
+                        tokio::pin!(timeout);
+
+                        let ready = loop {
+                            tokio::select! {
+                                received = rx.recv() => break Some(received),
+                                Some(command) = crx.recv() => {
+                                    writer.write(&command).await;
+                                }
+                                _ = &mut timeout => break None,
+                            }
+                        };
+
+                        match ready {
+                            Some(Some(_)) => (),
+                            Some(None) => {
+                                let exit = process_exit.lock().unwrap().clone();
+
+                                writer.fail(match exit.status(EXIT_STATUS_TIMEOUT).await {
+                                    Some(status) => EngineError::EngineExited(status),
+                                    _ => EngineError::Io("engine output closed".to_string()),
+                                });
+                            }
+                            _ => writer.fail(EngineError::Timeout(format!(
+                                "{} did not answer isready with readyok",
+                                path
+                            ))),
+                        }
+                    }
+                }
+
+                if awaits_result {
+                    // set before the cancel flag is checked, a job cancelled from now on
+                    // is seen running and stopped, one cancelled before is dropped here,
+                    // also if it was cancelled while waiting for readyok
+                    running_job.store(go_job.id, std::sync::atomic::Ordering::SeqCst);
+
+                    if go_job.is_cancelled() && !writer.dead {
+                        if log_enabled!(Level::Debug) {
+                            debug!("dropping go job cancelled before its search");
+                        }
+
+                        job_itx.lock().unwrap().take();
+
+                        {
+                            let mut clock = clock.lock().unwrap();
+
+                            clock.finish(Instant::now());
+                        }
+
+                        running_job.store(0, std::sync::atomic::Ordering::SeqCst);
+                        searching.store(false, std::sync::atomic::Ordering::SeqCst);
+                        search_done.notify_waiters();
+
+                        continue;
+                    }
+                }
+
+                if fast_path {
+                    // write all commands at once
+                    commands = vec![commands.concat()];
+                }
+
+                // the search is timed from its go, not from the readyok fence before
+                if awaits_result {
+                    let mut clock = clock.lock().unwrap();
+
+                    clock.go_written();
+                }
+
+                for command in commands {
+                    writer.write(&command).await;
+                }
+
+                // the process has QUIT_TIMEOUT to exit from now on
+                if go_job.custom_command.as_deref() == Some("quit") {
+                    process_exit.lock().unwrap().quit.notify_one();
+                }
+
+                if ends_ponder || (starts_ponder && writer.dead) {
+                    pondering.store(false, std::sync::atomic::Ordering::SeqCst);
+                }
+
+                if awaits_result && writer.dead {
+                    job_itx.lock().unwrap().take();
+
+                    // dropping the job fails its pending result
+                    {
+                        let mut clock = clock.lock().unwrap();
+
+                        clock.finish(Instant::now());
+                    }
+
+                    running_job.store(0, std::sync::atomic::Ordering::SeqCst);
+                    searching.store(false, std::sync::atomic::Ordering::SeqCst);
+                    search_done.notify_waiters();
+
+                    continue;
+                }
+
+                if awaits_result {
+                    // immediate commands ( like stop ) are still issued while waiting
+                    let received = loop {
+                        tokio::select! {
+                            received = rx.recv() => break received,
+                            Some(command) = crx.recv() => {
+                                writer.write(&command).await;
+                            }
+                        }
+                    };
+
+                    // closes the info stream of the job
+                    job_itx.lock().unwrap().take();
+
+                    let (recv_result, received_at) = match received {
+                        Some(received) => received,
+                        _ => {
+                            let exit = process_exit.lock().unwrap().clone();
+
+                            let err = match exit.status(EXIT_STATUS_TIMEOUT).await {
+                                Some(status) => EngineError::EngineExited(status),
+                                _ => EngineError::Io("engine output closed".to_string()),
+                            };
+
+                            writer.fail(err);
+
+                            {
+                                let mut clock = clock.lock().unwrap();
+
+                                clock.finish(Instant::now());
+                            }
+
+                            running_job.store(0, std::sync::atomic::Ordering::SeqCst);
+                            searching.store(false, std::sync::atomic::Ordering::SeqCst);
+                            search_done.notify_waiters();
+
+                            continue;
+                        }
+                    };
+
+                    if log_enabled!(Level::Debug) {
+                        debug!("recv result {:?}", recv_result);
+                    }
+
+                    let parts: Vec<&str> = recv_result.split(" ").collect();
+
+                    let send_ai: AnalysisInfo;
+
+                    {
+                        let ai = ai.lock().unwrap();
+
+                        send_ai = *ai;
+                    }
+
+                    let send_is_ready: bool;
+
+                    {
+                        let is_ready = is_ready.lock().unwrap();
+
+                        send_is_ready = *is_ready;
+                    }
+
+                    let mut telemetry: SearchTelemetry;
+                    let completeness: f64;
+                    let score_sign_flipped: bool;
+
+                    {
+                        let mut clock = clock.lock().unwrap();
+
+                        score_sign_flipped = clock.score_sign_flipped();
+
+                        completeness = clock.completeness(
+                            go_job
+                                .go_options
+                                .get("movetime")
+                                .and_then(|movetime| movetime.parse::<usize>().ok()),
+                        );
+
+                        telemetry = clock.finish(received_at);
+                    }
+
+                    telemetry.startup = startup.take();
+
+                    let mut result_labels = engine_labels.clone();
+
+                    result_labels.extend(go_job.labels);
+
+                    let mut go_result = GoResult {
+                        bestmove: None,
+                        ponder: None,
+                        ai: send_ai,
+                        is_ready: false,
+                        no_info: parts[0] == "bestmove"
+                            && telemetry.first_info.is_none()
+                            && !ends_ponder,
+                        telemetry,
+                        completeness,
+                        labels: result_labels,
+                        source: ResultSource::Search,
+                        anomalies: vec![],
+                        lines: full_lines.lock().unwrap().clone(),
+                        multipv: multipv.lock().unwrap().clone(),
+                    };
+
+                    // some engines send e7e8Q or e7e8=Q, the result is checked in uci
+                    if parts.len() > 1 {
+                        go_result.bestmove = Some(normalize_promotion(parts[1]));
+                    }
+
+                    if parts.len() > 3 {
+                        go_result.ponder = Some(normalize_promotion(parts[3]));
+                    }
+
+                    #[cfg(feature = "chess")]
+                    if let Some(pos) = position_command.as_deref().and_then(position_from_command) {
+                        truncate_illegal_pv(&pos, &mut go_result.ai);
+                    }
+
+                    go_result.source = ResultSource::classify(
+                        &go_result.ai,
+                        go_result.bestmove.as_deref(),
+                        &score_convention,
+                    );
+
+                    go_result.anomalies = Anomaly::detect(
+                        &go_result.ai,
+                        go_result.bestmove.as_deref(),
+                        go_result.source,
+                        score_sign_flipped,
+                    );
+
+                    if !go_result.anomalies.is_empty() && log_enabled!(Level::Debug) {
+                        debug!("anomalous search result {:?}", go_result.anomalies);
+                    }
+
+                    if promotion_style != PromotionStyle::Lowercase {
+                        go_result.bestmove = go_result.bestmove.map(|m| promotion_style.format(&m));
+                        go_result.ponder = go_result.ponder.map(|m| promotion_style.format(&m));
+                    }
+
+                    if let Some(rtx) = go_job.rtx {
+                        let send_result = rtx.send(go_result);
+
+                        if log_enabled!(Level::Debug) {
+                            debug!(
+                                "result of send go result {:?} {:?}",
+                                engine_labels, send_result
+                            );
+                        }
+                    }
+
+                    running_job.store(0, std::sync::atomic::Ordering::SeqCst);
+                    searching.store(false, std::sync::atomic::Ordering::SeqCst);
+                    search_done.notify_waiters();
+                }
+            }
+        });
+
+        if fast_path && !lazy {
+            // pre-warm engine, the result of the warm up is discarded
+            let send_result = gtx.send(GoJob::new());
+
+            if log_enabled!(Level::Debug) {
+                debug!("send warm up job result {:?}", send_result);
+            }
+        }
+
+        if log_enabled!(Level::Info) {
+            match lazy {
+                true => info!("created lazy uci engine : {}", path),
+                _ => info!("spawned uci engine : {}", path),
+            }
+        }
+
+        std::sync::Arc::new(UciEngine {
+            gtx: gtx,
+            ctx,
+            unsolicited,
+            startup_output,
+            pid,
+            labels,
+            banner,
+            uciok,
+            error,
+            unsent,
+            info_sampling,
+            searching,
+            search_done,
+            next_job: std::sync::atomic::AtomicU64::new(1),
+            running_job,
+            pondering,
+            full_lines,
+            multipv,
+            process_exit,
+            restarts,
+            dtx,
+            ai: ai,
+            atx: atx,
+        })
+    }
+
+    /// process id of the engine
+    pub fn pid(&self) -> Option<u32> {
+        *self.pid.lock().unwrap()
+    }
+
+    /// set sampling policy of the info stream, applies from the next info
+    pub fn set_info_sampling(&self, policy: InfoSampling) {
+        let mut info_sampling = self.info_sampling.lock().unwrap();
+
+        *info_sampling = policy;
+    }
+
+    /// labels of the engine
+    pub fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    /// identity of the engine from the last uci handshake, None before the handshake
+    /// ( see handshake and EngineBuilder::handshake )
+    pub fn identity(&self) -> Option<EngineIdentity> {
+        let banner = self.banner.lock().unwrap();
+
+        banner.identity()
+    }
+
+    /// options the engine declared in the last uci handshake, empty before the handshake,
+    /// setoption values can be checked with validate_option before they are sent
+    pub fn options(&self) -> EngineOptions {
+        let banner = self.banner.lock().unwrap();
+
+        banner.engine_options()
+    }
+
+    /// send uci with a fresh banner and wait for uciok, returns false on timeout
+    async fn uci(&self) -> bool {
+        {
+            let mut banner = self.banner.lock().unwrap();
+
+            *banner = EngineFingerprint::new();
+        }
+
+        // created before uci is sent, so the notification can not be missed
+        let uciok = self.uciok.notified();
+
+        self.issue_command("uci");
+
+        tokio::time::timeout(FINGERPRINT_TIMEOUT, uciok)
+            .await
+            .is_ok()
+    }
+
+    /// last lines the engine process wrote to stderr, oldest first
+    pub fn stderr(&self) -> Vec<String> {
+        let process_exit = self.process_exit();
+
+        let stderr = process_exit.stderr.lock().unwrap();
+
+        stderr.iter().cloned().collect()
+    }
+
+    /// send uci, wait for uciok and return the identity the engine answered with,
+    /// should be called while the engine is not searching
+    pub async fn handshake(&self) -> Result<EngineIdentity, EngineError> {
+        if !self.uci().await {
+            return Err(EngineError::Timeout(
+                "engine did not answer uci with uciok".to_string(),
+            ));
+        }
+
+        self.identity()
+            .ok_or_else(|| EngineError::ProtocolError("engine sent no id name".to_string()))
+    }
+
+    /// send uci and collect the banner the engine answers with,
+    /// should be called while the engine is not searching
+    pub async fn fingerprint(&self) -> EngineFingerprint {
+        if !self.uci().await {
+            warn!("engine did not answer uci with uciok");
+        }
+
+        let banner = self.banner.lock().unwrap();
+
+        banner.clone()
+    }
+
+    /// issue go job, the job is queued right away, the returned future resolves to
+    /// its result, failures are reported as the error that killed the engine,
+    /// or NoResult for jobs that end without a result ( cancelled jobs, custom commands )
+    pub fn try_go(&self, go_job: GoJob) -> PendingGo {
+        let (id, rrx) = self.enqueue(go_job);
+
+        PendingGo::new(id, rrx, self.error.clone())
+    }
+
+    /// issue go job and await its result for at most timeout, see try_go,
+    /// on timeout the job is cancelled, dropped if it did not start yet,
+    /// stopped if it is the running search, other jobs are left alone
+    pub async fn try_go_timeout(
+        &self,
+        go_job: GoJob,
+        timeout: Duration,
+    ) -> Result<GoResult, EngineError> {
+        let cancelled = go_job
+            .cancelled
+            .clone()
+            .unwrap_or_else(|| std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)));
+
+        let pending = self.try_go(go_job.cancel_flag(cancelled.clone()));
+
+        let id = pending.id();
+
+        match tokio::time::timeout(timeout, pending).await {
+            Ok(result) => result,
+            _ => {
+                cancelled.store(true, std::sync::atomic::Ordering::SeqCst);
+
+                if self.running_job() == Some(id) {
+                    self.stop().await;
+                }
+
+                Err(EngineError::Timeout(format!(
+                    "no result within {} ms",
+                    timeout.as_millis()
+                )))
+            }
+        }
+    }
+
+    /// send isready once the jobs before are done and wait for readyok, so the
+    /// commands written before ( setoption of Hash or Threads ) are applied,
+    /// go jobs setting options wait for readyok themselves before they search
+    pub async fn is_ready(&self) -> Result<(), EngineError> {
+        self.try_go(GoJob::new()).await.map(|_| ())
+    }
+
+    /// send ucinewgame once the jobs before are done and wait for readyok, the
+    /// following searches start with a cleared hash
+    pub async fn new_game(&self) -> Result<(), EngineError> {
+        self.try_go(GoJob::new().new_game(true)).await.map(|_| ())
+    }
+
+    /// error that killed the engine, None while it is alive,
+    /// once set the results of pending and further jobs fail
+    pub fn error(&self) -> Option<EngineError> {
+        let error = self.error.lock().unwrap();
+
+        error.to_owned()
+    }
+
+    /// true once the engine is dead
+    pub fn is_dead(&self) -> bool {
+        self.error().is_some()
+    }
+
+    /// take the commands not written since the engine died, in order
+    /// ( newline terminated, a supervisor can replay them on a fresh engine )
+    pub fn take_unsent(&self) -> Vec<String> {
+        let mut unsent = self.unsent.lock().unwrap();
+
+        std::mem::take(&mut *unsent)
+    }
+
+    /// get analysis info
+    pub fn get_ai(&self) -> AnalysisInfo {
+        let ai = self.ai.lock().unwrap();
+
+        *ai
+    }
+
+    /// issue go command
+    #[deprecated(note = "the receiver fails without telling why, use try_go")]
+    pub fn go(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        self.queue(go_job)
+    }
+
+    /// queue go job and return the receiver of its result
+    pub(crate) fn queue(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        self.enqueue(go_job).1
+    }
+
+    /// queue go job and return its id with the receiver of its result
+    fn enqueue(&self, go_job: GoJob) -> (u64, oneshot::Receiver<GoResult>) {
+        let mut go_job = go_job;
+
+        let (rtx, rrx): (oneshot::Sender<GoResult>, oneshot::Receiver<GoResult>) =
+            oneshot::channel();
+
+        go_job.rtx = Some(rtx);
+        go_job.id = self
+            .next_job
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+        let id = go_job.id;
+
+        let send_result = self.gtx.send(go_job);
+
+        if log_enabled!(Level::Debug) {
+            debug!("send go job result {:?}", send_result);
+        }
+
+        (id, rrx)
+    }
+
+    /// issue go command and stream every info of the search while it runs,
+    /// unlike the broadcast infos these are not sampled and belong to this job only
+    pub fn go_stream(&self, go_job: GoJob) -> GoStream {
+        let mut go_job = go_job;
+
+        let (itx, irx) = mpsc::unbounded_channel::<AnalysisInfo>();
+
+        go_job.itx = Some(itx);
+
+        GoStream {
+            infos: irx,
+            result: self.try_go(go_job),
+        }
+    }
+
+    pub fn check_ready(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        let mut go_job = go_job;
+
+        let (rtx, rrx): (oneshot::Sender<GoResult>, oneshot::Receiver<GoResult>) =
+            oneshot::channel();
+
+        go_job.rtx = Some(rtx);
+
+        let send_result = self.gtx.send(go_job);
+
+        if log_enabled!(Level::Debug) {
+            debug!("send go job result {:?}", send_result);
+        }
+
+        rrx
+    }
+
+    /// take bestmove / readyok lines received while no job awaited a result
+    /// ( protocol violations, or the results of custom go commands )
+    pub fn take_unsolicited(&self) -> Vec<String> {
+        let mut unsolicited = self.unsolicited.lock().unwrap();
+
+        std::mem::take(&mut *unsolicited)
+    }
+
+    /// output the engine printed before the handshake completed
+    /// ( banners, option lists, info strings about loaded networks ... ),
+    /// kept instead of being taken for search output
+    pub fn startup_output(&self) -> Vec<String> {
+        let startup_output = self.startup_output.lock().unwrap();
+
+        startup_output.clone()
+    }
+
+    /// issue command immediately, bypassing the job queue,
+    /// the command is written even while a go job is waiting for its result
+    pub fn issue_command<T>(&self, command: T)
+    where
+        T: core::fmt::Display,
+    {
+        let send_result = self.ctx.send(format!("{}\n", command));
+
+        if log_enabled!(Level::Debug) {
+            debug!("send immediate command result {:?}", send_result);
+        }
+    }
+
+    /// subscribe to the info string diagnostics of the engine, normalized to
+    /// canonical kinds across engine builds and languages
+    pub fn diagnostics(&self) -> broadcast::Receiver<EngineDiagnostic> {
+        self.dtx.subscribe()
+    }
+
+    /// true while a search awaits its result
+    pub fn is_searching(&self) -> bool {
+        self.searching.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// id of the job whose search is running ( its commands were written ),
+    /// None while the engine is idle or applies the options of a job
+    pub fn running_job(&self) -> Option<u64> {
+        match self.running_job.load(std::sync::atomic::Ordering::SeqCst) {
+            0 => None,
+            id => Some(id),
+        }
+    }
+
+    /// full pvs and currlines of the current search so far, the pvs of the analysis
+    /// infos are bounded
+    pub fn full_lines(&self) -> FullLines {
+        self.full_lines.lock().unwrap().clone()
+    }
+
+    /// multipv lines of the current search so far
+    pub fn multipv_analysis(&self) -> MultiPvAnalysis {
+        self.multipv.lock().unwrap().clone()
+    }
+
+    /// snapshot of the current ( or last ) search, the lines are those of the deepest
+    /// depth all of them were reported at, cheap enough to be polled from a gui thread
+    pub fn snapshot(&self) -> AnalysisSnapshot {
+        let lines = {
+            let multipv = self.multipv.lock().unwrap();
+
+            multipv.complete_lines()
+        };
+
+        let ai = *self.ai.lock().unwrap();
+
+        let (depth, score) = match lines.first() {
+            Some(best) => (best.depth, best.score),
+            _ => (ai.depth, ai.score),
+        };
+
+        AnalysisSnapshot {
+            searching: self.is_searching(),
+            depth,
+            score,
+            nodes: ai.nodes,
+            nps: ai.nps,
+            time: ai.time,
+            lines,
+        }
+    }
+
+    /// true while a ponder search ( go ponder ) waits for ponderhit or pondermiss
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// the opponent played the expected move, the ponder search goes on as a normal
+    /// search, its infos are kept and the result resolves with its bestmove,
+    /// the time control of the result counts from the ponderhit
+    ///
+    /// drop(engine.try_go(GoJob::new().pos_startpos().pos_moves("e2e4 e7e5").go_opt("wtime", 60000).ponder()));
+    /// // opponent played e7e5
+    /// let go_result = engine.ponderhit().await?;
+    pub fn ponderhit(&self) -> PendingGo {
+        self.try_go(GoJob::new().ponderhit())
+    }
+
+    /// the opponent played another move, the ponder search is stopped,
+    /// the result resolves with the bestmove it was stopped with, to be discarded
+    pub fn pondermiss(&self) -> PendingGo {
+        self.try_go(GoJob::new().pondermiss())
+    }
+
+    /// stop the running search ( go infinite, ponder or any long search ),
+    /// the pending go result resolves with the bestmove the engine answers stop with,
+    /// returns once it did, false if no search was running or the engine did not
+    /// answer in time
+    pub async fn stop(&self) -> bool {
+        if self.is_pondering() {
+            return match tokio::time::timeout(STOP_TIMEOUT, self.pondermiss()).await {
+                Ok(Ok(_)) => true,
+                _ => {
+                    warn!("engine did not answer pondermiss with bestmove");
+
+                    false
+                }
+            };
+        }
+
+        // created before stop is sent, so the notification can not be missed
+        let done = self.search_done.notified();
+
+        if !self.is_searching() {
+            return false;
+        }
+
+        self.issue_command("stop");
+
+        match tokio::time::timeout(STOP_TIMEOUT, done).await {
+            Ok(_) => true,
+            _ => {
+                warn!("engine did not answer stop with bestmove");
+
+                false
+            }
+        }
+    }
+
+    /// quit engine once the jobs before are done, the process is killed
+    /// if it does not exit within QUIT_TIMEOUT of the quit command
     pub fn quit(&self) {
-        self.go(GoJob::new().custom("quit"));
+        self.queue(GoJob::new().custom("quit"));
+    }
+
+    /// exit of the current engine process
+    fn process_exit(&self) -> std::sync::Arc<ProcessExit> {
+        self.process_exit.lock().unwrap().clone()
+    }
+
+    /// true once the engine process exited
+    pub fn has_exited(&self) -> bool {
+        self.process_exit().has_exited()
+    }
+
+    /// number of times the engine was restarted after its process died
+    pub fn restarts(&self) -> usize {
+        self.restarts.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// quit engine and wait for the process to exit, killing it if it does not
+    /// exit in time, returns false if it had not exited even then
+    /// ( an engine that was never spawned has nothing to wait for )
+    pub async fn shutdown(&self) -> bool {
+        // created before quit is sent, so the notification can not be missed
+        let process_exit = self.process_exit();
+
+        let done = process_exit.done.notified();
+
+        if self.has_exited() || self.pid().is_none() {
+            return true;
+        }
+
+        self.quit();
+
+        tokio::time::timeout(QUIT_TIMEOUT + STOP_TIMEOUT, done)
+            .await
+            .is_ok()
+    }
+}
+
+/// quit the engine process when the engine is dropped, so abandoned engines
+/// do not linger, it is killed if it does not exit within QUIT_TIMEOUT
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        if self.has_exited() {
+            return;
+        }
+
+        // written right away, the job queue may be waiting on a search
+        self.issue_command("quit");
+
+        self.process_exit().quit.notify_one();
+    }
+}
+
+/// write a fake engine script to the temp dir and return its path ( testing only ),
+/// the engine appends every command to path.log and answers it by the first matching
+/// arm of a shell case, uci, isready and quit are answered unless an arm does
+#[cfg(all(test, unix))]
+pub(crate) fn fake_engine(name: &str, arms: &str) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::env::temp_dir()
+        .join(format!("uciengine-{}-{}", name, std::process::id()))
+        .to_string_lossy()
+        .to_string();
+
+    let script = format!(
+        "#!/bin/sh\nwhile read -r line; do\n  echo \"$line\" >> {}.log\n  case \"$line\" in\n{}\n    uci) echo 'id name Fake'; echo uciok;;\n    isready) echo readyok;;\n    quit) exit 0;;\n  esac\ndone\n",
+        path, arms
+    );
+
+    let _ = std::fs::remove_file(format!("{}.log", path));
+
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    path
+}
+
+/// commands a fake engine received so far
+#[cfg(all(test, unix))]
+pub(crate) fn fake_engine_log(path: &str) -> Vec<String> {
+    std::fs::read_to_string(format!("{}.log", path))
+        .unwrap_or_default()
+        .lines()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+#[test]
+fn unbounded_go() {
+    assert!(GoJob::new()
+        .pos_startpos()
+        .searchmoves(vec!["e2e4"])
+        .is_unbounded());
+    assert!(!GoJob::new()
+        .pos_startpos()
+        .go_opt("depth", 10)
+        .is_unbounded());
+    assert!(!GoJob::new().pos_startpos().infinite().is_unbounded());
+    assert_eq!(
+        GoJob::new()
+            .pos_startpos()
+            .go_opt("depth", 12)
+            .searchmoves(["e2e4", "d2d4"])
+            .to_commands(),
+        vec!["position startpos", "go depth 12 searchmoves e2e4 d2d4"]
+    );
+    assert_eq!(
+        GoJob::new()
+            .pos_startpos()
+            .go_opt("depth", 12)
+            .searchmoves(Vec::<String>::new())
+            .to_commands(),
+        vec!["position startpos", "go depth 12"]
+    );
+    // no moves alone do not start a search
+    assert_eq!(
+        GoJob::new()
+            .pos_startpos()
+            .searchmoves(Vec::<String>::new())
+            .to_commands(),
+        vec!["position startpos", "isready"]
+    );
+    assert_eq!(
+        GoJob::new().pos_startpos().ponder().to_commands(),
+        vec!["position startpos", "go ponder"]
+    );
+    assert_eq!(GoJob::new().ponderhit().to_commands(), vec!["ponderhit"]);
+    assert_eq!(
+        GoJob::new().pos_startpos().infinite().to_commands(),
+        vec!["position startpos", "go infinite"]
+    );
+}
+
+#[test]
+fn go_limits() {
+    let limits = GoLimits::new()
+        .depth(18)
+        .nodes(1_000_000)
+        .movetime(Duration::from_secs(2));
+
+    assert_eq!(
+        limits.to_command(),
+        Ok("go depth 18 nodes 1000000 movetime 2000".to_string())
+    );
+    assert_eq!(
+        GoLimits::new().infinite().to_command(),
+        Ok("go infinite".to_string())
+    );
+    assert_eq!(
+        GoLimits::new().mate(5).infinite().validate(),
+        Err(GoLimitsError::Conflict("infinite with mate".to_string()))
+    );
+    assert_eq!(
+        GoLimits::new().depth(0).validate(),
+        Err(GoLimitsError::OutOfRange("depth 0".to_string()))
+    );
+    assert!(GoJob::new()
+        .tc(Timecontrol::default())
+        .limits(GoLimits::new().infinite())
+        .is_err());
+    assert_eq!(
+        GoJob::new()
+            .pos_startpos()
+            .limits(GoLimits::new().mate(3))
+            .unwrap()
+            .to_commands(),
+        vec!["position startpos", "go mate 3"]
+    );
+}
+
+#[test]
+fn new_game_commands() {
+    assert_eq!(
+        GoJob::new()
+            .uci_opt("Hash", 128)
+            .new_game(true)
+            .pos_startpos()
+            .go_opt("depth", 10)
+            .to_commands(),
+        vec![
+            "setoption name Hash value 128",
+            "ucinewgame",
+            "position startpos",
+            "go depth 10"
+        ]
+    );
+    assert_eq!(
+        GoJob::new().new_game(true).to_commands(),
+        vec!["ucinewgame", "isready"]
+    );
+}
+
+#[test]
+fn decode_lines() {
+    assert_eq!(decode_line(b"readyok\r\n"), "readyok");
+    assert_eq!(
+        decode_line(b"info string caf\xe9\n"),
+        "info string caf\u{fffd}"
+    );
+}
+
+#[tokio::test]
+async fn read_long_lines() {
+    let pv = vec!["g1f3 g8f6 f3g1 f6g8"; 6000].join(" ");
+    let output = format!("info depth 245 multipv 3 pv {}\nbestmove g1f3\n", pv);
+
+    let mut reader = output.as_bytes();
+    let mut buf = vec![];
+
+    let line = read_line(&mut reader, &mut buf).await.unwrap().unwrap();
+
+    assert!(buf.capacity() <= RETAINED_LINE_CAPACITY);
+
+    let mut lines = FullLines::new();
+
+    lines.observe(&line);
+
+    assert_eq!(lines.pv(3).unwrap().split(' ').count(), 24000);
+    assert_eq!(
+        read_line(&mut reader, &mut buf).await.unwrap().unwrap(),
+        "bestmove g1f3"
+    );
+    assert!(read_line(&mut reader, &mut buf).await.unwrap().is_none());
+}
+
+#[test]
+fn duration_api() {
+    let tc = Timecontrol::from_durations(
+        Duration::from_secs(60),
+        Duration::from_secs(1),
+        Duration::from_millis(59_500),
+        Duration::from_secs(1),
+    );
+
+    assert_eq!(tc.btime, 59_500);
+    assert_eq!(tc.winc_duration(), Duration::from_millis(1000));
+    assert_eq!(
+        GoJob::new()
+            .pos_startpos()
+            .movetime(Duration::from_millis(1500))
+            .to_commands(),
+        vec!["position startpos", "go movetime 1500"]
+    );
+}
+
+#[test]
+fn null_move_position() {
+    let go_job = || GoJob::new().pos_startpos().go_opt("depth", 1);
+
+    // left out unless enabled
+    assert_eq!(
+        go_job().pos_moves("e2e4 0000 d2d4").to_commands(),
+        vec!["position startpos moves e2e4 d2d4", "go depth 1"]
+    );
+    assert_eq!(
+        go_job().pos_moves("0000").to_commands(),
+        vec!["position startpos", "go depth 1"]
+    );
+    assert_eq!(
+        go_job()
+            .pos_moves("e2e4 0000 d2d4")
+            .null_moves(true)
+            .to_commands(),
+        vec!["position startpos moves e2e4 0000 d2d4", "go depth 1"]
+    );
+}
+
+#[test]
+fn one_sided_clock() {
+    let tc = Timecontrol::black(Duration::from_secs(30), Duration::from_secs(2)).movestogo(12);
+
+    assert_eq!(
+        tc.go_options(),
+        vec![("btime", 30000), ("binc", 2000), ("movestogo", 12)]
+    );
+
+    let go_job = GoJob::new()
+        .tc(Timecontrol::default())
+        .tc(tc)
+        .go_opt("depth", 20);
+
+    assert_eq!(go_job.go_option("btime"), Some("30000"));
+    assert_eq!(go_job.go_option("movestogo"), Some("12"));
+    // the clock of white set before is replaced
+    assert_eq!(go_job.go_option("wtime"), None);
+    assert!(!go_job.is_unbounded());
+
+    // so are the moves to go
+    let go_job = go_job.tc(Timecontrol::new(10000, 0, 10000, 0));
+
+    assert_eq!(go_job.go_option("movestogo"), None);
+    assert_eq!(go_job.go_option("wtime"), Some("10000"));
+}
+
+#[test]
+fn deadline_budget() {
+    let margin = Duration::from_millis(50);
+
+    assert_eq!(
+        GoJob::new()
+            .pos_startpos()
+            .infinite()
+            .deadline(Duration::from_secs(2), margin)
+            .to_commands(),
+        vec!["position startpos", "go movetime 1950"]
+    );
+    assert_eq!(
+        GoJob::new()
+            .pos_startpos()
+            .go_opt("movetime", 500)
+            .deadline(Duration::from_secs(2), margin)
+            .to_commands(),
+        vec!["position startpos", "go movetime 500"]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn fast_path_fences() {
+    let arms = "    go*) echo 'info depth 1 score cp 10 pv e2e4'; echo 'bestmove e2e4';;";
+
+    let go_job = || {
+        GoJob::new()
+            .uci_opt("Hash", 16)
+            .pos_startpos()
+            .go_opt("depth", 1)
+    };
+
+    let path = fake_engine("fast-path", arms);
+    let engine = EngineBuilder::new(&path).fast_path(true).build();
+
+    let go_result = engine.try_go(go_job()).await.unwrap();
+
+    assert_eq!(go_result.bestmove.as_deref(), Some("e2e4"));
+    // pre-warmed with isready, no fence between the option and go
+    assert_eq!(
+        fake_engine_log(&path),
+        [
+            "isready",
+            "setoption name Hash value 16",
+            "position startpos",
+            "go depth 1"
+        ]
+    );
+
+    let path = fake_engine("fenced", arms);
+    let engine = EngineBuilder::new(&path).build();
+
+    engine.try_go(go_job()).await.unwrap();
+
+    assert_eq!(
+        fake_engine_log(&path),
+        [
+            "setoption name Hash value 16",
+            "isready",
+            "position startpos",
+            "go depth 1"
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn ponder_bookkeeping() {
+    // ponder searches only end on ponderhit or stop
+    let path = fake_engine(
+        "ponder",
+        "    go\\ ponder) echo 'info depth 3 score cp 20 pv e2e4';;
+    ponderhit) echo 'bestmove e2e4';;
+    stop) echo 'bestmove d2d4';;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let start_ponder = || async {
+        drop(engine.try_go(GoJob::new().pos_startpos().ponder()));
+
+        while !engine.is_pondering() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+
+    // without a ponder search ponderhit is dropped instead of waiting forever
+    assert!(engine.ponderhit().await.is_err());
+
+    start_ponder().await;
+
+    let go_result = engine.ponderhit().await.unwrap();
+
+    // the infos of the ponder search are kept
+    assert_eq!(go_result.bestmove.as_deref(), Some("e2e4"));
+    assert_eq!(go_result.ai.depth, 3);
+    assert!(!engine.is_pondering());
+
+    start_ponder().await;
+
+    // stop while pondering is a pondermiss
+    assert!(engine.stop().await);
+    assert!(!engine.is_pondering());
+    assert_eq!(
+        fake_engine_log(&path),
+        [
+            "position startpos",
+            "go ponder",
+            "ponderhit",
+            "position startpos",
+            "go ponder",
+            "stop"
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn quit_and_drop_kill() {
+    // ignores quit, only a kill ends it
+    let path = fake_engine("stubborn", "    go\\ infinite) ;;\n    quit) ;;");
+
+    let engine = EngineBuilder::new(&path).build();
+
+    assert!(engine.pid().is_some());
+    assert!(!engine.has_exited());
+
+    let started = Instant::now();
+
+    assert!(engine.shutdown().await);
+    assert!(engine.has_exited());
+    assert!(started.elapsed() >= QUIT_TIMEOUT);
+
+    // dropping the engine quits right away, even while a search is running
+    let path = fake_engine("dropped", "    go\\ infinite) ;;");
+
+    let engine = EngineBuilder::new(&path).build();
+    let process_exit = engine.process_exit.lock().unwrap().clone();
+
+    drop(engine.try_go(GoJob::new().pos_startpos().infinite()));
+
+    while !engine.is_searching() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    drop(engine);
+
+    tokio::time::timeout(QUIT_TIMEOUT, async {
+        while !process_exit.has_exited() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        fake_engine_log(&path),
+        ["position startpos", "go infinite", "quit"]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn crash_restart() {
+    // crashes on depth 2, every process logs to the same file
+    let path = fake_engine(
+        "crash",
+        "    go\\ depth\\ 2) exit 1;;\n    go*) echo 'bestmove e2e4';;",
+    );
+
+    let engine = EngineBuilder::new(&path)
+        .restart_policy(EngineRestartPolicy::new())
+        .build();
+
+    let go_job = |depth: usize| GoJob::new().pos_startpos().go_opt("depth", depth);
+
+    engine.try_go(go_job(1).uci_opt("Hash", 32)).await.unwrap();
+
+    match engine.try_go(go_job(2)).await {
+        Err(EngineError::EngineExited(status)) => assert_eq!(status.code(), Some(1)),
+        result => panic!("expected engine exited, got {:?}", result),
+    }
+
+    // the restarted engine is sent uci and the options set before the crash
+    let go_result = engine.try_go(go_job(1)).await.unwrap();
+
+    assert_eq!(go_result.bestmove.as_deref(), Some("e2e4"));
+    assert_eq!(engine.restarts(), 1);
+    assert_eq!(
+        fake_engine_log(&path)[4..],
+        [
+            "position startpos",
+            "go depth 2",
+            "uci",
+            "setoption name Hash value 32",
+            "position startpos",
+            "go depth 1"
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn readyok_fence() {
+    // readyok comes late, a search that did not wait for it plays a2a3
+    let path = fake_engine(
+        "readyok",
+        "    isready) (sleep 0.2; touch \"$0.ready\"; echo readyok) & ;;
+    go*) if [ -e \"$0.ready\" ]; then echo 'bestmove e2e4'; else echo 'bestmove a2a3'; fi;;",
+    );
+
+    let ready = format!("{}.ready", path);
+
+    let _ = std::fs::remove_file(&ready);
+
+    let engine = EngineBuilder::new(&path).build();
+
+    // the option change is fenced with isready before the search
+    let go_result = engine
+        .try_go(
+            GoJob::new()
+                .uci_opt("Hash", 32)
+                .pos_startpos()
+                .go_opt("depth", 1),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(go_result.bestmove.as_deref(), Some("e2e4"));
+
+    std::fs::remove_file(&ready).unwrap();
+
+    // resolves on readyok only
+    engine.is_ready().await.unwrap();
+
+    assert!(std::path::Path::new(&ready).exists());
+    assert_eq!(
+        fake_engine_log(&path),
+        [
+            "setoption name Hash value 32",
+            "isready",
+            "position startpos",
+            "go depth 1",
+            "isready"
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn readyok_fence_timeout() {
+    // hangs on isready
+    let path = fake_engine("readyok-timeout", "    isready) ;;");
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let started = Instant::now();
+
+    let go_result = engine
+        .try_go(
+            GoJob::new()
+                .uci_opt("Hash", 32)
+                .pos_startpos()
+                .go_opt("depth", 1),
+        )
+        .await;
+
+    assert!(matches!(go_result, Err(EngineError::Timeout(_))));
+    assert!(started.elapsed() >= READY_TIMEOUT);
+    assert!(!fake_engine_log(&path)
+        .iter()
+        .any(|line| line.starts_with("go")));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn no_info_stream() {
+    // only the depth 5 search sends info
+    let path = fake_engine(
+        "no-info",
+        "    go\\ depth\\ 5) echo 'info depth 5 score cp 30 pv e2e4'; echo 'bestmove e2e4';;
+    go*) echo 'bestmove d2d4';;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let go_job = |depth: usize| GoJob::new().pos_startpos().go_opt("depth", depth);
+
+    let go_result = engine.try_go(go_job(5)).await.unwrap();
+
+    assert!(!go_result.no_info);
+    assert_eq!(go_result.ai.depth, 5);
+
+    let mut stream = engine.go_stream(go_job(1));
+
+    let mut infos = vec![];
+
+    while let Some(ai) = stream.infos.recv().await {
+        infos.push(ai);
+    }
+
+    let go_result = stream.result.await.unwrap();
+
+    // the final info is the only one, nothing is left over from the search before
+    assert_eq!(infos.len(), 1);
+    assert!(infos[0].done);
+    assert_eq!(infos[0].depth, 0);
+    assert!(go_result.no_info);
+    assert_eq!(go_result.bestmove.as_deref(), Some("d2d4"));
+    assert_eq!(go_result.ai.depth, 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn go_timeout_cancels_own_job() {
+    let path = fake_engine(
+        "timeout",
+        "    go\\ depth\\ 9) sleep 0.5; echo 'bestmove e2e4';;
+    stop) echo 'bestmove d2d4';;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let first = engine.try_go(GoJob::new().pos_startpos().go_opt("depth", 9));
+
+    // queued behind the first job, it times out before it starts
+    let timed_out = engine
+        .try_go_timeout(
+            GoJob::new().pos_startpos().go_opt("depth", 2),
+            Duration::from_millis(100),
+        )
+        .await;
+
+    assert!(matches!(timed_out, Err(EngineError::Timeout(_))));
+
+    // the first job is not stopped and the second one is never sent
+    assert_eq!(first.await.unwrap().bestmove.as_deref(), Some("e2e4"));
+
+    engine.is_ready().await.unwrap();
+
+    assert_eq!(
+        fake_engine_log(&path),
+        ["position startpos", "go depth 9", "isready"]
+    );
+
+    // the running search of the job is stopped
+    let timed_out = engine
+        .try_go_timeout(
+            GoJob::new().pos_startpos().infinite(),
+            Duration::from_millis(100),
+        )
+        .await;
+
+    assert!(matches!(timed_out, Err(EngineError::Timeout(_))));
+    assert!(!engine.is_searching());
+    assert_eq!(
+        fake_engine_log(&path)[3..],
+        ["position startpos", "go infinite", "stop"]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn clock_starts_at_go() {
+    let path = fake_engine(
+        "clock",
+        "    isready) sleep 0.3; echo readyok;;
+    go*) echo 'bestmove e2e4';;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let go_result = engine
+        .try_go(
+            GoJob::new()
+                .uci_opt("Hash", 32)
+                .pos_startpos()
+                .go_opt("movetime", 100),
+        )
+        .await
+        .unwrap();
+
+    // the readyok fence is not charged to the search
+    assert_eq!(go_result.bestmove.as_deref(), Some("e2e4"));
+    assert!(go_result.telemetry.wall < Duration::from_millis(300));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn keepalive_reconnect() {
+    // the first connection drops on its first keepalive
+    let path = fake_engine(
+        "keepalive",
+        "    isready) if [ -e \"$0.dropped\" ]; then echo readyok; else touch \"$0.dropped\"; exit 1; fi;;
+    go*) echo 'bestmove e2e4';;",
+    );
+
+    let dropped = format!("{}.dropped", path);
+
+    let _ = std::fs::remove_file(&dropped);
+
+    let engine = EngineBuilder::new(&path)
+        .keepalive(Duration::from_millis(100))
+        .restart_policy(EngineRestartPolicy::new().backoff(Duration::from_millis(10)))
+        .build();
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+
+    let go_result = engine
+        .try_go(GoJob::new().pos_startpos().go_opt("depth", 1))
+        .await
+        .unwrap();
+
+    // the idle engine is sent isready, the reconnected one the handshake
+    assert_eq!(go_result.bestmove.as_deref(), Some("e2e4"));
+    assert_eq!(engine.restarts(), 1);
+    assert_eq!(fake_engine_log(&path)[..3], ["isready", "uci", "isready"]);
+
+    std::fs::remove_file(&dropped).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn write_failure() {
+    // stops reading its input after the first search, but keeps running
+    let path = fake_engine(
+        "write-failure",
+        "    go*) exec 0<&-; echo 'bestmove e2e4'; sleep 1;;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    let go_job = |depth: usize| GoJob::new().pos_startpos().go_opt("depth", depth);
+
+    engine.try_go(go_job(1)).await.unwrap();
+
+    // the job fails with the write error, the commands it could not write are kept
+    assert!(matches!(
+        engine.try_go(go_job(2)).await,
+        Err(EngineError::WriteFailed(_))
+    ));
+    assert!(engine.is_dead());
+    assert_eq!(
+        engine.take_unsent(),
+        ["position startpos\n", "go depth 2\n"]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn startup_output() {
+    let path = fake_engine("startup", "    go*) echo 'bestmove e2e4';;");
+
+    // the engine prints a banner as it starts
+    let script = std::fs::read_to_string(&path).unwrap().replacen(
+        "\n",
+        "\necho 'Fake 1.0 by the fake developers'\necho 'info string loaded nn.nnue'\n",
+        1,
+    );
+
+    std::fs::write(&path, script).unwrap();
+
+    let engine = EngineBuilder::new(&path).try_build().await.unwrap();
+
+    let go_result = engine
+        .try_go(GoJob::new().pos_startpos().go_opt("depth", 1))
+        .await
+        .unwrap();
+
+    // set aside instead of being taken for search output
+    assert_eq!(
+        engine.startup_output(),
+        [
+            "Fake 1.0 by the fake developers",
+            "info string loaded nn.nnue",
+            "id name Fake"
+        ]
+    );
+    assert!(go_result.no_info);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn lazy_spawn() {
+    let path = fake_engine("lazy", "    go*) echo 'bestmove e2e4';;");
+
+    let engine = EngineBuilder::new(&path).lazy(true).build();
+
+    // nothing runs before the first job
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(engine.pid().is_none());
+    assert!(fake_engine_log(&path).is_empty());
+
+    let go_job = || GoJob::new().pos_startpos().go_opt("depth", 1);
+
+    let go_result = engine.try_go(go_job()).await.unwrap();
+
+    // the handshake is done before the first job, its time is reported once
+    assert!(engine.pid().is_some());
+    assert_eq!(engine.identity().unwrap().name, "Fake");
+    assert!(go_result.telemetry.startup.is_some());
+    assert!(engine
+        .try_go(go_job())
+        .await
+        .unwrap()
+        .telemetry
+        .startup
+        .is_none());
+    assert_eq!(
+        fake_engine_log(&path),
+        [
+            "uci",
+            "position startpos",
+            "go depth 1",
+            "position startpos",
+            "go depth 1"
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn handshake_identity() {
+    let path = fake_engine(
+        "identity",
+        "    uci) echo 'id name Fake 2.1'; echo 'id author The Fakers'; echo uciok;;
+    go*) echo 'bestmove e2e4';;",
+    );
+
+    // known once the first result arrives
+    let engine = EngineBuilder::new(&path).handshake(true).build();
+
+    assert!(engine
+        .try_go(GoJob::new().pos_startpos().go_opt("depth", 1))
+        .await
+        .is_ok());
+    assert_eq!(
+        engine.identity(),
+        Some(EngineIdentity {
+            name: "Fake 2.1".to_string(),
+            author: Some("The Fakers".to_string()),
+        })
+    );
+
+    // an engine without id name fails the handshake
+    let path = fake_engine("no-identity", "    uci) echo uciok;;");
+
+    let engine = EngineBuilder::new(&path).build();
+
+    assert!(engine.identity().is_none());
+    assert!(matches!(
+        engine.handshake().await,
+        Err(EngineError::ProtocolError(_))
+    ));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn stop_search() {
+    let path = fake_engine(
+        "stop",
+        "    go\\ infinite) echo 'info depth 7 score cp 30 pv d2d4';;
+    stop) echo 'bestmove d2d4';;",
+    );
+
+    let engine = EngineBuilder::new(&path).build();
+
+    // nothing to stop
+    assert!(!engine.stop().await);
+
+    let pending = engine.try_go(GoJob::new().pos_startpos().infinite());
+
+    while !engine.is_searching() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // returns once the search answered with its bestmove
+    assert!(engine.stop().await);
+    assert!(!engine.is_searching());
+
+    let go_result = pending.await.unwrap();
+
+    assert_eq!(go_result.bestmove.as_deref(), Some("d2d4"));
+    assert_eq!(go_result.ai.depth, 7);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn try_build_failure() {
+    let missing = std::env::temp_dir().join("uciengine-missing-engine");
+
+    // the os error of the launch
+    match EngineBuilder::new(missing.display()).try_build().await {
+        Err(EngineError::SpawnFailed(err)) => assert!(err.contains("uciengine-missing-engine")),
+        result => panic!("expected spawn failed, got {:?}", result.err()),
+    }
+
+    // the exit status and stderr of an engine exiting before uciok
+    let path = fake_engine("exits", "    uci) echo 'no network found' >&2; exit 2;;");
+
+    match UciEngine::try_new(&path).await {
+        Err(EngineError::SpawnFailed(err)) => {
+            assert!(err.contains("before uciok"));
+            assert!(err.contains("no network found"));
+        }
+        result => panic!("expected spawn failed, got {:?}", result.err()),
     }
+
+    let path = fake_engine("starts", "");
+
+    assert!(UciEngine::try_new(&path).await.is_ok());
 }
@@ -0,0 +1,202 @@
+use log::{debug, info, log_enabled, warn, Level};
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::uciengine::*;
+
+/// movetime of the verification search in milliseconds
+const VERIFY_MOVETIME: usize = 1000;
+/// time to wait for the verification search on top of its movetime
+const VERIFY_GRACE: Duration = Duration::from_secs(5);
+
+/// VariantError captures possible binary variant selection errors
+#[derive(Error, Debug)]
+pub enum VariantError {
+    #[error("build directory could not be read : {0}")]
+    Io(#[from] std::io::Error),
+    #[error("no build in {0} runs on this cpu")]
+    NoBinary(String),
+}
+
+/// cpu variant of an engine build, fastest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CpuVariant {
+    Avx512,
+    Bmi2,
+    Avx2,
+    Sse41Popcnt,
+    Popcnt,
+    /// plain x86-64 or unknown build
+    Generic,
+}
+
+/// cpu variant implementation
+impl CpuVariant {
+    /// variant of a build from its file name ( stockfish-ubuntu-x86-64-avx2 )
+    pub fn from_file_name(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+
+        if name.contains("avx512") || name.contains("vnni512") {
+            CpuVariant::Avx512
+        } else if name.contains("bmi2") {
+            CpuVariant::Bmi2
+        } else if name.contains("avx2") {
+            CpuVariant::Avx2
+        } else if name.contains("sse41") || name.contains("sse4.1") {
+            CpuVariant::Sse41Popcnt
+        } else if name.contains("popcnt") {
+            CpuVariant::Popcnt
+        } else {
+            CpuVariant::Generic
+        }
+    }
+
+    /// true if the host cpu runs the variant
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub fn is_supported(self) -> bool {
+        match self {
+            CpuVariant::Avx512 => {
+                is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw")
+            }
+            CpuVariant::Bmi2 => {
+                is_x86_feature_detected!("bmi2") && is_x86_feature_detected!("avx2")
+            }
+            CpuVariant::Avx2 => is_x86_feature_detected!("avx2"),
+            CpuVariant::Sse41Popcnt => {
+                is_x86_feature_detected!("sse4.1") && is_x86_feature_detected!("popcnt")
+            }
+            CpuVariant::Popcnt => is_x86_feature_detected!("popcnt"),
+            CpuVariant::Generic => true,
+        }
+    }
+
+    /// true if the host cpu runs the variant ( only generic builds off x86 )
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    pub fn is_supported(self) -> bool {
+        self == CpuVariant::Generic
+    }
+}
+
+/// order builds fastest first, keeping those the supported predicate accepts
+pub fn rank_builds<F>(builds: Vec<PathBuf>, supported: F) -> Vec<(CpuVariant, PathBuf)>
+where
+    F: Fn(CpuVariant) -> bool,
+{
+    let mut ranked: Vec<(CpuVariant, PathBuf)> = builds
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            (CpuVariant::from_file_name(&name), path)
+        })
+        .filter(|(variant, _)| supported(*variant))
+        .collect();
+
+    ranked.sort();
+
+    ranked
+}
+
+/// builds in directory the host cpu runs, fastest first
+pub fn stockfish_builds<P: AsRef<Path>>(
+    dir: P,
+) -> Result<Vec<(CpuVariant, PathBuf)>, VariantError> {
+    let mut builds = vec![];
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_file() {
+            builds.push(path);
+        }
+    }
+
+    Ok(rank_builds(builds, CpuVariant::is_supported))
+}
+
+/// true if the build at path answers a search ( a build using instructions
+/// the cpu lacks dies on its first search )
+async fn verify_build(path: &Path) -> bool {
+    // lazy, so a path that can not be spawned fails the search instead of panicking here
+    let engine = EngineBuilder::new(path.display()).lazy(true).build();
+
+    let go_job = GoJob::new()
+        .pos_startpos()
+        .go_opt("movetime", VERIFY_MOVETIME);
+
+    let search = engine.try_go(go_job);
+
+    let timeout = Duration::from_millis(VERIFY_MOVETIME as u64) + VERIFY_GRACE;
+
+    let verified = matches!(
+        tokio::time::timeout(timeout, search).await,
+        Ok(Ok(go_result)) if go_result.bestmove.is_some()
+    );
+
+    engine.quit();
+
+    verified
+}
+
+/// select the fastest build in a directory of stockfish builds the host cpu runs,
+/// with verify each candidate has to pass a one second search before it is chosen,
+/// returns the path for EngineBuilder
+pub async fn select_stockfish<P: AsRef<Path>>(
+    dir: P,
+    verify: bool,
+) -> Result<PathBuf, VariantError> {
+    let dir = dir.as_ref();
+
+    for (variant, path) in stockfish_builds(dir)? {
+        if log_enabled!(Level::Debug) {
+            debug!("candidate build {:?} {:?}", variant, path);
+        }
+
+        if verify && !verify_build(&path).await {
+            warn!("build {:?} failed verification", path);
+
+            continue;
+        }
+
+        if log_enabled!(Level::Info) {
+            info!("selected {:?} build {:?}", variant, path);
+        }
+
+        return Ok(path);
+    }
+
+    Err(VariantError::NoBinary(dir.display().to_string()))
+}
+
+#[test]
+fn rank_stockfish_builds() {
+    let builds = vec![
+        PathBuf::from("sf/stockfish-ubuntu-x86-64-sse41-popcnt"),
+        PathBuf::from("sf/stockfish-ubuntu-x86-64-avx512"),
+        PathBuf::from("sf/stockfish-ubuntu-x86-64"),
+        PathBuf::from("sf/stockfish-ubuntu-x86-64-avx2"),
+    ];
+
+    let ranked = rank_builds(builds, |variant| variant != CpuVariant::Avx512);
+
+    let variants: Vec<CpuVariant> = ranked.iter().map(|(variant, _)| *variant).collect();
+
+    assert_eq!(
+        variants,
+        vec![
+            CpuVariant::Avx2,
+            CpuVariant::Sse41Popcnt,
+            CpuVariant::Generic
+        ]
+    );
+    assert_eq!(
+        ranked[0].1,
+        PathBuf::from("sf/stockfish-ubuntu-x86-64-avx2")
+    );
+}
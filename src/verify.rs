@@ -0,0 +1,297 @@
+use log::warn;
+
+use serde::{Deserialize, Serialize};
+
+use shakmaty::fen::{Epd, Fen};
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, EnPassantMode, Position};
+
+use std::collections::HashMap;
+
+use crate::analysis::*;
+use crate::game::*;
+use crate::notation::*;
+use crate::tournament::*;
+
+/// issue found when replaying a game
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum VerificationIssue {
+    /// start fen could not be parsed
+    InvalidStartFen(String),
+    /// move at ply ( starting from 0 ) is not legal
+    IllegalMove { ply: usize, uci: String },
+    /// moves were recorded after the game ended by the rules at ply
+    MovesAfterGameEnd { ply: usize },
+    /// recorded result differs from the result by the rules
+    ResultMismatch {
+        recorded: GameResult,
+        expected: GameResult,
+        reason: String,
+    },
+    /// game ended by the rules but the recorded termination says otherwise
+    TerminationMismatch { recorded: String, expected: String },
+    /// game did not end by the rules, the result rests on the termination given
+    Adjudicated(String),
+}
+
+/// result of replaying a game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameVerification {
+    /// true if no issues other than adjudication were found
+    pub ok: bool,
+    /// issues found
+    pub issues: Vec<VerificationIssue>,
+}
+
+/// replay the moves of a game through the rules of chess
+/// and confirm their legality and the recorded result
+pub fn verify_game(record: &GameRecord) -> GameVerification {
+    let mut issues = vec![];
+
+    let pos: Option<Chess> = match &record.start_fen {
+        Some(fen) => Fen::from_ascii(fen.as_bytes())
+            .ok()
+            .and_then(|fen| fen.into_position(CastlingMode::Standard).ok()),
+        _ => Some(Chess::default()),
+    };
+
+    let mut pos = match pos {
+        Some(pos) => pos,
+        _ => {
+            return GameVerification {
+                ok: false,
+                issues: vec![VerificationIssue::InvalidStartFen(
+                    record.start_fen.to_owned().unwrap_or_default(),
+                )],
+            }
+        }
+    };
+
+    let mut repetitions: HashMap<String, usize> = HashMap::new();
+    let mut outcome = None;
+
+    for (ply, uci) in record.moves.iter().enumerate() {
+        let epd = Epd::from_position(&pos, EnPassantMode::Legal).to_string();
+        let seen = repetitions.entry(epd).or_insert(0);
+
+        *seen += 1;
+
+        if let Some(ended) = rules_outcome(&pos, *seen) {
+            issues.push(VerificationIssue::MovesAfterGameEnd { ply });
+
+            outcome = Some(ended);
+
+            break;
+        }
+
+        match uci
+            .parse::<UciMove>()
+            .ok()
+            .and_then(|uci_move| uci_move.to_move(&pos).ok())
+        {
+            Some(m) => pos.play_unchecked(m),
+            _ => {
+                issues.push(VerificationIssue::IllegalMove {
+                    ply,
+                    uci: uci.to_owned(),
+                });
+
+                break;
+            }
+        }
+    }
+
+    if issues.is_empty() {
+        let epd = Epd::from_position(&pos, EnPassantMode::Legal).to_string();
+        let seen = repetitions.entry(epd).or_insert(0);
+
+        *seen += 1;
+
+        outcome = rules_outcome(&pos, *seen);
+    }
+
+    match outcome {
+        Some((expected, termination)) => {
+            if expected != record.result {
+                issues.push(VerificationIssue::ResultMismatch {
+                    recorded: record.result,
+                    expected,
+                    reason: termination.to_string(),
+                });
+            } else if record.termination != termination {
+                issues.push(VerificationIssue::TerminationMismatch {
+                    recorded: record.termination.to_owned(),
+                    expected: termination.to_string(),
+                });
+            }
+        }
+        _ => {
+            let claims_rules = [
+                TERMINATION_CHECKMATE,
+                TERMINATION_STALEMATE,
+                TERMINATION_INSUFFICIENT_MATERIAL,
+                TERMINATION_REPETITION,
+                TERMINATION_FIFTY_MOVES,
+            ]
+            .contains(&record.termination.as_str());
+
+            if claims_rules {
+                issues.push(VerificationIssue::TerminationMismatch {
+                    recorded: record.termination.to_owned(),
+                    expected: "game not over".to_string(),
+                });
+            } else if issues.is_empty() {
+                issues.push(VerificationIssue::Adjudicated(
+                    record.termination.to_owned(),
+                ));
+            }
+        }
+    }
+
+    let ok = issues
+        .iter()
+        .all(|issue| matches!(issue, VerificationIssue::Adjudicated(_)));
+
+    GameVerification { ok, issues }
+}
+
+/// position set up by a uci position command
+/// ( "position startpos moves e2e4" or "position fen ... moves ..." ),
+/// None if the command can not be replayed
+pub fn position_from_command(command: &str) -> Option<Chess> {
+    let command = command.trim().strip_prefix("position ")?;
+
+    let (base, moves) = match command.find(" moves") {
+        Some(i) => (&command[0..i], &command[i + " moves".len()..]),
+        _ => (command, ""),
+    };
+
+    let mut pos: Chess = match base.strip_prefix("fen ") {
+        Some(fen) => Fen::from_ascii(fen.trim().as_bytes())
+            .ok()?
+            .into_position(CastlingMode::Standard)
+            .ok()?,
+        _ if base == "startpos" => Chess::default(),
+        _ => return None,
+    };
+
+    // null moves are only sent if enabled, they pass the turn
+    for uci in moves.split_whitespace() {
+        pos = play_uci(pos, uci)?;
+    }
+
+    Some(pos)
+}
+
+/// legal prefix of pv in position and the first illegal move if any
+pub fn legal_pv_prefix(pos: &Chess, pv: &str) -> (Vec<String>, Option<String>) {
+    let mut pos = pos.to_owned();
+    let mut legal = vec![];
+
+    // some engines put null moves into their pvs, they are legal unless the side
+    // to move is in check
+    for uci in pv.split_whitespace() {
+        match play_uci(pos.to_owned(), uci) {
+            Some(next) => {
+                pos = next;
+
+                legal.push(uci.to_string());
+            }
+            _ => return (legal, Some(uci.to_string())),
+        }
+    }
+
+    (legal, None)
+}
+
+/// truncate pv of analysis info at the first illegal move ( engines emit those
+/// on hash collisions ), returns true if the pv was truncated
+pub fn truncate_illegal_pv(pos: &Chess, ai: &mut AnalysisInfo) -> bool {
+    let pv = match ai.pv() {
+        Some(pv) => pv,
+        _ => return false,
+    };
+
+    match legal_pv_prefix(pos, &pv) {
+        (legal, Some(illegal)) => {
+            warn!(
+                "illegal move {} in pv {}, truncating pv to {} moves",
+                illegal,
+                pv,
+                legal.len()
+            );
+
+            ai.set_pv(legal.join(" "));
+
+            true
+        }
+        _ => false,
+    }
+}
+
+#[test]
+fn verify_fools_mate() {
+    let mut record = GameRecord {
+        white: "a".to_string(),
+        black: "b".to_string(),
+        opening: None,
+        start_fen: None,
+        time_control: "1+0".to_string(),
+        moves: "f2f3 e7e5 g2g4 d8h4"
+            .split(' ')
+            .map(|m| m.to_string())
+            .collect(),
+        move_times: vec![],
+        result: GameResult::BlackWins,
+        termination: TERMINATION_CHECKMATE.to_string(),
+        violations: vec![],
+    };
+
+    assert!(verify_game(&record).ok);
+
+    record.result = GameResult::Draw;
+
+    assert!(!verify_game(&record).ok);
+
+    record.result = GameResult::BlackWins;
+    record.moves[3] = "d8g5".to_string();
+
+    let verification = verify_game(&record);
+
+    assert!(!verification.ok);
+    assert_eq!(
+        verification.issues[0],
+        VerificationIssue::TerminationMismatch {
+            recorded: TERMINATION_CHECKMATE.to_string(),
+            expected: "game not over".to_string(),
+        }
+    );
+
+    record.moves[3] = "d8d5".to_string();
+
+    assert_eq!(
+        verify_game(&record).issues[0],
+        VerificationIssue::IllegalMove {
+            ply: 3,
+            uci: "d8d5".to_string()
+        }
+    );
+}
+
+#[test]
+fn pv_legality() {
+    let pos = position_from_command("position startpos moves e2e4 e7e5").unwrap();
+
+    assert_eq!(
+        legal_pv_prefix(&pos, "g1f3 b8c6 e1e3 d7d6"),
+        (
+            vec!["g1f3".to_string(), "b8c6".to_string()],
+            Some("e1e3".to_string())
+        )
+    );
+    assert!(position_from_command(
+        "position fen rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 moves e7e5"
+    )
+    .is_some());
+    assert!(position_from_command("position startpos moves e2e5").is_none());
+}
@@ -0,0 +1,148 @@
+use log::{info, log_enabled, warn, Level};
+
+use serde::{Deserialize, Serialize};
+
+use std::time::{Duration, Instant};
+
+use crate::telemetry::*;
+use crate::uciengine::*;
+
+/// standard warm-up positions, an opening, a middlegame and an endgame
+pub const WARMUP_FENS: &[&str] = &[
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP1B1PPP/R2QKB1R w KQ - 0 8",
+    "8/5pk1/6p1/3R4/5P2/6PK/r7/8 b - - 0 45",
+];
+
+/// engine warm-up, a few untimed searches on standard positions run before measured
+/// searches, so first search effects ( hash allocation, page faults, jit of engines
+/// running on a vm ) do not bias the first measurements
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Warmup {
+    /// positions searched
+    pub fens: Vec<String>,
+    /// depth of every search
+    pub depth: usize,
+    /// passes over the positions
+    pub passes: usize,
+}
+
+/// telemetry of a warm-up, kept apart from the measured searches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmupReport {
+    /// telemetry of every warm-up search, in order
+    pub telemetry: Vec<SearchTelemetry>,
+    /// searches that got no result
+    pub failed: usize,
+    /// total time of the warm-up
+    pub elapsed: Duration,
+}
+
+/// warmup implementation
+impl Warmup {
+    /// create new warm-up of one pass over the standard positions at depth 8
+    pub fn new() -> Self {
+        Self {
+            fens: WARMUP_FENS.iter().map(|fen| fen.to_string()).collect(),
+            depth: 8,
+            passes: 1,
+        }
+    }
+
+    /// set positions and return self
+    pub fn fens<I, T>(mut self, fens: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: core::fmt::Display,
+    {
+        self.fens = fens.into_iter().map(|fen| fen.to_string()).collect();
+
+        self
+    }
+
+    /// set depth of every search and return self
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth.max(1);
+
+        self
+    }
+
+    /// set number of passes over the positions and return self
+    pub fn passes(mut self, passes: usize) -> Self {
+        self.passes = passes;
+
+        self
+    }
+
+    /// go jobs of the warm-up, in order
+    pub fn to_go_jobs(&self) -> Vec<GoJob> {
+        (0..self.passes)
+            .flat_map(|_| self.fens.iter())
+            .map(|fen| GoJob::new().pos_fen(fen).go_opt("depth", self.depth))
+            .collect()
+    }
+
+    /// run warm-up on engine, the hash is cleared with ucinewgame afterwards
+    /// so the measured searches do not profit from the warm-up searches
+    pub async fn run(&self, engine: &UciEngine) -> WarmupReport {
+        let started = Instant::now();
+
+        let mut report = WarmupReport {
+            telemetry: vec![],
+            failed: 0,
+            elapsed: Duration::from_secs(0),
+        };
+
+        for go_job in self.to_go_jobs() {
+            match engine.try_go(go_job).await {
+                Ok(go_result) => report.telemetry.push(go_result.telemetry),
+                _ => report.failed += 1,
+            }
+        }
+
+        let _ = engine.new_game().await;
+
+        report.elapsed = started.elapsed();
+
+        if report.failed > 0 {
+            warn!("{} warm-up searches got no result", report.failed);
+        }
+
+        if log_enabled!(Level::Info) {
+            info!(
+                "engine warmed up with {} searches in {:?}",
+                report.telemetry.len(),
+                report.elapsed
+            );
+        }
+
+        report
+    }
+}
+
+/// implement Default for Warmup
+impl Default for Warmup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn warmup_jobs() {
+    let warmup = Warmup::new().passes(2).depth(6);
+
+    let jobs = warmup.to_go_jobs();
+
+    assert_eq!(jobs.len(), 2 * WARMUP_FENS.len());
+    assert_eq!(
+        jobs[0].to_commands(),
+        vec![
+            format!("position fen {}", WARMUP_FENS[0]),
+            "go depth 6".to_string()
+        ]
+    );
+    assert!(Warmup::new()
+        .fens(Vec::<String>::new())
+        .to_go_jobs()
+        .is_empty());
+}
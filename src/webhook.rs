@@ -0,0 +1,136 @@
+use log::{debug, log_enabled, warn, Level};
+
+use serde::Serialize;
+
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// time a webhook endpoint has to take a post and answer it
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// split plain http url into host, port and path
+fn split_url(url: &str) -> Option<(String, u16, String)> {
+    let rest = url.strip_prefix("http://")?;
+
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[0..i], &rest[i..]),
+        _ => (rest, "/"),
+    };
+
+    let (host, port) = match authority.rfind(':') {
+        Some(i) => (&authority[0..i], authority[i + 1..].parse::<u16>().ok()?),
+        _ => (authority, 80),
+    };
+
+    if host.is_empty() {
+        return None;
+    }
+
+    Some((host.to_string(), port, path.to_string()))
+}
+
+/// post value as json to plain http url, returns the response status code,
+/// only plain http is supported ( https urls are rejected ), the post fails
+/// if the endpoint did not answer within WEBHOOK_TIMEOUT
+pub async fn post_json<T: Serialize>(url: &str, value: &T) -> std::io::Result<u16> {
+    post_json_timeout(url, value, WEBHOOK_TIMEOUT).await
+}
+
+/// post value as json to plain http url, see post_json, the post fails if the
+/// endpoint did not answer within timeout
+pub async fn post_json_timeout<T: Serialize>(
+    url: &str,
+    value: &T,
+    timeout: Duration,
+) -> std::io::Result<u16> {
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+
+    let (host, port, path) =
+        split_url(url).ok_or_else(|| invalid(format!("unsupported webhook url {}", url)))?;
+
+    let body = serde_json::to_string(value).map_err(|err| invalid(err.to_string()))?;
+
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    );
+
+    // an endpoint that does not close the connection would be read forever
+    let response = tokio::time::timeout(timeout, async {
+        let mut stream = TcpStream::connect((host.as_str(), port)).await?;
+
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut response = String::new();
+
+        stream.read_to_string(&mut response).await?;
+
+        Ok::<String, std::io::Error>(response)
+    })
+    .await
+    .map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("webhook {} did not answer in time", url),
+        )
+    })??;
+
+    if log_enabled!(Level::Debug) {
+        debug!("webhook {} response {}", url, response);
+    }
+
+    // status line is "HTTP/1.1 200 OK"
+    response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| invalid(format!("invalid webhook response from {}", url)))
+}
+
+/// post value as json to all urls, failures are logged and otherwise ignored
+pub async fn notify_all<T: Serialize>(urls: &[String], value: &T) {
+    for url in urls {
+        match post_json(url, value).await {
+            Ok(status) if (200..300).contains(&status) => {}
+            Ok(status) => warn!("webhook {} returned status {}", url, status),
+            Err(err) => warn!("webhook {} failed {:?}", url, err),
+        }
+    }
+}
+
+#[test]
+fn split() {
+    assert_eq!(
+        split_url("http://localhost:8080/hook"),
+        Some(("localhost".to_string(), 8080, "/hook".to_string()))
+    );
+    assert_eq!(
+        split_url("http://example.com"),
+        Some(("example.com".to_string(), 80, "/".to_string()))
+    );
+    assert_eq!(split_url("https://example.com/hook"), None);
+}
+
+#[tokio::test]
+async fn webhook_timeout() {
+    // accepts the post and never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        let (_stream, _) = listener.accept().await.unwrap();
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+
+    let err = post_json_timeout(&url, &"finished", Duration::from_millis(100))
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+}